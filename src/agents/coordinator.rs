@@ -8,6 +8,8 @@
 
 use crate::{DreasResult, DreasError};
 use super::{PromptAgent, ResponseAgent};
+use super::prompt_agent::PromptResult;
+use super::response_agent::ResponseResult;
 use std::collections::HashMap;
 use tokio::sync::{RwLock, mpsc};
 use uuid::Uuid;
@@ -26,7 +28,7 @@ pub enum CoordinatorCommand {
     RegisterPromptAgent { id: Uuid, agent: PromptAgent },
     RegisterResponseAgent { id: Uuid, agent: ResponseAgent },
     ProcessPrompt { agent_id: Uuid, prompt: String },
-    ProcessResponse { agent_id: Uuid, response: String },
+    ProcessResponse { agent_id: Uuid, response: Vec<u8> },
     Shutdown,
 }
 
@@ -67,7 +69,7 @@ impl AgentCoordinator {
     }
     
     /// Process a prompt through the appropriate agent
-    pub async fn process_prompt(&self, agent_id: Uuid, prompt: String) -> DreasResult<String> {
+    pub async fn process_prompt(&self, agent_id: Uuid, prompt: String) -> DreasResult<PromptResult> {
         let prompt_agents = self.prompt_agents.read().await;
         
         if let Some(agent) = prompt_agents.get(&agent_id) {
@@ -78,7 +80,7 @@ impl AgentCoordinator {
     }
     
     /// Process a response through the appropriate agent
-    pub async fn process_response(&self, agent_id: Uuid, response: String) -> DreasResult<String> {
+    pub async fn process_response(&self, agent_id: Uuid, response: Vec<u8>) -> DreasResult<ResponseResult> {
         let response_agents = self.response_agents.read().await;
         
         if let Some(agent) = response_agents.get(&agent_id) {
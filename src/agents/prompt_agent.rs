@@ -8,8 +8,13 @@
 
 use crate::{DreasResult, DreasError};
 use super::shared::AgentContext;
+use crate::security::audit::{actions, AuditHandle, AuditLogger, AuditResult};
+use crate::security::key_provider::KeyProvider;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
 use std::time::SystemTime;
+use tokio::sync::Mutex;
 use uuid::Uuid;
 
 /// A secure prompt agent that processes and encrypts user prompts
@@ -18,12 +23,15 @@ pub struct PromptAgent {
     id: Uuid,
     context: AgentContext,
     encryption_enabled: bool,
+    key_provider: Arc<dyn KeyProvider>,
+    audit_logger: AuditHandle,
 }
 
 /// Prompt processing result
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PromptResult {
     pub agent_id: Uuid,
+    /// SHA-256 of the prompt as received, hex-encoded
     pub prompt_hash: String,
     pub encrypted_prompt: Vec<u8>,
     pub timestamp: SystemTime,
@@ -31,17 +39,25 @@ pub struct PromptResult {
 }
 
 impl PromptAgent {
-    /// Create a new prompt agent
-    pub fn new(context: AgentContext) -> Self {
+    /// Create a new prompt agent sealing prompts with the context's key from the provider
+    pub fn new(context: AgentContext, key_provider: Arc<dyn KeyProvider>) -> Self {
         Self {
             id: Uuid::new_v4(),
             context,
             encryption_enabled: true,
+            key_provider,
+            audit_logger: AuditHandle::detached(),
         }
     }
     
+    /// Record every processed prompt in the audit log
+    pub fn with_audit_logger(mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> Self {
+        self.audit_logger = AuditHandle::new(audit_logger);
+        self
+    }
+    
     /// Process a prompt securely
-    pub async fn process_prompt(&self, prompt: String) -> DreasResult<String> {
+    pub async fn process_prompt(&self, prompt: String) -> DreasResult<PromptResult> {
         // Validate prompt
         self.validate_prompt(&prompt)?;
         
@@ -55,8 +71,20 @@ impl PromptAgent {
         // Create audit log entry
         self.audit_prompt_processing(&prompt, &encrypted_prompt).await?;
         
-        // Return processed prompt (in real implementation, this would be sent to LLM)
-        Ok(format!("Processed prompt: {}", prompt))
+        // The sealed prompt is what travels on to the LLM
+        Ok(PromptResult {
+            agent_id: self.id,
+            prompt_hash: hex::encode(Sha256::digest(prompt.as_bytes())),
+            metadata: serde_json::json!({
+                "session_id": self.context.session_id,
+                "encryption_key_id": self.context.encryption_key_id,
+                "encrypted": self.encryption_enabled,
+                "prompt_length": prompt.len(),
+                "encrypted_length": encrypted_prompt.len(),
+            }),
+            encrypted_prompt,
+            timestamp: SystemTime::now(),
+        })
     }
     
     /// Validate prompt content
@@ -73,25 +101,20 @@ impl PromptAgent {
         Ok(())
     }
     
-    /// Encrypt prompt using KMS
+    /// Seal a prompt with the context's key
     async fn encrypt_prompt(&self, prompt: &str) -> DreasResult<Vec<u8>> {
-        // TODO: Implement actual KMS encryption
-        // For now, return a placeholder
-        Ok(format!("ENCRYPTED:{}", prompt).as_bytes().to_vec())
+        self.key_provider.encrypt(&self.context.encryption_key_id, prompt.as_bytes()).await
     }
     
     /// Create audit log entry for prompt processing
     async fn audit_prompt_processing(&self, original_prompt: &str, encrypted_prompt: &[u8]) -> DreasResult<()> {
-        let audit_entry = serde_json::json!({
-            "agent_id": self.id,
-            "action": "prompt_processed",
-            "timestamp": SystemTime::now(),
-            "prompt_length": original_prompt.len(),
-            "encrypted_length": encrypted_prompt.len()
-        });
+        let audit_entry = self.context.audit_entry(actions::PROMPT_PROCESSED, format!("agent:{}", self.id), AuditResult::Success)
+            .with_metadata("prompt_length", original_prompt.len().to_string())
+            .with_metadata("encrypted_length", encrypted_prompt.len().to_string())
+            .with_metadata("encrypted", self.encryption_enabled.to_string());
         
-        tracing::info!("Prompt processing audit: {}", audit_entry);
-        Ok(())
+        tracing::info!("Prompt processed by agent {}: {} bytes", self.id, original_prompt.len());
+        self.audit_logger.record(audit_entry).await
     }
     
     /// Get agent ID
@@ -8,8 +8,13 @@
 
 use crate::{DreasResult, DreasError};
 use super::shared::AgentContext;
+use crate::security::audit::{actions, AuditHandle, AuditLogger, AuditResult};
+use crate::security::key_provider::KeyProvider;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
 use std::time::SystemTime;
+use tokio::sync::Mutex;
 use uuid::Uuid;
 
 /// A secure response agent that processes and decrypts LLM responses
@@ -18,12 +23,15 @@ pub struct ResponseAgent {
     id: Uuid,
     context: AgentContext,
     encryption_enabled: bool,
+    key_provider: Arc<dyn KeyProvider>,
+    audit_logger: AuditHandle,
 }
 
 /// Response processing result
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ResponseResult {
     pub agent_id: Uuid,
+    /// SHA-256 of the decrypted response, hex-encoded
     pub response_hash: String,
     pub decrypted_response: String,
     pub timestamp: SystemTime,
@@ -31,22 +39,32 @@ pub struct ResponseResult {
 }
 
 impl ResponseAgent {
-    /// Create a new response agent
-    pub fn new(context: AgentContext) -> Self {
+    /// Create a new response agent opening responses with the context's key from the provider
+    pub fn new(context: AgentContext, key_provider: Arc<dyn KeyProvider>) -> Self {
         Self {
             id: Uuid::new_v4(),
             context,
             encryption_enabled: true,
+            key_provider,
+            audit_logger: AuditHandle::detached(),
         }
     }
     
+    /// Record every processed response in the audit log
+    pub fn with_audit_logger(mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> Self {
+        self.audit_logger = AuditHandle::new(audit_logger);
+        self
+    }
+    
     /// Process a response securely
-    pub async fn process_response(&self, response: String) -> DreasResult<String> {
+    ///
+    /// The response is sealed with the context's key, or plain UTF-8 text if encryption is disabled.
+    pub async fn process_response(&self, response: Vec<u8>) -> DreasResult<ResponseResult> {
         // Decrypt response if encryption is enabled
         let decrypted_response = if self.encryption_enabled {
             self.decrypt_response(&response).await?
         } else {
-            response.clone()
+            Self::response_text(response.clone())?
         };
         
         // Validate response
@@ -55,8 +73,19 @@ impl ResponseAgent {
         // Create audit log entry
         self.audit_response_processing(&response, &decrypted_response).await?;
         
-        // Return processed response
-        Ok(format!("Processed response: {}", decrypted_response))
+        Ok(ResponseResult {
+            agent_id: self.id,
+            response_hash: hex::encode(Sha256::digest(decrypted_response.as_bytes())),
+            metadata: serde_json::json!({
+                "session_id": self.context.session_id,
+                "encryption_key_id": self.context.encryption_key_id,
+                "encrypted": self.encryption_enabled,
+                "encrypted_length": response.len(),
+                "decrypted_length": decrypted_response.len(),
+            }),
+            decrypted_response,
+            timestamp: SystemTime::now(),
+        })
     }
     
     /// Validate response content
@@ -73,29 +102,27 @@ impl ResponseAgent {
         Ok(())
     }
     
-    /// Decrypt response using KMS
-    async fn decrypt_response(&self, encrypted_response: &str) -> DreasResult<String> {
-        // TODO: Implement actual KMS decryption
-        // For now, return a placeholder
-        if encrypted_response.starts_with("ENCRYPTED:") {
-            Ok(encrypted_response.strip_prefix("ENCRYPTED:").unwrap().to_string())
-        } else {
-            Err(DreasError::AgentCoordination("Invalid encrypted response format".to_string()))
-        }
+    /// Open a response with the context's key
+    async fn decrypt_response(&self, encrypted_response: &[u8]) -> DreasResult<String> {
+        let plaintext = self.key_provider.decrypt(&self.context.encryption_key_id, encrypted_response).await?;
+        Self::response_text(plaintext)
+    }
+    
+    /// Read response bytes as text
+    fn response_text(response: Vec<u8>) -> DreasResult<String> {
+        String::from_utf8(response)
+            .map_err(|_| DreasError::AgentCoordination("Response is not valid UTF-8".to_string()))
     }
     
     /// Create audit log entry for response processing
-    async fn audit_response_processing(&self, encrypted_response: &str, decrypted_response: &str) -> DreasResult<()> {
-        let audit_entry = serde_json::json!({
-            "agent_id": self.id,
-            "action": "response_processed",
-            "timestamp": SystemTime::now(),
-            "encrypted_length": encrypted_response.len(),
-            "decrypted_length": decrypted_response.len()
-        });
+    async fn audit_response_processing(&self, encrypted_response: &[u8], decrypted_response: &str) -> DreasResult<()> {
+        let audit_entry = self.context.audit_entry(actions::RESPONSE_PROCESSED, format!("agent:{}", self.id), AuditResult::Success)
+            .with_metadata("encrypted_length", encrypted_response.len().to_string())
+            .with_metadata("decrypted_length", decrypted_response.len().to_string())
+            .with_metadata("encrypted", self.encryption_enabled.to_string());
         
-        tracing::info!("Response processing audit: {}", audit_entry);
-        Ok(())
+        tracing::info!("Response processed by agent {}: {} bytes", self.id, decrypted_response.len());
+        self.audit_logger.record(audit_entry).await
     }
     
     /// Get agent ID
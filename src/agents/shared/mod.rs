@@ -9,6 +9,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
+use crate::security::TenantId;
+use crate::security::audit::{AuditEntry, AuditResult};
 
 /// Context information shared between agents
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,7 +18,10 @@ pub struct AgentContext {
     pub session_id: Uuid,
     pub user_id: Option<String>,
     pub metadata: HashMap<String, String>,
+    /// Key prompts and responses are sealed with, as named to the agents' key provider
     pub encryption_key_id: String,
+    #[serde(default)]
+    pub tenant_id: TenantId,
 }
 
 /// Agent status enumeration
@@ -47,9 +52,16 @@ impl AgentContext {
             user_id: None,
             metadata: HashMap::new(),
             encryption_key_id,
+            tenant_id: TenantId::default(),
         }
     }
     
+    /// Set the tenant the request acts for
+    pub fn with_tenant(mut self, tenant_id: TenantId) -> Self {
+        self.tenant_id = tenant_id;
+        self
+    }
+    
     /// Set user ID
     pub fn with_user_id(mut self, user_id: String) -> Self {
         self.user_id = Some(user_id);
@@ -61,6 +73,19 @@ impl AgentContext {
         self.metadata.insert(key, value);
         self
     }
+    
+    /// Start an audit entry attributed to this context's user, session and tenant
+    pub fn audit_entry(&self, action: &str, resource: String, result: AuditResult) -> AuditEntry {
+        let entry = AuditEntry::new(action, resource, result)
+            .with_session(self.session_id.to_string())
+            .with_tenant(self.tenant_id.clone())
+            .with_metadata("encryption_key_id", &self.encryption_key_id);
+        
+        match &self.user_id {
+            Some(user_id) => entry.with_user(user_id),
+            None => entry,
+        }
+    }
 }
 
 impl Default for AgentConfig {
@@ -5,23 +5,44 @@
 //! 
 //! Main entry point for the DREAS API service
 
-use dreas::{config::AppConfig, services::ApiService};
+use dreas::{
+    config::AppConfig,
+    security::{
+        anomaly::AnomalyDetector,
+        audit::AuditHandle,
+        policy::PolicyEngine,
+        redaction::{self, Redactor},
+        token::TokenService,
+        AuditLogger, IdentityManager,
+    },
+    services::{ApiService, ObserverService},
+};
 use std::env;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
 use tracing::{info, error};
+use tracing_subscriber::{filter::LevelFilter, prelude::*};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
-    
-    info!("Starting DREAS API Service");
-    
     // Load configuration
     let config_path = env::args()
         .nth(1)
         .unwrap_or_else(|| "config/config.toml".to_string());
+    let loaded_config = AppConfig::from_file(&config_path);
+    
+    // Initialize tracing, redacting personal data with the configured rules
+    let redaction_config = loaded_config.as_ref()
+        .map(|config| config.security.redaction.clone())
+        .unwrap_or_default();
+    let redactor = Arc::new(Redactor::from_config(&redaction_config)?);
+    tracing_subscriber::registry()
+        .with(redaction::layer(redactor.clone()).with_filter(LevelFilter::INFO))
+        .init();
     
-    let config = match AppConfig::from_file(&config_path) {
+    info!("Starting DREAS API Service");
+    
+    let config = match loaded_config {
         Ok(config) => {
             info!("Configuration loaded from: {}", config_path);
             config
@@ -41,6 +62,72 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Create API service
     let mut api_service = ApiService::new(config.api_port);
+    api_service.set_redactor(redactor.clone());
+    api_service.set_trusted_proxies(&config.security.trusted_proxies)?;
+    
+    // Verify access tokens offline against the token signing keys
+    if let Some(token_service) = TokenService::from_config(&config.security.tokens).await? {
+        api_service.set_token_verification(
+            token_service.jwks(),
+            config.security.tokens.issuer.clone(),
+            config.security.tokens.audience.clone(),
+        )?;
+        info!("Access token verification enabled");
+    }
+    
+    // Keep audit entries across restarts, continuing the chain already on disk
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::from_config(&config, "api_service", redactor).await?));
+    let audit_handle = AuditHandle::new(audit_logger.clone());
+    
+    // Sign Merkle tree heads over the audit log for external auditors
+    if let Some(checkpoint_config) = &config.security.audit_checkpoints {
+        let period = std::time::Duration::from_secs(checkpoint_config.interval_minutes.max(1) * 60);
+        tokio::spawn(audit_handle.clone().run_checkpoints(period));
+        info!("Audit checkpoints signed every {} minutes", checkpoint_config.interval_minutes);
+    }
+    
+    // Purge expired audit entries daily, keeping those under legal hold
+    tokio::spawn(audit_handle.run_retention(std::time::Duration::from_secs(24 * 60 * 60)));
+    let observer = Arc::new(Mutex::new(ObserverService::new()));
+    
+    // Watch the audit log for suspicious patterns, with baselines learned from the retained entries
+    if config.security.anomaly_detection.enabled {
+        let detector = AnomalyDetector::from_config(&config.security.anomaly_detection)?
+            .with_observer(observer.clone());
+        tokio::spawn(detector.watch(audit_logger.clone()));
+        info!("Anomaly detection enabled");
+    }
+    
+    // Resolve service account API keys to principals and throttle failed logins
+    let mut identity_manager = IdentityManager::from_config(&config.security.password)?
+        .with_mfa_config(&config.security.mfa)?
+        .with_api_key_config(config.security.api_keys.clone())
+        .with_lockout_config(&config.security.lockout)
+        .with_session_config(&config.security.sessions)?
+        .with_impersonation_config(&config.security.impersonation)
+        .with_audit_logger(audit_logger.clone())
+        .with_observer(observer);
+    
+    // Evaluate access policies, recording every decision in the audit log
+    if let Some(policy_config) = &config.security.policies {
+        let policy_engine = Arc::new(PolicyEngine::from_path(&policy_config.path)?.with_audit_logger(audit_logger.clone()));
+        
+        identity_manager = identity_manager.with_policy_engine(policy_engine.clone());
+        api_service.set_policy_engine(policy_engine);
+        info!("Access policies loaded from: {}", policy_config.path);
+    }
+    
+    api_service.set_identity_manager(Arc::new(RwLock::new(identity_manager)));
+    
+    // Let auditors search the audit log and security tooling follow it as it's written
+    api_service.enable_audit_search(audit_logger.clone()).await?;
+    api_service.enable_audit_streaming(audit_logger).await?;
+    
+    // Let the identity provider manage users and groups over SCIM
+    if let Some(scim_config) = &config.security.scim {
+        api_service.enable_scim(&scim_config.base_url).await?;
+        info!("SCIM provisioning enabled at {}/scim/v2", scim_config.base_url);
+    }
     
     // Register default endpoints
     register_default_endpoints(&mut api_service).await?;
@@ -95,5 +182,17 @@ async fn register_default_endpoints(api_service: &mut ApiService) -> Result<(),
     
     api_service.register_endpoint(stats_endpoint).await?;
     
+    // JWKS endpoint for offline access token verification
+    let jwks_endpoint = ApiEndpoint {
+        path: "/.well-known/jwks.json".to_string(),
+        method: HttpMethod::GET,
+        handler: "jwks".to_string(),
+        requires_auth: false,
+        rate_limit: Some(100),
+        timeout_seconds: Some(5),
+    };
+    
+    api_service.register_endpoint(jwks_endpoint).await?;
+    
     Ok(())
 }
@@ -0,0 +1,112 @@
+//! Audit Report Binary
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Generates a compliance report for a period from the audit file sink, in JSON, CSV
+//! or HTML, optionally with a detached signature so the report can be verified later
+
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use dreas::{
+    config::AppConfig,
+    security::{
+        audit_query::parse_time,
+        audit_report::{ReportFormat, ReportSignature, ReportTemplate},
+        audit_sink::FileAuditSink,
+        retention::DELETION_MANIFESTS_FILE,
+        token::{KmsTokenSigner, LocalTokenSigner, TokenSigner},
+        AuditLogger, KmsClient,
+    },
+};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Generate an audit report for a period
+#[derive(Debug, Parser)]
+struct Args {
+    /// Configuration file naming the audit file directory
+    #[arg(long, default_value = "config/config.toml")]
+    config: String,
+    /// Service whose audit log to read: api_service or coordinator
+    #[arg(long, default_value = "api_service")]
+    component: String,
+    /// Directory of the service's audit files, overriding the configuration
+    #[arg(long)]
+    directory: Option<PathBuf>,
+    /// Report template: activity, access_review, key_usage, escrow_recoveries or failed_logins
+    #[arg(long)]
+    template: ReportTemplate,
+    /// Start of the period, inclusive, as RFC 3339 or YYYY-MM-DD
+    #[arg(long, value_parser = |value: &str| parse_time(value, false))]
+    from: DateTime<Utc>,
+    /// End of the period, inclusive, as RFC 3339 or YYYY-MM-DD for the whole day
+    #[arg(long, value_parser = |value: &str| parse_time(value, true))]
+    to: DateTime<Utc>,
+    /// Output format: json, csv or html
+    #[arg(long, default_value = "html")]
+    format: ReportFormat,
+    /// File to write the report to
+    #[arg(long)]
+    output: String,
+    /// KMS key version that signed the deletion manifests, overriding the audit checkpoint key
+    #[arg(long)]
+    manifest_key_uri: Option<String>,
+    /// Sign the report with a hex-encoded Ed25519 seed file, writing the signature to `<output>.sig`
+    #[arg(long, conflicts_with = "kms_signing_key_uri")]
+    signing_key: Option<String>,
+    /// Sign the report with an EC_SIGN_ED25519 KMS key version instead
+    #[arg(long)]
+    kms_signing_key_uri: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt().with_writer(io::stderr).init();
+    
+    let args = Args::parse();
+    
+    let config = AppConfig::from_file(&args.config);
+    let directory = match args.directory {
+        Some(directory) => directory,
+        None => config.as_ref()
+            .map_err(|e| e.to_string())?
+            .security
+            .audit_sinks
+            .component_directory(&args.component)
+            .ok_or("No audit file directory is configured; pass --directory")?,
+    };
+    let manifest_key_uri = args.manifest_key_uri.or_else(|| {
+        config.as_ref().ok()?.security.audit_checkpoints.as_ref().map(|checkpoints| checkpoints.kms_signing_key_uri.clone())
+    });
+    
+    // Restoring verifies the chain, with purged entries checked against the deletion
+    // manifests, so reports are never built from a tampered log
+    let mut audit_logger = AuditLogger::new(u32::MAX)
+        .with_deletion_manifests(directory.join(DELETION_MANIFESTS_FILE))?;
+    if let Some(key_uri) = &manifest_key_uri {
+        let signer = KmsTokenSigner::new(KmsClient::from_key_version_uri(key_uri)?).await?;
+        audit_logger = audit_logger.with_checkpoint_signer(Arc::new(signer));
+    }
+    audit_logger.restore(FileAuditSink::read_entries(&directory)?)?;
+    
+    let report = audit_logger.generate_report(args.template, args.from, args.to)?;
+    let rendered = report.render(args.format)?;
+    std::fs::write(&args.output, &rendered)?;
+    tracing::info!("Wrote {} with {} rows to {}", report.title, report.rows.len(), args.output);
+    
+    let signer: Option<Box<dyn TokenSigner>> = match (&args.signing_key, &args.kms_signing_key_uri) {
+        (Some(path), _) => Some(Box::new(LocalTokenSigner::from_seed_file(path)?)),
+        (None, Some(key_uri)) => Some(Box::new(KmsTokenSigner::new(KmsClient::from_key_version_uri(key_uri)?).await?)),
+        (None, None) => None,
+    };
+    if let Some(signer) = signer {
+        let signature = ReportSignature::sign(&report, args.format, rendered.as_bytes(), signer.as_ref()).await?;
+        let signature_path = format!("{}.sig", args.output);
+        std::fs::write(&signature_path, serde_json::to_string_pretty(&signature)?)?;
+        tracing::info!("Signed report {} with key {} in {}", report.report_id, signature.key_id, signature_path);
+    }
+    
+    Ok(())
+}
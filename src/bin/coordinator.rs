@@ -8,24 +8,42 @@
 use dreas::{
     config::AppConfig,
     agents::{AgentCoordinator, PromptAgent, ResponseAgent, shared::AgentContext},
+    security::{
+        anomaly::AnomalyDetector,
+        audit::AuditHandle,
+        key_provider::{KeyProvider, KmsKeyProvider},
+        redaction::{self, Redactor},
+        AuditLogger, KmsClient,
+    },
+    services::ObserverService,
 };
 use std::env;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use tracing::{info, error};
+use tracing_subscriber::{filter::LevelFilter, prelude::*};
 use uuid::Uuid;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
-    
-    info!("Starting DREAS Agent Coordinator");
-    
     // Load configuration
     let config_path = env::args()
         .nth(1)
         .unwrap_or_else(|| "config/config.toml".to_string());
+    let loaded_config = AppConfig::from_file(&config_path);
     
-    let config = match AppConfig::from_file(&config_path) {
+    // Initialize tracing, redacting personal data with the configured rules
+    let redaction_config = loaded_config.as_ref()
+        .map(|config| config.security.redaction.clone())
+        .unwrap_or_default();
+    let redactor = Arc::new(Redactor::from_config(&redaction_config)?);
+    tracing_subscriber::registry()
+        .with(redaction::layer(redactor.clone()).with_filter(LevelFilter::INFO))
+        .init();
+    
+    info!("Starting DREAS Agent Coordinator");
+    
+    let config = match loaded_config {
         Ok(config) => {
             info!("Configuration loaded from: {}", config_path);
             config
@@ -50,8 +68,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let session_id = Uuid::new_v4();
     let context = AgentContext::new(session_id, config.gcp.kms_key_uri.clone());
     
-    let prompt_agent = PromptAgent::new(context.clone());
-    let response_agent = ResponseAgent::new(context);
+    // Agents record every prompt and response they process in a shared audit log, kept,
+    // checkpointed and purged the way the API service keeps its own
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::from_config(&config, "coordinator", redactor).await?));
+    let audit_handle = AuditHandle::new(audit_logger.clone());
+    if let Some(checkpoint_config) = &config.security.audit_checkpoints {
+        let period = std::time::Duration::from_secs(checkpoint_config.interval_minutes.max(1) * 60);
+        tokio::spawn(audit_handle.clone().run_checkpoints(period));
+    }
+    tokio::spawn(audit_handle.run_retention(std::time::Duration::from_secs(24 * 60 * 60)));
+    
+    // Agents' KMS decrypts are logged here, so unusual volumes are watched for here
+    if config.security.anomaly_detection.enabled {
+        let detector = AnomalyDetector::from_config(&config.security.anomaly_detection)?
+            .with_observer(Arc::new(Mutex::new(ObserverService::new())));
+        tokio::spawn(detector.watch(audit_logger.clone()));
+        info!("Anomaly detection enabled");
+    }
+    
+    // Agents seal prompts and open responses with the configured KMS key
+    let key_provider: Arc<dyn KeyProvider> = Arc::new(KmsKeyProvider::new(
+        KmsClient::from_key_version_uri(&config.gcp.kms_key_uri)?.with_audit_logger(audit_logger.clone())
+    ));
+    let prompt_agent = PromptAgent::new(context.clone(), key_provider.clone()).with_audit_logger(audit_logger.clone());
+    let response_agent = ResponseAgent::new(context.clone(), key_provider.clone()).with_audit_logger(audit_logger);
     
     // Register agents
     let prompt_agent_id = coordinator.register_prompt_agent(prompt_agent).await?;
@@ -73,13 +113,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Process prompt
     match coordinator.process_prompt(prompt_agent_id, sample_prompt.to_string()).await {
-        Ok(result) => info!("Prompt processing result: {}", result),
+        Ok(result) => info!("Prompt sealed: {} bytes, hash {}", result.encrypted_prompt.len(), result.prompt_hash),
         Err(e) => error!("Failed to process prompt: {}", e),
     }
     
-    // Process response
-    match coordinator.process_response(response_agent_id, sample_response.to_string()).await {
-        Ok(result) => info!("Response processing result: {}", result),
+    // Process response, sealed the way the LLM side would return it
+    let sealed_response = key_provider.encrypt(&context.encryption_key_id, sample_response.as_bytes()).await?;
+    match coordinator.process_response(response_agent_id, sealed_response).await {
+        Ok(result) => info!("Response opened: {} bytes, hash {}", result.decrypted_response.len(), result.response_hash),
         Err(e) => error!("Failed to process response: {}", e),
     }
     
@@ -3,9 +3,13 @@
 //! Author: Kiran Kumar Balijepalli
 //! Date: August 2025 
 
-use super::AppConfig;
+use super::{AnomalyConfig, ApiKeyConfig, AppConfig, AuditSinkConfig, ImpersonationConfig, LockoutConfig, MfaConfig, PasswordConfig, RedactionConfig, SessionConfig, TokenConfig};
 use crate::{DreasError, DreasResult};
+use crate::security::password::PasswordHashParams;
+use crate::security::audit_sink::{DEFAULT_MAX_FILE_AGE_HOURS, DEFAULT_MAX_FILE_BYTES};
 use config::{Config, ConfigError, File, FileFormat};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 impl AppConfig {
     /// Load configuration from TOML file
@@ -35,6 +39,22 @@ impl AppConfig {
                 enable_audit_logging: true,
                 enable_key_escrow: true,
                 audit_log_retention_days: 365,
+                audit_retention_categories: HashMap::new(),
+                password: PasswordConfig::default(),
+                tokens: TokenConfig::default(),
+                oidc: None,
+                mfa: MfaConfig::default(),
+                api_keys: ApiKeyConfig::default(),
+                policies: None,
+                lockout: LockoutConfig::default(),
+                sessions: SessionConfig::default(),
+                scim: None,
+                impersonation: ImpersonationConfig::default(),
+                audit_checkpoints: None,
+                audit_sinks: AuditSinkConfig::default(),
+                redaction: RedactionConfig::default(),
+                anomaly_detection: AnomalyConfig::default(),
+                trusted_proxies: Vec::new(),
             },
             api_port: 8080,
             log_level: "info".to_string(),
@@ -58,3 +78,154 @@ impl AppConfig {
         Ok(())
     }
 }
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            denylist_path: None,
+            hash_params: PasswordHashParams::default(),
+        }
+    }
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        Self {
+            issuer: "dreas".to_string(),
+            audience: "dreas-services".to_string(),
+            access_token_ttl_seconds: 900,
+            refresh_token_ttl_seconds: 30 * 24 * 3600,
+            signing_key_path: None,
+            kms_signing_key_uri: None,
+        }
+    }
+}
+
+impl Default for MfaConfig {
+    fn default() -> Self {
+        Self {
+            issuer: "DREAS".to_string(),
+            digits: 6,
+            step_seconds: 30,
+            skew_steps: 1,
+            recovery_code_count: 10,
+            step_up_window_seconds: 300,
+            step_up_permissions: vec![
+                "key_escrow".to_string(),
+                "key_recovery".to_string(),
+                "permission_change".to_string(),
+            ],
+        }
+    }
+}
+
+impl Default for ApiKeyConfig {
+    fn default() -> Self {
+        Self {
+            default_ttl_days: 90,
+            max_ttl_days: 365,
+            rotation_overlap_hours: 24,
+        }
+    }
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            max_failures_per_user: 5,
+            max_failures_per_ip: 20,
+            failure_window_seconds: 900,
+            lockout_seconds: 900,
+            free_failures: 2,
+            base_delay_ms: 500,
+            max_delay_seconds: 30,
+            spike_window_seconds: 60,
+            spike_threshold: 50,
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            idle_timeout_minutes: 30,
+            absolute_timeout_hours: 24,
+            max_sessions_per_user: 5,
+            bind_ip: false,
+            bind_user_agent: true,
+        }
+    }
+}
+
+impl Default for ImpersonationConfig {
+    fn default() -> Self {
+        Self {
+            max_duration_minutes: 60,
+            min_reason_length: 10,
+        }
+    }
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            denied_fields: [
+                "password", "secret", "token", "access_token", "refresh_token", "api_key",
+                "authorization", "prompt", "response", "plaintext",
+            ].iter().map(|field| field.to_string()).collect(),
+            hashed_fields: vec!["email".to_string()],
+            builtin_detectors: true,
+            detectors: HashMap::new(),
+            hash_key_path: None,
+        }
+    }
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_minutes: 60,
+            baseline_days: 14,
+            decrypt_volume_minimum: 100,
+            decrypt_volume_multiplier: 3.0,
+            new_login_ip: true,
+            recovery_attempt_threshold: 3,
+            key_admin_actions: vec![
+                "key_escrow".to_string(),
+                "key_recovery".to_string(),
+            ],
+            business_days: ["Mon", "Tue", "Wed", "Thu", "Fri"].iter().map(|day| day.to_string()).collect(),
+            business_hours_start: 8,
+            business_hours_end: 18,
+            utc_offset_hours: 0,
+            alert_cooldown_minutes: 60,
+        }
+    }
+}
+
+impl AuditSinkConfig {
+    /// Directory holding a component's audit files, if files are configured
+    ///
+    /// Each component writes its own chain, in a subdirectory named after it.
+    pub fn component_directory(&self, component: &str) -> Option<PathBuf> {
+        self.file_directory.as_ref().map(|directory| Path::new(directory).join(component))
+    }
+}
+
+impl Default for AuditSinkConfig {
+    fn default() -> Self {
+        Self {
+            file_directory: None,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_file_age_hours: DEFAULT_MAX_FILE_AGE_HOURS,
+            bigquery_dataset: None,
+            syslog: None,
+            mandatory: vec!["file".to_string()],
+        }
+    }
+}
@@ -3,7 +3,10 @@
 //! Author: Kiran Kumar Balijepalli
 //! Date: August 2025
 
+use crate::security::password::PasswordHashParams;
+use crate::security::siem::SiemFormat;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 
 mod config_impl;
@@ -25,6 +28,281 @@ pub struct SecurityConfig {
     pub enable_audit_logging: bool,
     pub enable_key_escrow: bool,
     pub audit_log_retention_days: u32,
+    /// Retention in days of audit entries by action, overriding `audit_log_retention_days`
+    #[serde(default)]
+    pub audit_retention_categories: HashMap<String, u32>,
+    #[serde(default)]
+    pub password: PasswordConfig,
+    #[serde(default)]
+    pub tokens: TokenConfig,
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+    #[serde(default)]
+    pub mfa: MfaConfig,
+    #[serde(default)]
+    pub api_keys: ApiKeyConfig,
+    #[serde(default)]
+    pub policies: Option<PolicyConfig>,
+    #[serde(default)]
+    pub lockout: LockoutConfig,
+    #[serde(default)]
+    pub sessions: SessionConfig,
+    #[serde(default)]
+    pub scim: Option<ScimConfig>,
+    #[serde(default)]
+    pub impersonation: ImpersonationConfig,
+    #[serde(default)]
+    pub audit_checkpoints: Option<AuditCheckpointConfig>,
+    #[serde(default)]
+    pub audit_sinks: AuditSinkConfig,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    #[serde(default)]
+    pub anomaly_detection: AnomalyConfig,
+    /// CIDR blocks of the proxies whose `X-Forwarded-For` hops are trusted
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+}
+
+/// Password policy and hashing settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordConfig {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub denylist_path: Option<String>,
+    #[serde(default)]
+    pub hash_params: PasswordHashParams,
+}
+
+/// OIDC relying-party settings for the corporate identity provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    #[serde(default = "default_username_claim")]
+    pub username_claim: String,
+    #[serde(default = "default_role_claim")]
+    pub role_claim: String,
+    /// Maps IdP claim values (e.g. group names) to DREAS role names
+    #[serde(default)]
+    pub role_mappings: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub default_roles: Vec<String>,
+    #[serde(default = "default_jwks_cache_ttl")]
+    pub jwks_cache_ttl_seconds: u64,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".to_string(), "email".to_string(), "profile".to_string()]
+}
+
+fn default_username_claim() -> String {
+    "email".to_string()
+}
+
+fn default_role_claim() -> String {
+    "groups".to_string()
+}
+
+fn default_jwks_cache_ttl() -> u64 {
+    3600
+}
+
+/// Access and refresh token settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenConfig {
+    pub issuer: String,
+    pub audience: String,
+    pub access_token_ttl_seconds: i64,
+    pub refresh_token_ttl_seconds: i64,
+    pub signing_key_path: Option<String>,
+    pub kms_signing_key_uri: Option<String>,
+}
+
+/// TOTP second factor and step-up settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaConfig {
+    pub issuer: String,
+    pub digits: u32,
+    pub step_seconds: u64,
+    pub skew_steps: u64,
+    pub recovery_code_count: usize,
+    /// How long a successful MFA verification satisfies step-up checks
+    pub step_up_window_seconds: i64,
+    /// Permissions that require a recent MFA verification on the session
+    pub step_up_permissions: Vec<String>,
+}
+
+/// Service account API key lifetimes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    pub default_ttl_days: i64,
+    pub max_ttl_days: i64,
+    /// How long the old key keeps working after a rotation
+    pub rotation_overlap_hours: i64,
+}
+
+/// Access policy files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyConfig {
+    /// A policy file, or a directory of `*.policy` files
+    pub path: String,
+}
+
+/// Failed-login throttling and lockout settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockoutConfig {
+    pub max_failures_per_user: u32,
+    pub max_failures_per_ip: u32,
+    /// Failures older than this no longer count towards a lockout
+    pub failure_window_seconds: i64,
+    pub lockout_seconds: i64,
+    /// Failures allowed before delays start
+    pub free_failures: u32,
+    /// Delay after the first counted failure; doubles with each further failure
+    pub base_delay_ms: i64,
+    pub max_delay_seconds: i64,
+    /// Failures across all accounts within this window that count as a spike
+    pub spike_window_seconds: i64,
+    pub spike_threshold: usize,
+}
+
+/// Session lifetime, storage and client binding settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// Persist sessions to this file; sessions are kept in memory when unset
+    pub path: Option<String>,
+    pub idle_timeout_minutes: i64,
+    pub absolute_timeout_hours: i64,
+    /// Oldest sessions are ended once a user exceeds this; 0 disables the cap
+    pub max_sessions_per_user: usize,
+    /// Reject requests from a different IP than the one that logged in
+    pub bind_ip: bool,
+    /// Reject requests from a different user agent than the one that logged in
+    pub bind_user_agent: bool,
+}
+
+/// Limits on support staff acting on behalf of users
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpersonationConfig {
+    /// Longest impersonation session that can be requested
+    pub max_duration_minutes: i64,
+    /// Shortest accepted justification for an impersonation session
+    pub min_reason_length: usize,
+}
+
+/// Signed Merkle checkpoints over the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditCheckpointConfig {
+    /// EC_SIGN_ED25519 KMS key version that signs tree heads
+    pub kms_signing_key_uri: String,
+    pub interval_minutes: u64,
+}
+
+/// Durable destinations for audit entries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditSinkConfig {
+    /// Append entries as JSON lines to files in this directory; disabled when unset
+    pub file_directory: Option<String>,
+    /// Start a new audit file once the current one reaches this size
+    pub max_file_bytes: u64,
+    /// Start a new audit file once the current one is this old
+    pub max_file_age_hours: i64,
+    /// Also stream entries to the `audit_logs` table of this BigQuery dataset
+    pub bigquery_dataset: Option<String>,
+    /// Also send entries to a SIEM's syslog collector
+    #[serde(default)]
+    pub syslog: Option<SyslogSinkConfig>,
+    /// Sinks ("file", "bigquery", "syslog") whose failures fail the audited operation
+    pub mandatory: Vec<String>,
+}
+
+/// Syslog collector receiving audit entries in a SIEM format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyslogSinkConfig {
+    /// Collector address as host:port
+    pub address: String,
+    /// Connect over TLS (RFC 5425) instead of plain TCP
+    #[serde(default)]
+    pub tls: bool,
+    /// PEM CA certificate to trust for the collector, in addition to the system roots
+    #[serde(default)]
+    pub ca_certificate_path: Option<String>,
+    #[serde(default = "default_syslog_format")]
+    pub format: SiemFormat,
+    /// HOSTNAME reported in messages; the nil value when unset
+    #[serde(default)]
+    pub hostname: Option<String>,
+    /// Give up connecting, or sending a message, after this many seconds
+    #[serde(default = "default_syslog_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+fn default_syslog_format() -> SiemFormat {
+    SiemFormat::Ocsf
+}
+
+fn default_syslog_timeout_seconds() -> u64 {
+    5
+}
+
+/// Redaction of personal data from audit metadata, logs and API error bodies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    /// Field names whose values are removed entirely, regardless of case
+    pub denied_fields: Vec<String>,
+    /// Field names whose values are replaced by a keyed hash, so they can still be correlated
+    pub hashed_fields: Vec<String>,
+    /// Detect emails, bearer tokens, card numbers and US social security numbers in any value
+    pub builtin_detectors: bool,
+    /// Further detectors by name, as regular expressions
+    #[serde(default)]
+    pub detectors: HashMap<String, String>,
+    /// File containing the hex-encoded hash key; a random key is used if unset
+    #[serde(default)]
+    pub hash_key_path: Option<String>,
+}
+
+/// Detection of suspicious patterns in the audit log, raised as observer alerts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomalyConfig {
+    pub enabled: bool,
+    /// Length of the windows decrypts and escrow recoveries are counted over
+    pub window_minutes: i64,
+    /// How far back a user's earlier activity counts towards their baseline
+    pub baseline_days: i64,
+    /// Fewest decrypts in a window that count as unusual, however low the user's baseline
+    pub decrypt_volume_minimum: u64,
+    /// Multiple of a user's average decrypts per window that counts as unusual
+    pub decrypt_volume_multiplier: f64,
+    /// Flag successful logins from an IP address the user hasn't used within the baseline
+    pub new_login_ip: bool,
+    /// Escrow recovery attempts by one requester within a window that count as repeated
+    pub recovery_attempt_threshold: usize,
+    /// Actions that count as key administration
+    pub key_admin_actions: Vec<String>,
+    /// Working days, as `Mon` to `Sun`
+    pub business_days: Vec<String>,
+    /// First hour of the working day
+    pub business_hours_start: u32,
+    /// Hour the working day ends
+    pub business_hours_end: u32,
+    /// Offset of business hours from UTC
+    pub utc_offset_hours: i32,
+    /// The same anomaly isn't raised again for the same subject for this long
+    pub alert_cooldown_minutes: i64,
+}
+
+/// SCIM 2.0 provisioning endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScimConfig {
+    /// Externally visible base URL of the API, used in resource locations
+    pub base_url: String,
 }
 
 /// Main configuration structure
@@ -14,6 +14,9 @@ pub enum DreasError {
     #[error("KMS decryption error: {0}")]
     KmsDecryption(String),
     
+    #[error("KMS signing error: {0}")]
+    KmsSigning(String),
+    
     #[error("Storage error: {0}")]
     Storage(String),
     
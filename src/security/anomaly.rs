@@ -0,0 +1,457 @@
+//! Anomaly detection over the audit log
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Watches audit entries as they're logged for patterns worth a second look: a user
+//! decrypting far more than usual, a login from an address the user hasn't used
+//! before, repeated escrow recovery attempts and key administration outside business
+//! hours. Fixed rules combine with per-user baselines learned from the log itself, and
+//! each finding is raised as an `ObserverService` alert carrying the entries behind it.
+
+use crate::{DreasResult, DreasError};
+use crate::config::AnomalyConfig;
+use crate::services::observer::{AlertSeverity, ObserverService};
+use super::audit::{actions, AuditEntry, AuditLogger, AuditResult};
+use super::audit_query::AuditFilter;
+use super::audit_stream::{AuditEvent, AuditSubscription};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Timelike, Utc, Weekday};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Most audit entries attached to an alert as evidence
+pub const MAX_EVIDENCE: usize = 20;
+
+/// Kind of suspicious pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnomalyKind {
+    /// A user decrypted far more within a window than their baseline allows
+    UnusualDecryptVolume,
+    /// A user logged in from an IP address they haven't used within the baseline
+    NewLoginIp,
+    /// Escrow recovery was attempted repeatedly within a window
+    RepeatedEscrowRecovery,
+    /// Keys were administered outside business hours
+    AfterHoursKeyAdministration,
+}
+
+/// Suspicious pattern found in the audit log
+#[derive(Debug, Clone)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    /// User the anomaly concerns, or the acting principal, session or resource where no user is recorded
+    pub subject: String,
+    pub message: String,
+    /// Figures behind the finding, such as counts and baselines
+    pub details: HashMap<String, String>,
+    /// Audit entries behind the finding, oldest first
+    pub evidence: Vec<AuditEntry>,
+}
+
+/// Finds suspicious patterns in audit entries and raises them as observer alerts
+///
+/// Entries are judged by their own timestamps, so replaying old entries through `learn`
+/// builds the same baselines as watching them live.
+#[derive(Debug, Clone)]
+pub struct AnomalyDetector {
+    window: Duration,
+    baseline: Duration,
+    decrypt_volume_minimum: u64,
+    decrypt_volume_multiplier: f64,
+    new_login_ip: bool,
+    recovery_attempt_threshold: usize,
+    key_admin_actions: Vec<String>,
+    business_days: Vec<Weekday>,
+    business_hours: Range<u32>,
+    business_offset: FixedOffset,
+    cooldown: Duration,
+    decrypts: HashMap<String, DecryptHistory>,
+    login_addresses: HashMap<String, HashMap<String, DateTime<Utc>>>,
+    recoveries: HashMap<String, VecDeque<AuditEntry>>,
+    last_raised: HashMap<(AnomalyKind, String), DateTime<Utc>>,
+    observer: Option<Arc<Mutex<ObserverService>>>,
+}
+
+/// Decrypts by one subject, counted per window
+#[derive(Debug, Clone)]
+struct DecryptHistory {
+    first_window: i64,
+    counts: BTreeMap<i64, u64>,
+    /// Latest decrypts of the current window
+    recent: VecDeque<AuditEntry>,
+}
+
+impl AnomalyKind {
+    /// Identifier of the kind, as recorded in alert metadata
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyKind::UnusualDecryptVolume => "unusual_decrypt_volume",
+            AnomalyKind::NewLoginIp => "new_login_ip",
+            AnomalyKind::RepeatedEscrowRecovery => "repeated_escrow_recovery",
+            AnomalyKind::AfterHoursKeyAdministration => "after_hours_key_administration",
+        }
+    }
+    
+    /// Name of the alert raised for the kind
+    pub fn alert_name(&self) -> &'static str {
+        match self {
+            AnomalyKind::UnusualDecryptVolume => "Unusual Decrypt Volume",
+            AnomalyKind::NewLoginIp => "Login From New IP Address",
+            AnomalyKind::RepeatedEscrowRecovery => "Repeated Escrow Recovery Attempts",
+            AnomalyKind::AfterHoursKeyAdministration => "After-Hours Key Administration",
+        }
+    }
+    
+    /// Severity of the alert raised for the kind
+    pub fn severity(&self) -> AlertSeverity {
+        match self {
+            AnomalyKind::UnusualDecryptVolume | AnomalyKind::RepeatedEscrowRecovery => AlertSeverity::High,
+            AnomalyKind::NewLoginIp | AnomalyKind::AfterHoursKeyAdministration => AlertSeverity::Medium,
+        }
+    }
+}
+
+impl AnomalyDetector {
+    /// Create a detector with the default settings
+    pub fn new() -> Self {
+        Self::from_config(&AnomalyConfig::default()).expect("default anomaly detection settings are valid")
+    }
+    
+    /// Create a detector from configuration
+    pub fn from_config(config: &AnomalyConfig) -> DreasResult<Self> {
+        if config.window_minutes <= 0 || config.baseline_days < 0 || config.alert_cooldown_minutes < 0 {
+            return Err(DreasError::Configuration(
+                "Anomaly detection windows must be positive and baselines and cooldowns not negative".to_string(),
+            ));
+        }
+        if config.business_hours_start > config.business_hours_end || config.business_hours_end > 24 {
+            return Err(DreasError::Configuration(format!(
+                "Invalid business hours {}-{}", config.business_hours_start, config.business_hours_end
+            )));
+        }
+        
+        let business_days = config.business_days.iter()
+            .map(|day| day.parse::<Weekday>()
+                .map_err(|_| DreasError::Configuration(format!("Invalid business day {:?}", day))))
+            .collect::<DreasResult<Vec<_>>>()?;
+        let business_offset = FixedOffset::east_opt(config.utc_offset_hours * 3600)
+            .ok_or_else(|| DreasError::Configuration(format!("Invalid UTC offset {}", config.utc_offset_hours)))?;
+        
+        Ok(Self {
+            window: Duration::minutes(config.window_minutes),
+            baseline: Duration::days(config.baseline_days),
+            decrypt_volume_minimum: config.decrypt_volume_minimum,
+            decrypt_volume_multiplier: config.decrypt_volume_multiplier,
+            new_login_ip: config.new_login_ip,
+            recovery_attempt_threshold: config.recovery_attempt_threshold,
+            key_admin_actions: config.key_admin_actions.clone(),
+            business_days,
+            business_hours: config.business_hours_start..config.business_hours_end,
+            business_offset,
+            cooldown: Duration::minutes(config.alert_cooldown_minutes),
+            decrypts: HashMap::new(),
+            login_addresses: HashMap::new(),
+            recoveries: HashMap::new(),
+            last_raised: HashMap::new(),
+            observer: None,
+        })
+    }
+    
+    /// Raise anomalies as alerts on an observer
+    pub fn with_observer(mut self, observer: Arc<Mutex<ObserverService>>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+    
+    /// Build baselines from entries already logged, without raising anything
+    pub fn learn<'a>(&mut self, entries: impl IntoIterator<Item = &'a AuditEntry>) {
+        for entry in entries {
+            self.inspect(entry);
+        }
+        self.last_raised.clear();
+    }
+    
+    /// Check an entry against every rule, updating the baselines
+    ///
+    /// An anomaly already raised for the same subject within the cooldown isn't returned again.
+    pub fn inspect(&mut self, entry: &AuditEntry) -> Vec<Anomaly> {
+        if entry.purged {
+            return Vec::new();
+        }
+        
+        let found = [
+            self.check_decrypt_volume(entry),
+            self.check_login_address(entry),
+            self.check_escrow_recoveries(entry),
+            self.check_key_administration(entry),
+        ];
+        found.into_iter()
+            .flatten()
+            .filter(|anomaly| self.should_raise(anomaly, entry.timestamp))
+            .collect()
+    }
+    
+    /// Check an entry and raise an alert for each anomaly it reveals
+    pub async fn observe(&mut self, entry: &AuditEntry) -> DreasResult<Vec<Anomaly>> {
+        let anomalies = self.inspect(entry);
+        
+        if let Some(observer) = &self.observer {
+            let mut observer = observer.lock().await;
+            for anomaly in &anomalies {
+                let mut metadata = anomaly.details.clone();
+                metadata.insert("anomaly".to_string(), anomaly.kind.as_str().to_string());
+                metadata.insert("subject".to_string(), anomaly.subject.clone());
+                
+                observer.create_alert_with_evidence(
+                    anomaly.kind.alert_name().to_string(),
+                    anomaly.kind.severity(),
+                    anomaly.message.clone(),
+                    metadata,
+                    anomaly.evidence.clone(),
+                ).await?;
+            }
+        }
+        
+        Ok(anomalies)
+    }
+    
+    /// Watch an audit log, with baselines learned from the entries it retains, raising alerts for what it reveals
+    pub async fn watch(mut self, audit_logger: Arc<Mutex<AuditLogger>>) {
+        let subscription = {
+            let audit_logger = audit_logger.lock().await;
+            self.learn(audit_logger.entries());
+            audit_logger.subscribe(AuditFilter::All)
+        };
+        // Holding on to the logger would keep the subscription open after everyone else is done with it
+        drop(audit_logger);
+        self.run(subscription).await;
+    }
+    
+    /// Watch a subscription until the audit logger is gone, raising alerts for what it reveals
+    ///
+    /// State no rule needs any longer is swept away once a window.
+    pub async fn run(mut self, mut subscription: AuditSubscription) {
+        let period = self.window.to_std().unwrap_or(std::time::Duration::from_secs(60));
+        let mut sweeps = tokio::time::interval(period);
+        loop {
+            tokio::select! {
+                _ = sweeps.tick() => self.sweep(Utc::now()),
+                event = subscription.next() => match event {
+                    Some(AuditEvent::Entry(entry)) => {
+                        if let Err(e) = self.observe(&entry).await {
+                            tracing::error!("Failed to raise anomaly alert: {}", e);
+                        }
+                    }
+                    Some(AuditEvent::Lagged { missed }) => {
+                        tracing::warn!("Anomaly detection fell behind and skipped {} audit entries", missed);
+                    }
+                    None => break,
+                },
+            }
+        }
+    }
+    
+    /// Forget what no rule can use any longer as of `now`
+    ///
+    /// Decrypt histories and login addresses outlive their last use by the baseline, escrow
+    /// recovery attempts by a window and raised anomalies by the cooldown.
+    pub fn sweep(&mut self, now: DateTime<Utc>) {
+        let window_seconds = self.window.num_seconds();
+        let oldest = now.timestamp().div_euclid(window_seconds) - self.baseline.num_seconds() / window_seconds;
+        self.decrypts.retain(|_, history| history.counts.last_key_value().is_some_and(|(window, _)| *window >= oldest));
+        
+        let baseline = self.baseline;
+        self.login_addresses.retain(|_, known| {
+            known.retain(|_, last_seen| now - *last_seen <= baseline);
+            !known.is_empty()
+        });
+        
+        let window = self.window;
+        self.recoveries.retain(|_, attempts| {
+            attempts.retain(|attempt| now - attempt.timestamp < window);
+            !attempts.is_empty()
+        });
+        
+        let cooldown = self.cooldown;
+        self.last_raised.retain(|_, last| now - *last < cooldown);
+    }
+    
+    /// Number of users, addresses and other subjects the detector holds state for
+    pub fn tracked_subjects(&self) -> usize {
+        self.decrypts.len() + self.login_addresses.len() + self.recoveries.len() + self.last_raised.len()
+    }
+    
+    /// Flag a subject decrypting more in a window than the minimum and a multiple of their average
+    ///
+    /// Decrypts are counted against the user, or for services decrypting outside any user's
+    /// request against the acting principal, the session and failing those the key.
+    fn check_decrypt_volume(&mut self, entry: &AuditEntry) -> Option<Anomaly> {
+        if entry.action != actions::DATA_DECRYPTION {
+            return None;
+        }
+        let subject = entry.user_id.clone()
+            .or_else(|| entry.actor_id.clone())
+            .or_else(|| entry.session_id.clone())
+            .unwrap_or_else(|| entry.resource.clone());
+        
+        let window_seconds = self.window.num_seconds();
+        let window_of = |time: &DateTime<Utc>| time.timestamp().div_euclid(window_seconds);
+        let window = window_of(&entry.timestamp);
+        let oldest = window - self.baseline.num_seconds() / window_seconds;
+        
+        let history = self.decrypts.entry(subject.clone()).or_insert_with(|| DecryptHistory {
+            first_window: window,
+            counts: BTreeMap::new(),
+            recent: VecDeque::new(),
+        });
+        history.counts = history.counts.split_off(&oldest);
+        history.recent.retain(|earlier| window_of(&earlier.timestamp) == window);
+        history.recent.push_back(entry.clone());
+        if history.recent.len() > MAX_EVIDENCE {
+            history.recent.pop_front();
+        }
+        
+        let count = history.counts.entry(window).or_insert(0);
+        *count += 1;
+        let count = *count;
+        
+        // Windows before the subject's first decrypt don't lower their average
+        let earlier_windows = window - history.first_window.max(oldest);
+        let earlier_decrypts: u64 = history.counts.range(..window).map(|(_, count)| count).sum();
+        let average = match earlier_windows {
+            windows if windows > 0 => earlier_decrypts as f64 / windows as f64,
+            _ => 0.0,
+        };
+        let threshold = self.decrypt_volume_minimum
+            .max((average * self.decrypt_volume_multiplier).ceil() as u64)
+            .max(1);
+        if count < threshold {
+            return None;
+        }
+        
+        Some(Anomaly {
+            kind: AnomalyKind::UnusualDecryptVolume,
+            subject: subject.clone(),
+            message: format!(
+                "{} decrypted {} times in {} minutes against an average of {:.1}",
+                subject, count, self.window.num_minutes(), average
+            ),
+            details: HashMap::from([
+                ("decrypts".to_string(), count.to_string()),
+                ("window_minutes".to_string(), self.window.num_minutes().to_string()),
+                ("baseline_average".to_string(), format!("{:.2}", average)),
+                ("threshold".to_string(), threshold.to_string()),
+            ]),
+            evidence: history.recent.iter().cloned().collect(),
+        })
+    }
+    
+    /// Flag a successful login from an address the user hasn't logged in from within the baseline
+    ///
+    /// A user's first login, or first after a baseline's absence, only records the address.
+    fn check_login_address(&mut self, entry: &AuditEntry) -> Option<Anomaly> {
+        if !self.new_login_ip || entry.action != actions::USER_AUTHENTICATION || entry.result != AuditResult::Success {
+            return None;
+        }
+        let user_id = entry.user_id.as_ref()?;
+        let address = entry.ip_address.as_ref()?;
+        
+        let baseline = self.baseline;
+        let known = self.login_addresses.entry(user_id.clone()).or_default();
+        known.retain(|_, last_seen| entry.timestamp - *last_seen <= baseline);
+        let known_addresses = known.len();
+        let is_new = known_addresses > 0 && !known.contains_key(address);
+        known.insert(address.clone(), entry.timestamp);
+        if !is_new {
+            return None;
+        }
+        
+        Some(Anomaly {
+            kind: AnomalyKind::NewLoginIp,
+            subject: user_id.clone(),
+            message: format!("{} logged in from new IP address {}", user_id, address),
+            details: HashMap::from([
+                ("ip_address".to_string(), address.clone()),
+                ("known_addresses".to_string(), known_addresses.to_string()),
+            ]),
+            evidence: vec![entry.clone()],
+        })
+    }
+    
+    /// Flag repeated escrow recovery attempts by one requester within a window
+    fn check_escrow_recoveries(&mut self, entry: &AuditEntry) -> Option<Anomaly> {
+        if entry.action != actions::KEY_RECOVERY || self.recovery_attempt_threshold == 0 {
+            return None;
+        }
+        
+        let subject = entry.user_id.clone().unwrap_or_else(|| entry.resource.clone());
+        let window = self.window;
+        let attempts = self.recoveries.entry(subject.clone()).or_default();
+        attempts.retain(|earlier| entry.timestamp - earlier.timestamp < window);
+        attempts.push_back(entry.clone());
+        if attempts.len() < self.recovery_attempt_threshold {
+            return None;
+        }
+        
+        let failed = attempts.iter().filter(|attempt| attempt.result != AuditResult::Success).count();
+        Some(Anomaly {
+            kind: AnomalyKind::RepeatedEscrowRecovery,
+            subject: subject.clone(),
+            message: format!(
+                "{} escrow recovery attempts by {} within {} minutes, {} unsuccessful",
+                attempts.len(), subject, window.num_minutes(), failed
+            ),
+            details: HashMap::from([
+                ("attempts".to_string(), attempts.len().to_string()),
+                ("unsuccessful".to_string(), failed.to_string()),
+                ("window_minutes".to_string(), window.num_minutes().to_string()),
+            ]),
+            evidence: attempts.iter().skip(attempts.len().saturating_sub(MAX_EVIDENCE)).cloned().collect(),
+        })
+    }
+    
+    /// Flag key administration outside business days and hours
+    fn check_key_administration(&self, entry: &AuditEntry) -> Option<Anomaly> {
+        if !self.key_admin_actions.contains(&entry.action) {
+            return None;
+        }
+        
+        let local_time = entry.timestamp.with_timezone(&self.business_offset);
+        if self.business_days.contains(&local_time.weekday()) && self.business_hours.contains(&local_time.hour()) {
+            return None;
+        }
+        
+        let subject = entry.user_id.clone()
+            .or_else(|| entry.actor_id.clone())
+            .unwrap_or_else(|| entry.resource.clone());
+        Some(Anomaly {
+            kind: AnomalyKind::AfterHoursKeyAdministration,
+            subject: subject.clone(),
+            message: format!("{} on {} by {} outside business hours", entry.action, entry.resource, subject),
+            details: HashMap::from([
+                ("action".to_string(), entry.action.clone()),
+                ("local_time".to_string(), local_time.to_rfc3339()),
+            ]),
+            evidence: vec![entry.clone()],
+        })
+    }
+    
+    /// Check an anomaly isn't in its cooldown, starting a new one if it isn't
+    fn should_raise(&mut self, anomaly: &Anomaly, at: DateTime<Utc>) -> bool {
+        let key = (anomaly.kind, anomaly.subject.clone());
+        if self.last_raised.get(&key).is_some_and(|last| at - *last < self.cooldown) {
+            return false;
+        }
+        
+        self.last_raised.insert(key, at);
+        true
+    }
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -5,12 +5,84 @@
 //! 
 //! This module provides comprehensive audit logging for compliance and security
 //! monitoring, tracking all operations within the DREAS framework.
+//! 
+//! Entries form a hash chain: each carries a sequence number and the hash of the
+//! entry before it, so edits, gaps and reordering are detected by `verify_chain`.
+//! The log is also a Merkle tree with signed checkpoints; see the `merkle` module.
+//! 
+//! Entries are kept in memory for queries and proofs, and written to any configured
+//! `audit_sink` sinks so they survive a restart. The retained entries are indexed for
+//! searching; see the `audit_query` module, and summarised in compliance reports; see
+//! the `audit_report` module.
+//! 
+//! Entries are kept for as long as the retention policy says for their action, unless
+//! a legal hold covers them; see the `retention` module.
 
 use crate::{DreasResult, DreasError};
+use crate::config::AppConfig;
+use crate::services::StorageService;
+use super::tenant::TenantId;
+use super::merkle::{self, ConsistencyProof, InclusionProof, MerkleHash, SignedTreeHead, CHECKPOINTS_FILE};
+use super::audit_sink::write_durably;
+use super::token::{KmsTokenSigner, TokenSigner};
+use super::kms::KmsClient;
+use super::siem::SyslogAuditSink;
+use super::audit_sink::{AuditSink, FanOutAuditSink, FileAuditSink, SinkMode, StorageAuditSink};
+use super::request_context::RequestContext;
+use super::redaction::Redactor;
+use super::audit_query::{AuditFilter, AuditIndex, AuditPage, AuditSearch, Pattern};
+use super::audit_report::{AuditReport, ReportTemplate};
+use super::retention::{
+    DeletionManifest, LegalHold, LegalHolds, PurgedRecord, RetainedRecord, RetentionPolicy, DELETION_MANIFESTS_FILE,
+    LEGAL_HOLDS_FILE,
+};
+use super::audit_stream::{AuditSubscription, AUDIT_STREAM_CAPACITY};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use ed25519_dalek::VerifyingKey;
+
+/// Previous hash of the first entry in a new chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Action names recorded in audit entries
+pub mod actions {
+    pub const USER_AUTHENTICATION: &str = "user_authentication";
+    pub const PERMISSION_CHANGE: &str = "permission_change";
+    pub const POLICY_DECISION: &str = "policy_decision";
+    pub const IMPERSONATION_START: &str = "impersonation_start";
+    pub const IMPERSONATION_END: &str = "impersonation_end";
+    pub const DATA_ENCRYPTION: &str = "data_encryption";
+    pub const DATA_DECRYPTION: &str = "data_decryption";
+    pub const DATA_SIGNING: &str = "data_signing";
+    pub const KEY_ESCROW: &str = "key_escrow";
+    pub const KEY_RECOVERY: &str = "key_recovery";
+    pub const STORAGE_WRITE: &str = "storage_write";
+    pub const STORAGE_READ: &str = "storage_read";
+    pub const STORAGE_DELETE: &str = "storage_delete";
+    pub const MODEL_REQUEST: &str = "model_request";
+    pub const PROMPT_PROCESSED: &str = "prompt_processed";
+    pub const RESPONSE_PROCESSED: &str = "response_processed";
+    pub const AUDIT_LOG_PRUNED: &str = "audit_log_pruned";
+    pub const LEGAL_HOLD_PLACED: &str = "legal_hold_placed";
+    pub const LEGAL_HOLD_RELEASED: &str = "legal_hold_released";
+    pub const RETENTION_PURGE: &str = "retention_purge";
+}
+
+/// File the audit log's ID is kept in, beside its entries
+pub const LOG_ID_FILE: &str = "log-id";
+
+/// Resource of a purged entry, whose own resource was removed
+const PURGED_RESOURCE: &str = "[purged]";
+
+/// Metadata key of a purged entry naming the deletion manifest it was purged by
+const DELETION_MANIFEST_METADATA: &str = "deletion_manifest";
 
 /// Audit logger for tracking all system operations
 #[derive(Debug, Clone)]
@@ -19,6 +91,33 @@ pub struct AuditLogger {
     retention_days: u32,
     audit_entries: Vec<AuditEntry>,
     sensitive_operations: Vec<String>,
+    /// Last entry removed by retention cleanup; the retained chain continues from it
+    chain_anchor: ChainAnchor,
+    /// Merkle leaf of every entry ever logged, kept after retention cleanup so proofs stay possible
+    merkle_leaves: Vec<MerkleHash>,
+    checkpoint_signer: Option<Arc<dyn TokenSigner>>,
+    checkpoints: Vec<SignedTreeHead>,
+    /// File every checkpoint is appended to before it's kept
+    checkpoint_path: Option<PathBuf>,
+    sinks: FanOutAuditSink,
+    index: AuditIndex,
+    retention: RetentionPolicy,
+    legal_holds: Arc<LegalHolds>,
+    /// Manifests of every retention purge, signed with the checkpoint signer
+    deletion_manifests: Vec<DeletionManifest>,
+    /// File every manifest is appended to before the purge is logged
+    manifest_path: Option<PathBuf>,
+    redactor: Option<Arc<Redactor>>,
+    /// Sealed entries, as they're logged, for subscribers
+    events: broadcast::Sender<Arc<AuditEntry>>,
+}
+
+/// Handle to the shared audit log, injected into every component with security-relevant operations
+///
+/// Clones record into the same log. A detached handle records nothing.
+#[derive(Debug, Clone, Default)]
+pub struct AuditHandle {
+    logger: Option<Arc<Mutex<AuditLogger>>>,
 }
 
 /// Individual audit entry
@@ -34,10 +133,130 @@ pub struct AuditEntry {
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
     pub metadata: HashMap<String, String>,
+    /// Tenant whose data or users the operation concerned
+    #[serde(default)]
+    pub tenant_id: Option<TenantId>,
+    /// Support engineer acting on behalf of `user_id` through an impersonation session
+    #[serde(default)]
+    pub actor_id: Option<String>,
+    /// API request during which the operation happened
+    #[serde(default)]
+    pub request_id: Option<Uuid>,
+    /// Distributed trace of that request
+    #[serde(default)]
+    pub trace_id: Option<String>,
+    /// Position in the log's hash chain, starting at 1
+    #[serde(default)]
+    pub sequence: u64,
+    /// Hash of the preceding entry, or `GENESIS_HASH` for the first
+    #[serde(default)]
+    pub previous_hash: String,
+    /// SHA-256 over this entry's contents, including `sequence` and `previous_hash`
+    #[serde(default)]
+    pub hash: String,
+    /// Contents were removed by retention, keeping the chain fields, action, result and timestamp
+    ///
+    /// A purged entry no longer matches its hash. The signed deletion manifest named in its
+    /// metadata lists the hash it was purged with and the hash of what was left.
+    #[serde(default)]
+    pub purged: bool,
+}
+
+/// Tree head snapshotted for a checkpoint, signed without holding the log
+#[derive(Debug, Clone)]
+pub struct PendingCheckpoint {
+    signer: Arc<dyn TokenSigner>,
+    log_id: Uuid,
+    tree_size: u64,
+    root_hash: String,
+    timestamp: DateTime<Utc>,
+}
+
+/// Expired entries picked for a retention purge, purged from sinks and signed without holding the log
+#[derive(Debug, Clone)]
+pub struct PendingPurge {
+    signer: Arc<dyn TokenSigner>,
+    sinks: FanOutAuditSink,
+    manifest: DeletionManifest,
+    tombstones: Vec<AuditEntry>,
+}
+
+/// Retention purge the sinks have carried out, with its signed manifest, to be applied to the log
+#[derive(Debug, Clone)]
+pub struct CompletedPurge {
+    manifest: DeletionManifest,
+    tombstones: Vec<AuditEntry>,
+}
+
+/// Sequence number and hash of the entry a chain segment continues from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainAnchor {
+    pub sequence: u64,
+    pub hash: String,
+}
+
+/// Outcome of verifying an audit hash chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainVerification {
+    pub valid: bool,
+    pub entries_checked: usize,
+    /// First entry that breaks the chain, if any
+    pub first_invalid: Option<ChainViolation>,
+}
+
+/// An entry that doesn't fit the hash chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainViolation {
+    /// Position of the entry in the verified slice
+    pub index: usize,
+    pub entry_id: Uuid,
+    pub sequence: u64,
+    pub kind: ChainViolationKind,
+}
+
+/// How an entry breaks the hash chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChainViolationKind {
+    /// Entries are missing or out of order
+    UnexpectedSequence { expected: u64 },
+    /// The entry doesn't link to the one before it
+    PreviousHashMismatch,
+    /// The entry was modified after it was logged
+    HashMismatch,
+    /// The entry is marked purged, but no signed deletion manifest accounts for it as it is
+    UnaccountedPurge,
+}
+
+/// Entry contents covered by its hash, serialized in a fixed field and key order
+#[derive(Serialize)]
+struct HashedContents<'a> {
+    sequence: u64,
+    previous_hash: &'a str,
+    entry_id: &'a Uuid,
+    timestamp: &'a DateTime<Utc>,
+    user_id: &'a Option<String>,
+    session_id: &'a Option<String>,
+    action: &'a str,
+    resource: &'a str,
+    result: &'a AuditResult,
+    ip_address: &'a Option<String>,
+    user_agent: &'a Option<String>,
+    metadata: BTreeMap<&'a String, &'a String>,
+    tenant_id: &'a Option<TenantId>,
+    actor_id: &'a Option<String>,
+    // Left out when unset so entries from before request context hash as they did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: &'a Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: &'a Option<String>,
+    // Only set on what's left of a purged entry, so live entries hash as they did
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    purged: bool,
 }
 
 /// Audit result enumeration
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum AuditResult {
     Success,
     Failure,
@@ -64,14 +283,241 @@ impl AuditLogger {
             retention_days,
             audit_entries: Vec::new(),
             sensitive_operations: vec![
-                "key_escrow".to_string(),
-                "key_recovery".to_string(),
-                "user_authentication".to_string(),
-                "permission_change".to_string(),
-                "data_encryption".to_string(),
-                "data_decryption".to_string(),
+                actions::KEY_ESCROW.to_string(),
+                actions::KEY_RECOVERY.to_string(),
+                actions::USER_AUTHENTICATION.to_string(),
+                actions::PERMISSION_CHANGE.to_string(),
+                actions::DATA_ENCRYPTION.to_string(),
+                actions::DATA_DECRYPTION.to_string(),
             ],
+            chain_anchor: ChainAnchor::genesis(),
+            merkle_leaves: Vec::new(),
+            checkpoint_signer: None,
+            checkpoints: Vec::new(),
+            checkpoint_path: None,
+            sinks: FanOutAuditSink::new(),
+            index: AuditIndex::new(),
+            retention: RetentionPolicy::new(retention_days),
+            legal_holds: Arc::new(LegalHolds::new()),
+            deletion_manifests: Vec::new(),
+            manifest_path: None,
+            redactor: None,
+            events: broadcast::channel(AUDIT_STREAM_CAPACITY).0,
+        }
+    }
+    
+    /// Build a component's logger with the retention, checkpoint key and sinks in the configuration
+    ///
+    /// Each component writes its own chain to a subdirectory of the audit file directory, so
+    /// services running as separate processes never append to the same chain; entries already
+    /// there are restored, along with the log's ID and its signed checkpoints. Legal holds are
+    /// kept in the audit file directory itself, shared by every component.
+    pub async fn from_config(config: &AppConfig, component: &str, redactor: Arc<Redactor>) -> DreasResult<Self> {
+        let security = &config.security;
+        let retention = RetentionPolicy::new(security.audit_log_retention_days)
+            .with_categories(&security.audit_retention_categories);
+        let mut audit_logger = Self::new(security.audit_log_retention_days)
+            .with_retention_policy(retention)
+            .with_redactor(redactor);
+        if let Some(checkpoint_config) = &security.audit_checkpoints {
+            let kms_client = KmsClient::from_key_version_uri(&checkpoint_config.kms_signing_key_uri)?;
+            audit_logger = audit_logger.with_checkpoint_signer(Arc::new(KmsTokenSigner::new(kms_client).await?));
+        }
+        
+        let sink_config = &security.audit_sinks;
+        let sink_mode = |name: &str| {
+            if sink_config.mandatory.iter().any(|mandatory| mandatory == name) {
+                SinkMode::Mandatory
+            } else {
+                SinkMode::BestEffort
+            }
+        };
+        if let (Some(root), Some(directory)) = (&sink_config.file_directory, sink_config.component_directory(component)) {
+            // Holds, manifests and checkpoints are loaded before the entries they account for
+            let legal_holds = LegalHolds::open(Path::new(root).join(LEGAL_HOLDS_FILE))?;
+            audit_logger = audit_logger
+                .with_legal_holds(Arc::new(legal_holds))
+                .with_deletion_manifests(directory.join(DELETION_MANIFESTS_FILE))?;
+            
+            let file_sink = FileAuditSink::open(&directory)?.with_rotation(
+                sink_config.max_file_bytes,
+                chrono::Duration::hours(sink_config.max_file_age_hours),
+            );
+            audit_logger = audit_logger
+                .with_log_id_file(directory.join(LOG_ID_FILE))?
+                .with_checkpoint_file(directory.join(CHECKPOINTS_FILE))?;
+            let restored = audit_logger.restore(FileAuditSink::read_entries(&directory)?)?;
+            tracing::info!("Restored {} audit entries from {}", restored, directory.display());
+            
+            let mode = sink_mode(file_sink.name());
+            audit_logger = audit_logger.with_sink(Arc::new(file_sink), mode);
+        }
+        if let Some(dataset) = &sink_config.bigquery_dataset {
+            // Audit entries only go to BigQuery, so the sink needs no bucket
+            let storage = StorageService::new(String::new(), dataset.clone()).with_project_id(config.gcp.project_id.clone());
+            let bigquery_sink = StorageAuditSink::new(storage);
+            let mode = sink_mode(bigquery_sink.name());
+            audit_logger = audit_logger.with_sink(Arc::new(bigquery_sink), mode);
         }
+        if let Some(syslog_config) = &sink_config.syslog {
+            let syslog_sink = SyslogAuditSink::from_config(syslog_config)?;
+            let mode = sink_mode(syslog_sink.name());
+            audit_logger = audit_logger.with_sink(Arc::new(syslog_sink), mode);
+            tracing::info!("Forwarding audit entries to syslog collector {}", syslog_config.address);
+        }
+        
+        Ok(audit_logger)
+    }
+    
+    /// Write every entry to a durable sink as well as keeping it in memory
+    pub fn with_sink(mut self, sink: Arc<dyn AuditSink>, mode: SinkMode) -> Self {
+        self.sinks = self.sinks.with_sink(sink, mode);
+        self
+    }
+    
+    /// Reload entries read back from a durable sink into an empty logger
+    ///
+    /// The entries must form an intact chain from the genesis entry, with purged entries
+    /// accounted for by the logger's deletion manifests, and match every checkpoint the logger
+    /// has loaded; new entries continue it. A copy of an entry another mandatory sink refused is
+    /// dropped, as the entry logged next took its place.
+    pub fn restore(&mut self, entries: Vec<AuditEntry>) -> DreasResult<usize> {
+        if !self.audit_entries.is_empty() || !self.merkle_leaves.is_empty() {
+            return Err(DreasError::AuditLogging("Audit entries can only be restored into an empty log".to_string()));
+        }
+        
+        let mut kept: Vec<AuditEntry> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let supersedes = kept.last()
+                .is_some_and(|last| last.sequence == entry.sequence && last.previous_hash == entry.previous_hash);
+            if supersedes {
+                kept.pop();
+            }
+            kept.push(entry);
+        }
+        let entries = kept;
+        
+        let verification = self.verify_purges(&entries, &ChainAnchor::genesis())?;
+        if let Some(violation) = verification.first_invalid {
+            return Err(DreasError::AuditLogging(format!(
+                "Restored audit log is broken at entry {} (sequence {}): {:?}",
+                violation.entry_id, violation.sequence, violation.kind
+            )));
+        }
+        
+        let merkle_leaves: Vec<MerkleHash> = entries.iter().map(merkle::entry_leaf_hash).collect::<DreasResult<_>>()?;
+        if let Some(tree_head) = self.checkpoints.iter().find(|tree_head| !tree_head.commits_to(&self.log_id, &merkle_leaves)) {
+            return Err(DreasError::AuditLogging(format!(
+                "Restored audit log doesn't match its checkpoint at size {}: {}", tree_head.tree_size, tree_head.root_hash
+            )));
+        }
+        
+        self.merkle_leaves = merkle_leaves;
+        entries.iter().for_each(|entry| self.index.insert(entry));
+        self.audit_entries = entries;
+        
+        tracing::info!("Restored {} audit entries", self.audit_entries.len());
+        Ok(self.audit_entries.len())
+    }
+    
+    /// Sign checkpoints with the given key, e.g. a `KmsTokenSigner` for an EC_SIGN_ED25519 KMS key
+    ///
+    /// Deletion manifests are signed with the same key, so retention cleanup needs one.
+    pub fn with_checkpoint_signer(mut self, signer: Arc<dyn TokenSigner>) -> Self {
+        self.checkpoint_signer = Some(signer);
+        self
+    }
+    
+    /// Keep the log's ID in a file, so it stays the same across restarts
+    ///
+    /// The ID already in the file is used; if there's none yet, this log's ID is written to it.
+    pub fn with_log_id_file<P: AsRef<Path>>(mut self, path: P) -> DreasResult<Self> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                self.log_id = Uuid::parse_str(contents.trim()).map_err(|e| DreasError::AuditLogging(format!(
+                    "Invalid audit log ID in {}: {}", path.display(), e
+                )))?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                write_durably(path, self.log_id.to_string().as_bytes())?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(self)
+    }
+    
+    /// Keep signed checkpoints in a file, loading the checkpoints signed before a restart from it
+    ///
+    /// Load them before restoring entries, so the restored entries are checked against them.
+    pub fn with_checkpoint_file<P: AsRef<Path>>(mut self, path: P) -> DreasResult<Self> {
+        self.checkpoints = SignedTreeHead::read_all(&path)?;
+        self.checkpoint_path = Some(path.as_ref().to_path_buf());
+        Ok(self)
+    }
+    
+    /// Redact personal data from the metadata of every entry before it's logged
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+    
+    /// Keep entries for as long as a retention policy says for their action
+    pub fn with_retention_policy(mut self, retention: RetentionPolicy) -> Self {
+        self.retention_days = retention.default_days();
+        self.retention = retention;
+        self
+    }
+    
+    /// Check legal holds in a shared registry, e.g. the one storage services check, before purging
+    pub fn with_legal_holds(mut self, legal_holds: Arc<LegalHolds>) -> Self {
+        self.legal_holds = legal_holds;
+        self
+    }
+    
+    /// Get the legal holds checked before purging
+    pub fn legal_holds(&self) -> &Arc<LegalHolds> {
+        &self.legal_holds
+    }
+    
+    /// Place a legal hold and log it
+    pub async fn place_legal_hold(&mut self, hold: LegalHold) -> DreasResult<Uuid> {
+        let entry = AuditEntry::new(actions::LEGAL_HOLD_PLACED, format!("legal_hold:{}", hold.hold_id), AuditResult::Success)
+            .with_user(hold.placed_by.clone())
+            .with_metadata("matter", hold.matter.clone())
+            .with_metadata("reason", hold.reason.clone())
+            .with_metadata("scope", hold.scope.describe());
+        
+        let hold_id = self.legal_holds.place(hold)?;
+        self.log_entry(entry).await?;
+        Ok(hold_id)
+    }
+    
+    /// Release a legal hold and log it
+    pub async fn release_legal_hold(&mut self, hold_id: &Uuid, released_by: &str, reason: &str) -> DreasResult<LegalHold> {
+        let hold = self.legal_holds.release(hold_id)?;
+        
+        let entry = AuditEntry::new(actions::LEGAL_HOLD_RELEASED, format!("legal_hold:{}", hold_id), AuditResult::Success)
+            .with_user(released_by)
+            .with_metadata("matter", hold.matter.clone())
+            .with_metadata("reason", reason)
+            .with_metadata("scope", hold.scope.describe());
+        self.log_entry(entry).await?;
+        Ok(hold)
+    }
+    
+    /// Keep deletion manifests in a file, loading the manifests of earlier purges from it
+    ///
+    /// Load them before restoring entries, so purged entries can be accounted for.
+    pub fn with_deletion_manifests<P: AsRef<Path>>(mut self, path: P) -> DreasResult<Self> {
+        self.deletion_manifests = DeletionManifest::read_all(&path)?;
+        self.manifest_path = Some(path.as_ref().to_path_buf());
+        Ok(self)
+    }
+    
+    /// Get the manifests of every retention purge, oldest first
+    pub fn deletion_manifests(&self) -> &[DeletionManifest] {
+        &self.deletion_manifests
     }
     
     /// Log an audit entry
@@ -84,23 +530,40 @@ impl AuditLogger {
         result: AuditResult,
         metadata: Option<HashMap<String, String>>,
     ) -> DreasResult<Uuid> {
-        let entry_id = Uuid::new_v4();
+        let mut entry = AuditEntry::new(action, resource, result);
+        entry.user_id = user_id;
+        entry.session_id = session_id;
+        entry.metadata = metadata.unwrap_or_default();
         
-        let entry = AuditEntry {
-            entry_id,
-            timestamp: Utc::now(),
-            user_id,
-            session_id,
-            action: action.clone(),
-            resource,
-            result: result.clone(),
-            ip_address: None, // TODO: Extract from request context
-            user_agent: None, // TODO: Extract from request context
-            metadata: metadata.unwrap_or_default(),
+        self.log_entry(entry).await
+    }
+    
+    /// Log a prepared audit entry
+    ///
+    /// Details of the request being handled, such as the client IP address, are filled in from
+    /// the current `RequestContext` where the entry doesn't set them, and the metadata is redacted
+    /// if a redactor is configured. Fails if a mandatory sink couldn't record the entry, so the operation being audited fails
+    /// closed, and the entry is then left out of the chain. Sinks that did record it hold a copy the next entry supersedes
+    /// by taking its sequence number, which `restore` drops.
+    pub async fn log_entry(&mut self, entry: AuditEntry) -> DreasResult<Uuid> {
+        let mut entry = match RequestContext::current() {
+            Some(context) => context.apply(entry),
+            None => entry,
         };
+        if let Some(redactor) = &self.redactor {
+            entry.metadata = redactor.redact_metadata(std::mem::take(&mut entry.metadata));
+        }
+        let entry_id = entry.entry_id;
+        let action = entry.action.clone();
+        let result = entry.result.clone();
         
         // Store the audit entry
-        self.audit_entries.push(entry.clone());
+        let entry = self.seal(entry)?;
+        self.sinks.write(&entry).await?;
+        self.commit(entry.clone())?;
+        
+        // Subscribers only see entries that are durably recorded; nobody listening isn't an error
+        let _ = self.events.send(Arc::new(entry.clone()));
         
         // Log to tracing for immediate visibility
         let log_level = match result {
@@ -118,6 +581,13 @@ impl AuditLogger {
             "action": entry.action,
             "resource": entry.resource,
             "result": entry.result,
+            "tenant_id": entry.tenant_id,
+            "actor_id": entry.actor_id,
+            "request_id": entry.request_id,
+            "trace_id": entry.trace_id,
+            "ip_address": entry.ip_address,
+            "sequence": entry.sequence,
+            "hash": entry.hash,
             "metadata": entry.metadata
         });
         
@@ -131,109 +601,439 @@ impl AuditLogger {
         Ok(entry_id)
     }
     
-    /// Query audit entries
-    pub fn query_audit_entries(&self, query: AuditQuery) -> DreasResult<Vec<AuditEntry>> {
-        let mut results = self.audit_entries.clone();
+    /// Link an entry to the end of the hash chain without storing it
+    fn seal(&self, mut entry: AuditEntry) -> DreasResult<AuditEntry> {
+        let head = self.head();
+        entry.sequence = head.sequence + 1;
+        entry.previous_hash = head.hash;
+        entry.hash = entry.compute_hash()?;
+        Ok(entry)
+    }
+    
+    /// Store a sealed entry at the end of the chain
+    fn commit(&mut self, entry: AuditEntry) -> DreasResult<()> {
+        self.merkle_leaves.push(merkle::entry_leaf_hash(&entry)?);
+        self.index.insert(&entry);
+        self.audit_entries.push(entry);
+        Ok(())
+    }
+    
+    /// Receive the entries logged from now on that match a filter
+    pub fn subscribe(&self, filter: AuditFilter) -> AuditSubscription {
+        AuditSubscription::new(filter, Vec::new(), self.events.subscribe())
+    }
+    
+    /// Receive the matching entries logged after a sequence number, retained ones first
+    ///
+    /// Lets a subscriber that lost its connection pick up where it left off. Entries already
+    /// removed by retention can't be replayed.
+    pub fn subscribe_after(&self, filter: AuditFilter, sequence: u64) -> AuditSubscription {
+        let backlog = self.index.matching(&self.audit_entries, &filter).into_iter()
+            .filter(|entry| entry.sequence > sequence && !entry.purged)
+            .cloned()
+            .collect();
+        AuditSubscription::new(filter, backlog, self.events.subscribe())
+    }
+    
+    /// Sign a tree head over every entry logged so far
+    ///
+    /// For a shared log, use `AuditHandle::checkpoint`, which doesn't hold the log while signing.
+    pub async fn checkpoint(&mut self) -> DreasResult<SignedTreeHead> {
+        let tree_head = self.prepare_checkpoint()?.sign().await?;
+        self.record_checkpoint(tree_head.clone())?;
+        Ok(tree_head)
+    }
+    
+    /// Snapshot the tree head over every entry logged so far, to be signed and then recorded
+    pub fn prepare_checkpoint(&self) -> DreasResult<PendingCheckpoint> {
+        let signer = self.checkpoint_signer.clone()
+            .ok_or_else(|| DreasError::Configuration("Audit checkpoint signing key not configured".to_string()))?;
         
-        // Apply filters
-        if let Some(start_date) = query.start_date {
-            results.retain(|entry| entry.timestamp >= start_date);
+        Ok(PendingCheckpoint {
+            signer,
+            log_id: self.log_id,
+            tree_size: self.merkle_leaves.len() as u64,
+            root_hash: hex::encode(merkle::root_hash(&self.merkle_leaves)),
+            timestamp: Utc::now(),
+        })
+    }
+    
+    /// Keep a tree head signed from a snapshot of this log
+    ///
+    /// Fails if the tree head isn't of this log, or is older than the last one kept.
+    pub fn record_checkpoint(&mut self, tree_head: SignedTreeHead) -> DreasResult<()> {
+        if !tree_head.commits_to(&self.log_id, &self.merkle_leaves) {
+            return Err(DreasError::AuditLogging(format!(
+                "Tree head at size {} doesn't match audit log {}", tree_head.tree_size, self.log_id
+            )));
         }
-        
-        if let Some(end_date) = query.end_date {
-            results.retain(|entry| entry.timestamp <= end_date);
+        if self.checkpoints.last().is_some_and(|last| last.tree_size > tree_head.tree_size) {
+            return Err(DreasError::AuditLogging(format!(
+                "Tree head at size {} is older than the last checkpoint", tree_head.tree_size
+            )));
         }
         
-        if let Some(user_id) = query.user_id {
-            results.retain(|entry| entry.user_id.as_ref() == Some(&user_id));
+        if let Some(path) = &self.checkpoint_path {
+            tree_head.append_to(path)?;
+        }
+        tracing::info!("Audit checkpoint signed at tree size {}: {}", tree_head.tree_size, tree_head.root_hash);
+        self.checkpoints.push(tree_head);
+        Ok(())
+    }
+    
+    /// Get all signed checkpoints, oldest first
+    pub fn checkpoints(&self) -> &[SignedTreeHead] {
+        &self.checkpoints
+    }
+    
+    /// Get the ID tree heads and reports name this log by
+    pub fn log_id(&self) -> Uuid {
+        self.log_id
+    }
+    
+    /// Prove the entry with the given sequence number is in the tree of the given size
+    pub fn inclusion_proof(&self, sequence: u64, tree_size: u64) -> DreasResult<InclusionProof> {
+        if sequence == 0 || sequence > tree_size || tree_size > self.merkle_leaves.len() as u64 {
+            return Err(DreasError::AuditLogging(format!(
+                "No entry {} in a tree of size {} (log has {} entries)", sequence, tree_size, self.merkle_leaves.len()
+            )));
         }
         
-        if let Some(action) = query.action {
-            results.retain(|entry| entry.action == action);
+        let leaves = &self.merkle_leaves[..tree_size as usize];
+        let audit_path = merkle::inclusion_path(leaves, (sequence - 1) as usize);
+        
+        Ok(InclusionProof {
+            leaf_index: sequence - 1,
+            tree_size,
+            audit_path: audit_path.iter().map(hex::encode).collect(),
+        })
+    }
+    
+    /// Prove the tree of `new_size` entries extends the tree of `old_size` entries
+    pub fn consistency_proof(&self, old_size: u64, new_size: u64) -> DreasResult<ConsistencyProof> {
+        if old_size > new_size || new_size > self.merkle_leaves.len() as u64 {
+            return Err(DreasError::AuditLogging(format!(
+                "No consistency proof from size {} to {} (log has {} entries)", old_size, new_size, self.merkle_leaves.len()
+            )));
         }
         
-        if let Some(resource) = query.resource {
-            results.retain(|entry| entry.resource == resource);
+        let leaves = &self.merkle_leaves[..new_size as usize];
+        let proof = merkle::consistency_path(leaves, old_size as usize);
+        
+        Ok(ConsistencyProof {
+            old_size,
+            new_size,
+            proof: proof.iter().map(hex::encode).collect(),
+        })
+    }
+    
+    /// Get the sequence number and hash of the latest entry, for checkpointing the chain elsewhere
+    pub fn head(&self) -> ChainAnchor {
+        self.audit_entries.last()
+            .map(|entry| ChainAnchor { sequence: entry.sequence, hash: entry.hash.clone() })
+            .unwrap_or_else(|| self.chain_anchor.clone())
+    }
+    
+    /// Get the anchor the retained entries continue from
+    pub fn chain_anchor(&self) -> &ChainAnchor {
+        &self.chain_anchor
+    }
+    
+    /// Get all retained entries in chain order
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.audit_entries
+    }
+    
+    /// Verify the retained entries form an unbroken hash chain
+    ///
+    /// Purged entries are checked against this log's deletion manifests, signed with the checkpoint signer.
+    pub fn verify_chain(&self) -> DreasResult<ChainVerification> {
+        self.verify_purges(&self.audit_entries, &self.chain_anchor)
+    }
+    
+    /// Verify entries continuing from an anchor, accepting purges recorded in this log's manifests
+    fn verify_purges(&self, entries: &[AuditEntry], anchor: &ChainAnchor) -> DreasResult<ChainVerification> {
+        match &self.checkpoint_signer {
+            Some(signer) => Self::verify_entries_with_manifests(entries, anchor, &self.deletion_manifests, &signer.verifying_key()),
+            None => Self::verify_entries(entries, anchor),
+        }
+    }
+    
+    /// Verify entries, e.g. exported from another store, form a hash chain continuing from an anchor
+    ///
+    /// Purged entries can't be accounted for without their deletion manifests, so they fail
+    /// verification; see `verify_entries_with_manifests`.
+    pub fn verify_entries(entries: &[AuditEntry], anchor: &ChainAnchor) -> DreasResult<ChainVerification> {
+        Self::check_entries(entries, anchor, &HashMap::new())
+    }
+    
+    /// Verify entries form a hash chain continuing from an anchor, accepting purges recorded in manifests
+    ///
+    /// A purged entry is accepted if a manifest signed by the key lists it with the hash it was
+    /// purged with and the hash of what was left, so marking an entry purged or editing what's
+    /// left of one is detected like any other change.
+    pub fn verify_entries_with_manifests(
+        entries: &[AuditEntry],
+        anchor: &ChainAnchor,
+        manifests: &[DeletionManifest],
+        verifying_key: &VerifyingKey,
+    ) -> DreasResult<ChainVerification> {
+        let mut purges = HashMap::new();
+        for manifest in manifests {
+            if let Err(e) = manifest.verify_signature(verifying_key) {
+                tracing::error!("Ignoring deletion manifest {}: {}", manifest.manifest_id, e);
+                continue;
+            }
+            for record in &manifest.purged {
+                purges.insert(record.record_id.as_str(), (manifest.manifest_id, record));
+            }
         }
         
-        if let Some(result) = query.result {
-            results.retain(|entry| entry.result == result);
+        Self::check_entries(entries, anchor, &purges)
+    }
+    
+    /// Check entries against the chain, accepting purged entries listed in verified manifests
+    fn check_entries(
+        entries: &[AuditEntry],
+        anchor: &ChainAnchor,
+        purges: &HashMap<&str, (Uuid, &PurgedRecord)>,
+    ) -> DreasResult<ChainVerification> {
+        let mut previous_hash = anchor.hash.as_str();
+        
+        for (index, entry) in entries.iter().enumerate() {
+            let expected_sequence = anchor.sequence + 1 + index as u64;
+            let kind = if entry.sequence != expected_sequence {
+                Some(ChainViolationKind::UnexpectedSequence { expected: expected_sequence })
+            } else if entry.previous_hash != previous_hash {
+                Some(ChainViolationKind::PreviousHashMismatch)
+            } else if entry.purged {
+                let accounted = purges.get(entry.entry_id.to_string().as_str())
+                    .is_some_and(|(manifest_id, record)| {
+                        entry.metadata.get(DELETION_MANIFEST_METADATA) == Some(&manifest_id.to_string())
+                            && record.digest.as_deref() == Some(entry.hash.as_str())
+                            && record.tombstone_digest.as_deref() == entry.compute_hash().ok().as_deref()
+                    });
+                (!accounted).then_some(ChainViolationKind::UnaccountedPurge)
+            } else if entry.hash != entry.compute_hash()? {
+                Some(ChainViolationKind::HashMismatch)
+            } else {
+                None
+            };
+            
+            if let Some(kind) = kind {
+                tracing::error!("Audit chain broken at entry {} (sequence {}): {:?}", entry.entry_id, entry.sequence, kind);
+                return Ok(ChainVerification {
+                    valid: false,
+                    entries_checked: index + 1,
+                    first_invalid: Some(ChainViolation {
+                        index,
+                        entry_id: entry.entry_id,
+                        sequence: entry.sequence,
+                        kind,
+                    }),
+                });
+            }
+            
+            previous_hash = &entry.hash;
         }
         
+        Ok(ChainVerification {
+            valid: true,
+            entries_checked: entries.len(),
+            first_invalid: None,
+        })
+    }
+    
+    /// Query audit entries
+    pub fn query_audit_entries(&self, query: AuditQuery) -> DreasResult<Vec<AuditEntry>> {
+        self.query_filtered(&query.filter(), query.limit)
+    }
+    
+    /// Query the audit entries of a single tenant
+    pub fn query_tenant_entries(&self, tenant_id: &TenantId, query: AuditQuery) -> DreasResult<Vec<AuditEntry>> {
+        let filter = AuditFilter::all_of(vec![query.filter(), AuditFilter::Tenant(tenant_id.clone())]);
+        self.query_filtered(&filter, query.limit)
+    }
+    
+    /// Get the entries matching a filter, newest first
+    fn query_filtered(&self, filter: &AuditFilter, limit: Option<usize>) -> DreasResult<Vec<AuditEntry>> {
+        let mut results: Vec<AuditEntry> = self.index.matching(&self.audit_entries, filter)
+            .into_iter()
+            .cloned()
+            .collect();
+        
         // Sort by timestamp (newest first)
         results.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
         
         // Apply limit
-        if let Some(limit) = query.limit {
+        if let Some(limit) = limit {
             results.truncate(limit);
         }
         
         Ok(results)
     }
     
-    /// Generate audit report
-    pub fn generate_report(&self, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> DreasResult<serde_json::Value> {
-        let entries = self.query_audit_entries(AuditQuery {
-            start_date: Some(start_date),
-            end_date: Some(end_date),
-            user_id: None,
-            action: None,
-            resource: None,
-            result: None,
-            limit: None,
-        })?;
-        
-        let total_operations = entries.len();
-        let successful_operations = entries.iter().filter(|e| e.result == AuditResult::Success).count();
-        let failed_operations = entries.iter().filter(|e| e.result == AuditResult::Failure).count();
-        let partial_operations = entries.iter().filter(|e| e.result == AuditResult::Partial).count();
-        
-        let mut action_counts = HashMap::new();
-        let mut user_counts = HashMap::new();
-        
-        for entry in &entries {
-            *action_counts.entry(entry.action.clone()).or_insert(0) += 1;
-            if let Some(user_id) = &entry.user_id {
-                *user_counts.entry(user_id.clone()).or_insert(0) += 1;
+    /// Get one page of the entries matching a search
+    pub fn search(&self, search: &AuditSearch) -> DreasResult<AuditPage> {
+        self.index.search(&self.audit_entries, &self.log_id, search)
+    }
+    
+    /// Generate a report from a template over the entries logged in a period, inclusive
+    pub fn generate_report(&self, template: ReportTemplate, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> DreasResult<AuditReport> {
+        let entries = self.query_filtered(&AuditFilter::all_of(vec![AuditFilter::Since(start_date), AuditFilter::Until(end_date)]), None)?;
+        
+        let report = AuditReport::build(template, &entries, start_date, end_date, self.log_id, self.head());
+        tracing::info!("Generated {} report {} with {} rows", report.title, report.report_id, report.rows.len());
+        Ok(report)
+    }
+    
+    /// Clean up old audit entries based on retention policy
+    ///
+    /// Expired entries not under a legal hold are purged in place, keeping their place in the
+    /// chain, and the oldest run of purged entries is removed, so the retained entries stay a
+    /// contiguous chain. Sinks replace their copies with the same tombstones first. The purge
+    /// is recorded in a deletion manifest, naming any sink that kept copies, and logged as an
+    /// `audit_log_pruned` entry.
+    ///
+    /// A log written to durable sinks is only purged with its legal holds and deletion
+    /// manifests kept in files too, and the holds are loaded from their file first.
+    ///
+    /// For a shared log, use `AuditHandle::apply_retention`, which doesn't hold the log while
+    /// sinks purge their copies and the manifest is signed.
+    pub async fn cleanup_old_entries(&mut self) -> DreasResult<usize> {
+        match self.prepare_purge()? {
+            Some(pending) => {
+                let purge = pending.execute().await?;
+                self.record_purge(purge).await
+            }
+            None => Ok(0),
+        }
+    }
+    
+    /// Pick the expired entries not under a legal hold, to be purged from the sinks and then recorded
+    ///
+    /// Returns `None` when nothing has expired.
+    pub fn prepare_purge(&self) -> DreasResult<Option<PendingPurge>> {
+        // Nothing is purged without signed evidence of it
+        let signer = self.checkpoint_signer.clone()
+            .ok_or_else(|| DreasError::Configuration("Audit retention needs a signing key for deletion manifests".to_string()))?;
+        if !self.sinks.is_empty() && (!self.legal_holds.is_persistent() || self.manifest_path.is_none()) {
+            return Err(DreasError::Configuration(
+                "Durable audit logs are only purged with legal holds and deletion manifests kept in files".to_string()
+            ));
+        }
+        self.legal_holds.reload()?;
+        let now = Utc::now();
+        let mut manifest = DeletionManifest::new(format!("audit_log:{}", self.log_id), "retention policy");
+        
+        let mut tombstones = Vec::new();
+        for entry in self.audit_entries.iter().filter(|entry| !entry.purged) {
+            if !self.retention.is_expired(&entry.action, entry.timestamp, now) {
+                continue;
+            }
+            
+            let record = RetainedRecord::new(&entry.resource)
+                .with_user(entry.user_id.as_deref())
+                .with_created_at(entry.timestamp);
+            if let Some(hold_id) = self.legal_holds.holding(&record)? {
+                manifest.withhold(entry.entry_id.to_string(), hold_id);
+                continue;
             }
+            
+            let mut tombstone = entry.clone();
+            tombstone.purge(&manifest.manifest_id);
+            manifest.purge(PurgedRecord {
+                record_id: entry.entry_id.to_string(),
+                category: entry.action.clone(),
+                created_at: entry.timestamp,
+                retention_days: self.retention.retention_days(&entry.action),
+                digest: Some(entry.hash.clone()),
+                tombstone_digest: Some(tombstone.compute_hash()?),
+            });
+            tombstones.push(tombstone);
         }
         
-        Ok(serde_json::json!({
-            "report_id": Uuid::new_v4(),
-            "generated_at": Utc::now(),
-            "period": {
-                "start_date": start_date,
-                "end_date": end_date
-            },
-            "summary": {
-                "total_operations": total_operations,
-                "successful_operations": successful_operations,
-                "failed_operations": failed_operations,
-                "partial_operations": partial_operations,
-                "success_rate": if total_operations > 0 { 
-                    (successful_operations as f64 / total_operations as f64) * 100.0 
-                } else { 0.0 }
-            },
-            "action_breakdown": action_counts,
-            "user_activity": user_counts,
-            "audit_log_id": self.log_id
+        if tombstones.is_empty() {
+            return Ok(None);
+        }
+        
+        Ok(Some(PendingPurge {
+            signer,
+            sinks: self.sinks.clone(),
+            manifest,
+            tombstones,
         }))
     }
     
-    /// Clean up old audit entries based on retention policy
-    pub fn cleanup_old_entries(&mut self) -> DreasResult<usize> {
-        let cutoff_date = Utc::now() - chrono::Duration::days(self.retention_days as i64);
-        let initial_count = self.audit_entries.len();
+    /// Apply a purge the sinks have carried out to this log, recording its manifest
+    ///
+    /// Fails if any of the purged entries was purged or removed from the log meanwhile.
+    pub async fn record_purge(&mut self, purge: CompletedPurge) -> DreasResult<usize> {
+        let CompletedPurge { manifest, tombstones } = purge;
+        let purged_ids: HashSet<Uuid> = tombstones.iter().map(|tombstone| tombstone.entry_id).collect();
+        let still_present = self.audit_entries.iter()
+            .filter(|entry| !entry.purged && purged_ids.contains(&entry.entry_id))
+            .count();
+        if still_present != tombstones.len() {
+            return Err(DreasError::AuditLogging(format!(
+                "Audit log {} changed while deletion manifest {} was prepared", self.log_id, manifest.manifest_id
+            )));
+        }
         
-        self.audit_entries.retain(|entry| entry.timestamp > cutoff_date);
+        if let Some(path) = &self.manifest_path {
+            manifest.append_to(path)?;
+        }
         
-        let removed_count = initial_count - self.audit_entries.len();
+        let mut tombstones = tombstones.into_iter().peekable();
+        for entry in self.audit_entries.iter_mut() {
+            if let Some(tombstone) = tombstones.next_if(|tombstone| tombstone.entry_id == entry.entry_id) {
+                self.index.remove(entry);
+                *entry = tombstone;
+                self.index.insert(entry);
+            }
+        }
         
+        let removed_count = self.audit_entries.iter()
+            .take_while(|entry| entry.purged)
+            .count();
+        let previous_anchor = self.chain_anchor.clone();
         if removed_count > 0 {
-            tracing::info!("Cleaned up {} old audit entries", removed_count);
+            let last_removed = self.audit_entries.drain(..removed_count).next_back()
+                .expect("at least one entry was removed");
+            self.chain_anchor = ChainAnchor {
+                sequence: last_removed.sequence,
+                hash: last_removed.hash,
+            };
+            self.index.prune_before(self.chain_anchor.sequence + 1);
         }
         
-        Ok(removed_count)
+        let purged_count = manifest.purged.len();
+        
+        let mut pruned = AuditEntry::new(actions::AUDIT_LOG_PRUNED, format!("audit_log:{}", self.log_id), AuditResult::Success)
+            .with_metadata("purged_entries", purged_count.to_string())
+            .with_metadata("withheld_entries", manifest.withheld.len().to_string())
+            .with_metadata("deletion_manifest", manifest.manifest_id.to_string())
+            .with_metadata("manifest_digest", manifest.digest()?)
+            .with_metadata("removed_entries", removed_count.to_string());
+        if removed_count > 0 {
+            pruned = pruned
+                .with_metadata("from_sequence", (previous_anchor.sequence + 1).to_string())
+                .with_metadata("to_sequence", self.chain_anchor.sequence.to_string())
+                .with_metadata("anchor_hash", self.chain_anchor.hash.clone());
+        }
+        if !manifest.retained_by.is_empty() {
+            pruned = pruned.with_metadata("retained_by", manifest.retained_by.join(","));
+        }
+        self.deletion_manifests.push(manifest);
+        self.log_entry(pruned).await?;
+        
+        tracing::info!("Purged {} old audit entries, removing {}", purged_count, removed_count);
+        Ok(purged_count)
+    }
+    
+    /// Get the operations treated as sensitive
+    pub fn sensitive_operations(&self) -> &[String] {
+        &self.sensitive_operations
     }
     
     /// Get audit statistics
@@ -244,7 +1044,278 @@ impl AuditLogger {
             "retention_days": self.retention_days,
             "sensitive_operations_tracked": self.sensitive_operations.len(),
             "oldest_entry": self.audit_entries.iter().map(|e| e.timestamp).min(),
-            "newest_entry": self.audit_entries.iter().map(|e| e.timestamp).max()
+            "newest_entry": self.audit_entries.iter().map(|e| e.timestamp).max(),
+            "chain_head": self.head(),
+            "subscribers": self.events.receiver_count()
         })
     }
 }
+
+impl AuditHandle {
+    /// Create a handle recording into a shared audit logger
+    pub fn new(logger: Arc<Mutex<AuditLogger>>) -> Self {
+        Self { logger: Some(logger) }
+    }
+    
+    /// Create a handle that records nothing
+    pub fn detached() -> Self {
+        Self::default()
+    }
+    
+    /// Get the shared audit logger, if attached
+    pub fn logger(&self) -> Option<&Arc<Mutex<AuditLogger>>> {
+        self.logger.as_ref()
+    }
+    
+    /// Record an entry in the shared log
+    ///
+    /// Errors from the logger, such as a failed mandatory sink, are returned so the caller can
+    /// fail the operation being audited.
+    pub async fn record(&self, entry: AuditEntry) -> DreasResult<()> {
+        if let Some(logger) = &self.logger {
+            logger.lock().await.log_entry(entry).await?;
+        }
+        Ok(())
+    }
+    
+    /// Sign a checkpoint of the shared log every `period`, logging failures
+    pub async fn run_checkpoints(self, period: std::time::Duration) {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.checkpoint().await {
+                tracing::error!("Failed to sign audit checkpoint: {}", e);
+            }
+        }
+    }
+    
+    /// Purge expired entries from the shared log every `period`, logging failures
+    pub async fn run_retention(self, period: std::time::Duration) {
+        if self.logger.is_none() {
+            return;
+        }
+        
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.apply_retention().await {
+                tracing::error!("Failed to apply audit retention: {}", e);
+            }
+        }
+    }
+    
+    /// Purge expired entries from the shared log
+    ///
+    /// The log is locked only to pick the expired entries and to apply the purge, so entries
+    /// are still logged while sinks purge their copies and a remote key signs the manifest.
+    pub async fn apply_retention(&self) -> DreasResult<usize> {
+        let logger = self.logger.as_ref()
+            .ok_or_else(|| DreasError::Configuration("No audit log to purge".to_string()))?;
+        
+        let Some(pending) = logger.lock().await.prepare_purge()? else {
+            return Ok(0);
+        };
+        let purge = pending.execute().await?;
+        logger.lock().await.record_purge(purge).await
+    }
+    
+    /// Sign a checkpoint of the shared log
+    ///
+    /// The log is locked only to snapshot the tree head and to keep the signed one, so
+    /// entries are still logged while a remote key signs it.
+    pub async fn checkpoint(&self) -> DreasResult<SignedTreeHead> {
+        let logger = self.logger.as_ref()
+            .ok_or_else(|| DreasError::Configuration("No audit log to checkpoint".to_string()))?;
+        
+        let pending = logger.lock().await.prepare_checkpoint()?;
+        let tree_head = pending.sign().await?;
+        logger.lock().await.record_checkpoint(tree_head.clone())?;
+        Ok(tree_head)
+    }
+}
+
+impl PendingPurge {
+    /// Number of entries to be purged
+    pub fn len(&self) -> usize {
+        self.tombstones.len()
+    }
+    
+    /// Check whether nothing is to be purged
+    pub fn is_empty(&self) -> bool {
+        self.tombstones.is_empty()
+    }
+    
+    /// Replace the sinks' copies of the entries with tombstones and sign the manifest
+    ///
+    /// Sinks go first, so a failed purge leaves the log as it was.
+    pub async fn execute(self) -> DreasResult<CompletedPurge> {
+        let mut manifest = self.manifest;
+        manifest.retained_by = self.sinks.purge_all(&self.tombstones).await?;
+        let manifest = manifest.sign(self.signer.as_ref()).await?;
+        
+        Ok(CompletedPurge {
+            manifest,
+            tombstones: self.tombstones,
+        })
+    }
+}
+
+impl PendingCheckpoint {
+    /// Number of entries the tree head covers
+    pub fn tree_size(&self) -> u64 {
+        self.tree_size
+    }
+    
+    /// Sign the snapshotted tree head
+    pub async fn sign(self) -> DreasResult<SignedTreeHead> {
+        let payload = SignedTreeHead::signing_payload(&self.log_id, self.tree_size, &self.root_hash, &self.timestamp);
+        let signature = self.signer.sign(&payload).await?;
+        
+        Ok(SignedTreeHead {
+            log_id: self.log_id,
+            tree_size: self.tree_size,
+            root_hash: self.root_hash,
+            timestamp: self.timestamp,
+            key_id: self.signer.key_id().to_string(),
+            signature: STANDARD.encode(signature),
+        })
+    }
+}
+
+impl From<Arc<Mutex<AuditLogger>>> for AuditHandle {
+    fn from(logger: Arc<Mutex<AuditLogger>>) -> Self {
+        Self::new(logger)
+    }
+}
+
+impl AuditEntry {
+    /// Create an audit entry for an action on a resource
+    pub fn new(action: impl Into<String>, resource: impl Into<String>, result: AuditResult) -> Self {
+        Self {
+            entry_id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            user_id: None,
+            session_id: None,
+            action: action.into(),
+            resource: resource.into(),
+            result,
+            ip_address: None,
+            user_agent: None,
+            metadata: HashMap::new(),
+            tenant_id: None,
+            actor_id: None,
+            request_id: None,
+            trace_id: None,
+            sequence: 0,
+            previous_hash: String::new(),
+            hash: String::new(),
+            purged: false,
+        }
+    }
+    
+    /// Compute the SHA-256 hash of the entry's contents, as hex
+    pub fn compute_hash(&self) -> DreasResult<String> {
+        let contents = HashedContents {
+            sequence: self.sequence,
+            previous_hash: &self.previous_hash,
+            entry_id: &self.entry_id,
+            timestamp: &self.timestamp,
+            user_id: &self.user_id,
+            session_id: &self.session_id,
+            action: &self.action,
+            resource: &self.resource,
+            result: &self.result,
+            ip_address: &self.ip_address,
+            user_agent: &self.user_agent,
+            metadata: self.metadata.iter().collect(),
+            tenant_id: &self.tenant_id,
+            actor_id: &self.actor_id,
+            request_id: &self.request_id,
+            trace_id: &self.trace_id,
+            purged: self.purged,
+        };
+        
+        Ok(hex::encode(Sha256::digest(serde_json::to_vec(&contents)?)))
+    }
+    
+    /// Set the user who performed the action
+    pub fn with_user(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+    
+    /// Set the session the action was performed in
+    pub fn with_session(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+    
+    /// Set the support engineer acting on the user's behalf
+    pub fn with_actor(mut self, actor_id: impl Into<String>) -> Self {
+        self.actor_id = Some(actor_id.into());
+        self
+    }
+    
+    /// Set the tenant the action concerned
+    pub fn with_tenant(mut self, tenant_id: TenantId) -> Self {
+        self.tenant_id = Some(tenant_id);
+        self
+    }
+    
+    /// Add a metadata value
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+    
+    /// Remove the entry's contents for retention, keeping what holds its place in the chain
+    fn purge(&mut self, manifest_id: &Uuid) {
+        self.user_id = None;
+        self.session_id = None;
+        self.resource = PURGED_RESOURCE.to_string();
+        self.ip_address = None;
+        self.user_agent = None;
+        self.metadata = HashMap::from([(DELETION_MANIFEST_METADATA.to_string(), manifest_id.to_string())]);
+        self.actor_id = None;
+        self.request_id = None;
+        self.trace_id = None;
+        self.purged = true;
+    }
+}
+
+impl ChainAnchor {
+    /// Anchor of a chain with no earlier entries
+    pub fn genesis() -> Self {
+        Self {
+            sequence: 0,
+            hash: GENESIS_HASH.to_string(),
+        }
+    }
+}
+
+impl AuditQuery {
+    /// Filter selecting the entries this query matches
+    pub fn filter(&self) -> AuditFilter {
+        let mut conditions = Vec::new();
+        if let Some(start_date) = self.start_date {
+            conditions.push(AuditFilter::Since(start_date));
+        }
+        if let Some(end_date) = self.end_date {
+            conditions.push(AuditFilter::Until(end_date));
+        }
+        if let Some(user_id) = &self.user_id {
+            conditions.push(AuditFilter::User(user_id.clone()));
+        }
+        if let Some(action) = &self.action {
+            conditions.push(AuditFilter::Action(Pattern::Exact(action.clone())));
+        }
+        if let Some(resource) = &self.resource {
+            conditions.push(AuditFilter::Resource(Pattern::Exact(resource.clone())));
+        }
+        if let Some(result) = &self.result {
+            conditions.push(AuditFilter::Result(result.clone()));
+        }
+        
+        AuditFilter::all_of(conditions)
+    }
+}
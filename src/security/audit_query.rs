@@ -0,0 +1,710 @@
+//! Audit log search
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Retained audit entries are indexed by action, resource, user, tenant, result,
+//! time and the words of their metadata, so a search only looks at entries that
+//! can match instead of scanning the whole log. Filters combine with AND, OR and
+//! NOT, match actions and resources exactly, by prefix or by wildcard, and can be
+//! written as query strings such as `action:storage_* AND NOT result:success`.
+//! Results come back in pages, each with a cursor for the next.
+
+use crate::{DreasResult, DreasError};
+use super::audit::{AuditEntry, AuditResult};
+use super::tenant::TenantId;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use uuid::Uuid;
+
+/// Permission needed to search the audit log through the API
+pub const AUDIT_READ_PERMISSION: &str = "audit:read";
+
+/// Permission needed to read the audit entries of tenants other than the principal's own
+pub const AUDIT_READ_ALL_TENANTS_PERMISSION: &str = "audit:read_all_tenants";
+
+/// Number of entries in a page of results unless the search asks for another size
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page of results a search can ask for
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// How a string field is matched
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum Pattern {
+    Exact(String),
+    Prefix(String),
+    /// `*` matches any run of characters and `?` any single character
+    Wildcard(String),
+}
+
+/// Condition on audit entries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditFilter {
+    /// Every entry
+    All,
+    Action(Pattern),
+    Resource(Pattern),
+    User(String),
+    Actor(String),
+    Session(String),
+    Tenant(TenantId),
+    Result(AuditResult),
+    RequestId(Uuid),
+    TraceId(String),
+    /// Logged at or after the time
+    Since(DateTime<Utc>),
+    /// Logged at or before the time
+    Until(DateTime<Utc>),
+    /// Entries whose metadata value for the key matches
+    Metadata { key: String, value: Pattern },
+    /// Entries whose metadata contains every word of the text, ignoring case
+    Text(String),
+    And(Vec<AuditFilter>),
+    Or(Vec<AuditFilter>),
+    Not(Box<AuditFilter>),
+}
+
+/// Order in which search results are returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// Most recently logged first
+    #[default]
+    NewestFirst,
+    /// Oldest first, as the entries were logged
+    OldestFirst,
+}
+
+/// Search of the audit log, one page at a time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditSearch {
+    pub filter: AuditFilter,
+    pub order: SortOrder,
+    /// Maximum number of entries per page, capped at `MAX_PAGE_SIZE`
+    pub page_size: usize,
+    /// Cursor returned with the previous page; the search starts at the first page without one
+    pub cursor: Option<String>,
+}
+
+/// One page of search results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    /// Cursor for the next page, or `None` when this is the last
+    pub next_cursor: Option<String>,
+}
+
+/// Indexes over the retained entries of an audit log
+///
+/// Entries are referred to by sequence number, which stays valid as older entries are pruned.
+#[derive(Debug, Clone, Default)]
+pub struct AuditIndex {
+    actions: BTreeMap<String, BTreeSet<u64>>,
+    resources: BTreeMap<String, BTreeSet<u64>>,
+    users: HashMap<String, BTreeSet<u64>>,
+    tenants: HashMap<TenantId, BTreeSet<u64>>,
+    results: HashMap<AuditResult, BTreeSet<u64>>,
+    timestamps: BTreeMap<DateTime<Utc>, BTreeSet<u64>>,
+    words: HashMap<String, BTreeSet<u64>>,
+}
+
+/// Token of a query string
+#[derive(Debug, Clone, PartialEq)]
+enum QueryToken {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term { field: Option<String>, value: String, quoted: bool },
+}
+
+impl Pattern {
+    /// Read a query value: a trailing `*` makes a prefix, any other `*` or `?` a wildcard
+    pub fn parse(value: &str) -> Self {
+        match value.strip_suffix('*') {
+            Some(prefix) if !prefix.contains(['*', '?']) => Pattern::Prefix(prefix.to_string()),
+            _ if value.contains(['*', '?']) => Pattern::Wildcard(value.to_string()),
+            _ => Pattern::Exact(value.to_string()),
+        }
+    }
+    
+    /// Check whether a value matches
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Exact(exact) => value == exact,
+            Pattern::Prefix(prefix) => value.starts_with(prefix.as_str()),
+            Pattern::Wildcard(pattern) => wildcard_match(pattern, value),
+        }
+    }
+    
+    /// Longest prefix every matching value starts with
+    fn literal_prefix(&self) -> &str {
+        match self {
+            Pattern::Exact(exact) => exact,
+            Pattern::Prefix(prefix) => prefix,
+            Pattern::Wildcard(pattern) => &pattern[..pattern.find(['*', '?']).unwrap_or(pattern.len())],
+        }
+    }
+}
+
+/// Match a value against a pattern of literal characters, `*` and `?`
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` swallow one more character and retry
+            backtrack = Some((star, matched + 1));
+            p = star + 1;
+            v = matched + 1;
+        } else {
+            return false;
+        }
+    }
+    
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Lowercase words of a text, for free-text search
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+/// Words of an entry's metadata keys and values
+fn metadata_words(entry: &AuditEntry) -> BTreeSet<String> {
+    entry.metadata.iter()
+        .flat_map(|(key, value)| words(key).chain(words(value)))
+        .collect()
+}
+
+impl AuditFilter {
+    /// Combine conditions that must all hold
+    pub fn all_of(mut filters: Vec<AuditFilter>) -> Self {
+        match filters.len() {
+            0 => AuditFilter::All,
+            1 => filters.remove(0),
+            _ => AuditFilter::And(filters),
+        }
+    }
+    
+    /// Parse a query string
+    ///
+    /// Terms are `field:value` or bare words, which search metadata. Fields are `action`,
+    /// `resource`, `user`, `actor`, `session`, `tenant`, `result`, `request`, `trace`,
+    /// `since`, `until` and `metadata.KEY`. Action, resource and metadata values may use
+    /// `*` and `?` unless quoted. Terms combine with `AND` (also implied between terms), `OR`,
+    /// `NOT` and parentheses; `since` and `until` take RFC 3339 times or whole dates, with
+    /// `until` covering the whole of a date. An empty query matches every entry.
+    pub fn parse(query: &str) -> DreasResult<Self> {
+        let tokens = tokenize(query)?;
+        let mut position = 0;
+        if tokens.is_empty() {
+            return Ok(AuditFilter::All);
+        }
+        
+        let filter = parse_or(&tokens, &mut position)?;
+        if position < tokens.len() {
+            return Err(invalid_query(format!("unexpected {:?}", tokens[position])));
+        }
+        Ok(filter)
+    }
+    
+    /// Build a filter from API query parameters
+    ///
+    /// `q` takes a query string for `parse`; `action`, `result` and `user` are shorthands
+    /// for the fields of the same names. All given parameters must hold.
+    pub fn from_query_params(params: &HashMap<String, String>) -> DreasResult<Self> {
+        let mut filters = vec![Self::parse(params.get("q").map_or("", String::as_str))?];
+        for field in ["action", "result", "user"] {
+            if let Some(value) = params.get(field) {
+                filters.push(parse_term(Some(field), value, false)?);
+            }
+        }
+        filters.retain(|filter| filter != &AuditFilter::All);
+        
+        Ok(Self::all_of(filters))
+    }
+    
+    /// Check whether an entry satisfies the condition
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        match self {
+            AuditFilter::All => true,
+            AuditFilter::Action(pattern) => pattern.matches(&entry.action),
+            AuditFilter::Resource(pattern) => pattern.matches(&entry.resource),
+            AuditFilter::User(user_id) => entry.user_id.as_ref() == Some(user_id),
+            AuditFilter::Actor(actor_id) => entry.actor_id.as_ref() == Some(actor_id),
+            AuditFilter::Session(session_id) => entry.session_id.as_ref() == Some(session_id),
+            AuditFilter::Tenant(tenant_id) => entry.tenant_id.as_ref() == Some(tenant_id),
+            AuditFilter::Result(result) => &entry.result == result,
+            AuditFilter::RequestId(request_id) => entry.request_id.as_ref() == Some(request_id),
+            AuditFilter::TraceId(trace_id) => entry.trace_id.as_ref() == Some(trace_id),
+            AuditFilter::Since(start) => entry.timestamp >= *start,
+            AuditFilter::Until(end) => entry.timestamp <= *end,
+            AuditFilter::Metadata { key, value } => entry.metadata.get(key).is_some_and(|found| value.matches(found)),
+            AuditFilter::Text(text) => {
+                let entry_words = metadata_words(entry);
+                words(text).all(|word| entry_words.contains(&word))
+            }
+            AuditFilter::And(filters) => filters.iter().all(|filter| filter.matches(entry)),
+            AuditFilter::Or(filters) => filters.iter().any(|filter| filter.matches(entry)),
+            AuditFilter::Not(filter) => !filter.matches(entry),
+        }
+    }
+}
+
+/// Error for a query string that can't be parsed
+fn invalid_query(detail: String) -> DreasError {
+    DreasError::AuditLogging(format!("Invalid audit query: {}", detail))
+}
+
+/// Split a query string into tokens
+fn tokenize(query: &str) -> DreasResult<Vec<QueryToken>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    
+    let read_quoted = |chars: &mut std::iter::Peekable<std::str::Chars<'_>>| -> DreasResult<String> {
+        chars.next();
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => value.extend(chars.next()),
+                Some(c) => value.push(c),
+                None => return Err(invalid_query("unterminated quote".to_string())),
+            }
+        }
+    };
+    
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(QueryToken::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(QueryToken::Close);
+            }
+            '"' => tokens.push(QueryToken::Term { field: None, value: read_quoted(&mut chars)?, quoted: true }),
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                
+                let token = match (word.as_str(), word.split_once(':')) {
+                    ("AND", _) => QueryToken::And,
+                    ("OR", _) => QueryToken::Or,
+                    ("NOT", _) => QueryToken::Not,
+                    (_, Some((field, ""))) if chars.peek() == Some(&'"') => QueryToken::Term {
+                        field: Some(field.to_string()),
+                        value: read_quoted(&mut chars)?,
+                        quoted: true,
+                    },
+                    (_, Some((field, value))) => QueryToken::Term {
+                        field: Some(field.to_string()),
+                        value: value.to_string(),
+                        quoted: false,
+                    },
+                    _ => QueryToken::Term { field: None, value: word.clone(), quoted: false },
+                };
+                tokens.push(token);
+            }
+        }
+    }
+    
+    Ok(tokens)
+}
+
+/// Parse terms separated by `OR`
+fn parse_or(tokens: &[QueryToken], position: &mut usize) -> DreasResult<AuditFilter> {
+    let mut filters = vec![parse_and(tokens, position)?];
+    while tokens.get(*position) == Some(&QueryToken::Or) {
+        *position += 1;
+        filters.push(parse_and(tokens, position)?);
+    }
+    
+    Ok(if filters.len() == 1 { filters.remove(0) } else { AuditFilter::Or(filters) })
+}
+
+/// Parse terms joined by `AND` or by juxtaposition
+fn parse_and(tokens: &[QueryToken], position: &mut usize) -> DreasResult<AuditFilter> {
+    let mut filters = vec![parse_unary(tokens, position)?];
+    loop {
+        match tokens.get(*position) {
+            Some(QueryToken::And) => *position += 1,
+            Some(QueryToken::Or) | Some(QueryToken::Close) | None => break,
+            Some(_) => {}
+        }
+        filters.push(parse_unary(tokens, position)?);
+    }
+    
+    Ok(AuditFilter::all_of(filters))
+}
+
+/// Parse a term, a negated term or a parenthesized group
+fn parse_unary(tokens: &[QueryToken], position: &mut usize) -> DreasResult<AuditFilter> {
+    let token = tokens.get(*position).ok_or_else(|| invalid_query("query ends early".to_string()))?;
+    *position += 1;
+    
+    match token {
+        QueryToken::Not => Ok(AuditFilter::Not(Box::new(parse_unary(tokens, position)?))),
+        QueryToken::Open => {
+            let filter = parse_or(tokens, position)?;
+            if tokens.get(*position) != Some(&QueryToken::Close) {
+                return Err(invalid_query("missing closing parenthesis".to_string()));
+            }
+            *position += 1;
+            Ok(filter)
+        }
+        QueryToken::Term { field, value, quoted } => parse_term(field.as_deref(), value, *quoted),
+        other => Err(invalid_query(format!("unexpected {:?}", other))),
+    }
+}
+
+/// Build the condition of a single `field:value` or free-text term
+fn parse_term(field: Option<&str>, value: &str, quoted: bool) -> DreasResult<AuditFilter> {
+    let pattern = || if quoted { Pattern::Exact(value.to_string()) } else { Pattern::parse(value) };
+    
+    let Some(field) = field else {
+        return Ok(AuditFilter::Text(value.to_string()));
+    };
+    if let Some(key) = field.strip_prefix("metadata.") {
+        return Ok(AuditFilter::Metadata { key: key.to_string(), value: pattern() });
+    }
+    
+    Ok(match field {
+        "action" => AuditFilter::Action(pattern()),
+        "resource" => AuditFilter::Resource(pattern()),
+        "user" => AuditFilter::User(value.to_string()),
+        "actor" => AuditFilter::Actor(value.to_string()),
+        "session" => AuditFilter::Session(value.to_string()),
+        "trace" => AuditFilter::TraceId(value.to_ascii_lowercase()),
+        "tenant" => AuditFilter::Tenant(TenantId::new(value).map_err(|e| invalid_query(e.to_string()))?),
+        "request" => AuditFilter::RequestId(
+            Uuid::parse_str(value).map_err(|e| invalid_query(format!("bad request ID {:?}: {}", value, e)))?,
+        ),
+        "result" => AuditFilter::Result(match value.to_ascii_lowercase().as_str() {
+            "success" => AuditResult::Success,
+            "failure" => AuditResult::Failure,
+            "partial" => AuditResult::Partial,
+            _ => return Err(invalid_query(format!("unknown result {:?}", value))),
+        }),
+        "since" => AuditFilter::Since(parse_time(value, false).map_err(|e| invalid_query(e.to_string()))?),
+        "until" => AuditFilter::Until(parse_time(value, true).map_err(|e| invalid_query(e.to_string()))?),
+        _ => return Err(invalid_query(format!("unknown field {:?}", field))),
+    })
+}
+
+/// Parse an RFC 3339 time, or a `YYYY-MM-DD` date as its first or last instant in UTC
+pub fn parse_time(value: &str, end_of_day: bool) -> DreasResult<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let start = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time").and_utc();
+        return Ok(if end_of_day { start + Duration::days(1) - Duration::nanoseconds(1) } else { start });
+    }
+    
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| DreasError::Generic(format!("Invalid time {:?}, expected RFC 3339 or YYYY-MM-DD: {}", value, e)))
+}
+
+impl AuditSearch {
+    /// Search for entries matching a filter, newest first
+    pub fn new(filter: AuditFilter) -> Self {
+        Self {
+            filter,
+            order: SortOrder::default(),
+            page_size: DEFAULT_PAGE_SIZE,
+            cursor: None,
+        }
+    }
+    
+    /// Return results in the given order
+    pub fn with_order(mut self, order: SortOrder) -> Self {
+        self.order = order;
+        self
+    }
+    
+    /// Return at most `page_size` entries per page
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+    
+    /// Continue from the page that returned the cursor
+    pub fn with_cursor(mut self, cursor: impl Into<String>) -> Self {
+        self.cursor = Some(cursor.into());
+        self
+    }
+    
+    /// Build a search from API query parameters `order` (`newest` or `oldest`), `limit` and
+    /// `cursor`, plus those of `AuditFilter::from_query_params`
+    pub fn from_query_params(params: &HashMap<String, String>) -> DreasResult<Self> {
+        let mut search = Self::new(AuditFilter::from_query_params(params)?);
+        
+        if let Some(order) = params.get("order") {
+            search.order = match order.as_str() {
+                "newest" => SortOrder::NewestFirst,
+                "oldest" => SortOrder::OldestFirst,
+                _ => return Err(invalid_query(format!("unknown order {:?}", order))),
+            };
+        }
+        if let Some(limit) = params.get("limit") {
+            search.page_size = limit.parse()
+                .map_err(|_| invalid_query(format!("bad limit {:?}", limit)))?;
+        }
+        search.cursor = params.get("cursor").cloned();
+        
+        Ok(search)
+    }
+}
+
+impl AuditIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Index an entry
+    pub fn insert(&mut self, entry: &AuditEntry) {
+        let sequence = entry.sequence;
+        self.actions.entry(entry.action.clone()).or_default().insert(sequence);
+        self.resources.entry(entry.resource.clone()).or_default().insert(sequence);
+        if let Some(user_id) = &entry.user_id {
+            self.users.entry(user_id.clone()).or_default().insert(sequence);
+        }
+        if let Some(tenant_id) = &entry.tenant_id {
+            self.tenants.entry(tenant_id.clone()).or_default().insert(sequence);
+        }
+        self.results.entry(entry.result.clone()).or_default().insert(sequence);
+        self.timestamps.entry(entry.timestamp).or_default().insert(sequence);
+        for word in metadata_words(entry) {
+            self.words.entry(word).or_default().insert(sequence);
+        }
+    }
+    
+    /// Forget an entry, e.g. before re-indexing it with its contents purged
+    pub fn remove(&mut self, entry: &AuditEntry) {
+        fn forget<K: Ord>(postings: &mut BTreeMap<K, BTreeSet<u64>>, key: &K, sequence: u64) {
+            if let Some(set) = postings.get_mut(key) {
+                set.remove(&sequence);
+                if set.is_empty() {
+                    postings.remove(key);
+                }
+            }
+        }
+        fn forget_hashed<K: std::hash::Hash + Eq>(postings: &mut HashMap<K, BTreeSet<u64>>, key: &K, sequence: u64) {
+            if let Some(set) = postings.get_mut(key) {
+                set.remove(&sequence);
+                if set.is_empty() {
+                    postings.remove(key);
+                }
+            }
+        }
+        
+        let sequence = entry.sequence;
+        forget(&mut self.actions, &entry.action, sequence);
+        forget(&mut self.resources, &entry.resource, sequence);
+        forget(&mut self.timestamps, &entry.timestamp, sequence);
+        if let Some(user_id) = &entry.user_id {
+            forget_hashed(&mut self.users, user_id, sequence);
+        }
+        if let Some(tenant_id) = &entry.tenant_id {
+            forget_hashed(&mut self.tenants, tenant_id, sequence);
+        }
+        forget_hashed(&mut self.results, &entry.result, sequence);
+        for word in metadata_words(entry) {
+            forget_hashed(&mut self.words, &word, sequence);
+        }
+    }
+    
+    /// Forget entries before the given sequence number
+    pub fn prune_before(&mut self, sequence: u64) {
+        fn prune<K: Ord>(postings: &mut BTreeMap<K, BTreeSet<u64>>, sequence: u64) {
+            postings.values_mut().for_each(|set| *set = set.split_off(&sequence));
+            postings.retain(|_, set| !set.is_empty());
+        }
+        fn prune_hashed<K: std::hash::Hash + Eq>(postings: &mut HashMap<K, BTreeSet<u64>>, sequence: u64) {
+            postings.values_mut().for_each(|set| *set = set.split_off(&sequence));
+            postings.retain(|_, set| !set.is_empty());
+        }
+        
+        prune(&mut self.actions, sequence);
+        prune(&mut self.resources, sequence);
+        prune(&mut self.timestamps, sequence);
+        prune_hashed(&mut self.users, sequence);
+        prune_hashed(&mut self.tenants, sequence);
+        prune_hashed(&mut self.results, sequence);
+        prune_hashed(&mut self.words, sequence);
+    }
+    
+    /// Sequence numbers of a superset of the entries matching a filter, or `None` if the
+    /// filter can't be narrowed down by the indexes
+    fn candidates(&self, filter: &AuditFilter) -> Option<BTreeSet<u64>> {
+        let lookup = |set: Option<&BTreeSet<u64>>| Some(set.cloned().unwrap_or_default());
+        
+        match filter {
+            AuditFilter::Action(pattern) => Some(Self::matching_keys(&self.actions, pattern)),
+            AuditFilter::Resource(pattern) => Some(Self::matching_keys(&self.resources, pattern)),
+            AuditFilter::User(user_id) => lookup(self.users.get(user_id)),
+            AuditFilter::Tenant(tenant_id) => lookup(self.tenants.get(tenant_id)),
+            AuditFilter::Result(result) => lookup(self.results.get(result)),
+            AuditFilter::Since(start) => Some(self.timestamps.range(start..).flat_map(|(_, set)| set).copied().collect()),
+            AuditFilter::Until(end) => Some(self.timestamps.range(..=end).flat_map(|(_, set)| set).copied().collect()),
+            AuditFilter::Text(text) => {
+                let mut sets = words(text).map(|word| self.words.get(&word));
+                let first = sets.next()?;
+                let mut result = first.cloned().unwrap_or_default();
+                for set in sets {
+                    result = set.map(|set| result.intersection(set).copied().collect()).unwrap_or_default();
+                }
+                Some(result)
+            }
+            AuditFilter::And(filters) => {
+                let mut narrowed: Vec<BTreeSet<u64>> = filters.iter().filter_map(|filter| self.candidates(filter)).collect();
+                narrowed.sort_by_key(BTreeSet::len);
+                let mut sets = narrowed.into_iter();
+                let first = sets.next()?;
+                Some(sets.fold(first, |result, set| result.intersection(&set).copied().collect()))
+            }
+            AuditFilter::Or(filters) => filters.iter()
+                .map(|filter| self.candidates(filter))
+                .try_fold(BTreeSet::new(), |mut result, set| {
+                    result.extend(set?);
+                    Some(result)
+                }),
+            _ => None,
+        }
+    }
+    
+    /// Sequence numbers of entries whose key matches a pattern
+    fn matching_keys(postings: &BTreeMap<String, BTreeSet<u64>>, pattern: &Pattern) -> BTreeSet<u64> {
+        // Every match starts with the pattern's literal prefix, so only that range of keys is visited
+        let prefix = pattern.literal_prefix();
+        postings.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .filter(|(key, _)| pattern.matches(key))
+            .flat_map(|(_, set)| set)
+            .copied()
+            .collect()
+    }
+    
+    /// Entries matching a filter, in log order
+    ///
+    /// `entries` must be the indexed entries, contiguous by sequence number.
+    pub fn matching<'a>(&self, entries: &'a [AuditEntry], filter: &AuditFilter) -> Vec<&'a AuditEntry> {
+        let Some(first) = entries.first().map(|entry| entry.sequence) else {
+            return Vec::new();
+        };
+        
+        match self.candidates(filter) {
+            Some(sequences) => sequences.into_iter()
+                .filter_map(|sequence| entries.get(sequence.checked_sub(first)? as usize))
+                .filter(|entry| filter.matches(entry))
+                .collect(),
+            None => entries.iter().filter(|entry| filter.matches(entry)).collect(),
+        }
+    }
+    
+    /// Run one page of a search over the indexed entries of a log
+    pub fn search(&self, entries: &[AuditEntry], log_id: &Uuid, search: &AuditSearch) -> DreasResult<AuditPage> {
+        let page_size = search.page_size.clamp(1, MAX_PAGE_SIZE);
+        let after = search.cursor.as_deref()
+            .map(|cursor| decode_cursor(cursor, log_id, search.order))
+            .transpose()?;
+        
+        let empty = AuditPage { entries: Vec::new(), next_cursor: None };
+        let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+            return Ok(empty);
+        };
+        let (first, last) = (first.sequence, last.sequence);
+        
+        // The cursor is the last entry already returned, so the page starts just past it
+        let (low, high) = match (search.order, after) {
+            (_, None) => (first, last),
+            (SortOrder::NewestFirst, Some(after)) => (first, last.min(after.saturating_sub(1))),
+            (SortOrder::OldestFirst, Some(after)) => (first.max(after.saturating_add(1)), last),
+        };
+        if low > high {
+            return Ok(empty);
+        }
+        
+        let candidates = self.candidates(&search.filter);
+        let sequences: Box<dyn Iterator<Item = u64> + '_> = match (&candidates, search.order) {
+            (Some(set), SortOrder::NewestFirst) => Box::new(set.range(low..=high).rev().copied()),
+            (Some(set), SortOrder::OldestFirst) => Box::new(set.range(low..=high).copied()),
+            (None, SortOrder::NewestFirst) => Box::new((low..=high).rev()),
+            (None, SortOrder::OldestFirst) => Box::new(low..=high),
+        };
+        
+        let mut matches = sequences
+            .filter_map(|sequence| entries.get((sequence - first) as usize))
+            .filter(|entry| search.filter.matches(entry));
+        let page: Vec<AuditEntry> = matches.by_ref().take(page_size).cloned().collect();
+        
+        let next_cursor = match page.last() {
+            Some(last_returned) if page.len() == page_size && matches.next().is_some() => {
+                Some(encode_cursor(log_id, search.order, last_returned.sequence))
+            }
+            _ => None,
+        };
+        
+        Ok(AuditPage { entries: page, next_cursor })
+    }
+}
+
+/// Tag of a sort order in cursors
+fn order_tag(order: SortOrder) -> &'static str {
+    match order {
+        SortOrder::NewestFirst => "newest",
+        SortOrder::OldestFirst => "oldest",
+    }
+}
+
+/// Encode the position after an entry as an opaque cursor
+fn encode_cursor(log_id: &Uuid, order: SortOrder, sequence: u64) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}:{}", log_id, order_tag(order), sequence))
+}
+
+/// Decode a cursor, checking it was issued by this log for the same sort order
+fn decode_cursor(cursor: &str, log_id: &Uuid, order: SortOrder) -> DreasResult<u64> {
+    let invalid = || DreasError::AuditLogging("Invalid audit search cursor".to_string());
+    
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?).map_err(|_| invalid())?;
+    let mut parts = decoded.splitn(3, ':');
+    let (Some(cursor_log_id), Some(cursor_order), Some(sequence)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    if cursor_log_id != log_id.to_string() || cursor_order != order_tag(order) {
+        return Err(DreasError::AuditLogging(
+            "Audit search cursor belongs to another log or sort order".to_string(),
+        ));
+    }
+    
+    sequence.parse().map_err(|_| invalid())
+}
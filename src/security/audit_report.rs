@@ -0,0 +1,720 @@
+//! Audit reports for compliance evidence
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! SOC 2 and ISO 27001 audits ask for evidence that access is reviewed, keys are used
+//! as intended, escrowed keys are only recovered with approval and failed sign-ins are
+//! watched. Each `ReportTemplate` builds a typed report answering one of those questions
+//! from the audit log, which renders as JSON, CSV or HTML. A `ReportSignature` is a
+//! detached signature over a rendered report, so the evidence can be verified later.
+
+use crate::{DreasResult, DreasError};
+use super::audit::{actions, AuditEntry, AuditResult, ChainAnchor};
+use super::token::{verify_signature, TokenSigner};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+
+/// Domain separator of report signatures
+const REPORT_SIGNATURE_CONTEXT: &[u8] = b"dreas-audit-report/v1\n";
+
+/// Separator of list values within a CSV or HTML cell
+const LIST_SEPARATOR: &str = "; ";
+
+/// Kind of report to build from the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportTemplate {
+    /// Operations by action
+    Activity,
+    /// Sign-ins, permission changes, denials and impersonation by user
+    AccessReview,
+    /// Use of each KMS key
+    KeyUsage,
+    /// Every recovery of an escrowed key
+    EscrowRecoveries,
+    /// Failed sign-ins by username
+    FailedLogins,
+}
+
+/// Format a report is rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Json,
+    Csv,
+    Html,
+}
+
+/// Report over the audit entries logged in a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditReport {
+    pub report_id: Uuid,
+    pub title: String,
+    pub generated_at: DateTime<Utc>,
+    /// Start of the period, inclusive
+    pub period_start: DateTime<Utc>,
+    /// End of the period, inclusive
+    pub period_end: DateTime<Utc>,
+    pub audit_log_id: Uuid,
+    /// Head of the audit hash chain when the report was generated
+    pub chain_head: ChainAnchor,
+    /// SOC 2 and ISO 27001 controls the report is evidence for
+    pub controls: Vec<String>,
+    pub summary: ReportSummary,
+    #[serde(flatten)]
+    pub rows: ReportRows,
+}
+
+/// Outcome counts of every operation in the period
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReportSummary {
+    pub total_operations: usize,
+    pub successful_operations: usize,
+    pub failed_operations: usize,
+    pub partial_operations: usize,
+    /// Percentage of operations that succeeded
+    pub success_rate: f64,
+}
+
+/// Rows of a report, typed by template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "template", content = "rows", rename_all = "snake_case")]
+pub enum ReportRows {
+    Activity(Vec<ActivityRow>),
+    AccessReview(Vec<AccessReviewRow>),
+    KeyUsage(Vec<KeyUsageRow>),
+    EscrowRecoveries(Vec<EscrowRecoveryRow>),
+    FailedLogins(Vec<FailedLoginRow>),
+}
+
+/// Operations of one action
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActivityRow {
+    pub action: String,
+    pub total: usize,
+    pub successful: usize,
+    pub failed: usize,
+    pub partial: usize,
+    /// Distinct users who performed the action
+    pub users: usize,
+}
+
+/// Access activity of one user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessReviewRow {
+    pub user_id: String,
+    pub successful_logins: usize,
+    pub failed_logins: usize,
+    pub permission_changes: usize,
+    /// Requests the policy engine denied
+    pub denied_requests: usize,
+    /// Support engineers who acted on the user's behalf
+    pub impersonated_by: Vec<String>,
+    pub last_activity: DateTime<Utc>,
+}
+
+/// Use of one KMS key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyUsageRow {
+    pub key: String,
+    pub encryptions: usize,
+    pub decryptions: usize,
+    pub signings: usize,
+    pub failures: usize,
+    /// Users on whose behalf the key was used
+    pub principals: Vec<String>,
+    pub last_used: DateTime<Utc>,
+}
+
+/// One recovery of an escrowed key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowRecoveryRow {
+    pub entry_id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub key: String,
+    pub requested_by: Option<String>,
+    pub reason: Option<String>,
+    /// Custodians whose signatures approved the recovery
+    pub signers: Vec<String>,
+    pub result: AuditResult,
+}
+
+/// Failed sign-ins of one username
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedLoginRow {
+    pub username: String,
+    pub failures: usize,
+    pub source_addresses: Vec<String>,
+    pub first_failure: DateTime<Utc>,
+    pub last_failure: DateTime<Utc>,
+}
+
+/// Detached signature over a rendered report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportSignature {
+    pub report_id: Uuid,
+    pub format: ReportFormat,
+    /// SHA-256 of the rendered report, as hex
+    pub digest: String,
+    pub signed_at: DateTime<Utc>,
+    pub key_id: String,
+    /// Base64-encoded Ed25519 signature over `signing_payload`
+    pub signature: String,
+}
+
+/// Row of a report table
+trait TableRow {
+    const COLUMNS: &'static [&'static str];
+    
+    fn cells(&self) -> Vec<String>;
+}
+
+impl ReportTemplate {
+    /// Title of reports built from the template
+    pub fn title(&self) -> &'static str {
+        match self {
+            ReportTemplate::Activity => "Audit Activity Summary",
+            ReportTemplate::AccessReview => "User Access Review",
+            ReportTemplate::KeyUsage => "Cryptographic Key Usage",
+            ReportTemplate::EscrowRecoveries => "Escrowed Key Recoveries",
+            ReportTemplate::FailedLogins => "Failed Sign-in Attempts",
+        }
+    }
+    
+    /// SOC 2 criteria and ISO 27001:2022 Annex A controls the report is evidence for
+    pub fn controls(&self) -> &'static [&'static str] {
+        match self {
+            ReportTemplate::Activity => &["SOC 2 CC7.2", "ISO 27001 A.8.15"],
+            ReportTemplate::AccessReview => &["SOC 2 CC6.2", "SOC 2 CC6.3", "ISO 27001 A.5.18"],
+            ReportTemplate::KeyUsage => &["SOC 2 CC6.1", "ISO 27001 A.8.24"],
+            ReportTemplate::EscrowRecoveries => &["SOC 2 CC6.1", "SOC 2 CC6.3", "ISO 27001 A.8.24"],
+            ReportTemplate::FailedLogins => &["SOC 2 CC6.1", "SOC 2 CC7.2", "ISO 27001 A.8.5", "ISO 27001 A.8.16"],
+        }
+    }
+}
+
+impl std::str::FromStr for ReportTemplate {
+    type Err = DreasError;
+    
+    fn from_str(template: &str) -> DreasResult<Self> {
+        match template.to_ascii_lowercase().replace('-', "_").as_str() {
+            "activity" => Ok(ReportTemplate::Activity),
+            "access_review" => Ok(ReportTemplate::AccessReview),
+            "key_usage" => Ok(ReportTemplate::KeyUsage),
+            "escrow_recoveries" => Ok(ReportTemplate::EscrowRecoveries),
+            "failed_logins" => Ok(ReportTemplate::FailedLogins),
+            _ => Err(DreasError::Configuration(format!(
+                "Unknown report template {:?}: use activity, access_review, key_usage, escrow_recoveries or failed_logins",
+                template
+            ))),
+        }
+    }
+}
+
+impl ReportFormat {
+    /// File extension of reports in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = DreasError;
+    
+    fn from_str(format: &str) -> DreasResult<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(DreasError::Configuration(format!("Unknown report format {:?}: use json, csv or html", format))),
+        }
+    }
+}
+
+impl AuditReport {
+    /// Build a report from the entries logged in a period
+    ///
+    /// `entries` must already be limited to the period. Purged entries count towards the
+    /// summary but, having lost their details, not towards the rows.
+    pub fn build(
+        template: ReportTemplate,
+        entries: &[AuditEntry],
+        period_start: DateTime<Utc>,
+        period_end: DateTime<Utc>,
+        audit_log_id: Uuid,
+        chain_head: ChainAnchor,
+    ) -> Self {
+        let retained: Vec<&AuditEntry> = entries.iter().filter(|entry| !entry.purged).collect();
+        let rows = match template {
+            ReportTemplate::Activity => ReportRows::Activity(activity_rows(&retained)),
+            ReportTemplate::AccessReview => ReportRows::AccessReview(access_review_rows(&retained)),
+            ReportTemplate::KeyUsage => ReportRows::KeyUsage(key_usage_rows(&retained)),
+            ReportTemplate::EscrowRecoveries => ReportRows::EscrowRecoveries(escrow_recovery_rows(&retained)),
+            ReportTemplate::FailedLogins => ReportRows::FailedLogins(failed_login_rows(&retained)),
+        };
+        
+        Self {
+            report_id: Uuid::new_v4(),
+            title: template.title().to_string(),
+            generated_at: Utc::now(),
+            period_start,
+            period_end,
+            audit_log_id,
+            chain_head,
+            controls: template.controls().iter().map(|control| control.to_string()).collect(),
+            summary: ReportSummary::of(entries),
+            rows,
+        }
+    }
+    
+    /// Get the template the report was built from
+    pub fn template(&self) -> ReportTemplate {
+        match &self.rows {
+            ReportRows::Activity(_) => ReportTemplate::Activity,
+            ReportRows::AccessReview(_) => ReportTemplate::AccessReview,
+            ReportRows::KeyUsage(_) => ReportTemplate::KeyUsage,
+            ReportRows::EscrowRecoveries(_) => ReportTemplate::EscrowRecoveries,
+            ReportRows::FailedLogins(_) => ReportTemplate::FailedLogins,
+        }
+    }
+    
+    /// Render the report in a format
+    pub fn render(&self, format: ReportFormat) -> DreasResult<String> {
+        match format {
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ReportFormat::Csv => Ok(self.to_csv()),
+            ReportFormat::Html => Ok(self.to_html()),
+        }
+    }
+    
+    /// Render the rows as CSV with a header line (RFC 4180)
+    pub fn to_csv(&self) -> String {
+        let (columns, rows) = self.rows.table();
+        
+        let mut csv = String::new();
+        for cells in std::iter::once(columns.iter().map(|column| column.to_string()).collect::<Vec<_>>()).chain(rows) {
+            let line: Vec<String> = cells.iter().map(|cell| csv_field(cell)).collect();
+            csv.push_str(&line.join(","));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+    
+    /// Render the report as a standalone HTML document
+    pub fn to_html(&self) -> String {
+        let (columns, rows) = self.rows.table();
+        let timestamp = |time: &DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+        
+        let details = [
+            ("Report ID", self.report_id.to_string()),
+            ("Period", format!("{} to {}", timestamp(&self.period_start), timestamp(&self.period_end))),
+            ("Generated", timestamp(&self.generated_at)),
+            ("Audit log", self.audit_log_id.to_string()),
+            ("Chain head", format!("{} ({})", self.chain_head.sequence, self.chain_head.hash)),
+            ("Controls", self.controls.join(LIST_SEPARATOR)),
+            ("Operations", format!(
+                "{} total, {} successful, {} failed, {} partial ({:.1}% success)",
+                self.summary.total_operations, self.summary.successful_operations, self.summary.failed_operations,
+                self.summary.partial_operations, self.summary.success_rate
+            )),
+        ];
+        
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>{}</title>\n", html_escape(&self.title)));
+        html.push_str("<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}th,td{border:1px solid #999;padding:4px 8px;text-align:left}th{background:#eee}dt{font-weight:bold}</style>\n");
+        html.push_str("</head>\n<body>\n");
+        html.push_str(&format!("<h1>{}</h1>\n<dl>\n", html_escape(&self.title)));
+        for (term, description) in details {
+            html.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", term, html_escape(&description)));
+        }
+        html.push_str("</dl>\n<table>\n<thead><tr>");
+        for column in columns {
+            html.push_str(&format!("<th>{}</th>", html_escape(column)));
+        }
+        html.push_str("</tr></thead>\n<tbody>\n");
+        for cells in rows {
+            html.push_str("<tr>");
+            for cell in cells {
+                html.push_str(&format!("<td>{}</td>", html_escape(&cell)));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+impl ReportSummary {
+    /// Count the outcomes of a set of entries
+    pub fn of(entries: &[AuditEntry]) -> Self {
+        let count = |result: AuditResult| entries.iter().filter(|entry| entry.result == result).count();
+        let total_operations = entries.len();
+        let successful_operations = count(AuditResult::Success);
+        
+        Self {
+            total_operations,
+            successful_operations,
+            failed_operations: count(AuditResult::Failure),
+            partial_operations: count(AuditResult::Partial),
+            success_rate: if total_operations > 0 {
+                (successful_operations as f64 / total_operations as f64) * 100.0
+            } else { 0.0 },
+        }
+    }
+}
+
+impl ReportRows {
+    /// Get the number of rows
+    pub fn len(&self) -> usize {
+        match self {
+            ReportRows::Activity(rows) => rows.len(),
+            ReportRows::AccessReview(rows) => rows.len(),
+            ReportRows::KeyUsage(rows) => rows.len(),
+            ReportRows::EscrowRecoveries(rows) => rows.len(),
+            ReportRows::FailedLogins(rows) => rows.len(),
+        }
+    }
+    
+    /// Check whether there are no rows
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// Column headings and the cells of every row
+    fn table(&self) -> (&'static [&'static str], Vec<Vec<String>>) {
+        fn table<R: TableRow>(rows: &[R]) -> (&'static [&'static str], Vec<Vec<String>>) {
+            (R::COLUMNS, rows.iter().map(TableRow::cells).collect())
+        }
+        
+        match self {
+            ReportRows::Activity(rows) => table(rows),
+            ReportRows::AccessReview(rows) => table(rows),
+            ReportRows::KeyUsage(rows) => table(rows),
+            ReportRows::EscrowRecoveries(rows) => table(rows),
+            ReportRows::FailedLogins(rows) => table(rows),
+        }
+    }
+}
+
+impl TableRow for ActivityRow {
+    const COLUMNS: &'static [&'static str] = &["Action", "Total", "Successful", "Failed", "Partial", "Users"];
+    
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.action.clone(),
+            self.total.to_string(),
+            self.successful.to_string(),
+            self.failed.to_string(),
+            self.partial.to_string(),
+            self.users.to_string(),
+        ]
+    }
+}
+
+impl TableRow for AccessReviewRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "User", "Successful logins", "Failed logins", "Permission changes", "Denied requests", "Impersonated by", "Last activity",
+    ];
+    
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.user_id.clone(),
+            self.successful_logins.to_string(),
+            self.failed_logins.to_string(),
+            self.permission_changes.to_string(),
+            self.denied_requests.to_string(),
+            self.impersonated_by.join(LIST_SEPARATOR),
+            self.last_activity.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ]
+    }
+}
+
+impl TableRow for KeyUsageRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "Key", "Encryptions", "Decryptions", "Signings", "Failures", "Principals", "Last used",
+    ];
+    
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.key.clone(),
+            self.encryptions.to_string(),
+            self.decryptions.to_string(),
+            self.signings.to_string(),
+            self.failures.to_string(),
+            self.principals.join(LIST_SEPARATOR),
+            self.last_used.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ]
+    }
+}
+
+impl TableRow for EscrowRecoveryRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "Entry", "Time", "Key", "Requested by", "Reason", "Signers", "Result",
+    ];
+    
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.entry_id.to_string(),
+            self.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+            self.key.clone(),
+            self.requested_by.clone().unwrap_or_default(),
+            self.reason.clone().unwrap_or_default(),
+            self.signers.join(LIST_SEPARATOR),
+            format!("{:?}", self.result),
+        ]
+    }
+}
+
+impl TableRow for FailedLoginRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "Username", "Failures", "Source addresses", "First failure", "Last failure",
+    ];
+    
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.username.clone(),
+            self.failures.to_string(),
+            self.source_addresses.join(LIST_SEPARATOR),
+            self.first_failure.to_rfc3339_opts(SecondsFormat::Secs, true),
+            self.last_failure.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ]
+    }
+}
+
+impl ReportSignature {
+    /// Bytes covered by the signature
+    pub fn signing_payload(report_id: &Uuid, format: ReportFormat, digest: &str, signed_at: &DateTime<Utc>) -> Vec<u8> {
+        let mut payload = REPORT_SIGNATURE_CONTEXT.to_vec();
+        payload.extend(format!(
+            "{}\n{}\n{}\n{}",
+            report_id, format.extension(), digest, signed_at.to_rfc3339_opts(SecondsFormat::Micros, true)
+        ).into_bytes());
+        payload
+    }
+    
+    /// Sign a report as rendered in a format
+    pub async fn sign(report: &AuditReport, format: ReportFormat, rendered: &[u8], signer: &dyn TokenSigner) -> DreasResult<Self> {
+        let digest = hex::encode(Sha256::digest(rendered));
+        let signed_at = Utc::now();
+        
+        let payload = Self::signing_payload(&report.report_id, format, &digest, &signed_at);
+        let signature = signer.sign(&payload).await?;
+        
+        Ok(Self {
+            report_id: report.report_id,
+            format,
+            digest,
+            signed_at,
+            key_id: signer.key_id().to_string(),
+            signature: STANDARD.encode(signature),
+        })
+    }
+    
+    /// Verify the signature covers a rendered report
+    pub fn verify(&self, rendered: &[u8], verifying_key: &VerifyingKey) -> DreasResult<()> {
+        if hex::encode(Sha256::digest(rendered)) != self.digest {
+            return Err(DreasError::Authentication("Report does not match its signed digest".to_string()));
+        }
+        
+        let payload = Self::signing_payload(&self.report_id, self.format, &self.digest, &self.signed_at);
+        verify_signature(verifying_key, &payload, &self.signature, "report")
+    }
+}
+
+/// Operations by action
+fn activity_rows(entries: &[&AuditEntry]) -> Vec<ActivityRow> {
+    let mut rows: BTreeMap<&str, (ActivityRow, BTreeSet<&str>)> = BTreeMap::new();
+    for entry in entries {
+        let (row, users) = rows.entry(entry.action.as_str()).or_insert_with(|| (
+            ActivityRow { action: entry.action.clone(), ..ActivityRow::default() },
+            BTreeSet::new(),
+        ));
+        row.total += 1;
+        match entry.result {
+            AuditResult::Success => row.successful += 1,
+            AuditResult::Failure => row.failed += 1,
+            AuditResult::Partial => row.partial += 1,
+        }
+        users.extend(entry.user_id.as_deref());
+    }
+    
+    rows.into_values()
+        .map(|(row, users)| ActivityRow { users: users.len(), ..row })
+        .collect()
+}
+
+/// Access activity by user, covering every user who signed in, was changed or made requests
+fn access_review_rows(entries: &[&AuditEntry]) -> Vec<AccessReviewRow> {
+    let mut rows: BTreeMap<&str, (AccessReviewRow, BTreeSet<&str>)> = BTreeMap::new();
+    for entry in entries {
+        let Some(user_id) = entry.user_id.as_deref() else { continue };
+        let (row, actors) = rows.entry(user_id).or_insert_with(|| (
+            AccessReviewRow {
+                user_id: user_id.to_string(),
+                successful_logins: 0,
+                failed_logins: 0,
+                permission_changes: 0,
+                denied_requests: 0,
+                impersonated_by: Vec::new(),
+                last_activity: entry.timestamp,
+            },
+            BTreeSet::new(),
+        ));
+        
+        match (entry.action.as_str(), &entry.result) {
+            (actions::USER_AUTHENTICATION, AuditResult::Success) => row.successful_logins += 1,
+            (actions::USER_AUTHENTICATION, _) => row.failed_logins += 1,
+            (actions::PERMISSION_CHANGE, _) => row.permission_changes += 1,
+            (actions::POLICY_DECISION, AuditResult::Failure) => row.denied_requests += 1,
+            _ => {}
+        }
+        actors.extend(entry.actor_id.as_deref());
+        row.last_activity = row.last_activity.max(entry.timestamp);
+    }
+    
+    rows.into_values()
+        .map(|(row, actors)| AccessReviewRow {
+            impersonated_by: actors.into_iter().map(str::to_string).collect(),
+            ..row
+        })
+        .collect()
+}
+
+/// Use of each KMS key, by the entries the KMS client logs
+fn key_usage_rows(entries: &[&AuditEntry]) -> Vec<KeyUsageRow> {
+    let mut rows: BTreeMap<&str, (KeyUsageRow, BTreeSet<&str>)> = BTreeMap::new();
+    for entry in entries {
+        if !matches!(entry.action.as_str(), actions::DATA_ENCRYPTION | actions::DATA_DECRYPTION | actions::DATA_SIGNING) {
+            continue;
+        }
+        
+        let (row, principals) = rows.entry(entry.resource.as_str()).or_insert_with(|| (
+            KeyUsageRow {
+                key: entry.resource.clone(),
+                encryptions: 0,
+                decryptions: 0,
+                signings: 0,
+                failures: 0,
+                principals: Vec::new(),
+                last_used: entry.timestamp,
+            },
+            BTreeSet::new(),
+        ));
+        match entry.action.as_str() {
+            actions::DATA_ENCRYPTION => row.encryptions += 1,
+            actions::DATA_DECRYPTION => row.decryptions += 1,
+            _ => row.signings += 1,
+        }
+        if entry.result == AuditResult::Failure {
+            row.failures += 1;
+        }
+        principals.extend(entry.user_id.as_deref());
+        row.last_used = row.last_used.max(entry.timestamp);
+    }
+    
+    rows.into_values()
+        .map(|(row, principals)| KeyUsageRow {
+            principals: principals.into_iter().map(str::to_string).collect(),
+            ..row
+        })
+        .collect()
+}
+
+/// Every key recovery, oldest first
+fn escrow_recovery_rows(entries: &[&AuditEntry]) -> Vec<EscrowRecoveryRow> {
+    let mut rows: Vec<EscrowRecoveryRow> = entries.iter()
+        .filter(|entry| entry.action == actions::KEY_RECOVERY)
+        .map(|entry| EscrowRecoveryRow {
+            entry_id: entry.entry_id,
+            timestamp: entry.timestamp,
+            key: entry.resource.clone(),
+            requested_by: entry.user_id.clone(),
+            reason: entry.metadata.get("reason").cloned(),
+            signers: entry.metadata.get("signers")
+                .map(|signers| signers.split(',').filter(|signer| !signer.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+            result: entry.result.clone(),
+        })
+        .collect();
+    
+    rows.sort_by_key(|row| row.timestamp);
+    rows
+}
+
+/// Failed sign-ins by the username tried, which needn't belong to a user
+fn failed_login_rows(entries: &[&AuditEntry]) -> Vec<FailedLoginRow> {
+    let mut rows: BTreeMap<&str, (FailedLoginRow, BTreeSet<&str>)> = BTreeMap::new();
+    for entry in entries {
+        if entry.action != actions::USER_AUTHENTICATION || entry.result == AuditResult::Success {
+            continue;
+        }
+        let Some(username) = entry.metadata.get("username").or(entry.user_id.as_ref()) else { continue };
+        
+        let (row, addresses) = rows.entry(username.as_str()).or_insert_with(|| (
+            FailedLoginRow {
+                username: username.clone(),
+                failures: 0,
+                source_addresses: Vec::new(),
+                first_failure: entry.timestamp,
+                last_failure: entry.timestamp,
+            },
+            BTreeSet::new(),
+        ));
+        row.failures += 1;
+        addresses.extend(entry.ip_address.as_deref());
+        row.first_failure = row.first_failure.min(entry.timestamp);
+        row.last_failure = row.last_failure.max(entry.timestamp);
+    }
+    
+    let mut rows: Vec<FailedLoginRow> = rows.into_values()
+        .map(|(row, addresses)| FailedLoginRow {
+            source_addresses: addresses.into_iter().map(str::to_string).collect(),
+            ..row
+        })
+        .collect();
+    // Most targeted accounts first
+    rows.sort_by(|a, b| b.failures.cmp(&a.failures).then_with(|| a.username.cmp(&b.username)));
+    rows
+}
+
+/// Quote a CSV field where needed, neutralising leading characters spreadsheets treat as formulas
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// Escape text for HTML content and attribute values
+fn html_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,442 @@
+//! Durable destinations for audit entries
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! The audit logger keeps entries in memory for queries and proofs; sinks make them
+//! survive a restart. The file sink appends fsync'd JSON lines to rotating files, the
+//! storage sink streams entries to BigQuery, and the fan-out sink sends each entry to
+//! several sinks, failing when a mandatory one fails so the audited operation fails closed.
+//! 
+//! When retention purges entries, sinks replace their copies with the tombstones left in
+//! the chain; the file sink rewrites only the files holding purged entries. Sinks that
+//! can't recall what they were sent are named in the deletion manifest instead.
+
+use crate::{DreasResult, DreasError};
+use crate::services::StorageService;
+use super::audit::AuditEntry;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use futures::future::join_all;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// Default size at which the file sink starts a new file
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Default age at which the file sink starts a new file, in hours
+pub const DEFAULT_MAX_FILE_AGE_HOURS: i64 = 24;
+
+/// File name prefix of audit log segments
+const SEGMENT_PREFIX: &str = "audit-";
+
+/// File name extension of audit log segments
+const SEGMENT_EXTENSION: &str = "jsonl";
+
+/// Destination that durably records audit entries
+#[async_trait]
+pub trait AuditSink: std::fmt::Debug + Send + Sync {
+    /// Short name identifying the sink in logs and errors
+    fn name(&self) -> &str;
+    
+    /// Record an entry; `Ok` means the entry is durably stored
+    async fn write(&self, entry: &AuditEntry) -> DreasResult<()>;
+    
+    /// Replace the stored copies of purged entries with their tombstones
+    ///
+    /// Sinks that can't recall what they've recorded keep copies, which they report.
+    async fn purge(&self, _tombstones: &[AuditEntry]) -> DreasResult<PurgeOutcome> {
+        Ok(PurgeOutcome::CopiesRetained)
+    }
+}
+
+/// What a sink did with its copies of purged entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PurgeOutcome {
+    /// Every copy was replaced by its tombstone
+    Replaced,
+    /// The sink still holds the entries as they were recorded
+    CopiesRetained,
+}
+
+/// Whether a sink failure fails the audited operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkMode {
+    /// Failures are returned, so the operation being audited fails closed
+    Mandatory,
+    /// Failures are logged and otherwise ignored
+    BestEffort,
+}
+
+/// Sink appending entries as JSON lines to rotating files in a directory
+///
+/// Each write is flushed with `fdatasync` before it returns. Files are created fresh and only
+/// appended to, including after a restart; a new file is started once the current one
+/// reaches the size or age limit. Retention purges replace a file as a whole.
+///
+/// File IO runs on Tokio's blocking pool. Clones share the file being appended to.
+#[derive(Debug, Clone)]
+pub struct FileAuditSink {
+    directory: PathBuf,
+    max_file_bytes: u64,
+    max_file_age: Duration,
+    segment: Arc<Mutex<Segment>>,
+}
+
+/// File currently being appended to
+#[derive(Debug)]
+struct Segment {
+    file: File,
+    path: PathBuf,
+    size: u64,
+    opened_at: DateTime<Utc>,
+}
+
+/// Sink streaming entries to the BigQuery audit table of a storage service
+///
+/// The storage service needs a project ID; see `StorageService::with_project_id`.
+#[derive(Debug)]
+pub struct StorageAuditSink {
+    storage: StorageService,
+}
+
+/// Sink writing every entry to several sinks
+#[derive(Debug, Clone, Default)]
+pub struct FanOutAuditSink {
+    sinks: Vec<(Arc<dyn AuditSink>, SinkMode)>,
+}
+
+impl FileAuditSink {
+    /// Open a sink writing to a new file in the directory, creating the directory if needed
+    pub fn open<P: AsRef<Path>>(directory: P) -> DreasResult<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        std::fs::create_dir_all(&directory)?;
+        
+        let segment = Segment::create(&directory)?;
+        tracing::info!("Audit file sink writing to {}", segment.path.display());
+        
+        Ok(Self {
+            directory,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_file_age: Duration::hours(DEFAULT_MAX_FILE_AGE_HOURS),
+            segment: Arc::new(Mutex::new(segment)),
+        })
+    }
+    
+    /// Start a new file once the current one reaches `max_file_bytes` or `max_file_age`
+    pub fn with_rotation(mut self, max_file_bytes: u64, max_file_age: Duration) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self.max_file_age = max_file_age;
+        self
+    }
+    
+    /// Get the directory the sink writes to
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+    
+    /// List the audit files in a directory, oldest first
+    pub fn segments<P: AsRef<Path>>(directory: P) -> DreasResult<Vec<PathBuf>> {
+        let mut segments = Vec::new();
+        for dir_entry in std::fs::read_dir(directory)? {
+            let path = dir_entry?.path();
+            let is_segment = path.extension().is_some_and(|extension| extension == SEGMENT_EXTENSION)
+                && path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(SEGMENT_PREFIX));
+            
+            if is_segment {
+                segments.push(path);
+            }
+        }
+        
+        // Segment names start with their creation time, so name order is write order
+        segments.sort();
+        Ok(segments)
+    }
+    
+    /// Read back every entry written to a directory, oldest first
+    pub fn read_entries<P: AsRef<Path>>(directory: P) -> DreasResult<Vec<AuditEntry>> {
+        let mut entries = Vec::new();
+        for path in Self::segments(directory)? {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    entries.push(serde_json::from_str(&line)?);
+                }
+            }
+        }
+        Ok(entries)
+    }
+    
+    /// Run file IO on the blocking pool, so it doesn't stall the async workers
+    async fn blocking<T, F>(&self, operation: F) -> DreasResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Self) -> DreasResult<T> + Send + 'static,
+    {
+        let sink = self.clone();
+        tokio::task::spawn_blocking(move || operation(&sink)).await
+            .map_err(|e| DreasError::AuditLogging(format!("Audit file sink task failed: {}", e)))?
+    }
+    
+    /// Append a line to the current file, rotating first if it's full or too old
+    fn append(&self, line: &[u8]) -> DreasResult<()> {
+        let mut segment = self.segment.lock()
+            .map_err(|_| DreasError::AuditLogging("Audit file sink lock poisoned".to_string()))?;
+        
+        let full = segment.size > 0 && segment.size + line.len() as u64 > self.max_file_bytes;
+        let expired = Utc::now() - segment.opened_at >= self.max_file_age;
+        if full || expired {
+            *segment = Segment::create(&self.directory)?;
+            tracing::info!("Audit file sink rotated to {}", segment.path.display());
+        }
+        
+        segment.file.write_all(line)
+            .and_then(|_| segment.file.sync_data())
+            .map_err(|e| DreasError::AuditLogging(format!(
+                "Failed to write audit entry to {}: {}", segment.path.display(), e
+            )))?;
+        segment.size += line.len() as u64;
+        
+        Ok(())
+    }
+    
+    /// Rewrite every file holding a purged entry, swapping in its tombstone
+    fn replace_entries(&self, tombstones: &HashMap<Uuid, AuditEntry>) -> DreasResult<()> {
+        let mut segment = self.segment.lock()
+            .map_err(|_| DreasError::AuditLogging("Audit file sink lock poisoned".to_string()))?;
+        
+        for path in Self::segments(&self.directory)? {
+            let contents = std::fs::read_to_string(&path)?;
+            let mut rewritten = String::with_capacity(contents.len());
+            let mut replaced = 0;
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                let entry: AuditEntry = serde_json::from_str(line)?;
+                match tombstones.get(&entry.entry_id) {
+                    Some(tombstone) if !entry.purged => {
+                        rewritten.push_str(&serde_json::to_string(tombstone)?);
+                        replaced += 1;
+                    }
+                    _ => rewritten.push_str(line),
+                }
+                rewritten.push('\n');
+            }
+            
+            if replaced == 0 {
+                continue;
+            }
+            
+            // The file is replaced rather than edited, so new entries go to a new one
+            if path == segment.path {
+                *segment = Segment::create(&self.directory)?;
+                tracing::info!("Audit file sink rotated to {}", segment.path.display());
+            }
+            write_durably(&path, rewritten.as_bytes())?;
+            tracing::info!("Replaced {} purged audit entries in {}", replaced, path.display());
+        }
+        
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuditSink for FileAuditSink {
+    fn name(&self) -> &str {
+        "file"
+    }
+    
+    async fn write(&self, entry: &AuditEntry) -> DreasResult<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        
+        self.blocking(move |sink| sink.append(&line)).await
+    }
+    
+    async fn purge(&self, tombstones: &[AuditEntry]) -> DreasResult<PurgeOutcome> {
+        let tombstones: HashMap<_, _> = tombstones.iter()
+            .map(|tombstone| (tombstone.entry_id, tombstone.clone()))
+            .collect();
+        
+        self.blocking(move |sink| sink.replace_entries(&tombstones)).await?;
+        Ok(PurgeOutcome::Replaced)
+    }
+}
+
+impl Segment {
+    /// Create a new, empty segment named after the current time
+    fn create(directory: &Path) -> DreasResult<Self> {
+        let opened_at = Utc::now();
+        let stem = format!("{}{}", SEGMENT_PREFIX, opened_at.format("%Y%m%dT%H%M%S%.6fZ"));
+        
+        let mut attempt = 0;
+        let (file, path) = loop {
+            // Numbered so that names created in the same microsecond still sort in creation order
+            let path = directory.join(format!("{}-{:04}.{}", stem, attempt, SEGMENT_EXTENSION));
+            
+            // Never reopen an existing file, so written entries can't be truncated or interleaved
+            match OpenOptions::new().append(true).create_new(true).open(&path) {
+                Ok(file) => break (file, path),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(e) => return Err(DreasError::AuditLogging(format!(
+                    "Failed to create audit file {}: {}", path.display(), e
+                ))),
+            }
+        };
+        
+        // Make the new directory entry itself durable
+        File::open(directory)?.sync_all()?;
+        
+        Ok(Self {
+            file,
+            path,
+            size: 0,
+            opened_at,
+        })
+    }
+}
+
+impl StorageAuditSink {
+    /// Create a sink writing to the storage service's BigQuery dataset
+    pub fn new(storage: StorageService) -> Self {
+        Self { storage }
+    }
+}
+
+#[async_trait]
+impl AuditSink for StorageAuditSink {
+    fn name(&self) -> &str {
+        "bigquery"
+    }
+    
+    async fn write(&self, entry: &AuditEntry) -> DreasResult<()> {
+        let result = self.storage.store_audit_logs(vec![serde_json::to_value(entry)?]).await?;
+        
+        if !result.success {
+            return Err(DreasError::AuditLogging(format!(
+                "BigQuery rejected audit entry {}", entry.entry_id
+            )));
+        }
+        
+        Ok(())
+    }    
+    /// Delete the purged rows and stream in their tombstones
+    async fn purge(&self, tombstones: &[AuditEntry]) -> DreasResult<PurgeOutcome> {
+        let rows = tombstones.iter().map(serde_json::to_value).collect::<Result<Vec<_>, _>>()?;
+        self.storage.replace_audit_logs(rows).await?;
+        Ok(PurgeOutcome::Replaced)
+    }
+}
+
+impl FanOutAuditSink {
+    /// Create a fan-out sink with no sinks
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Add a sink
+    pub fn with_sink(mut self, sink: Arc<dyn AuditSink>, mode: SinkMode) -> Self {
+        self.sinks.push((sink, mode));
+        self
+    }
+    
+    /// Check whether any sinks have been added
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+    
+    /// Purge entries from every sink, returning the names of those that kept copies
+    ///
+    /// Fails if a mandatory sink failed; best-effort sinks that failed are counted as keeping copies.
+    pub async fn purge_all(&self, tombstones: &[AuditEntry]) -> DreasResult<Vec<String>> {
+        let results = join_all(self.sinks.iter().map(|(sink, _)| sink.purge(tombstones))).await;
+        
+        let mut retained = Vec::new();
+        let mut failures = Vec::new();
+        for ((sink, mode), result) in self.sinks.iter().zip(results) {
+            match (result, mode) {
+                (Ok(PurgeOutcome::Replaced), _) => {}
+                (Ok(PurgeOutcome::CopiesRetained), _) => retained.push(sink.name().to_string()),
+                (Err(e), SinkMode::Mandatory) => {
+                    tracing::error!("Mandatory audit sink {} failed to purge entries: {}", sink.name(), e);
+                    failures.push(format!("{}: {}", sink.name(), e));
+                }
+                (Err(e), SinkMode::BestEffort) => {
+                    tracing::warn!("Audit sink {} failed to purge entries, so keeps copies: {}", sink.name(), e);
+                    retained.push(sink.name().to_string());
+                }
+            }
+        }
+        
+        if !failures.is_empty() {
+            return Err(DreasError::AuditLogging(format!(
+                "Purged audit entries were not removed from mandatory sinks: {}", failures.join("; ")
+            )));
+        }
+        
+        Ok(retained)
+    }
+}
+
+#[async_trait]
+impl AuditSink for FanOutAuditSink {
+    fn name(&self) -> &str {
+        "fan-out"
+    }
+    
+    /// Write to every sink at once, failing if any mandatory sink failed
+    ///
+    /// All sinks are attempted even after a failure, so best-effort sinks still see the entry.
+    async fn write(&self, entry: &AuditEntry) -> DreasResult<()> {
+        let results = join_all(self.sinks.iter().map(|(sink, _)| sink.write(entry))).await;
+        
+        let mut failures = Vec::new();
+        for ((sink, mode), result) in self.sinks.iter().zip(results) {
+            if let Err(e) = result {
+                match mode {
+                    SinkMode::Mandatory => {
+                        tracing::error!("Mandatory audit sink {} failed for entry {}: {}", sink.name(), entry.entry_id, e);
+                        failures.push(format!("{}: {}", sink.name(), e));
+                    }
+                    SinkMode::BestEffort => {
+                        tracing::warn!("Audit sink {} failed for entry {}: {}", sink.name(), entry.entry_id, e);
+                    }
+                }
+            }
+        }
+        
+        if !failures.is_empty() {
+            return Err(DreasError::AuditLogging(format!(
+                "Audit entry {} was not recorded by mandatory sinks: {}", entry.entry_id, failures.join("; ")
+            )));
+        }
+        
+        Ok(())
+    }
+    
+    async fn purge(&self, tombstones: &[AuditEntry]) -> DreasResult<PurgeOutcome> {
+        if self.purge_all(tombstones).await?.is_empty() {
+            Ok(PurgeOutcome::Replaced)
+        } else {
+            Ok(PurgeOutcome::CopiesRetained)
+        }
+    }
+}
+
+/// Replace a file's contents so that either the old or the new contents survive a crash
+pub(crate) fn write_durably(path: &Path, contents: &[u8]) -> DreasResult<()> {
+    let temporary = path.with_extension("tmp");
+    let result = File::create(&temporary)
+        .and_then(|mut file| file.write_all(contents).and_then(|_| file.sync_all()))
+        .and_then(|_| std::fs::rename(&temporary, path))
+        .and_then(|_| match path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            Some(parent) => File::open(parent)?.sync_all(),
+            None => Ok(()),
+        });
+    
+    result.map_err(|e| DreasError::Storage(format!("Failed to write {}: {}", path.display(), e)))
+}
@@ -0,0 +1,76 @@
+//! Real-time audit event subscriptions
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Every entry the audit logger records is broadcast to its subscribers as soon as it
+//! joins the chain, so security tooling can react to sensitive operations without
+//! polling. Each subscription carries an `AuditFilter` and only sees the entries it
+//! matches. A subscription can resume from a sequence number, replaying the retained
+//! entries it missed before it starts following new ones.
+
+use super::audit::AuditEntry;
+use super::audit_query::AuditFilter;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Entries buffered per subscriber before a slow subscriber starts missing them
+pub const AUDIT_STREAM_CAPACITY: usize = 1024;
+
+/// Something that happened on an audit subscription
+#[derive(Debug, Clone)]
+pub enum AuditEvent {
+    /// An entry matching the subscription's filter
+    Entry(Arc<AuditEntry>),
+    /// The subscriber fell behind and missed entries, matching or not
+    ///
+    /// The missed entries can be found by searching the log from the last sequence number seen.
+    Lagged { missed: u64 },
+}
+
+/// Filtered view of the entries an audit logger records from now on
+#[derive(Debug)]
+pub struct AuditSubscription {
+    filter: AuditFilter,
+    backlog: VecDeque<Arc<AuditEntry>>,
+    receiver: broadcast::Receiver<Arc<AuditEntry>>,
+}
+
+impl AuditSubscription {
+    /// Follow a broadcast channel, after replaying already retained entries
+    pub(crate) fn new(
+        filter: AuditFilter,
+        backlog: Vec<AuditEntry>,
+        receiver: broadcast::Receiver<Arc<AuditEntry>>,
+    ) -> Self {
+        Self {
+            filter,
+            backlog: backlog.into_iter().map(Arc::new).collect(),
+            receiver,
+        }
+    }
+    
+    /// Condition entries must meet to be delivered
+    pub fn filter(&self) -> &AuditFilter {
+        &self.filter
+    }
+    
+    /// Wait for the next matching entry
+    ///
+    /// Returns `None` once the audit logger is gone and every entry has been delivered.
+    pub async fn next(&mut self) -> Option<AuditEvent> {
+        if let Some(entry) = self.backlog.pop_front() {
+            return Some(AuditEvent::Entry(entry));
+        }
+        
+        loop {
+            match self.receiver.recv().await {
+                Ok(entry) if self.filter.matches(&entry) => return Some(AuditEvent::Entry(entry)),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => return Some(AuditEvent::Lagged { missed }),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
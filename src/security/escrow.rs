@@ -7,8 +7,11 @@
 //! and disaster recovery scenarios, ensuring keys can be recovered when needed.
 
 use crate::{DreasResult, DreasError};
+use super::audit::{actions, AuditEntry, AuditHandle, AuditLogger, AuditResult};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use uuid::Uuid;
 
 /// Key escrow manager for secure key storage and recovery
@@ -18,6 +21,7 @@ pub struct KeyEscrow {
     authorized_parties: Vec<String>,
     minimum_signatures: usize,
     escrow_data: HashMap<String, EscrowEntry>,
+    audit_logger: AuditHandle,
 }
 
 /// Individual escrow entry
@@ -66,9 +70,16 @@ impl KeyEscrow {
             authorized_parties,
             minimum_signatures,
             escrow_data: HashMap::new(),
+            audit_logger: AuditHandle::detached(),
         })
     }
     
+    /// Record escrow and every recovery attempt in the audit log
+    pub fn with_audit_logger(mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> Self {
+        self.audit_logger = AuditHandle::new(audit_logger);
+        self
+    }
+    
     /// Escrow a key for later recovery
     pub async fn escrow_key(
         &mut self,
@@ -84,6 +95,13 @@ impl KeyEscrow {
             metadata: HashMap::new(),
         };
         
+        let mut audit_entry = AuditEntry::new(actions::KEY_ESCROW, format!("escrow_key:{}", key_id), AuditResult::Success)
+            .with_metadata("escrow_id", self.escrow_id.to_string());
+        if let Some(expires_at) = expires_at {
+            audit_entry = audit_entry.with_metadata("expires_at", expires_at.to_rfc3339());
+        }
+        self.audit_logger.record(audit_entry).await?;
+        
         self.escrow_data.insert(key_id, entry);
         
         tracing::info!("Key escrowed successfully: {}", self.escrow_id);
@@ -91,12 +109,24 @@ impl KeyEscrow {
     }
     
     /// Recover a key from escrow with multi-party authorization
+    ///
+    /// Refused attempts are audited as well as successful ones.
     pub async fn recover_key(
         &self,
         request: RecoveryRequest,
     ) -> DreasResult<Vec<u8>> {
+        let recovered = self.authorize_recovery(&request);
+        
+        // Log the recovery operation
+        self.audit_recovery(&request, recovered.as_ref().err()).await?;
+        
+        recovered
+    }
+    
+    /// Check a recovery request and return the escrowed key
+    fn authorize_recovery(&self, request: &RecoveryRequest) -> DreasResult<Vec<u8>> {
         // Validate the request
-        self.validate_recovery_request(&request)?;
+        self.validate_recovery_request(request)?;
         
         // Check if the key exists in escrow
         let entry = self.escrow_data.get(&request.key_id)
@@ -110,10 +140,7 @@ impl KeyEscrow {
         }
         
         // Validate signatures
-        self.validate_signatures(&request)?;
-        
-        // Log the recovery operation
-        self.audit_recovery(&request)?;
+        self.validate_signatures(request)?;
         
         Ok(entry.encrypted_key.clone())
     }
@@ -160,20 +187,24 @@ impl KeyEscrow {
     }
     
     /// Audit recovery operation
-    fn audit_recovery(&self, request: &RecoveryRequest) -> DreasResult<()> {
-        let audit_entry = serde_json::json!({
-            "escrow_id": self.escrow_id,
-            "action": "key_recovery",
-            "request_id": request.request_id,
-            "requester": request.requester,
-            "key_id": request.key_id,
-            "reason": request.reason,
-            "signature_count": request.signatures.len(),
-            "timestamp": request.timestamp
-        });
-        
-        tracing::info!("Key recovery audit: {}", audit_entry);
-        Ok(())
+    async fn audit_recovery(&self, request: &RecoveryRequest, error: Option<&DreasError>) -> DreasResult<()> {
+        let result = if error.is_some() { AuditResult::Failure } else { AuditResult::Success };
+        let signers: Vec<&str> = request.signatures.iter().map(|signature| signature.signer.as_str()).collect();
+        
+        let mut audit_entry = AuditEntry::new(actions::KEY_RECOVERY, format!("escrow_key:{}", request.key_id), result)
+            .with_user(&request.requester)
+            .with_metadata("escrow_id", self.escrow_id.to_string())
+            .with_metadata("request_id", request.request_id.to_string())
+            .with_metadata("reason", &request.reason)
+            .with_metadata("signers", signers.join(","))
+            .with_metadata("requested_at", request.timestamp.to_rfc3339());
+        if let Some(error) = error {
+            audit_entry = audit_entry.with_metadata("error", error.to_string());
+        }
+        
+        let outcome = if error.is_some() { "refused" } else { "granted" };
+        tracing::info!("Key recovery {} for {} requested by {}", outcome, request.key_id, request.requester);
+        self.audit_logger.record(audit_entry).await
     }
     
     /// List all escrowed keys
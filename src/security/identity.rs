@@ -7,17 +7,66 @@
 //! services for the DREAS framework, ensuring secure access control.
 
 use crate::{DreasResult, DreasError};
+use crate::config::{ApiKeyConfig, ImpersonationConfig, LockoutConfig, MfaConfig, PasswordConfig, SessionConfig};
+use super::password::{CredentialHasher, PasswordHashParams, PasswordPolicy};
+use super::token::{AccessClaims, Jwks, RefreshOutcome, TokenPair, TokenService};
+use super::oidc::OidcClient;
+use super::mfa::{MfaEnrollment, TotpAuthenticator, TotpEnrollment};
+use super::service_account::{self, ApiKey, IssuedApiKey, ServiceAccount};
+use super::permission::{self, PermissionDecision, PermissionExplanation};
+use super::policy::{PolicyDecision, PolicyEngine, PolicyEntity, PolicyRequest};
+use super::lockout::{AttemptTracker, FailureRateMonitor, LockoutPolicy, ThrottleStatus};
+use super::session::{ClientInfo, FileSessionStore, InMemorySessionStore, SessionPolicy, SessionStore};
+use super::tenant::{tenant_of, TenantId, TENANT_ATTRIBUTE};
+use super::impersonation::{
+    Impersonation, ImpersonationPolicy, InMemoryNotifier, UserNotification, UserNotifier, IMPERSONATE_PERMISSION,
+};
+use super::audit::{actions, AuditEntry, AuditHandle, AuditLogger, AuditResult};
+use crate::services::observer::{AlertSeverity, ObserverService};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+/// Name of the observer alert raised when failed logins spike
+const AUTH_FAILURE_SPIKE_ALERT: &str = "Authentication Failure Spike";
+
+/// Session activity is only written back once it is this stale, to limit store writes
+const SESSION_ACTIVITY_RESOLUTION_SECONDS: i64 = 60;
+
 /// Identity manager for user authentication and authorization
 #[derive(Debug, Clone)]
 pub struct IdentityManager {
     users: HashMap<String, User>,
     roles: HashMap<String, Role>,
-    sessions: HashMap<String, UserSession>,
+    sessions: Arc<dyn SessionStore>,
+    session_policy: SessionPolicy,
+    credentials: HashMap<String, String>,
+    hasher: CredentialHasher,
+    password_policy: PasswordPolicy,
+    token_service: Option<TokenService>,
+    external_identities: HashMap<String, String>,
+    mfa_enrollments: HashMap<String, MfaEnrollment>,
+    totp: TotpAuthenticator,
+    recovery_code_count: usize,
+    step_up_permissions: HashSet<String>,
+    step_up_window: chrono::Duration,
+    service_accounts: HashMap<String, ServiceAccount>,
+    api_keys: HashMap<String, ApiKey>,
+    api_key_config: ApiKeyConfig,
+    policy_engine: Option<Arc<PolicyEngine>>,
+    user_attempts: AttemptTracker,
+    ip_attempts: AttemptTracker,
+    /// Failed TOTP and recovery code attempts, per user ID
+    mfa_attempts: AttemptTracker,
+    failure_monitor: FailureRateMonitor,
+    failure_spike_threshold: usize,
+    audit_logger: AuditHandle,
+    observer: Option<Arc<Mutex<ObserverService>>>,
+    impersonation_policy: ImpersonationPolicy,
+    notifier: Arc<dyn UserNotifier>,
 }
 
 /// User entity
@@ -28,6 +77,13 @@ pub struct User {
     pub email: String,
     pub roles: Vec<String>,
     pub permissions: Vec<String>,
+    #[serde(default)]
+    pub denied_permissions: Vec<String>,
+    /// Attributes available to access policies, e.g. `tenant` or `department`
+    #[serde(default)]
+    pub attributes: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub tenant_id: TenantId,
     pub created_at: DateTime<Utc>,
     pub last_login: Option<DateTime<Utc>>,
     pub is_active: bool,
@@ -38,6 +94,12 @@ pub struct User {
 pub struct Role {
     pub name: String,
     pub permissions: Vec<String>,
+    /// Roles whose permissions this role inherits
+    #[serde(default)]
+    pub parent_roles: Vec<String>,
+    /// Permissions refused to anyone holding this role, overriding any allow
+    #[serde(default)]
+    pub denied_permissions: Vec<String>,
     pub description: String,
     pub created_at: DateTime<Utc>,
 }
@@ -47,10 +109,35 @@ pub struct Role {
 pub struct UserSession {
     pub session_id: String,
     pub user_id: String,
+    /// Tenant of the user at login
+    #[serde(default)]
+    pub tenant_id: TenantId,
     pub created_at: DateTime<Utc>,
+    /// Absolute expiry, regardless of activity
     pub expires_at: DateTime<Utc>,
+    pub last_active_at: DateTime<Utc>,
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
+    pub mfa_verified_at: Option<DateTime<Utc>>,
+    /// Set when a support engineer is acting as the user
+    #[serde(default)]
+    pub impersonation: Option<Impersonation>,
+}
+
+/// Authenticated caller, either a user session or a service account API key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Principal {
+    User {
+        user_id: String,
+        session_id: String,
+        roles: Vec<String>,
+    },
+    ServiceAccount {
+        account_id: String,
+        key_id: String,
+        scopes: Vec<String>,
+    },
 }
 
 /// Authentication result
@@ -59,7 +146,11 @@ pub struct AuthResult {
     pub success: bool,
     pub user: Option<User>,
     pub session_id: Option<String>,
+    pub access_token: Option<String>,
+    pub refresh_token: Option<String>,
     pub error: Option<String>,
+    /// Set when login attempts are being throttled
+    pub retry_after: Option<DateTime<Utc>>,
 }
 
 /// Permission check result
@@ -67,65 +158,575 @@ pub struct AuthResult {
 pub struct PermissionResult {
     pub allowed: bool,
     pub reason: Option<String>,
+    pub mfa_required: bool,
 }
 
 impl IdentityManager {
     /// Create a new identity manager
     pub fn new() -> Self {
+        let lockout = LockoutConfig::default();
+        
         Self {
             users: HashMap::new(),
             roles: HashMap::new(),
-            sessions: HashMap::new(),
+            sessions: Arc::new(InMemorySessionStore::new()),
+            session_policy: SessionPolicy::default(),
+            credentials: HashMap::new(),
+            hasher: CredentialHasher::new(PasswordHashParams::default())
+                .expect("default Argon2 parameters are valid"),
+            password_policy: PasswordPolicy::default(),
+            token_service: None,
+            external_identities: HashMap::new(),
+            mfa_enrollments: HashMap::new(),
+            totp: TotpAuthenticator::default(),
+            recovery_code_count: 10,
+            step_up_permissions: HashSet::new(),
+            step_up_window: chrono::Duration::minutes(5),
+            service_accounts: HashMap::new(),
+            api_keys: HashMap::new(),
+            api_key_config: ApiKeyConfig::default(),
+            policy_engine: None,
+            user_attempts: AttemptTracker::new(lockout_policy(&lockout, lockout.max_failures_per_user)),
+            ip_attempts: AttemptTracker::new(lockout_policy(&lockout, lockout.max_failures_per_ip)),
+            mfa_attempts: AttemptTracker::new(lockout_policy(&lockout, lockout.max_failures_per_user)),
+            failure_monitor: FailureRateMonitor::new(chrono::Duration::seconds(lockout.spike_window_seconds)),
+            failure_spike_threshold: lockout.spike_threshold,
+            audit_logger: AuditHandle::detached(),
+            observer: None,
+            impersonation_policy: ImpersonationPolicy::default(),
+            notifier: Arc::new(InMemoryNotifier::new()),
+        }
+    }
+    
+    /// Create an identity manager from password configuration
+    pub fn from_config(config: &PasswordConfig) -> DreasResult<Self> {
+        let mut policy = PasswordPolicy::new(config.min_length, config.max_length)?;
+        
+        if let Some(path) = &config.denylist_path {
+            policy = policy.with_denylist_file(path)?;
+        }
+        
+        Ok(Self::new()
+            .with_password_policy(policy)
+            .with_hash_params(config.hash_params.clone())?)
+    }
+    
+    /// Set the password policy
+    pub fn with_password_policy(mut self, policy: PasswordPolicy) -> Self {
+        self.password_policy = policy;
+        self
+    }
+    
+    /// Set the Argon2id hashing parameters
+    pub fn with_hash_params(mut self, params: PasswordHashParams) -> DreasResult<Self> {
+        self.set_hash_params(params)?;
+        Ok(self)
+    }
+    
+    /// Issue signed access and refresh tokens on login
+    pub fn with_token_service(mut self, token_service: TokenService) -> Self {
+        self.token_service = Some(token_service);
+        self
+    }
+    
+    /// Configure TOTP and the permissions that require step-up authentication
+    pub fn with_mfa_config(mut self, config: &MfaConfig) -> DreasResult<Self> {
+        self.totp = TotpAuthenticator::new(
+            config.issuer.clone(),
+            config.digits,
+            config.step_seconds,
+            config.skew_steps,
+        )?;
+        self.recovery_code_count = config.recovery_code_count;
+        self.step_up_window = chrono::Duration::seconds(config.step_up_window_seconds);
+        Ok(self.with_step_up_permissions(config.step_up_permissions.iter().cloned()))
+    }
+    
+    /// Require a recent MFA verification for the given permissions
+    pub fn with_step_up_permissions<I: IntoIterator<Item = String>>(mut self, permissions: I) -> Self {
+        self.step_up_permissions.extend(permissions);
+        self
+    }
+    
+    /// Set API key lifetimes
+    pub fn with_api_key_config(mut self, config: ApiKeyConfig) -> Self {
+        self.api_key_config = config;
+        self
+    }
+    
+    /// Evaluate access policies as part of every permission check
+    pub fn with_policy_engine(mut self, policy_engine: Arc<PolicyEngine>) -> Self {
+        self.policy_engine = Some(policy_engine);
+        self
+    }
+    
+    /// Set failed-login throttling and lockout thresholds
+    pub fn with_lockout_config(mut self, config: &LockoutConfig) -> Self {
+        self.user_attempts = AttemptTracker::new(lockout_policy(config, config.max_failures_per_user));
+        self.ip_attempts = AttemptTracker::new(lockout_policy(config, config.max_failures_per_ip));
+        self.mfa_attempts = AttemptTracker::new(lockout_policy(config, config.max_failures_per_user));
+        self.failure_monitor = FailureRateMonitor::new(chrono::Duration::seconds(config.spike_window_seconds));
+        self.failure_spike_threshold = config.spike_threshold;
+        self
+    }
+    
+    /// Set session timeouts, caps and binding, opening a file store if a path is configured
+    pub fn with_session_config(mut self, config: &SessionConfig) -> DreasResult<Self> {
+        if let Some(path) = &config.path {
+            self.sessions = Arc::new(FileSessionStore::open(path)?);
         }
+        
+        self.session_policy = SessionPolicy::from_config(config);
+        Ok(self)
+    }
+    
+    /// Keep sessions in the given store
+    pub fn with_session_store(mut self, store: Arc<dyn SessionStore>) -> Self {
+        self.sessions = store;
+        self
+    }
+    
+    /// Record authentication attempts in the audit log
+    pub fn with_audit_logger(mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> Self {
+        self.audit_logger = AuditHandle::new(audit_logger);
+        self
+    }
+    
+    /// Report authentication failure rates and spikes to the observer
+    pub fn with_observer(mut self, observer: Arc<Mutex<ObserverService>>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+    
+    /// Apply impersonation limits from configuration
+    pub fn with_impersonation_config(mut self, config: &ImpersonationConfig) -> Self {
+        self.impersonation_policy = ImpersonationPolicy::from_config(config);
+        self
+    }
+    
+    /// Deliver notifications about account activity, such as impersonation, through the given notifier
+    pub fn with_notifier(mut self, notifier: Arc<dyn UserNotifier>) -> Self {
+        self.notifier = notifier;
+        self
+    }
+    
+    /// Change the Argon2id hashing parameters; existing hashes are upgraded on next login
+    pub fn set_hash_params(&mut self, params: PasswordHashParams) -> DreasResult<()> {
+        self.hasher = CredentialHasher::new(params)?;
+        Ok(())
     }
     
     /// Authenticate a user
     pub async fn authenticate(&mut self, username: &str, password: &str) -> DreasResult<AuthResult> {
-        // TODO: Implement actual authentication logic
-        // This is a placeholder implementation
-        
-        if let Some(user) = self.users.get(username) {
-            if user.is_active {
-                // Simulate password verification (in real implementation, use proper hashing)
-                if password == "password123" {
-                    let session = self.create_session(user.id.clone())?;
-                    
-                    return Ok(AuthResult {
-                        success: true,
-                        user: Some(user.clone()),
-                        session_id: Some(session.session_id),
-                        error: None,
-                    });
-                }
+        self.authenticate_from(username, password, None).await
+    }
+    
+    /// Authenticate a user, throttling failed attempts per username and per source IP
+    pub async fn authenticate_from(
+        &mut self,
+        username: &str,
+        password: &str,
+        source_ip: Option<&str>,
+    ) -> DreasResult<AuthResult> {
+        self.authenticate_with_client(username, password, &ClientInfo::from_ip(source_ip)).await
+    }
+    
+    /// Authenticate a user, binding the new session to the client's IP and user agent
+    pub async fn authenticate_with_client(
+        &mut self,
+        username: &str,
+        password: &str,
+        client: &ClientInfo,
+    ) -> DreasResult<AuthResult> {
+        let now = Utc::now();
+        let source_ip = client.ip_address.as_deref();
+        let user_key = username.to_lowercase();
+        
+        // Refuse throttled attempts without checking the password; unknown usernames are
+        // throttled the same way so lockouts don't reveal which accounts exist
+        let throttled = [
+            Some(self.user_attempts.status(&user_key, now)),
+            source_ip.map(|ip| self.ip_attempts.status(ip, now)),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|status| status.retry_after())
+        .max();
+        
+        if let Some(retry_after) = throttled {
+            self.audit_authentication(username, None, source_ip, AuditResult::Failure, "throttled").await?;
+            return Ok(Self::failed_login("Too many failed attempts; try again later", Some(retry_after)));
+        }
+        
+        let user = self.users.get(username).cloned();
+        
+        // Always run a full verification so response timing doesn't reveal which usernames exist
+        let verified = match user.as_ref().and_then(|user| self.credentials.get(&user.id)) {
+            Some(stored_hash) => self.hasher.verify(password, stored_hash)?,
+            None => {
+                self.hasher.verify_dummy(password)?;
+                false
             }
+        };
+        
+        if let Some(user) = user.filter(|user| verified && user.is_active) {
+            // Only the username counter resets, so one valid account can't clear an IP's record
+            self.user_attempts.record_success(&user_key);
+            self.rehash_if_needed(&user.id, password)?;
+            
+            let session = self.create_session(user.id.clone(), client)?;
+            let tokens = self.issue_tokens(&user, &session.session_id, None).await?;
+            self.audit_authentication(username, Some(&user.id), source_ip, AuditResult::Success, "password").await?;
+            
+            return Ok(AuthResult {
+                success: true,
+                user: self.users.get(username).cloned(),
+                session_id: Some(session.session_id),
+                access_token: tokens.as_ref().map(|tokens| tokens.access_token.clone()),
+                refresh_token: tokens.map(|tokens| tokens.refresh_token),
+                error: None,
+                retry_after: None,
+            });
         }
         
-        Ok(AuthResult {
+        self.record_failed_login(username, &user_key, source_ip, now).await?;
+        Ok(Self::failed_login("Invalid credentials", None))
+    }
+    
+    /// Count a failed login, lock out repeat offenders and report failure spikes
+    async fn record_failed_login(
+        &mut self,
+        username: &str,
+        user_key: &str,
+        source_ip: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> DreasResult<()> {
+        let user_id = self.users.get(username).map(|user| user.id.clone());
+        let user_status = self.user_attempts.record_failure(user_key, now);
+        let ip_status = source_ip.map(|ip| self.ip_attempts.record_failure(ip, now));
+        
+        self.audit_authentication(username, user_id.as_deref(), source_ip, AuditResult::Failure, "invalid_credentials")
+            .await?;
+        
+        if let ThrottleStatus::Locked(until) = user_status {
+            tracing::warn!("Account locked after repeated failures: {} until {}", username, until);
+            self.audit_authentication(username, user_id.as_deref(), source_ip, AuditResult::Failure, "account_locked")
+                .await?;
+        }
+        
+        if let (Some(ThrottleStatus::Locked(until)), Some(ip)) = (ip_status, source_ip) {
+            tracing::warn!("Source IP locked after repeated failures: {} until {}", ip, until);
+        }
+        
+        let recent_failures = self.failure_monitor.record(now);
+        
+        if let Some(observer) = &self.observer {
+            let window_seconds = self.failure_monitor.window().num_seconds().max(1);
+            let failures_per_minute = recent_failures as f64 * 60.0 / window_seconds as f64;
+            let mut observer = observer.lock().await;
+            
+            observer.record_metric(
+                "auth_failures_per_minute".to_string(),
+                failures_per_minute,
+                "failures/min".to_string(),
+                None,
+            ).await?;
+            
+            // Raise one alert per spike rather than one per failed attempt
+            let spike_active = observer.get_active_alerts().iter()
+                .any(|alert| alert.name == AUTH_FAILURE_SPIKE_ALERT);
+            
+            if recent_failures >= self.failure_spike_threshold && !spike_active {
+                observer.create_alert(
+                    AUTH_FAILURE_SPIKE_ALERT.to_string(),
+                    AlertSeverity::High,
+                    format!(
+                        "{} failed logins in the last {} seconds",
+                        recent_failures,
+                        window_seconds
+                    ),
+                ).await?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Record an authentication attempt in the audit log
+    async fn audit_authentication(
+        &self,
+        username: &str,
+        user_id: Option<&str>,
+        source_ip: Option<&str>,
+        result: AuditResult,
+        outcome: &str,
+    ) -> DreasResult<()> {
+        let mut entry = AuditEntry::new(actions::USER_AUTHENTICATION, format!("user:{}", username), result)
+            .with_metadata("username", username)
+            .with_metadata("outcome", outcome);
+        if let Some(user_id) = user_id {
+            entry = entry.with_user(user_id);
+        }
+        if let Some(user) = user_id.and_then(|user_id| self.find_user_by_id(user_id)) {
+            entry = entry.with_tenant(user.tenant_id.clone());
+        }
+        if let Some(source_ip) = source_ip {
+            entry = entry.with_metadata("source_ip", source_ip);
+        }
+        
+        self.audit_logger.record(entry).await
+    }
+    
+    /// Build a failed authentication result
+    fn failed_login(error: &str, retry_after: Option<DateTime<Utc>>) -> AuthResult {
+        AuthResult {
             success: false,
             user: None,
             session_id: None,
-            error: Some("Invalid credentials".to_string()),
+            access_token: None,
+            refresh_token: None,
+            error: Some(error.to_string()),
+            retry_after,
+        }
+    }
+    
+    /// Lift a username lockout, and the user's MFA lockout, before its cooldown ends
+    pub async fn unlock_account(&mut self, username: &str) -> DreasResult<bool> {
+        let mfa_unlocked = self.users.get(username)
+            .is_some_and(|user| self.mfa_attempts.unlock(&user.id));
+        let unlocked = self.user_attempts.unlock(&username.to_lowercase()) || mfa_unlocked;
+        
+        if unlocked {
+            tracing::info!("Account unlocked by administrator: {}", username);
+            self.audit_authentication(username, None, None, AuditResult::Success, "account_unlocked").await?;
+        }
+        
+        Ok(unlocked)
+    }
+    
+    /// Lift a source IP lockout before its cooldown ends
+    pub fn unlock_ip(&mut self, source_ip: &str) -> bool {
+        let unlocked = self.ip_attempts.unlock(source_ip);
+        
+        if unlocked {
+            tracing::info!("Source IP unlocked by administrator: {}", source_ip);
+        }
+        
+        unlocked
+    }
+    
+    /// Get when a username's lockout ends, if it is locked
+    pub fn account_locked_until(&self, username: &str) -> Option<DateTime<Utc>> {
+        match self.user_attempts.status(&username.to_lowercase(), Utc::now()) {
+            ThrottleStatus::Locked(until) => Some(until),
+            _ => None,
+        }
+    }
+    
+    /// Forget expired failed-attempt records
+    pub fn cleanup_login_attempts(&mut self) -> usize {
+        let now = Utc::now();
+        self.user_attempts.cleanup(now) + self.ip_attempts.cleanup(now) + self.mfa_attempts.cleanup(now)
+    }
+    
+    /// Issue an access/refresh token pair if a token service is configured
+    async fn issue_tokens(
+        &mut self,
+        user: &User,
+        session_id: &str,
+        family_id: Option<Uuid>,
+    ) -> DreasResult<Option<TokenPair>> {
+        let token_service = match self.token_service.as_mut() {
+            Some(token_service) => token_service,
+            None => return Ok(None),
+        };
+        
+        let access_token = token_service
+            .issue_access_token(&user.id, session_id, user.roles.clone())
+            .await?;
+        let refresh_token = token_service.issue_refresh_token(&user.id, session_id, family_id);
+        
+        Ok(Some(TokenPair {
+            access_token,
+            refresh_token,
+            expires_in: token_service.access_token_ttl_seconds(),
+        }))
+    }
+    
+    /// Exchange a refresh token for a new token pair, rotating the refresh token
+    pub async fn refresh_tokens(&mut self, refresh_token: &str) -> DreasResult<TokenPair> {
+        let token_service = self.token_service.as_mut()
+            .ok_or_else(|| DreasError::Configuration("Token service not configured".to_string()))?;
+        
+        let record = match token_service.consume_refresh_token(refresh_token)? {
+            RefreshOutcome::Rotated(record) => record,
+            RefreshOutcome::Reused(record) => {
+                // A replayed token means the session may be compromised, so end it
+                self.sessions.remove(&record.session_id)?;
+                tracing::warn!("Session revoked after refresh token reuse: {}", record.session_id);
+                
+                return Err(DreasError::Authentication("Refresh token reuse detected".to_string()));
+            }
+        };
+        
+        let session = self.active_session(&record.session_id)?
+            .ok_or_else(|| DreasError::Authentication("Session expired".to_string()))?;
+        
+        let user = self.find_user_by_id(&session.user_id)
+            .filter(|user| user.is_active)
+            .cloned()
+            .ok_or_else(|| DreasError::Authentication("User account is inactive".to_string()))?;
+        
+        let tokens = self.issue_tokens(&user, &record.session_id, Some(record.family_id)).await?;
+        tokens.ok_or_else(|| DreasError::Configuration("Token service not configured".to_string()))
+    }
+    
+    /// Verify an access token and confirm its session is still live
+    pub fn verify_access_token(&self, access_token: &str) -> DreasResult<AccessClaims> {
+        let token_service = self.token_service.as_ref()
+            .ok_or_else(|| DreasError::Configuration("Token service not configured".to_string()))?;
+        
+        let claims = token_service.verifier()?.verify(access_token)?;
+        
+        if self.get_user_by_session(&claims.sid)?.is_none() {
+            return Err(DreasError::Authentication("Session has been revoked".to_string()));
+        }
+        
+        Ok(claims)
+    }
+    
+    /// Get the JWKS that other services use to verify access tokens
+    pub fn jwks(&self) -> Option<Jwks> {
+        self.token_service.as_ref().map(|token_service| token_service.jwks())
+    }
+    
+    /// Authenticate a user from an OIDC callback, provisioning them on first login
+    ///
+    /// Every attempt is audited; a callback rejected before the user is known is logged against
+    /// the identity provider.
+    pub async fn authenticate_oidc(
+        &mut self,
+        oidc_client: &mut OidcClient,
+        state: &str,
+        code: &str,
+    ) -> DreasResult<AuthResult> {
+        let claims = match oidc_client.complete_login(state, code).await {
+            Ok(claims) => claims,
+            Err(e) => {
+                let provider = format!("oidc:{}", oidc_client.metadata().issuer);
+                self.audit_authentication(&provider, None, None, AuditResult::Failure, "oidc_login_rejected").await?;
+                return Err(e);
+            }
+        };
+        let external_id = format!("{}#{}", claims.iss, claims.sub);
+        let roles = oidc_client.map_roles(&claims);
+        let email = claims.claim_values("email").into_iter().next().unwrap_or_default();
+        
+        let username = match self.external_identities.get(&external_id) {
+            Some(username) => username.clone(),
+            None => {
+                let username = oidc_client.username(&claims);
+                
+                // Never silently link an IdP identity to an existing local account
+                if let Some(user) = self.users.get(&username) {
+                    let user_id = user.id.clone();
+                    self.audit_authentication(&username, Some(&user_id), None, AuditResult::Failure, "oidc_identity_not_linked")
+                        .await?;
+                    return Err(DreasError::Authentication(format!(
+                        "User {} already exists and is not linked to this identity provider", username
+                    )));
+                }
+                
+                self.provision_user(username.clone(), email.clone(), Vec::new())?;
+                self.external_identities.insert(external_id, username.clone());
+                tracing::info!("Provisioned user from identity provider: {}", username);
+                username
+            }
+        };
+        
+        let user = self.users.get_mut(&username)
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        
+        // The identity provider is the source of truth for role membership
+        user.roles = roles;
+        if !email.is_empty() {
+            user.email = email;
+        }
+        
+        let user = user.clone();
+        if !user.is_active {
+            self.audit_authentication(&username, Some(&user.id), None, AuditResult::Failure, "account_inactive").await?;
+            return Ok(Self::failed_login("User account is inactive", None));
+        }
+        
+        let session = self.create_session(user.id.clone(), &ClientInfo::default())?;
+        let tokens = self.issue_tokens(&user, &session.session_id, None).await?;
+        self.audit_authentication(&username, Some(&user.id), None, AuditResult::Success, "oidc").await?;
+        
+        Ok(AuthResult {
+            success: true,
+            user: self.users.get(&username).cloned(),
+            session_id: Some(session.session_id),
+            access_token: tokens.as_ref().map(|tokens| tokens.access_token.clone()),
+            refresh_token: tokens.map(|tokens| tokens.refresh_token),
+            error: None,
+            retry_after: None,
         })
     }
     
-    /// Create a user session
-    fn create_session(&mut self, user_id: String) -> DreasResult<UserSession> {
-        let session_id = Uuid::new_v4().to_string();
+    /// Upgrade a stored hash that was produced with outdated parameters
+    fn rehash_if_needed(&mut self, user_id: &str, password: &str) -> DreasResult<()> {
+        let outdated = self.credentials.get(user_id)
+            .map_or(false, |stored_hash| self.hasher.needs_rehash(stored_hash));
+        
+        if outdated {
+            let new_hash = self.hasher.hash(password)?;
+            self.credentials.insert(user_id.to_string(), new_hash);
+            tracing::info!("Password hash upgraded for user: {}", user_id);
+        }
+        
+        Ok(())
+    }
+    
+    /// Create a user session, ending the user's oldest sessions if they are over the cap
+    fn create_session(&mut self, user_id: String, client: &ClientInfo) -> DreasResult<UserSession> {
         let now = Utc::now();
+        let tenant_id = self.find_user_by_id(&user_id)
+            .map(|user| user.tenant_id.clone())
+            .unwrap_or_default();
         
         let session = UserSession {
-            session_id: session_id.clone(),
+            session_id: Uuid::new_v4().to_string(),
             user_id,
+            tenant_id,
             created_at: now,
-            expires_at: now + chrono::Duration::hours(24), // 24 hour session
-            ip_address: None,
-            user_agent: None,
+            expires_at: now + self.session_policy.absolute_timeout,
+            last_active_at: now,
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            mfa_verified_at: None,
+            impersonation: None,
         };
         
-        self.sessions.insert(session_id.clone(), session.clone());
+        let max_sessions = self.session_policy.max_sessions_per_user;
+        if max_sessions > 0 {
+            let live_sessions: Vec<UserSession> = self.sessions.list_for_user(&session.user_id)?
+                .into_iter()
+                .filter(|existing| !existing.is_expired(now, self.session_policy.idle_timeout))
+                .collect();
+            
+            // Sessions are listed oldest first
+            let excess = (live_sessions.len() + 1).saturating_sub(max_sessions);
+            for evicted in live_sessions.iter().take(excess) {
+                tracing::info!("Session limit reached for user {}; ending session {}", evicted.user_id, evicted.session_id);
+                self.end_session(&evicted.session_id)?;
+            }
+        }
+        
+        self.sessions.save(&session)?;
         
         // Update user's last login
-        if let Some(user) = self.users.get_mut(&session.user_id) {
+        if let Some(user) = self.users.values_mut().find(|user| user.id == session.user_id) {
             user.last_login = Some(now);
         }
         
@@ -138,109 +739,1255 @@ impl IdentityManager {
         session_id: &str,
         permission: &str,
     ) -> DreasResult<PermissionResult> {
-        let session = self.sessions.get(session_id)
+        self.check_permission_in_context(session_id, permission, None, HashMap::new()).await
+    }
+    
+    /// Check if user has permission on a resource, evaluating access policies with the given context
+    pub async fn check_permission_in_context(
+        &self,
+        session_id: &str,
+        permission: &str,
+        resource: Option<PolicyEntity>,
+        context: HashMap<String, serde_json::Value>,
+    ) -> DreasResult<PermissionResult> {
+        let session = self.sessions.get(session_id)?
             .ok_or_else(|| DreasError::Authentication("Invalid session".to_string()))?;
         
         // Check if session is expired
-        if Utc::now() > session.expires_at {
+        if self.active_session(session_id)?.is_none() {
             return Ok(PermissionResult {
                 allowed: false,
                 reason: Some("Session expired".to_string()),
+                mfa_required: false,
             });
         }
         
-        let user = self.users.get(&session.user_id)
+        let user = self.find_user_by_id(&session.user_id)
             .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
         
         if !user.is_active {
             return Ok(PermissionResult {
                 allowed: false,
                 reason: Some("User account is inactive".to_string()),
+                mfa_required: false,
+            });
+        }
+        
+        let explanation = self.explain_grants(
+            format!("user:{}", user.username),
+            &user.permissions,
+            &user.denied_permissions,
+            &user.roles,
+            permission,
+        );
+        
+        let principal = self.user_entity(user);
+        
+        // Resources tagged with another tenant are off limits unless a policy explicitly permits access
+        let cross_tenant = resource.as_ref()
+            .and_then(tenant_of)
+            .is_some_and(|owner| owner != user.tenant_id);
+        
+        let mut context = context;
+        context.insert("session_id".to_string(), session_id.into());
+        context.insert("mfa_verified".to_string(), self.has_recent_mfa(&session).into());
+        context.insert("cross_tenant".to_string(), cross_tenant.into());
+        if let Some(impersonation) = &session.impersonation {
+            context.insert("impersonated_by".to_string(), impersonation.actor_id.clone().into());
+        }
+        
+        let decision = self.evaluate_policies(principal, permission, resource, context).await?;
+        
+        if let Some(reason) = Self::refusal(&explanation, decision.as_ref()) {
+            return Ok(PermissionResult {
+                allowed: false,
+                reason: Some(reason),
+                mfa_required: false,
             });
         }
         
-        // Check direct permissions
-        if user.permissions.contains(&permission.to_string()) {
+        if cross_tenant && !decision.as_ref().is_some_and(|decision| decision.is_permitted()) {
             return Ok(PermissionResult {
-                allowed: true,
-                reason: None,
+                allowed: false,
+                reason: Some("Cross-tenant access requires an explicit policy permit".to_string()),
+                mfa_required: false,
             });
         }
         
-        // Check role-based permissions
-        for role_name in &user.roles {
-            if let Some(role) = self.roles.get(role_name) {
-                if role.permissions.contains(&permission.to_string()) {
-                    return Ok(PermissionResult {
-                        allowed: true,
-                        reason: None,
-                    });
-                }
-            }
+        // Sensitive permissions also need a recent second factor on this session
+        if self.requires_step_up(permission) && !self.has_recent_mfa(&session) {
+            return Ok(PermissionResult {
+                allowed: false,
+                reason: Some("Recent MFA verification required".to_string()),
+                mfa_required: true,
+            });
         }
         
         Ok(PermissionResult {
-            allowed: false,
-            reason: Some("Insufficient permissions".to_string()),
+            allowed: true,
+            reason: None,
+            mfa_required: false,
         })
     }
     
-    /// Create a new user
-    pub async fn create_user(
-        &mut self,
-        username: String,
-        email: String,
-        password: String,
-        roles: Vec<String>,
-    ) -> DreasResult<User> {
-        let user_id = Uuid::new_v4().to_string();
-        
-        let user = User {
-            id: user_id.clone(),
-            username: username.clone(),
-            email,
-            roles,
-            permissions: Vec::new(),
-            created_at: Utc::now(),
-            last_login: None,
-            is_active: true,
+    /// Run the policy engine, if one is configured, for a permission check
+    async fn evaluate_policies(
+        &self,
+        principal: PolicyEntity,
+        permission: &str,
+        resource: Option<PolicyEntity>,
+        context: HashMap<String, serde_json::Value>,
+    ) -> DreasResult<Option<PolicyDecision>> {
+        let policy_engine = match &self.policy_engine {
+            Some(policy_engine) => policy_engine,
+            None => return Ok(None),
         };
         
-        self.users.insert(username, user.clone());
-        Ok(user)
+        let resource = resource.unwrap_or_else(|| PolicyEntity::new("permission", permission));
+        let mut request = PolicyRequest::new(principal, permission, resource);
+        request.context.extend(context);
+        
+        policy_engine.evaluate(&request).await.map(Some)
     }
     
-    /// Create a new role
-    pub async fn create_role(
-        &mut self,
-        name: String,
-        permissions: Vec<String>,
-        description: String,
-    ) -> DreasResult<Role> {
-        let role = Role {
-            name: name.clone(),
-            permissions,
-            description,
-            created_at: Utc::now(),
-        };
-        
-        self.roles.insert(name, role.clone());
-        Ok(role)
+    /// Combine role grants with policy decisions: forbids and deny rules always win, permits can add access
+    fn refusal(explanation: &PermissionExplanation, decision: Option<&PolicyDecision>) -> Option<String> {
+        match decision {
+            Some(decision) if decision.is_forbidden() => {
+                Some(format!("Forbidden by policy {}", decision.forbidden_by.join(", ")))
+            }
+            Some(decision) if decision.is_permitted() && explanation.decision != PermissionDecision::Denied => None,
+            _ => explanation.reason(),
+        }
     }
     
-    /// Logout user
-    pub async fn logout(&mut self, session_id: &str) -> DreasResult<()> {
-        self.sessions.remove(session_id);
-        Ok(())
+    /// Check whether an authenticated principal has a permission
+    pub async fn check_principal_permission(
+        &self,
+        principal: &Principal,
+        permission: &str,
+    ) -> DreasResult<PermissionResult> {
+        self.check_principal_permission_in_context(principal, permission, None, HashMap::new()).await
     }
     
-    /// Get user by session ID
-    pub fn get_user_by_session(&self, session_id: &str) -> DreasResult<Option<User>> {
-        if let Some(session) = self.sessions.get(session_id) {
-            if Utc::now() <= session.expires_at {
-                return Ok(self.users.get(&session.user_id).cloned());
+    /// Check whether an authenticated principal has a permission on a resource, evaluating access policies with the given context
+    ///
+    /// Service accounts are kept to their own tenant's resources the same way users are.
+    pub async fn check_principal_permission_in_context(
+        &self,
+        principal: &Principal,
+        permission: &str,
+        resource: Option<PolicyEntity>,
+        context: HashMap<String, serde_json::Value>,
+    ) -> DreasResult<PermissionResult> {
+        let (account_id, scopes) = match principal {
+            Principal::User { session_id, .. } => {
+                return self.check_permission_in_context(session_id, permission, resource, context).await;
             }
+            Principal::ServiceAccount { account_id, scopes, .. } => (account_id, scopes),
+        };
+        
+        let denied = |reason: &str| PermissionResult {
+            allowed: false,
+            reason: Some(reason.to_string()),
+            mfa_required: false,
+        };
+        
+        let account = match self.service_accounts.get(account_id) {
+            Some(account) if account.is_active => account,
+            _ => return Ok(denied("Service account is inactive")),
+        };
+        
+        if !scopes.iter().any(|scope| permission::permission_matches(scope, permission)) {
+            return Ok(denied("Permission is outside the API key's scopes"));
+        }
+        
+        // Scopes only narrow access, so the account must still hold the permission itself
+        let explanation = self.explain_account_grants(account, permission);
+        let tenant_id = service_account_tenant(account).unwrap_or_default();
+        let cross_tenant = resource.as_ref()
+            .and_then(tenant_of)
+            .is_some_and(|owner| owner != tenant_id);
+        
+        let mut context = context;
+        context.insert("cross_tenant".to_string(), cross_tenant.into());
+        let decision = self.evaluate_policies(self.principal_entity(principal), permission, resource, context).await?;
+        
+        if let Some(reason) = Self::refusal(&explanation, decision.as_ref()) {
+            return Ok(PermissionResult {
+                allowed: false,
+                reason: Some(reason),
+                mfa_required: false,
+            });
+        }
+        
+        if cross_tenant && !decision.as_ref().is_some_and(|decision| decision.is_permitted()) {
+            return Ok(denied("Cross-tenant access requires an explicit policy permit"));
+        }
+        
+        // API keys can't complete a second factor
+        if self.requires_step_up(permission) {
+            return Ok(denied("Step-up permissions cannot be exercised with an API key"));
         }
-        Ok(None)
+        
+        Ok(PermissionResult {
+            allowed: true,
+            reason: None,
+            mfa_required: false,
+        })
+    }
+    
+    /// Explain how a session's user was granted or denied a permission
+    pub fn explain_permission(&self, session_id: &str, permission: &str) -> DreasResult<PermissionExplanation> {
+        let session = self.live_session(session_id)?;
+        let user = self.find_user_by_id(&session.user_id)
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        
+        Ok(self.explain_grants(
+            format!("user:{}", user.username),
+            &user.permissions,
+            &user.denied_permissions,
+            &user.roles,
+            permission,
+        ))
+    }
+    
+    /// Evaluate a service account's own grants for a permission
+    fn explain_account_grants(&self, account: &ServiceAccount, permission: &str) -> PermissionExplanation {
+        self.explain_grants(
+            format!("service_account:{}", account.name),
+            &account.permissions,
+            &account.denied_permissions,
+            &account.roles,
+            permission,
+        )
+    }
+    
+    /// Walk a subject's direct grants and inherited roles; any matching deny wins over allows
+    fn explain_grants(
+        &self,
+        subject: String,
+        permissions: &[String],
+        denied_permissions: &[String],
+        roles: &[String],
+        permission: &str,
+    ) -> PermissionExplanation {
+        let mut sources = vec![(vec![subject.clone()], permissions, denied_permissions)];
+        let mut pending: Vec<(String, Vec<String>)> = roles.iter()
+            .rev()
+            .map(|role_name| (role_name.clone(), vec![subject.clone()]))
+            .collect();
+        let mut visited = HashSet::new();
+        
+        // Depth-first, so the reported chain is the first path in declaration order
+        while let Some((role_name, mut chain)) = pending.pop() {
+            if !visited.insert(role_name.clone()) {
+                continue;
+            }
+            
+            if let Some(role) = self.roles.get(&role_name) {
+                chain.push(format!("role:{}", role_name));
+                pending.extend(role.parent_roles.iter().rev().map(|parent| (parent.clone(), chain.clone())));
+                sources.push((chain, &role.permissions, &role.denied_permissions));
+            }
+        }
+        
+        let matching = |patterns: &[String]| {
+            patterns.iter().find(|pattern| permission::permission_matches(pattern, permission)).cloned()
+        };
+        let mut allowed_by = None;
+        
+        // Every source is checked for denies before an allow is accepted
+        for (chain, allows, denies) in &sources {
+            if let Some(pattern) = matching(denies) {
+                return PermissionExplanation {
+                    permission: permission.to_string(),
+                    decision: PermissionDecision::Denied,
+                    chain: chain.clone(),
+                    rule: Some(format!("deny {}", pattern)),
+                };
+            }
+            
+            if allowed_by.is_none() {
+                allowed_by = matching(allows).map(|pattern| (chain.clone(), pattern));
+            }
+        }
+        
+        match allowed_by {
+            Some((chain, pattern)) => PermissionExplanation {
+                permission: permission.to_string(),
+                decision: PermissionDecision::Allowed,
+                chain,
+                rule: Some(format!("allow {}", pattern)),
+            },
+            None => PermissionExplanation {
+                permission: permission.to_string(),
+                decision: PermissionDecision::NotGranted,
+                chain: vec![subject],
+                rule: None,
+            },
+        }
+    }
+    
+    /// List a subject's roles along with every role they inherit
+    fn expand_roles(&self, roles: &[String]) -> Vec<String> {
+        let mut expanded: Vec<String> = Vec::new();
+        let mut pending: Vec<String> = roles.iter().rev().cloned().collect();
+        
+        while let Some(role_name) = pending.pop() {
+            if expanded.contains(&role_name) {
+                continue;
+            }
+            
+            if let Some(role) = self.roles.get(&role_name) {
+                pending.extend(role.parent_roles.iter().rev().cloned());
+            }
+            expanded.push(role_name);
+        }
+        
+        expanded
+    }
+    
+    /// Check whether a permission needs a recent MFA verification
+    fn requires_step_up(&self, permission: &str) -> bool {
+        self.step_up_permissions.iter()
+            .any(|pattern| permission::permission_matches(pattern, permission))
+    }
+    
+    /// Start TOTP enrollment for the session's user
+    pub fn begin_totp_enrollment(&mut self, session_id: &str) -> DreasResult<TotpEnrollment> {
+        let session = self.live_session(session_id)?;
+        let user = self.find_user_by_id(&session.user_id)
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        
+        // Replacing a confirmed authenticator must go through disable_totp, which needs step-up
+        if self.mfa_enrollments.get(&user.id).is_some_and(|enrollment| enrollment.confirmed) {
+            return Err(DreasError::Authentication("TOTP is already enrolled for this user".to_string()));
+        }
+        
+        let secret = self.totp.generate_secret();
+        let enrollment = TotpEnrollment {
+            secret: data_encoding::BASE32_NOPAD.encode(&secret),
+            provisioning_uri: self.totp.provisioning_uri(&user.username, &secret)?,
+        };
+        
+        self.mfa_enrollments.insert(user.id.clone(), MfaEnrollment::new(secret));
+        Ok(enrollment)
+    }
+    
+    /// Confirm TOTP enrollment with a code from the authenticator, returning recovery codes
+    pub fn confirm_totp_enrollment(&mut self, session_id: &str, code: &str) -> DreasResult<Vec<String>> {
+        let user_id = self.live_session(session_id)?.user_id.clone();
+        let enrollment = self.mfa_enrollments.get_mut(&user_id)
+            .filter(|enrollment| !enrollment.confirmed)
+            .ok_or_else(|| DreasError::Authentication("No pending TOTP enrollment".to_string()))?;
+        
+        let step = self.totp.verify(&enrollment.secret, code, Utc::now(), enrollment.last_used_step)?
+            .ok_or_else(|| DreasError::Authentication("Invalid verification code".to_string()))?;
+        
+        enrollment.confirmed = true;
+        enrollment.last_used_step = Some(step);
+        let recovery_codes = enrollment.regenerate_recovery_codes(self.recovery_code_count);
+        
+        self.mark_mfa_verified(session_id)?;
+        tracing::info!("TOTP enrolled for user: {}", user_id);
+        Ok(recovery_codes)
+    }
+    
+    /// Verify a TOTP or recovery code and record the step-up on the session
+    ///
+    /// Failed codes are audited and throttled per user like failed logins, and a user locked out
+    /// is refused without the code being checked, so recovery codes aren't used up meanwhile.
+    pub async fn verify_mfa(&mut self, session_id: &str, code: &str) -> DreasResult<bool> {
+        let now = Utc::now();
+        let user_id = self.live_session(session_id)?.user_id.clone();
+        let username = self.find_user_by_id(&user_id)
+            .map(|user| user.username.clone())
+            .unwrap_or_else(|| user_id.clone());
+        
+        if let Some(retry_after) = self.mfa_attempts.status(&user_id, now).retry_after() {
+            self.audit_authentication(&username, Some(&user_id), None, AuditResult::Failure, "mfa_throttled").await?;
+            return Err(DreasError::Authentication(format!(
+                "Too many failed MFA attempts; try again after {}", retry_after.to_rfc3339()
+            )));
+        }
+        
+        let enrollment = self.mfa_enrollments.get_mut(&user_id)
+            .filter(|enrollment| enrollment.confirmed)
+            .ok_or_else(|| DreasError::Authentication("MFA is not enrolled for this user".to_string()))?;
+        
+        let verified = match self.totp.verify(&enrollment.secret, code, now, enrollment.last_used_step)? {
+            Some(step) => {
+                enrollment.last_used_step = Some(step);
+                true
+            }
+            None if enrollment.consume_recovery_code(code) => {
+                tracing::warn!(
+                    "Recovery code used by user {}; {} remaining",
+                    user_id,
+                    enrollment.remaining_recovery_codes()
+                );
+                true
+            }
+            None => false,
+        };
+        
+        if verified {
+            self.mfa_attempts.record_success(&user_id);
+            self.mark_mfa_verified(session_id)?;
+            return Ok(true);
+        }
+        
+        tracing::warn!("MFA verification failed for user: {}", user_id);
+        let status = self.mfa_attempts.record_failure(&user_id, now);
+        self.audit_authentication(&username, Some(&user_id), None, AuditResult::Failure, "invalid_mfa_code").await?;
+        
+        if let ThrottleStatus::Locked(until) = status {
+            tracing::warn!("MFA locked after repeated failures: {} until {}", username, until);
+            self.audit_authentication(&username, Some(&user_id), None, AuditResult::Failure, "mfa_locked").await?;
+        }
+        
+        Ok(false)
+    }
+    
+    /// Replace the user's recovery codes; requires a recent MFA verification
+    pub fn regenerate_recovery_codes(&mut self, session_id: &str) -> DreasResult<Vec<String>> {
+        let user_id = self.require_recent_mfa(session_id)?;
+        let enrollment = self.mfa_enrollments.get_mut(&user_id)
+            .ok_or_else(|| DreasError::Authentication("MFA is not enrolled for this user".to_string()))?;
+        
+        Ok(enrollment.regenerate_recovery_codes(self.recovery_code_count))
+    }
+    
+    /// Remove the user's TOTP enrollment; requires a recent MFA verification
+    pub fn disable_totp(&mut self, session_id: &str) -> DreasResult<()> {
+        let user_id = self.require_recent_mfa(session_id)?;
+        self.mfa_enrollments.remove(&user_id);
+        
+        tracing::info!("TOTP disabled for user: {}", user_id);
+        Ok(())
+    }
+    
+    /// Check whether a user has a confirmed second factor
+    pub fn is_mfa_enrolled(&self, user_id: &str) -> bool {
+        self.mfa_enrollments.get(user_id).is_some_and(|enrollment| enrollment.confirmed)
+    }
+    
+    /// Look up a session that exists and hasn't expired
+    fn live_session(&self, session_id: &str) -> DreasResult<UserSession> {
+        self.active_session(session_id)?
+            .ok_or_else(|| DreasError::Authentication("Invalid session".to_string()))
+    }
+    
+    /// Look up a session, ending it if it has timed out and otherwise recording activity
+    fn active_session(&self, session_id: &str) -> DreasResult<Option<UserSession>> {
+        let mut session = match self.sessions.get(session_id)? {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+        
+        let now = Utc::now();
+        if session.is_expired(now, self.session_policy.idle_timeout) {
+            self.sessions.remove(session_id)?;
+            return Ok(None);
+        }
+        
+        // Impersonation ends with the support engineer's own session
+        if let Some(impersonation) = &session.impersonation {
+            let actor_session_ended = self.sessions.get(&impersonation.actor_session_id)?
+                .is_none_or(|actor_session| actor_session.is_expired(now, self.session_policy.idle_timeout));
+            if actor_session_ended {
+                self.sessions.remove(session_id)?;
+                return Ok(None);
+            }
+        }
+        
+        if now - session.last_active_at >= chrono::Duration::seconds(SESSION_ACTIVITY_RESOLUTION_SECONDS) {
+            session.last_active_at = now;
+            self.sessions.save(&session)?;
+        }
+        
+        Ok(Some(session))
+    }
+    
+    /// Check whether the session completed MFA within the step-up window
+    fn has_recent_mfa(&self, session: &UserSession) -> bool {
+        session.mfa_verified_at
+            .is_some_and(|verified_at| Utc::now() - verified_at <= self.step_up_window)
+    }
+    
+    /// Return the session's user ID if the session has a recent MFA verification
+    fn require_recent_mfa(&self, session_id: &str) -> DreasResult<String> {
+        let session = self.live_session(session_id)?;
+        
+        if !self.has_recent_mfa(&session) {
+            return Err(DreasError::Authentication("Recent MFA verification required".to_string()));
+        }
+        
+        Ok(session.user_id.clone())
+    }
+    
+    /// Record a successful MFA verification on a session
+    fn mark_mfa_verified(&self, session_id: &str) -> DreasResult<()> {
+        if let Some(mut session) = self.sessions.get(session_id)? {
+            session.mfa_verified_at = Some(Utc::now());
+            self.sessions.save(&session)?;
+        }
+        Ok(())
+    }
+    
+    /// Create a new user
+    pub async fn create_user(
+        &mut self,
+        username: String,
+        email: String,
+        password: String,
+        roles: Vec<String>,
+    ) -> DreasResult<User> {
+        if self.users.contains_key(&username) {
+            return Err(DreasError::Authentication(format!("User {} already exists", username)));
+        }
+        
+        self.password_policy.validate(&username, &password)?;
+        let password_hash = self.hasher.hash(&password)?;
+        
+        let user_id = Uuid::new_v4().to_string();
+        
+        let user = User {
+            id: user_id.clone(),
+            username: username.clone(),
+            email,
+            roles,
+            permissions: Vec::new(),
+            denied_permissions: Vec::new(),
+            attributes: HashMap::new(),
+            tenant_id: TenantId::default(),
+            created_at: Utc::now(),
+            last_login: None,
+            is_active: true,
+        };
+        
+        self.credentials.insert(user_id, password_hash);
+        self.users.insert(username, user.clone());
+        Ok(user)
+    }
+    
+    /// Create a user without a local password, for accounts that sign in through an identity provider
+    pub fn provision_user(&mut self, username: String, email: String, roles: Vec<String>) -> DreasResult<User> {
+        if self.users.contains_key(&username) {
+            return Err(DreasError::Authentication(format!("User {} already exists", username)));
+        }
+        
+        let user = User {
+            id: Uuid::new_v4().to_string(),
+            username: username.clone(),
+            email,
+            roles,
+            permissions: Vec::new(),
+            denied_permissions: Vec::new(),
+            attributes: HashMap::new(),
+            tenant_id: TenantId::default(),
+            created_at: Utc::now(),
+            last_login: None,
+            is_active: true,
+        };
+        
+        self.users.insert(username, user.clone());
+        Ok(user)
+    }
+    
+    /// Get a user by ID
+    pub fn get_user(&self, user_id: &str) -> Option<User> {
+        self.find_user_by_id(user_id).cloned()
+    }
+    
+    /// List all users, ordered by creation time
+    pub fn list_users(&self) -> Vec<User> {
+        let mut users: Vec<User> = self.users.values().cloned().collect();
+        users.sort_by_key(|user| user.created_at);
+        users
+    }
+    
+    /// Replace a user's profile, roles and status, keyed by user ID
+    ///
+    /// Renames are applied to linked IdP identities, and deactivating a user ends all their sessions.
+    pub async fn update_user(&mut self, updated: User) -> DreasResult<User> {
+        let existing = self.find_user_by_id(&updated.id)
+            .cloned()
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        
+        if updated.username != existing.username {
+            if self.users.contains_key(&updated.username) {
+                return Err(DreasError::Authentication(format!("User {} already exists", updated.username)));
+            }
+            
+            self.users.remove(&existing.username);
+            for username in self.external_identities.values_mut() {
+                if *username == existing.username {
+                    *username = updated.username.clone();
+                }
+            }
+        }
+        
+        // Identity and bookkeeping fields can't be changed by callers
+        let user = User {
+            tenant_id: existing.tenant_id.clone(),
+            created_at: existing.created_at,
+            last_login: existing.last_login,
+            ..updated
+        };
+        self.users.insert(user.username.clone(), user.clone());
+        
+        if existing.is_active && !user.is_active {
+            self.revoke_all_sessions(&user.id).await?;
+            tracing::info!("User deactivated: {}", user.username);
+        }
+        
+        Ok(user)
+    }
+    
+    /// Move a user to another tenant, ending sessions opened under the old one
+    pub async fn set_user_tenant(&mut self, user_id: &str, tenant_id: TenantId) -> DreasResult<User> {
+        let user = self.users.values_mut()
+            .find(|user| user.id == user_id)
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        
+        if user.tenant_id == tenant_id {
+            return Ok(user.clone());
+        }
+        
+        tracing::info!("User {} moved from tenant {} to {}", user.username, user.tenant_id, tenant_id);
+        user.tenant_id = tenant_id;
+        let user = user.clone();
+        
+        self.revoke_all_sessions(&user.id).await?;
+        Ok(user)
+    }
+    
+    /// Deactivate a user and end all their sessions
+    pub async fn deactivate_user(&mut self, user_id: &str) -> DreasResult<User> {
+        let user = self.get_user(user_id)
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        
+        self.update_user(User { is_active: false, ..user }).await
+    }
+    
+    /// Create a new service account
+    pub async fn create_service_account(
+        &mut self,
+        name: String,
+        description: String,
+        roles: Vec<String>,
+    ) -> DreasResult<ServiceAccount> {
+        if self.service_accounts.values().any(|account| account.name == name) {
+            return Err(DreasError::Authentication(format!("Service account {} already exists", name)));
+        }
+        
+        let account = ServiceAccount::new(name, description, roles);
+        self.service_accounts.insert(account.id.clone(), account.clone());
+        
+        tracing::info!("Service account created: {}", account.name);
+        Ok(account)
+    }
+    
+    /// Deactivate a service account; its keys stop working immediately
+    pub fn deactivate_service_account(&mut self, account_id: &str) -> DreasResult<()> {
+        let account = self.service_accounts.get_mut(account_id)
+            .ok_or_else(|| DreasError::Authentication("Service account not found".to_string()))?;
+        
+        account.is_active = false;
+        tracing::info!("Service account deactivated: {}", account.name);
+        Ok(())
+    }
+    
+    /// Assign a service account to a tenant
+    pub fn set_service_account_tenant(&mut self, account_id: &str, tenant_id: TenantId) -> DreasResult<()> {
+        let account = self.service_accounts.get_mut(account_id)
+            .ok_or_else(|| DreasError::Authentication("Service account not found".to_string()))?;
+        
+        account.attributes.insert(TENANT_ATTRIBUTE.to_string(), tenant_id.as_str().into());
+        tracing::info!("Service account {} assigned to tenant {}", account.name, tenant_id);
+        Ok(())
+    }
+    
+    /// Issue an API key for a service account, limited to the given scopes
+    pub fn create_api_key(
+        &mut self,
+        account_id: &str,
+        name: String,
+        scopes: Vec<String>,
+        ttl: Option<chrono::Duration>,
+    ) -> DreasResult<IssuedApiKey> {
+        let account = self.service_accounts.get(account_id)
+            .filter(|account| account.is_active)
+            .ok_or_else(|| DreasError::Authentication("Service account not found".to_string()))?;
+        
+        if scopes.is_empty() {
+            return Err(DreasError::Authentication("API keys must have at least one scope".to_string()));
+        }
+        
+        for scope in &scopes {
+            if !self.explain_account_grants(account, scope).is_allowed() {
+                return Err(DreasError::Authentication(format!(
+                    "Scope {} is not granted to service account {}", scope, account.name
+                )));
+            }
+            
+            if self.requires_step_up(scope) {
+                return Err(DreasError::Authentication(format!(
+                    "Scope {} requires step-up authentication and cannot be delegated to an API key", scope
+                )));
+            }
+        }
+        
+        let max_ttl = chrono::Duration::days(self.api_key_config.max_ttl_days);
+        let ttl = ttl.unwrap_or_else(|| chrono::Duration::days(self.api_key_config.default_ttl_days));
+        
+        if ttl <= chrono::Duration::zero() || ttl > max_ttl {
+            return Err(DreasError::Authentication(format!(
+                "API key lifetime must be between 1 second and {} days", self.api_key_config.max_ttl_days
+            )));
+        }
+        
+        let (record, issued) = ApiKey::generate(account.id.clone(), name, scopes, Utc::now() + ttl);
+        self.api_keys.insert(record.key_id.clone(), record);
+        
+        tracing::info!("API key {} issued for service account: {}", issued.key_id, account_id);
+        Ok(issued)
+    }
+    
+    /// Replace an API key, keeping the old one valid for an overlap period
+    pub fn rotate_api_key(
+        &mut self,
+        key_id: &str,
+        overlap: Option<chrono::Duration>,
+    ) -> DreasResult<IssuedApiKey> {
+        let old_key = self.api_keys.get(key_id)
+            .filter(|key| key.is_usable(Utc::now()))
+            .cloned()
+            .ok_or_else(|| DreasError::Authentication("API key not found or no longer valid".to_string()))?;
+        
+        let issued = self.create_api_key(&old_key.service_account_id, old_key.name, old_key.scopes, None)?;
+        let overlap = overlap.unwrap_or_else(|| chrono::Duration::hours(self.api_key_config.rotation_overlap_hours));
+        
+        if let Some(old_key) = self.api_keys.get_mut(key_id) {
+            old_key.expires_at = old_key.expires_at.min(Utc::now() + overlap);
+            old_key.replaced_by = Some(issued.key_id.clone());
+        }
+        
+        tracing::info!("API key {} rotated to {}", key_id, issued.key_id);
+        Ok(issued)
+    }
+    
+    /// Revoke an API key immediately
+    pub fn revoke_api_key(&mut self, key_id: &str) -> DreasResult<()> {
+        let key = self.api_keys.get_mut(key_id)
+            .ok_or_else(|| DreasError::Authentication("API key not found".to_string()))?;
+        
+        key.revoked_at.get_or_insert_with(Utc::now);
+        tracing::info!("API key revoked: {}", key_id);
+        Ok(())
+    }
+    
+    /// List the API keys belonging to a service account
+    pub fn list_api_keys(&self, account_id: &str) -> Vec<ApiKey> {
+        self.api_keys.values()
+            .filter(|key| key.service_account_id == account_id)
+            .cloned()
+            .collect()
+    }
+    
+    /// Resolve a presented API key to its service account principal
+    pub fn authenticate_api_key(&mut self, api_key: &str) -> DreasResult<Principal> {
+        let invalid = || DreasError::Authentication("Invalid API key".to_string());
+        let now = Utc::now();
+        
+        let key_id = service_account::parse_key_id(api_key)?;
+        let key = self.api_keys.get_mut(key_id)
+            .filter(|key| key.matches(api_key))
+            .ok_or_else(invalid)?;
+        
+        if !key.is_usable(now) {
+            tracing::warn!("Expired or revoked API key presented: {}", key.key_id);
+            return Err(invalid());
+        }
+        
+        if !self.service_accounts.get(&key.service_account_id).is_some_and(|account| account.is_active) {
+            return Err(invalid());
+        }
+        
+        key.last_used_at = Some(now);
+        
+        Ok(Principal::ServiceAccount {
+            account_id: key.service_account_id.clone(),
+            key_id: key.key_id.clone(),
+            scopes: key.scopes.clone(),
+        })
+    }
+    
+    /// Create a new role
+    pub async fn create_role(
+        &mut self,
+        name: String,
+        permissions: Vec<String>,
+        description: String,
+    ) -> DreasResult<Role> {
+        for pattern in &permissions {
+            permission::validate_permission(pattern)?;
+        }
+        
+        // Keep inheritance and denials when an existing role's permissions are replaced
+        let existing = self.roles.get(&name);
+        let role = Role {
+            name: name.clone(),
+            permissions,
+            parent_roles: existing.map(|role| role.parent_roles.clone()).unwrap_or_default(),
+            denied_permissions: existing.map(|role| role.denied_permissions.clone()).unwrap_or_default(),
+            description,
+            created_at: Utc::now(),
+        };
+        
+        self.roles.insert(name, role.clone());
+        Ok(role)
+    }
+    
+    /// Get a role by name
+    pub fn get_role(&self, name: &str) -> Option<Role> {
+        self.roles.get(name).cloned()
+    }
+    
+    /// List all roles, ordered by name
+    pub fn list_roles(&self) -> Vec<Role> {
+        let mut roles: Vec<Role> = self.roles.values().cloned().collect();
+        roles.sort_by(|a, b| a.name.cmp(&b.name));
+        roles
+    }
+    
+    /// Delete a role and remove it from every user, service account and child role
+    pub fn delete_role(&mut self, name: &str) -> DreasResult<()> {
+        if self.roles.remove(name).is_none() {
+            return Err(DreasError::Configuration(format!("Role {} does not exist", name)));
+        }
+        
+        for user in self.users.values_mut() {
+            user.roles.retain(|role| role != name);
+        }
+        for account in self.service_accounts.values_mut() {
+            account.roles.retain(|role| role != name);
+        }
+        for role in self.roles.values_mut() {
+            role.parent_roles.retain(|parent| parent != name);
+        }
+        
+        Ok(())
+    }
+    
+    /// Make a role inherit the permissions and denials of its parent roles
+    pub fn set_role_parents(&mut self, name: &str, parent_roles: Vec<String>) -> DreasResult<()> {
+        if !self.roles.contains_key(name) {
+            return Err(DreasError::Configuration(format!("Role {} does not exist", name)));
+        }
+        
+        for parent in &parent_roles {
+            if !self.roles.contains_key(parent) {
+                return Err(DreasError::Configuration(format!("Parent role {} does not exist", parent)));
+            }
+            
+            if let Some(cycle) = self.inheritance_path(parent, name) {
+                return Err(DreasError::Configuration(format!(
+                    "Role inheritance cycle: {} -> {}", name, cycle.join(" -> ")
+                )));
+            }
+        }
+        
+        if let Some(role) = self.roles.get_mut(name) {
+            role.parent_roles = parent_roles;
+        }
+        
+        Ok(())
+    }
+    
+    /// Find the inheritance path from one role to another, if there is one
+    fn inheritance_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        if from == to {
+            return Some(vec![from.to_string()]);
+        }
+        
+        let mut pending = vec![vec![from.to_string()]];
+        let mut visited = HashSet::new();
+        
+        while let Some(path) = pending.pop() {
+            let current = path.last()?;
+            
+            if !visited.insert(current.clone()) {
+                continue;
+            }
+            
+            for parent in self.roles.get(current).map(|role| role.parent_roles.as_slice()).unwrap_or_default() {
+                let mut next = path.clone();
+                next.push(parent.clone());
+                
+                if parent == to {
+                    return Some(next);
+                }
+                
+                pending.push(next);
+            }
+        }
+        
+        None
+    }
+    
+    /// Add a deny rule to a role; it overrides allows from any source
+    pub fn deny_role_permission(&mut self, name: &str, pattern: &str) -> DreasResult<()> {
+        permission::validate_permission(pattern)?;
+        
+        let role = self.roles.get_mut(name)
+            .ok_or_else(|| DreasError::Configuration(format!("Role {} does not exist", name)))?;
+        role.denied_permissions.push(pattern.to_string());
+        Ok(())
+    }
+    
+    /// Set an attribute on a user for use in access policies
+    ///
+    /// The `tenant` attribute moves the user to another tenant through `set_user_tenant`, ending their sessions.
+    pub async fn set_user_attribute(&mut self, username: &str, name: &str, value: serde_json::Value) -> DreasResult<()> {
+        let user = self.users.get_mut(username)
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        
+        if name == TENANT_ATTRIBUTE {
+            let tenant = value.as_str()
+                .ok_or_else(|| DreasError::Configuration("Tenant attribute must be a string".to_string()))?;
+            let tenant_id = TenantId::new(tenant)?;
+            let user_id = user.id.clone();
+            self.set_user_tenant(&user_id, tenant_id).await?;
+            return Ok(());
+        }
+        
+        user.attributes.insert(name.to_string(), value);
+        Ok(())
+    }
+    
+    /// Grant a permission directly to a user
+    pub fn grant_user_permission(&mut self, username: &str, pattern: &str) -> DreasResult<()> {
+        permission::validate_permission(pattern)?;
+        
+        let user = self.users.get_mut(username)
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        user.permissions.push(pattern.to_string());
+        Ok(())
+    }
+    
+    /// Deny a permission to a user regardless of their roles
+    pub fn deny_user_permission(&mut self, username: &str, pattern: &str) -> DreasResult<()> {
+        permission::validate_permission(pattern)?;
+        
+        let user = self.users.get_mut(username)
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        user.denied_permissions.push(pattern.to_string());
+        Ok(())
+    }
+    
+    /// Logout user
+    pub async fn logout(&mut self, session_id: &str) -> DreasResult<()> {
+        self.end_session(session_id)
+    }
+    
+    /// End every session belonging to a user, e.g. after a password reset or suspected compromise
+    pub async fn revoke_all_sessions(&mut self, user_id: &str) -> DreasResult<usize> {
+        let sessions = self.sessions.list_for_user(user_id)?;
+        
+        for session in &sessions {
+            self.end_session(&session.session_id)?;
+        }
+        
+        tracing::info!("Revoked {} sessions for user {}", sessions.len(), user_id);
+        Ok(sessions.len())
+    }
+    
+    /// List a user's sessions that haven't expired
+    pub fn list_sessions(&self, user_id: &str) -> DreasResult<Vec<UserSession>> {
+        let now = Utc::now();
+        
+        Ok(self.sessions.list_for_user(user_id)?
+            .into_iter()
+            .filter(|session| !session.is_expired(now, self.session_policy.idle_timeout))
+            .collect())
+    }
+    
+    /// Validate a session for a request, enforcing timeouts and the session's client binding
+    pub fn validate_session(&self, session_id: &str, client: &ClientInfo) -> DreasResult<UserSession> {
+        let session = self.live_session(session_id)?;
+        
+        if !self.session_policy.binding_matches(&session, client) {
+            tracing::warn!(
+                "Session {} presented from a different client (ip {:?}, user agent {:?})",
+                session_id, client.ip_address, client.user_agent
+            );
+            return Err(DreasError::Authentication("Session is bound to a different client".to_string()));
+        }
+        
+        if !self.find_user_by_id(&session.user_id).is_some_and(|user| user.is_active) {
+            return Err(DreasError::Authentication("User account is inactive".to_string()));
+        }
+        
+        Ok(session)
+    }
+    
+    /// Remove sessions past their idle or absolute timeout, revoking their refresh tokens
+    pub fn cleanup_expired_sessions(&mut self) -> DreasResult<usize> {
+        let expired = self.sessions.remove_expired(Utc::now(), self.session_policy.idle_timeout)?;
+        
+        if let Some(token_service) = self.token_service.as_mut() {
+            for session in &expired {
+                token_service.revoke_session(&session.session_id);
+            }
+        }
+        
+        Ok(expired.len())
+    }
+    
+    /// Start a time-boxed session acting as another user, e.g. for support to reproduce an issue
+    ///
+    /// The actor needs `identity:impersonate` on the subject. The session records both identities,
+    /// ends no later than the actor's own session, and the subject is notified.
+    pub async fn start_impersonation(
+        &mut self,
+        actor_session_id: &str,
+        subject_user_id: &str,
+        reason: &str,
+        duration: chrono::Duration,
+    ) -> DreasResult<UserSession> {
+        let actor_session = self.live_session(actor_session_id)?;
+        
+        if actor_session.impersonation.is_some() {
+            return Err(DreasError::Authentication("Impersonation sessions can't start another impersonation".to_string()));
+        }
+        if actor_session.user_id == subject_user_id {
+            return Err(DreasError::Authentication("Users can't impersonate themselves".to_string()));
+        }
+        self.impersonation_policy.validate(reason, duration)?;
+        
+        let subject = self.find_user_by_id(subject_user_id)
+            .filter(|user| user.is_active)
+            .cloned()
+            .ok_or_else(|| DreasError::Authentication("User not found".to_string()))?;
+        
+        let resource = PolicyEntity::new("user", &subject.id)
+            .with_attribute("username", subject.username.clone())
+            .with_attribute(TENANT_ATTRIBUTE, subject.tenant_id.as_str());
+        let context = [("impersonation_reason".to_string(), reason.into())].into_iter().collect();
+        let permission = self.check_permission_in_context(actor_session_id, IMPERSONATE_PERMISSION, Some(resource), context)
+            .await?;
+        if !permission.allowed {
+            return Err(DreasError::Authentication(format!(
+                "Impersonation not permitted: {}",
+                permission.reason.unwrap_or_else(|| "permission denied".to_string())
+            )));
+        }
+        
+        let now = Utc::now();
+        let session = UserSession {
+            session_id: Uuid::new_v4().to_string(),
+            user_id: subject.id.clone(),
+            tenant_id: subject.tenant_id.clone(),
+            created_at: now,
+            expires_at: (now + duration).min(actor_session.expires_at),
+            last_active_at: now,
+            ip_address: actor_session.ip_address.clone(),
+            user_agent: actor_session.user_agent.clone(),
+            mfa_verified_at: None,
+            impersonation: Some(Impersonation {
+                actor_id: actor_session.user_id.clone(),
+                actor_session_id: actor_session_id.to_string(),
+                reason: reason.trim().to_string(),
+                started_at: now,
+            }),
+        };
+        self.sessions.save(&session)?;
+        
+        let actor_name = self.find_user_by_id(&actor_session.user_id)
+            .map(|actor| actor.username.clone())
+            .unwrap_or_else(|| actor_session.user_id.clone());
+        tracing::warn!("User {} started impersonating {} until {}", actor_name, subject.username, session.expires_at);
+        
+        self.record_impersonation(
+            &session,
+            actions::IMPERSONATION_START,
+            format!(
+                "Support engineer {} is acting on your behalf until {}. Reason: {}",
+                actor_name, session.expires_at.to_rfc3339(), reason.trim()
+            ),
+        ).await?;
+        
+        Ok(session)
+    }
+    
+    /// End an impersonation session before it expires
+    pub async fn end_impersonation(&mut self, session_id: &str) -> DreasResult<()> {
+        let session = self.sessions.get(session_id)?
+            .filter(|session| session.impersonation.is_some())
+            .ok_or_else(|| DreasError::Authentication("Not an impersonation session".to_string()))?;
+        
+        self.end_session(session_id)?;
+        tracing::info!("Impersonation session {} ended", session_id);
+        
+        self.record_impersonation(
+            &session,
+            actions::IMPERSONATION_END,
+            "The support session acting on your behalf has ended".to_string(),
+        ).await
+    }
+    
+    /// Start an audit entry for an action in a session, naming the support engineer when it is impersonated
+    pub fn session_audit_entry(
+        &self,
+        session_id: &str,
+        action: &str,
+        resource: &str,
+        result: AuditResult,
+    ) -> DreasResult<AuditEntry> {
+        let session = self.sessions.get(session_id)?
+            .ok_or_else(|| DreasError::Authentication("Invalid session".to_string()))?;
+        
+        Ok(Self::audit_entry_for(&session, action, resource, result))
+    }
+    
+    /// Build an audit entry attributed to a session's user and any impersonating actor
+    fn audit_entry_for(session: &UserSession, action: &str, resource: &str, result: AuditResult) -> AuditEntry {
+        let entry = AuditEntry::new(action, resource, result)
+            .with_user(&session.user_id)
+            .with_session(&session.session_id)
+            .with_tenant(session.tenant_id.clone());
+        
+        match &session.impersonation {
+            Some(impersonation) => entry
+                .with_actor(&impersonation.actor_id)
+                .with_metadata("impersonation_reason", &impersonation.reason),
+            None => entry,
+        }
+    }
+    
+    /// Audit an impersonation session starting or ending and notify its subject
+    async fn record_impersonation(&self, session: &UserSession, action: &str, message: String) -> DreasResult<()> {
+        let username = self.find_user_by_id(&session.user_id)
+            .map(|user| user.username.clone())
+            .unwrap_or_else(|| session.user_id.clone());
+        
+        let entry = Self::audit_entry_for(session, action, &format!("user:{}", username), AuditResult::Success)
+            .with_metadata("expires_at", session.expires_at.to_rfc3339());
+        self.audit_logger.record(entry).await?;
+        
+        self.notifier.notify(&UserNotification::new(&session.user_id, "Support access to your account", message))
+    }
+    
+    /// Get user by session ID
+    pub fn get_user_by_session(&self, session_id: &str) -> DreasResult<Option<User>> {
+        Ok(self.active_session(session_id)?
+            .and_then(|session| self.find_user_by_id(&session.user_id).cloned()))
+    }
+    
+    /// Get the tenant a user or service account belongs to
+    pub fn principal_tenant(&self, principal: &Principal) -> Option<TenantId> {
+        match principal {
+            Principal::User { user_id, .. } => self.find_user_by_id(user_id).map(|user| user.tenant_id.clone()),
+            Principal::ServiceAccount { account_id, .. } => self.service_accounts.get(account_id)
+                .and_then(service_account_tenant),
+        }
+    }
+    
+    /// Describe an authenticated principal for the policy engine, with its roles, tenant and attributes
+    ///
+    /// A principal whose user or service account no longer exists is described from its credentials alone.
+    pub fn principal_entity(&self, principal: &Principal) -> PolicyEntity {
+        let credentials = principal.to_policy_entity();
+        let entity = match principal {
+            Principal::User { user_id, .. } => self.find_user_by_id(user_id)
+                .map(|user| self.user_entity(user)),
+            Principal::ServiceAccount { account_id, .. } => self.service_accounts.get(account_id)
+                .map(|account| self.service_account_entity(account)),
+        };
+        
+        match entity {
+            Some(entity) => entity.with_attributes(&credentials.attributes),
+            None => credentials,
+        }
+    }
+    
+    /// Describe a user for the policy engine
+    fn user_entity(&self, user: &User) -> PolicyEntity {
+        PolicyEntity::new("user", &user.id)
+            .with_roles(self.expand_roles(&user.roles))
+            .with_attribute("username", user.username.clone())
+            .with_attributes(&user.attributes)
+            .with_attribute(TENANT_ATTRIBUTE, user.tenant_id.as_str())
+    }
+    
+    /// Describe a service account for the policy engine; accounts with no tenant belong to the default one
+    fn service_account_entity(&self, account: &ServiceAccount) -> PolicyEntity {
+        let tenant_id = service_account_tenant(account).unwrap_or_default();
+        PolicyEntity::new("service_account", &account.id)
+            .with_roles(self.expand_roles(&account.roles))
+            .with_attribute("name", account.name.clone())
+            .with_attributes(&account.attributes)
+            .with_attribute(TENANT_ATTRIBUTE, tenant_id.as_str())
+    }
+    
+    /// Remove a session and revoke its refresh tokens
+    fn end_session(&mut self, session_id: &str) -> DreasResult<()> {
+        self.sessions.remove(session_id)?;
+        
+        if let Some(token_service) = self.token_service.as_mut() {
+            token_service.revoke_session(session_id);
+        }
+        
+        Ok(())
+    }
+    
+    /// Look up a user by ID
+    fn find_user_by_id(&self, user_id: &str) -> Option<&User> {
+        self.users.values().find(|user| user.id == user_id)
+    }
+}
+
+impl Principal {
+    /// Get the user or service account ID
+    pub fn id(&self) -> &str {
+        match self {
+            Principal::User { user_id, .. } => user_id,
+            Principal::ServiceAccount { account_id, .. } => account_id,
+        }
+    }
+    
+    /// Describe the principal for the policy engine from its credentials alone
+    ///
+    /// Use `IdentityManager::principal_entity` to include its tenant and attributes.
+    pub fn to_policy_entity(&self) -> PolicyEntity {
+        match self {
+            Principal::User { user_id, roles, .. } => PolicyEntity::new("user", user_id)
+                .with_roles(roles.clone()),
+            Principal::ServiceAccount { account_id, key_id, scopes } => PolicyEntity::new("service_account", account_id)
+                .with_attribute("key_id", key_id.clone())
+                .with_attribute("scopes", scopes.clone()),
+        }
+    }
+}
+
+/// Get the tenant a service account is assigned to
+fn service_account_tenant(account: &ServiceAccount) -> Option<TenantId> {
+    account.attributes.get(TENANT_ATTRIBUTE)
+        .and_then(|tenant| tenant.as_str())
+        .and_then(|tenant| TenantId::new(tenant).ok())
+}
+
+/// Build an attempt tracker policy from lockout configuration
+fn lockout_policy(config: &LockoutConfig, max_failures: u32) -> LockoutPolicy {
+    LockoutPolicy {
+        max_failures,
+        free_failures: config.free_failures,
+        failure_window: chrono::Duration::seconds(config.failure_window_seconds),
+        lockout_duration: chrono::Duration::seconds(config.lockout_seconds),
+        base_delay: chrono::Duration::milliseconds(config.base_delay_ms),
+        max_delay: chrono::Duration::seconds(config.max_delay_seconds),
     }
 }
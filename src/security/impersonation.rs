@@ -0,0 +1,129 @@
+//! Audited impersonation for support staff
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Support engineers sometimes act on behalf of a user to reproduce an issue.
+//! Impersonation sessions are time-boxed and need a written reason; they record
+//! both the engineer (actor) and the user (subject), and the subject is notified
+//! when one starts and ends.
+
+use crate::{DreasResult, DreasError};
+use crate::config::ImpersonationConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+/// Permission needed to impersonate another user
+pub const IMPERSONATE_PERMISSION: &str = "identity:impersonate";
+
+/// Who is acting through an impersonation session, and why
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Impersonation {
+    /// User ID of the support engineer
+    pub actor_id: String,
+    /// The engineer's own session; impersonation ends with it
+    pub actor_session_id: String,
+    pub reason: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Limits on impersonation requests
+#[derive(Debug, Clone)]
+pub struct ImpersonationPolicy {
+    pub max_duration: Duration,
+    pub min_reason_length: usize,
+}
+
+/// Message telling a user about activity on their account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserNotification {
+    pub notification_id: Uuid,
+    pub user_id: String,
+    pub subject: String,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Delivery channel for user notifications, e.g. email or an in-app inbox
+pub trait UserNotifier: std::fmt::Debug + Send + Sync {
+    /// Deliver a notification to its user
+    fn notify(&self, notification: &UserNotification) -> DreasResult<()>;
+}
+
+/// Notifier keeping undelivered notifications in memory until the user's client collects them
+#[derive(Debug, Default)]
+pub struct InMemoryNotifier {
+    inbox: RwLock<HashMap<String, Vec<UserNotification>>>,
+}
+
+impl ImpersonationPolicy {
+    /// Build an impersonation policy from configuration
+    pub fn from_config(config: &ImpersonationConfig) -> Self {
+        Self {
+            max_duration: Duration::minutes(config.max_duration_minutes),
+            min_reason_length: config.min_reason_length,
+        }
+    }
+    
+    /// Check the reason and duration of an impersonation request
+    pub fn validate(&self, reason: &str, duration: Duration) -> DreasResult<()> {
+        if reason.trim().chars().count() < self.min_reason_length {
+            return Err(DreasError::Authentication(format!(
+                "Impersonation reason must be at least {} characters", self.min_reason_length
+            )));
+        }
+        
+        if duration <= Duration::zero() || duration > self.max_duration {
+            return Err(DreasError::Authentication(format!(
+                "Impersonation duration must be between 1 and {} minutes", self.max_duration.num_minutes()
+            )));
+        }
+        
+        Ok(())
+    }
+}
+
+impl Default for ImpersonationPolicy {
+    fn default() -> Self {
+        Self::from_config(&ImpersonationConfig::default())
+    }
+}
+
+impl UserNotification {
+    /// Create a notification for a user
+    pub fn new(user_id: &str, subject: &str, message: String) -> Self {
+        Self {
+            notification_id: Uuid::new_v4(),
+            user_id: user_id.to_string(),
+            subject: subject.to_string(),
+            message,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+impl InMemoryNotifier {
+    /// Create an empty notifier
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Remove and return a user's pending notifications, oldest first
+    pub fn take_for_user(&self, user_id: &str) -> DreasResult<Vec<UserNotification>> {
+        let mut inbox = self.inbox.write()
+            .map_err(|_| DreasError::Storage("Notification inbox lock poisoned".to_string()))?;
+        Ok(inbox.remove(user_id).unwrap_or_default())
+    }
+}
+
+impl UserNotifier for InMemoryNotifier {
+    fn notify(&self, notification: &UserNotification) -> DreasResult<()> {
+        let mut inbox = self.inbox.write()
+            .map_err(|_| DreasError::Storage("Notification inbox lock poisoned".to_string()))?;
+        inbox.entry(notification.user_id.clone()).or_default().push(notification.clone());
+        Ok(())
+    }
+}
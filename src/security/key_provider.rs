@@ -0,0 +1,128 @@
+//! Key providers for sealing agent payloads
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Prompt and response agents seal what they handle with the key named in their
+//! `AgentContext`. A `KeyProvider` resolves that name to a key and does the
+//! cryptography: `KmsKeyProvider` hands the work to Cloud KMS, and `LocalKeyProvider`
+//! holds AES-256-GCM keys in memory for development and tests.
+
+use crate::{DreasResult, DreasError};
+use super::kms::KmsClient;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use async_trait::async_trait;
+use rand::{rngs::OsRng, RngCore};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Length of the nonce preceding each `LocalKeyProvider` ciphertext
+const NONCE_LENGTH: usize = 12;
+
+/// Encrypts and decrypts data under named keys
+#[async_trait]
+pub trait KeyProvider: std::fmt::Debug + Send + Sync {
+    /// Seal plaintext under the key
+    async fn encrypt(&self, key_id: &str, plaintext: &[u8]) -> DreasResult<Vec<u8>>;
+    
+    /// Open ciphertext sealed under the key
+    async fn decrypt(&self, key_id: &str, ciphertext: &[u8]) -> DreasResult<Vec<u8>>;
+}
+
+/// Key provider backed by Cloud KMS
+///
+/// Key IDs are key version resource names in the provider client's key ring. Each is used
+/// through a client derived from the provider's, so that client's access policies, tenant and
+/// audit log apply to every call.
+#[derive(Debug, Clone)]
+pub struct KmsKeyProvider {
+    kms_client: KmsClient,
+}
+
+/// Key provider holding AES-256-GCM keys in memory
+///
+/// Ciphertexts are a random nonce followed by the sealed data and its tag.
+#[derive(Clone, Default)]
+pub struct LocalKeyProvider {
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl KmsKeyProvider {
+    /// Create a provider using the client's settings for every key
+    pub fn new(kms_client: KmsClient) -> Self {
+        Self { kms_client }
+    }
+}
+
+#[async_trait]
+impl KeyProvider for KmsKeyProvider {
+    async fn encrypt(&self, key_id: &str, plaintext: &[u8]) -> DreasResult<Vec<u8>> {
+        let result = self.kms_client.for_key_version(key_id)?.encrypt(plaintext).await?;
+        Ok(result.ciphertext)
+    }
+    
+    async fn decrypt(&self, key_id: &str, ciphertext: &[u8]) -> DreasResult<Vec<u8>> {
+        let result = self.kms_client.for_key_version(key_id)?.decrypt(ciphertext).await?;
+        Ok(result.plaintext)
+    }
+}
+
+impl LocalKeyProvider {
+    /// Create a provider without keys
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Add a 256-bit key under an ID
+    pub fn with_key(mut self, key_id: impl Into<String>, key: &[u8; 32]) -> Self {
+        self.keys.insert(key_id.into(), Aes256Gcm::new(key.into()));
+        self
+    }
+    
+    /// Add a randomly generated key under an ID
+    pub fn with_generated_key(self, key_id: impl Into<String>) -> Self {
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        self.with_key(key_id, &key)
+    }
+    
+    /// Look up the cipher for a key
+    fn cipher(&self, key_id: &str) -> DreasResult<&Aes256Gcm> {
+        self.keys.get(key_id)
+            .ok_or_else(|| DreasError::Configuration(format!("Unknown encryption key {}", key_id)))
+    }
+}
+
+#[async_trait]
+impl KeyProvider for LocalKeyProvider {
+    async fn encrypt(&self, key_id: &str, plaintext: &[u8]) -> DreasResult<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        
+        let sealed = self.cipher(key_id)?
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| DreasError::KmsEncryption(format!("Failed to encrypt with key {}", key_id)))?;
+        
+        Ok([nonce.as_slice(), &sealed].concat())
+    }
+    
+    async fn decrypt(&self, key_id: &str, ciphertext: &[u8]) -> DreasResult<Vec<u8>> {
+        let cipher = self.cipher(key_id)?;
+        if ciphertext.len() < NONCE_LENGTH {
+            return Err(DreasError::KmsDecryption("Ciphertext is too short".to_string()));
+        }
+        
+        let (nonce, sealed) = ciphertext.split_at(NONCE_LENGTH);
+        cipher.decrypt(Nonce::from_slice(nonce), sealed)
+            .map_err(|_| DreasError::KmsDecryption(format!("Ciphertext could not be opened with key {}", key_id)))
+    }
+}
+
+impl fmt::Debug for LocalKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalKeyProvider")
+            .field("keys", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
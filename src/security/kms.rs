@@ -7,8 +7,26 @@
 //! Google Cloud KMS with HSM-backed keys for enterprise-grade security.
 
 use crate::{DreasResult, DreasError};
+use super::audit::{actions, AuditEntry, AuditHandle, AuditLogger, AuditResult};
+use super::policy::{PolicyEngine, PolicyEntity, PolicyRequest};
+use super::request_context::RequestContext;
+use super::tenant::{tenant_of, TenantId};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Cloud KMS REST API base URL
+const KMS_API_BASE: &str = "https://cloudkms.googleapis.com/v1";
+
+/// GCE metadata server endpoint for service account access tokens
+pub(crate) const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// How long a KMS or token request may take before it fails
+const REQUEST_TIMEOUT_SECONDS: u64 = 30;
 
 /// KMS client for encryption and decryption operations
 #[derive(Debug, Clone)]
@@ -20,6 +38,15 @@ pub struct KmsClient {
     key_version: String,
     // In a real implementation, this would hold the actual KMS client
     client_data: HashMap<String, String>,
+    http_client: reqwest::Client,
+    /// Base URL of the Cloud KMS REST API
+    api_base: String,
+    /// Endpoint access tokens are obtained from
+    token_url: String,
+    policy_engine: Option<Arc<PolicyEngine>>,
+    /// Tenant whose key namespace this client uses; `None` for deployment-wide keys
+    tenant_id: Option<TenantId>,
+    audit_logger: AuditHandle,
 }
 
 /// Encryption result containing the encrypted data and metadata
@@ -55,21 +82,170 @@ impl KmsClient {
             key_name,
             key_version,
             client_data: HashMap::new(),
+            http_client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECONDS))
+                .build()
+                .expect("Failed to initialise the HTTP client"),
+            api_base: KMS_API_BASE.to_string(),
+            token_url: METADATA_TOKEN_URL.to_string(),
+            policy_engine: None,
+            tenant_id: None,
+            audit_logger: AuditHandle::detached(),
+        }
+    }
+    
+    /// Create a KMS client from a full key version resource name
+    pub fn from_key_version_uri(uri: &str) -> DreasResult<Self> {
+        let parts: Vec<&str> = uri.split('/').collect();
+        
+        match parts.as_slice() {
+            ["projects", project_id, "locations", location, "keyRings", key_ring,
+             "cryptoKeys", key_name, "cryptoKeyVersions", key_version] => Ok(Self::new(
+                project_id.to_string(),
+                location.to_string(),
+                key_ring.to_string(),
+                key_name.to_string(),
+                key_version.to_string(),
+            )),
+            _ => Err(DreasError::Configuration(format!("Invalid KMS key version URI: {}", uri))),
+        }
+    }
+    
+    /// Call another KMS API base URL and token endpoint, e.g. a regional endpoint or an emulator
+    pub fn with_endpoints(mut self, api_base: impl Into<String>, token_url: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self.token_url = token_url.into();
+        self
+    }
+    
+    /// Evaluate access policies before each use of this key
+    pub fn with_policy_engine(mut self, policy_engine: Arc<PolicyEngine>) -> Self {
+        self.policy_engine = Some(policy_engine);
+        self
+    }
+    
+    /// Record every encrypt, decrypt and sign call in the audit log
+    ///
+    /// Don't attach the logger to the client that signs its own checkpoints: the logger is
+    /// locked while a checkpoint is signed.
+    pub fn with_audit_logger(mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> Self {
+        self.audit_logger = AuditHandle::new(audit_logger);
+        self
+    }
+    
+    /// Get a client for the same key in a tenant's own key ring
+    ///
+    /// Encrypt and decrypt calls made while handling a request check the caller may use the
+    /// key, so a principal of another tenant needs an explicit permit.
+    pub fn for_tenant(&self, tenant_id: TenantId) -> Self {
+        Self {
+            tenant_id: Some(tenant_id),
+            ..self.clone()
         }
     }
     
+    /// Get a client for another key version, keeping this client's policies, tenant and audit log
+    ///
+    /// The key must live in this client's key ring, named either as the deployment's key ring or
+    /// as the tenant's own; keys of other projects, locations, key rings or tenants are refused.
+    pub fn for_key_version(&self, uri: &str) -> DreasResult<Self> {
+        let key = Self::from_key_version_uri(uri)?;
+        let key_ring = self.tenant_id.as_ref()
+            .map(|tenant_id| tenant_id.key_ring(&self.key_ring));
+        let in_namespace = key.project_id == self.project_id
+            && key.location == self.location
+            && (key.key_ring == self.key_ring || key_ring.as_ref() == Some(&key.key_ring));
+        if !in_namespace {
+            return Err(DreasError::Authentication(format!(
+                "KMS key {} is outside the key ring of {}", uri, self.get_key_ring_id()
+            )));
+        }
+        
+        Ok(Self {
+            key_name: key.key_name,
+            key_version: key.key_version,
+            ..self.clone()
+        })
+    }
+    
+    /// Get the tenant whose key namespace this client uses
+    pub fn tenant_id(&self) -> Option<&TenantId> {
+        self.tenant_id.as_ref()
+    }
+    
+    /// Check that a principal may use this key, e.g. for action `kms:decrypt`
+    ///
+    /// Principals from another tenant than the key's need an explicit `permit` policy.
+    pub async fn check_usage(&self, request: PolicyRequest) -> DreasResult<()> {
+        let cross_tenant = self.tenant_id.as_ref()
+            .is_some_and(|tenant_id| tenant_of(&request.principal).as_ref() != Some(tenant_id));
+        
+        let policy_engine = match &self.policy_engine {
+            Some(policy_engine) => policy_engine,
+            None if cross_tenant => return Err(self.cross_tenant_denied()),
+            None => return Ok(()),
+        };
+        
+        let mut request = request
+            .with_context("kms_key", self.get_key_id())
+            .with_context("cross_tenant", cross_tenant);
+        if let Some(tenant_id) = &self.tenant_id {
+            request = request.with_context("kms_tenant", tenant_id.as_str());
+        }
+        let decision = policy_engine.evaluate(&request).await?;
+        
+        if decision.is_forbidden() {
+            return Err(DreasError::Authentication(format!(
+                "KMS key use denied by policy {}", decision.forbidden_by.join(", ")
+            )));
+        }
+        
+        if cross_tenant && !decision.is_permitted() {
+            return Err(self.cross_tenant_denied());
+        }
+        
+        Ok(())
+    }
+    
+    /// Build the error for a cross-tenant key use no policy permits
+    fn cross_tenant_denied(&self) -> DreasError {
+        DreasError::Authentication(format!(
+            "Cross-tenant use of KMS key {} is not permitted", self.get_key_id()
+        ))
+    }
+    
+    /// Check the caller of the current request may use this key, auditing a refusal
+    ///
+    /// Work outside any request is the service's own and isn't checked.
+    async fn authorize(&self, action: &str, audit_action: &str, input_bytes: usize) -> DreasResult<()> {
+        let principal = match RequestContext::current().and_then(|context| context.caller()) {
+            Some(principal) => principal,
+            None => return Ok(()),
+        };
+        
+        let request = PolicyRequest::new(principal, action, PolicyEntity::new("kms_key", &self.get_key_id()));
+        if let Err(e) = self.check_usage(request).await {
+            self.audit_key_use(audit_action, AuditResult::Failure, input_bytes, Some(&e)).await?;
+            return Err(e);
+        }
+        
+        Ok(())
+    }
+    
     /// Encrypt data using KMS
     pub async fn encrypt(&self, plaintext: &[u8]) -> DreasResult<EncryptionResult> {
-        // TODO: Implement actual KMS encryption
-        // This is a placeholder implementation
-        let key_id = self.get_key_id();
+        self.authorize("kms:encrypt", actions::DATA_ENCRYPTION, plaintext.len()).await?;
         
-        // Simulate encryption by base64 encoding (NOT secure, just for structure)
-        let ciphertext = base64::encode(plaintext);
+        let ciphertext = self.request_encryption(plaintext).await;
+        
+        match &ciphertext {
+            Ok(_) => self.audit_key_use(actions::DATA_ENCRYPTION, AuditResult::Success, plaintext.len(), None).await?,
+            Err(e) => self.audit_key_use(actions::DATA_ENCRYPTION, AuditResult::Failure, plaintext.len(), Some(e)).await?,
+        }
         
         Ok(EncryptionResult {
-            ciphertext: ciphertext.as_bytes().to_vec(),
-            key_id,
+            ciphertext: ciphertext?,
+            key_id: self.get_key_id(),
             algorithm: "GOOGLE_SYMMETRIC_ENCRYPTION".to_string(),
             timestamp: chrono::Utc::now(),
         })
@@ -77,30 +253,171 @@ impl KmsClient {
     
     /// Decrypt data using KMS
     pub async fn decrypt(&self, ciphertext: &[u8]) -> DreasResult<DecryptionResult> {
-        // TODO: Implement actual KMS decryption
-        // This is a placeholder implementation
-        let key_id = self.get_key_id();
+        self.authorize("kms:decrypt", actions::DATA_DECRYPTION, ciphertext.len()).await?;
         
-        // Simulate decryption by base64 decoding (NOT secure, just for structure)
-        let ciphertext_str = String::from_utf8(ciphertext.to_vec())
-            .map_err(|e| DreasError::KmsDecryption(format!("Invalid ciphertext: {}", e)))?;
+        let plaintext = self.request_decryption(ciphertext).await;
         
-        let plaintext = base64::decode(&ciphertext_str)
-            .map_err(|e| DreasError::KmsDecryption(format!("Failed to decode ciphertext: {}", e)))?;
+        match &plaintext {
+            Ok(_) => self.audit_key_use(actions::DATA_DECRYPTION, AuditResult::Success, ciphertext.len(), None).await?,
+            Err(e) => self.audit_key_use(actions::DATA_DECRYPTION, AuditResult::Failure, ciphertext.len(), Some(e)).await?,
+        }
         
         Ok(DecryptionResult {
-            plaintext,
-            key_id,
+            plaintext: plaintext?,
+            key_id: self.get_key_id(),
             timestamp: chrono::Utc::now(),
         })
     }
     
+    /// Call the encrypt API with the configured key version
+    async fn request_encryption(&self, plaintext: &[u8]) -> DreasResult<Vec<u8>> {
+        let url = format!("{}/{}:encrypt", self.api_base, self.get_key_id());
+        let token = self.access_token().await?;
+        
+        let response: serde_json::Value = self.http_client
+            .post(&url)
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "plaintext": STANDARD.encode(plaintext) }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DreasError::KmsEncryption(format!("encrypt request failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| DreasError::KmsEncryption(format!("Invalid encrypt response: {}", e)))?;
+        
+        let ciphertext = response["ciphertext"].as_str()
+            .ok_or_else(|| DreasError::KmsEncryption("encrypt response missing ciphertext".to_string()))?;
+        
+        STANDARD.decode(ciphertext)
+            .map_err(|e| DreasError::KmsEncryption(format!("Failed to decode ciphertext: {}", e)))
+    }
+    
+    /// Call the decrypt API, which picks the key version from the ciphertext
+    async fn request_decryption(&self, ciphertext: &[u8]) -> DreasResult<Vec<u8>> {
+        let url = format!("{}/{}:decrypt", self.api_base, self.get_crypto_key_id());
+        let token = self.access_token().await?;
+        
+        let response: serde_json::Value = self.http_client
+            .post(&url)
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "ciphertext": STANDARD.encode(ciphertext) }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DreasError::KmsDecryption(format!("decrypt request failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| DreasError::KmsDecryption(format!("Invalid decrypt response: {}", e)))?;
+        
+        // An empty plaintext is left out of the response
+        let plaintext = response["plaintext"].as_str().unwrap_or_default();
+        
+        STANDARD.decode(plaintext)
+            .map_err(|e| DreasError::KmsDecryption(format!("Failed to decode plaintext: {}", e)))
+    }
+    
+    /// Sign data with the configured asymmetric key version (EC_SIGN_ED25519)
+    pub async fn asymmetric_sign(&self, data: &[u8]) -> DreasResult<Vec<u8>> {
+        let signature = self.request_signature(data).await;
+        
+        match &signature {
+            Ok(_) => self.audit_key_use(actions::DATA_SIGNING, AuditResult::Success, data.len(), None).await?,
+            Err(e) => self.audit_key_use(actions::DATA_SIGNING, AuditResult::Failure, data.len(), Some(e)).await?,
+        }
+        
+        signature
+    }
+    
+    /// Call the asymmetricSign API
+    async fn request_signature(&self, data: &[u8]) -> DreasResult<Vec<u8>> {
+        let url = format!("{}/{}:asymmetricSign", self.api_base, self.get_key_id());
+        let token = self.access_token().await?;
+        
+        let response: serde_json::Value = self.http_client
+            .post(&url)
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "data": STANDARD.encode(data) }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DreasError::KmsSigning(format!("asymmetricSign request failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| DreasError::KmsSigning(format!("Invalid asymmetricSign response: {}", e)))?;
+        
+        let signature = response["signature"].as_str()
+            .ok_or_else(|| DreasError::KmsSigning("asymmetricSign response missing signature".to_string()))?;
+        
+        STANDARD.decode(signature)
+            .map_err(|e| DreasError::KmsSigning(format!("Failed to decode signature: {}", e)))
+    }
+    
+    /// Fetch the PEM-encoded public key of the configured asymmetric key version
+    pub async fn get_public_key(&self) -> DreasResult<String> {
+        let url = format!("{}/{}/publicKey", self.api_base, self.get_key_id());
+        let token = self.access_token().await?;
+        
+        let response: serde_json::Value = self.http_client
+            .get(&url)
+            .bearer_auth(token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DreasError::KmsSigning(format!("getPublicKey request failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| DreasError::KmsSigning(format!("Invalid getPublicKey response: {}", e)))?;
+        
+        response["pem"].as_str()
+            .map(|pem| pem.to_string())
+            .ok_or_else(|| DreasError::KmsSigning("getPublicKey response missing pem".to_string()))
+    }
+    
+    /// Record a use of this key in the audit log
+    async fn audit_key_use(
+        &self,
+        action: &str,
+        result: AuditResult,
+        input_bytes: usize,
+        error: Option<&DreasError>,
+    ) -> DreasResult<()> {
+        let mut entry = AuditEntry::new(action, format!("kms_key:{}", self.get_key_id()), result)
+            .with_metadata("input_bytes", input_bytes.to_string());
+        if let Some(tenant_id) = &self.tenant_id {
+            entry = entry.with_tenant(tenant_id.clone());
+        }
+        if let Some(error) = error {
+            entry = entry.with_metadata("error", error.to_string());
+        }
+        
+        self.audit_logger.record(entry).await
+    }
+    
+    /// Obtain an OAuth access token for the attached service account
+    async fn access_token(&self) -> DreasResult<String> {
+        service_account_token(&self.http_client, &self.token_url).await
+    }
+    
     /// Get the full key ID for this KMS client
     fn get_key_id(&self) -> String {
-        format!(
-            "projects/{}/locations/{}/keyRings/{}/cryptoKeys/{}/cryptoKeyVersions/{}",
-            self.project_id, self.location, self.key_ring, self.key_name, self.key_version
-        )
+        format!("{}/cryptoKeyVersions/{}", self.get_crypto_key_id(), self.key_version)
+    }
+    
+    /// Get the resource name of the key, without its version
+    fn get_crypto_key_id(&self) -> String {
+        format!("{}/cryptoKeys/{}", self.get_key_ring_id(), self.key_name)
+    }
+    
+    /// Get the resource name of the key ring this client's keys live in
+    fn get_key_ring_id(&self) -> String {
+        // Each tenant's keys live in a key ring of their own
+        let key_ring = match &self.tenant_id {
+            Some(tenant_id) => tenant_id.key_ring(&self.key_ring),
+            None => self.key_ring.clone(),
+        };
+        
+        format!("projects/{}/locations/{}/keyRings/{}", self.project_id, self.location, key_ring)
     }
     
     /// Validate KMS configuration
@@ -130,11 +447,9 @@ impl KmsClient {
     
     /// Test KMS connectivity
     pub async fn test_connection(&self) -> DreasResult<()> {
-        // TODO: Implement actual KMS connectivity test
-        // For now, just validate the configuration
         self.validate_config()?;
         
-        // Simulate a test encryption/decryption cycle
+        // Round-trip test data through the key
         let test_data = b"test data";
         let encrypted = self.encrypt(test_data).await?;
         let decrypted = self.decrypt(&encrypted.ciphertext).await?;
@@ -146,3 +461,21 @@ impl KmsClient {
         Ok(())
     }
 }
+
+/// Obtain an OAuth access token for the attached service account from a metadata server endpoint
+pub(crate) async fn service_account_token(http_client: &reqwest::Client, token_url: &str) -> DreasResult<String> {
+    let response: serde_json::Value = http_client
+        .get(token_url)
+        .header("Metadata-Flavor", "Google")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| DreasError::Authentication(format!("Failed to obtain access token: {}", e)))?
+        .json()
+        .await
+        .map_err(|e| DreasError::Authentication(format!("Invalid access token response: {}", e)))?;
+    
+    response["access_token"].as_str()
+        .map(|token| token.to_string())
+        .ok_or_else(|| DreasError::Authentication("Access token response missing access_token".to_string()))
+}
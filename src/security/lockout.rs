@@ -0,0 +1,196 @@
+//! Account lockout and brute-force protection
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module tracks failed login attempts per key (a username or a source IP),
+//! enforcing exponential backoff between attempts and a temporary lockout once
+//! too many attempts fail within a window.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use chrono::{DateTime, Duration, Utc};
+
+/// Thresholds for one attempt tracker
+#[derive(Debug, Clone)]
+pub struct LockoutPolicy {
+    pub max_failures: u32,
+    /// Failures allowed before backoff starts, so a single typo isn't penalised
+    pub free_failures: u32,
+    pub failure_window: Duration,
+    pub lockout_duration: Duration,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+/// Failed attempts recorded against a single key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttemptRecord {
+    pub failures: u32,
+    pub window_started_at: DateTime<Utc>,
+    pub last_failure_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+/// Whether a key may attempt to log in right now
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ThrottleStatus {
+    Allowed,
+    Delayed(DateTime<Utc>),
+    Locked(DateTime<Utc>),
+}
+
+/// Failed-attempt tracker for one kind of key
+#[derive(Debug, Clone)]
+pub struct AttemptTracker {
+    policy: LockoutPolicy,
+    attempts: HashMap<String, AttemptRecord>,
+}
+
+/// Sliding-window counter of failures across all keys, for spotting spikes
+#[derive(Debug, Clone)]
+pub struct FailureRateMonitor {
+    window: Duration,
+    failures: VecDeque<DateTime<Utc>>,
+}
+
+impl ThrottleStatus {
+    /// When the next attempt will be accepted, if it is being held back
+    pub fn retry_after(&self) -> Option<DateTime<Utc>> {
+        match self {
+            ThrottleStatus::Allowed => None,
+            ThrottleStatus::Delayed(until) | ThrottleStatus::Locked(until) => Some(*until),
+        }
+    }
+}
+
+impl AttemptTracker {
+    /// Create a new attempt tracker
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self {
+            policy,
+            attempts: HashMap::new(),
+        }
+    }
+    
+    /// Check whether a key may attempt to log in
+    pub fn status(&self, key: &str, now: DateTime<Utc>) -> ThrottleStatus {
+        let record = match self.active_record(key, now) {
+            Some(record) => record,
+            None => return ThrottleStatus::Allowed,
+        };
+        
+        if let Some(locked_until) = record.locked_until {
+            return ThrottleStatus::Locked(locked_until);
+        }
+        
+        let next_attempt_at = record.last_failure_at + self.delay_after(record.failures);
+        if now < next_attempt_at {
+            ThrottleStatus::Delayed(next_attempt_at)
+        } else {
+            ThrottleStatus::Allowed
+        }
+    }
+    
+    /// Record a failed attempt, locking the key once it reaches the limit
+    pub fn record_failure(&mut self, key: &str, now: DateTime<Utc>) -> ThrottleStatus {
+        // Start over once a lockout or the counting window has expired
+        if self.active_record(key, now).is_none() {
+            self.attempts.remove(key);
+        }
+        
+        let record = self.attempts.entry(key.to_string()).or_insert(AttemptRecord {
+            failures: 0,
+            window_started_at: now,
+            last_failure_at: now,
+            locked_until: None,
+        });
+        
+        record.failures += 1;
+        record.last_failure_at = now;
+        
+        if record.failures >= self.policy.max_failures {
+            record.locked_until = Some(now + self.policy.lockout_duration);
+        }
+        
+        self.status(key, now)
+    }
+    
+    /// Clear a key's failures after a successful login
+    pub fn record_success(&mut self, key: &str) {
+        self.attempts.remove(key);
+    }
+    
+    /// Lift a lockout early; returns whether the key had any recorded failures
+    pub fn unlock(&mut self, key: &str) -> bool {
+        self.attempts.remove(key).is_some()
+    }
+    
+    /// Get the recorded failures for a key
+    pub fn record(&self, key: &str) -> Option<&AttemptRecord> {
+        self.attempts.get(key)
+    }
+    
+    /// Drop records whose lockout or window has expired
+    pub fn cleanup(&mut self, now: DateTime<Utc>) -> usize {
+        let initial_count = self.attempts.len();
+        let expired: Vec<String> = self.attempts.keys()
+            .filter(|key| self.active_record(key, now).is_none())
+            .cloned()
+            .collect();
+        
+        for key in expired {
+            self.attempts.remove(&key);
+        }
+        
+        initial_count - self.attempts.len()
+    }
+    
+    /// Get a record that still counts, i.e. its lockout or window hasn't expired
+    fn active_record(&self, key: &str, now: DateTime<Utc>) -> Option<&AttemptRecord> {
+        self.attempts.get(key).filter(|record| match record.locked_until {
+            Some(locked_until) => now < locked_until,
+            None => now - record.window_started_at < self.policy.failure_window,
+        })
+    }
+    
+    /// Exponential backoff after the given number of failures
+    fn delay_after(&self, failures: u32) -> Duration {
+        if failures <= self.policy.free_failures {
+            return Duration::zero();
+        }
+        
+        // Capping the exponent keeps the multiplier well inside i32
+        let multiplier = 1i32 << (failures - self.policy.free_failures - 1).min(20);
+        self.policy.base_delay
+            .checked_mul(multiplier)
+            .unwrap_or(self.policy.max_delay)
+            .min(self.policy.max_delay)
+    }
+}
+
+impl FailureRateMonitor {
+    /// Create a new monitor over the given window
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            failures: VecDeque::new(),
+        }
+    }
+    
+    /// Record a failure and return the number of failures in the current window
+    pub fn record(&mut self, now: DateTime<Utc>) -> usize {
+        self.failures.push_back(now);
+        
+        while self.failures.front().is_some_and(|failure| now - *failure > self.window) {
+            self.failures.pop_front();
+        }
+        
+        self.failures.len()
+    }
+    
+    /// Get the monitoring window
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+}
@@ -0,0 +1,374 @@
+//! Merkle tree checkpoints for the audit log
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Audit entries are the leaves of an RFC 6962 Merkle tree. Signed tree heads commit
+//! to the whole log at a point in time; inclusion proofs show an entry is in a tree
+//! head, and consistency proofs show a later tree head extends an earlier one, so an
+//! external auditor can check history was never rewritten without seeing the log.
+
+use crate::{DreasResult, DreasError};
+use super::audit::AuditEntry;
+use super::token::verify_signature;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+/// File signed tree heads are appended to, beside the audit entries they commit to
+pub const CHECKPOINTS_FILE: &str = "checkpoints.jsonl";
+
+/// Domain separation prefix of leaf hashes (RFC 6962 section 2.1)
+const LEAF_PREFIX: u8 = 0x00;
+
+/// Domain separation prefix of interior node hashes
+const NODE_PREFIX: u8 = 0x01;
+
+/// Version tag at the start of every signed tree head payload
+const TREE_HEAD_PAYLOAD_VERSION: &str = "dreas-audit-tree-head/v1";
+
+/// SHA-256 node of a Merkle tree
+pub type MerkleHash = [u8; 32];
+
+/// Signed commitment to the first `tree_size` entries of an audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedTreeHead {
+    pub log_id: Uuid,
+    pub tree_size: u64,
+    /// Hex-encoded Merkle tree hash
+    pub root_hash: String,
+    pub timestamp: DateTime<Utc>,
+    /// ID of the key that signed the tree head
+    pub key_id: String,
+    /// Base64-encoded Ed25519 signature over `signing_payload`
+    pub signature: String,
+}
+
+/// Proof that an entry is a leaf of a tree of the given size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    /// Hex-encoded sibling hashes from the leaf up to the root
+    pub audit_path: Vec<String>,
+}
+
+/// Proof that a tree is an append-only extension of a smaller one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsistencyProof {
+    pub old_size: u64,
+    pub new_size: u64,
+    /// Hex-encoded hashes as defined by RFC 6962 section 2.1.2
+    pub proof: Vec<String>,
+}
+
+impl SignedTreeHead {
+    /// Bytes covered by the signature
+    pub fn signing_payload(log_id: &Uuid, tree_size: u64, root_hash: &str, timestamp: &DateTime<Utc>) -> Vec<u8> {
+        format!(
+            "{}\n{}\n{}\n{}\n{}",
+            TREE_HEAD_PAYLOAD_VERSION, log_id, tree_size, timestamp.timestamp_millis(), root_hash
+        ).into_bytes()
+    }
+    
+    /// Check the tree head was signed by the given key
+    pub fn verify_signature(&self, verifying_key: &VerifyingKey) -> DreasResult<()> {
+        let payload = Self::signing_payload(&self.log_id, self.tree_size, &self.root_hash, &self.timestamp);
+        verify_signature(verifying_key, &payload, &self.signature, "tree head")
+    }
+    
+    /// Decode the root hash
+    pub fn root(&self) -> DreasResult<MerkleHash> {
+        decode_hash(&self.root_hash)
+    }
+    
+    /// Check the tree head is of the given log and commits to the first leaves of it
+    pub fn commits_to(&self, log_id: &Uuid, leaves: &[MerkleHash]) -> bool {
+        let tree_size = self.tree_size as usize;
+        self.log_id == *log_id
+            && tree_size <= leaves.len()
+            && self.root_hash == hex::encode(root_hash(&leaves[..tree_size]))
+    }
+    
+    /// Append the tree head to a file of JSON lines, synced before returning
+    pub fn append_to<P: AsRef<Path>>(&self, path: P) -> DreasResult<()> {
+        let path = path.as_ref();
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        
+        OpenOptions::new().create(true).append(true).open(path)
+            .and_then(|mut file| file.write_all(&line).and_then(|_| file.sync_data()))
+            .map_err(|e| DreasError::Storage(format!(
+                "Failed to record tree head at size {} in {}: {}", self.tree_size, path.display(), e
+            )))
+    }
+    
+    /// Read every tree head appended to a file, none if it doesn't exist yet
+    pub fn read_all<P: AsRef<Path>>(path: P) -> DreasResult<Vec<Self>> {
+        let file = match File::open(path.as_ref()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        
+        let mut tree_heads = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                tree_heads.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(tree_heads)
+    }
+}
+
+impl InclusionProof {
+    /// Check the entry is a leaf of the tree committed to by the tree head
+    ///
+    /// The entry's own chain hash is recomputed, so a modified entry fails to verify.
+    pub fn verify(&self, entry: &AuditEntry, tree_head: &SignedTreeHead) -> DreasResult<()> {
+        if self.tree_size != tree_head.tree_size {
+            return Err(DreasError::Authentication(format!(
+                "Inclusion proof is for tree size {}, not {}", self.tree_size, tree_head.tree_size
+            )));
+        }
+        if entry.sequence != self.leaf_index + 1 {
+            return Err(DreasError::Authentication(format!(
+                "Entry sequence {} doesn't match leaf index {}", entry.sequence, self.leaf_index
+            )));
+        }
+        if entry.hash != entry.compute_hash()? {
+            return Err(DreasError::Authentication("Entry contents don't match its hash".to_string()));
+        }
+        
+        let path = self.audit_path.iter().map(|hash| decode_hash(hash)).collect::<DreasResult<Vec<_>>>()?;
+        let root = root_from_inclusion_path(entry_leaf_hash(entry)?, self.leaf_index, self.tree_size, &path)
+            .ok_or_else(|| DreasError::Authentication("Malformed inclusion proof".to_string()))?;
+        
+        if root != tree_head.root()? {
+            return Err(DreasError::Authentication("Inclusion proof doesn't match the tree head".to_string()));
+        }
+        
+        Ok(())
+    }
+}
+
+impl ConsistencyProof {
+    /// Check the newer tree head extends the older one without rewriting it
+    pub fn verify(&self, old_head: &SignedTreeHead, new_head: &SignedTreeHead) -> DreasResult<()> {
+        if old_head.log_id != new_head.log_id {
+            return Err(DreasError::Authentication("Tree heads belong to different logs".to_string()));
+        }
+        if self.old_size != old_head.tree_size || self.new_size != new_head.tree_size {
+            return Err(DreasError::Authentication(format!(
+                "Consistency proof is for sizes {} to {}, not {} to {}",
+                self.old_size, self.new_size, old_head.tree_size, new_head.tree_size
+            )));
+        }
+        
+        let proof = self.proof.iter().map(|hash| decode_hash(hash)).collect::<DreasResult<Vec<_>>>()?;
+        if !verify_consistency(self.old_size, self.new_size, &old_head.root()?, &new_head.root()?, &proof) {
+            return Err(DreasError::Authentication("History was rewritten between the tree heads".to_string()));
+        }
+        
+        Ok(())
+    }
+}
+
+/// Hash an audit entry as a Merkle leaf, committing to its chain hash
+pub fn entry_leaf_hash(entry: &AuditEntry) -> DreasResult<MerkleHash> {
+    let chain_hash = hex::decode(&entry.hash)
+        .map_err(|e| DreasError::Authentication(format!("Invalid audit entry hash: {}", e)))?;
+    Ok(leaf_hash(&chain_hash))
+}
+
+/// Hash leaf data with the leaf prefix
+pub fn leaf_hash(data: &[u8]) -> MerkleHash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Hash two child nodes with the node prefix
+fn node_hash(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Largest power of two smaller than `n`, for `n > 1`
+fn split_point(n: usize) -> usize {
+    let mut k = 1;
+    while k << 1 < n {
+        k <<= 1;
+    }
+    k
+}
+
+/// Merkle tree hash over leaf hashes
+pub fn root_hash(leaves: &[MerkleHash]) -> MerkleHash {
+    match leaves.len() {
+        0 => Sha256::digest(b"").into(),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&root_hash(&leaves[..k]), &root_hash(&leaves[k..]))
+        }
+    }
+}
+
+/// Audit path of the leaf at `index` (RFC 6962 section 2.1.1)
+pub fn inclusion_path(leaves: &[MerkleHash], index: usize) -> Vec<MerkleHash> {
+    let n = leaves.len();
+    if n <= 1 {
+        return Vec::new();
+    }
+    
+    let k = split_point(n);
+    if index < k {
+        let mut path = inclusion_path(&leaves[..k], index);
+        path.push(root_hash(&leaves[k..]));
+        path
+    } else {
+        let mut path = inclusion_path(&leaves[k..], index - k);
+        path.push(root_hash(&leaves[..k]));
+        path
+    }
+}
+
+/// Consistency proof between the first `old_size` leaves and all leaves (RFC 6962 section 2.1.2)
+pub fn consistency_path(leaves: &[MerkleHash], old_size: usize) -> Vec<MerkleHash> {
+    if old_size == 0 || old_size >= leaves.len() {
+        return Vec::new();
+    }
+    subproof(old_size, leaves, true)
+}
+
+/// SUBPROOF from RFC 6962 section 2.1.2
+fn subproof(m: usize, leaves: &[MerkleHash], complete_subtree: bool) -> Vec<MerkleHash> {
+    let n = leaves.len();
+    if m == n {
+        return if complete_subtree { Vec::new() } else { vec![root_hash(leaves)] };
+    }
+    
+    let k = split_point(n);
+    if m <= k {
+        let mut proof = subproof(m, &leaves[..k], complete_subtree);
+        proof.push(root_hash(&leaves[k..]));
+        proof
+    } else {
+        let mut proof = subproof(m - k, &leaves[k..], false);
+        proof.push(root_hash(&leaves[..k]));
+        proof
+    }
+}
+
+/// Compute the root implied by an inclusion path (RFC 9162 section 2.1.3.2)
+pub fn root_from_inclusion_path(
+    leaf: MerkleHash,
+    index: u64,
+    tree_size: u64,
+    path: &[MerkleHash],
+) -> Option<MerkleHash> {
+    if index >= tree_size {
+        return None;
+    }
+    
+    let mut f_n = index;
+    let mut s_n = tree_size - 1;
+    let mut root = leaf;
+    
+    for sibling in path {
+        if s_n == 0 {
+            return None;
+        }
+        if f_n & 1 == 1 || f_n == s_n {
+            root = node_hash(sibling, &root);
+            while f_n & 1 == 0 && f_n != 0 {
+                f_n >>= 1;
+                s_n >>= 1;
+            }
+        } else {
+            root = node_hash(&root, sibling);
+        }
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+    
+    (s_n == 0).then_some(root)
+}
+
+/// Check a consistency proof between two tree hashes (RFC 9162 section 2.1.4.2)
+pub fn verify_consistency(
+    old_size: u64,
+    new_size: u64,
+    old_root: &MerkleHash,
+    new_root: &MerkleHash,
+    proof: &[MerkleHash],
+) -> bool {
+    if old_size > new_size {
+        return false;
+    }
+    if old_size == new_size {
+        return proof.is_empty() && old_root == new_root;
+    }
+    if old_size == 0 {
+        // Every tree extends the empty tree
+        return proof.is_empty();
+    }
+    if proof.is_empty() {
+        return false;
+    }
+    
+    // A complete old tree is its own first node in the proof
+    let mut path: Vec<MerkleHash> = Vec::with_capacity(proof.len() + 1);
+    if old_size.is_power_of_two() {
+        path.push(*old_root);
+    }
+    path.extend_from_slice(proof);
+    
+    let mut f_n = old_size - 1;
+    let mut s_n = new_size - 1;
+    while f_n & 1 == 1 {
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+    
+    let mut old_hash = path[0];
+    let mut new_hash = path[0];
+    for node in &path[1..] {
+        if s_n == 0 {
+            return false;
+        }
+        if f_n & 1 == 1 || f_n == s_n {
+            old_hash = node_hash(node, &old_hash);
+            new_hash = node_hash(node, &new_hash);
+            while f_n & 1 == 0 && f_n != 0 {
+                f_n >>= 1;
+                s_n >>= 1;
+            }
+        } else {
+            new_hash = node_hash(&new_hash, node);
+        }
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+    
+    s_n == 0 && &old_hash == old_root && &new_hash == new_root
+}
+
+/// Decode a hex-encoded Merkle hash
+fn decode_hash(hash: &str) -> DreasResult<MerkleHash> {
+    hex::decode(hash)
+        .map_err(|e| DreasError::Authentication(format!("Invalid Merkle hash: {}", e)))?
+        .try_into()
+        .map_err(|_| DreasError::Authentication("Merkle hash must be 32 bytes".to_string()))
+}
@@ -0,0 +1,233 @@
+//! TOTP second factor and recovery codes
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module implements RFC 6238 time-based one-time passwords for step-up
+//! authentication, along with single-use recovery codes for lost authenticators.
+
+use crate::{DreasResult, DreasError};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use chrono::{DateTime, Utc};
+
+/// Length of generated TOTP secrets in bytes (RFC 4226 recommends 160 bits)
+const SECRET_LENGTH: usize = 20;
+
+/// Alphabet for recovery codes, without easily confused characters
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// TOTP code generator and verifier
+#[derive(Debug, Clone)]
+pub struct TotpAuthenticator {
+    issuer: String,
+    digits: u32,
+    step_seconds: u64,
+    skew_steps: u64,
+}
+
+/// A user's second-factor enrollment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaEnrollment {
+    pub secret: Vec<u8>,
+    pub confirmed: bool,
+    pub enrolled_at: DateTime<Utc>,
+    pub last_used_step: Option<u64>,
+    pub recovery_code_hashes: Vec<String>,
+}
+
+/// Details shown to the user when they start TOTP enrollment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+impl TotpAuthenticator {
+    /// Create a new TOTP authenticator
+    pub fn new(issuer: String, digits: u32, step_seconds: u64, skew_steps: u64) -> DreasResult<Self> {
+        if !(6..=8).contains(&digits) {
+            return Err(DreasError::Configuration("TOTP codes must have 6 to 8 digits".to_string()));
+        }
+        
+        if step_seconds == 0 {
+            return Err(DreasError::Configuration("TOTP time step must be positive".to_string()));
+        }
+        
+        Ok(Self {
+            issuer,
+            digits,
+            step_seconds,
+            skew_steps,
+        })
+    }
+    
+    /// Generate a new random shared secret
+    pub fn generate_secret(&self) -> Vec<u8> {
+        let mut secret = vec![0u8; SECRET_LENGTH];
+        OsRng.fill_bytes(&mut secret);
+        secret
+    }
+    
+    /// Build the otpauth:// URI that authenticator apps import from a QR code
+    pub fn provisioning_uri(&self, account: &str, secret: &[u8]) -> DreasResult<String> {
+        let mut url = reqwest::Url::parse("otpauth://totp/")
+            .map_err(|e| DreasError::Generic(format!("Invalid provisioning URI: {}", e)))?;
+        
+        url.set_path(&format!("{}:{}", self.issuer, account));
+        url.query_pairs_mut()
+            .append_pair("secret", &BASE32_NOPAD.encode(secret))
+            .append_pair("issuer", &self.issuer)
+            .append_pair("algorithm", "SHA1")
+            .append_pair("digits", &self.digits.to_string())
+            .append_pair("period", &self.step_seconds.to_string());
+        
+        Ok(url.to_string())
+    }
+    
+    /// Compute the code for a given time step
+    pub fn code_at_step(&self, secret: &[u8], step: u64) -> DreasResult<String> {
+        let mut mac = Hmac::<Sha1>::new_from_slice(secret)
+            .map_err(|e| DreasError::Authentication(format!("Invalid TOTP secret: {}", e)))?;
+        mac.update(&step.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        
+        // Dynamic truncation per RFC 4226 section 5.3
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        
+        Ok(format!(
+            "{:0width$}",
+            binary % 10u32.pow(self.digits),
+            width = self.digits as usize,
+        ))
+    }
+    
+    /// Compute the current code for a secret
+    pub fn code_at(&self, secret: &[u8], time: DateTime<Utc>) -> DreasResult<String> {
+        self.code_at_step(secret, self.step_for(time))
+    }
+    
+    /// Verify a code, returning the matched time step
+    ///
+    /// Codes at or before `last_used_step` are rejected so a code can't be replayed
+    /// within its validity window.
+    pub fn verify(
+        &self,
+        secret: &[u8],
+        code: &str,
+        time: DateTime<Utc>,
+        last_used_step: Option<u64>,
+    ) -> DreasResult<Option<u64>> {
+        let code = code.trim();
+        
+        if code.len() != self.digits as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(None);
+        }
+        
+        let current = self.step_for(time);
+        let mut matched = None;
+        
+        // Check every step in the window so timing doesn't reveal which one matched
+        for step in current.saturating_sub(self.skew_steps)..=current + self.skew_steps {
+            let expected = self.code_at_step(secret, step)?;
+            let is_match: bool = expected.as_bytes().ct_eq(code.as_bytes()).into();
+            
+            if is_match && last_used_step.is_none_or(|last| step > last) {
+                matched = Some(step);
+            }
+        }
+        
+        Ok(matched)
+    }
+    
+    /// Convert a timestamp into a TOTP time step
+    fn step_for(&self, time: DateTime<Utc>) -> u64 {
+        time.timestamp().max(0) as u64 / self.step_seconds
+    }
+}
+
+impl Default for TotpAuthenticator {
+    fn default() -> Self {
+        Self {
+            issuer: "DREAS".to_string(),
+            digits: 6,
+            step_seconds: 30,
+            skew_steps: 1,
+        }
+    }
+}
+
+impl MfaEnrollment {
+    /// Start a new, unconfirmed enrollment
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self {
+            secret,
+            confirmed: false,
+            enrolled_at: Utc::now(),
+            last_used_step: None,
+            recovery_code_hashes: Vec::new(),
+        }
+    }
+    
+    /// Replace the recovery codes, returning the new plaintext codes once
+    pub fn regenerate_recovery_codes(&mut self, count: usize) -> Vec<String> {
+        let codes: Vec<String> = (0..count).map(|_| generate_recovery_code()).collect();
+        self.recovery_code_hashes = codes.iter().map(|code| hash_recovery_code(code)).collect();
+        codes
+    }
+    
+    /// Consume a recovery code if it matches one that hasn't been used
+    pub fn consume_recovery_code(&mut self, code: &str) -> bool {
+        let candidate = hash_recovery_code(code);
+        let position = self.recovery_code_hashes.iter()
+            .position(|stored| bool::from(stored.as_bytes().ct_eq(candidate.as_bytes())));
+        
+        match position {
+            Some(index) => {
+                self.recovery_code_hashes.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Number of recovery codes left
+    pub fn remaining_recovery_codes(&self) -> usize {
+        self.recovery_code_hashes.len()
+    }
+}
+
+/// Generate a recovery code formatted as two groups of five characters
+fn generate_recovery_code() -> String {
+    let mut rng = OsRng;
+    let chars: String = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+    
+    format!("{}-{}", &chars[..5], &chars[5..])
+}
+
+/// Hash a recovery code for storage
+///
+/// Recovery codes carry about 50 bits of entropy and are single-use, so a fast
+/// hash is sufficient; normalization lets users type them without the dash.
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
@@ -10,8 +10,32 @@ pub mod kms;
 pub mod escrow;
 pub mod identity;
 pub mod audit;
+pub mod password;
+pub mod token;
+pub mod oidc;
+pub mod mfa;
+pub mod service_account;
+pub mod permission;
+pub mod policy;
+pub mod lockout;
+pub mod session;
+pub mod tenant;
+pub mod impersonation;
+pub mod merkle;
+pub mod audit_sink;
+pub mod request_context;
+pub mod siem;
+pub mod audit_query;
+pub mod retention;
+pub mod audit_report;
+pub mod redaction;
+pub mod audit_stream;
+pub mod anomaly;
+pub mod key_provider;
 
 pub use kms::KmsClient;
 pub use escrow::KeyEscrow;
 pub use identity::IdentityManager;
-pub use audit::AuditLogger;
+pub use audit::{AuditHandle, AuditLogger};
+pub use tenant::TenantId;
+pub use request_context::RequestContext;
@@ -0,0 +1,447 @@
+//! OpenID Connect relying-party login
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module implements the OIDC authorization-code flow with PKCE against the
+//! corporate identity provider, including discovery, JWKS caching and ID token validation.
+
+use crate::{DreasResult, DreasError};
+use crate::config::OidcConfig;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use rand::{rngs::OsRng, RngCore};
+use rsa::signature::Verifier;
+use rsa::{BigUint, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// How long a started login may take before its state is discarded
+const PENDING_LOGIN_TTL_MINUTES: i64 = 10;
+
+/// Minimum interval between JWKS refetches triggered by unknown key IDs
+const JWKS_MIN_REFRESH_SECONDS: i64 = 60;
+
+/// Allowed clock skew when validating token timestamps
+const CLOCK_SKEW_SECONDS: i64 = 60;
+
+/// OIDC client for a single identity provider
+#[derive(Debug, Clone)]
+pub struct OidcClient {
+    config: OidcConfig,
+    metadata: ProviderMetadata,
+    http_client: reqwest::Client,
+    jwks_cache: JwksCache,
+    pending_logins: HashMap<String, PendingLogin>,
+}
+
+/// Provider metadata from the discovery document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// Authorization request the user agent should be redirected to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+}
+
+/// Validated ID token claims
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: OneOrMany,
+    pub exp: i64,
+    pub iat: i64,
+    pub nonce: Option<String>,
+    pub azp: Option<String>,
+    #[serde(flatten)]
+    pub additional_claims: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Claim value that may be a single string or an array of strings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// State kept between redirecting to the IdP and handling its callback
+#[derive(Debug, Clone)]
+struct PendingLogin {
+    code_verifier: String,
+    nonce: String,
+    created_at: DateTime<Utc>,
+}
+
+/// Cached provider signing keys
+#[derive(Debug, Clone, Default)]
+struct JwksCache {
+    keys: HashMap<String, ProviderKey>,
+    fetched_at: Option<DateTime<Utc>>,
+}
+
+/// Provider signing key
+#[derive(Debug, Clone)]
+enum ProviderKey {
+    Rs256(rsa::pkcs1v15::VerifyingKey<Sha256>),
+    EdDsa(ed25519_dalek::VerifyingKey),
+}
+
+/// Token endpoint response
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// JWS header of an ID token
+#[derive(Debug, Deserialize)]
+struct IdTokenHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+impl OneOrMany {
+    /// Check whether the value is or contains the given string
+    pub fn contains(&self, value: &str) -> bool {
+        match self {
+            OneOrMany::One(single) => single == value,
+            OneOrMany::Many(values) => values.iter().any(|v| v == value),
+        }
+    }
+    
+    /// Number of values
+    pub fn count(&self) -> usize {
+        match self {
+            OneOrMany::One(_) => 1,
+            OneOrMany::Many(values) => values.len(),
+        }
+    }
+}
+
+impl IdTokenClaims {
+    /// Get the string values of a claim, whether it is a string or an array
+    pub fn claim_values(&self, name: &str) -> Vec<String> {
+        match self.additional_claims.get(name) {
+            Some(serde_json::Value::String(value)) => vec![value.clone()],
+            Some(serde_json::Value::Array(values)) => values
+                .iter()
+                .filter_map(|value| value.as_str().map(|s| s.to_string()))
+                .collect(),
+            _ if name == "sub" => vec![self.sub.clone()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl OidcClient {
+    /// Create a client by fetching the provider's discovery document
+    pub async fn discover(config: OidcConfig) -> DreasResult<Self> {
+        let http_client = reqwest::Client::new();
+        let discovery_url = format!(
+            "{}/.well-known/openid-configuration",
+            config.issuer_url.trim_end_matches('/')
+        );
+        
+        let metadata: ProviderMetadata = http_client
+            .get(&discovery_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DreasError::Authentication(format!("OIDC discovery failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| DreasError::Authentication(format!("Invalid OIDC discovery document: {}", e)))?;
+        
+        if metadata.issuer.trim_end_matches('/') != config.issuer_url.trim_end_matches('/') {
+            return Err(DreasError::Authentication(format!(
+                "OIDC issuer mismatch: expected {}, provider reported {}",
+                config.issuer_url, metadata.issuer
+            )));
+        }
+        
+        let mut client = Self {
+            config,
+            metadata,
+            http_client,
+            jwks_cache: JwksCache::default(),
+            pending_logins: HashMap::new(),
+        };
+        
+        client.refresh_jwks().await?;
+        
+        tracing::info!("OIDC provider discovered: {}", client.metadata.issuer);
+        Ok(client)
+    }
+    
+    /// Start a login, returning the authorization URL to redirect the user to
+    pub fn begin_login(&mut self) -> DreasResult<AuthorizationRequest> {
+        self.pending_logins.retain(|_, pending| {
+            Utc::now() - pending.created_at < Duration::minutes(PENDING_LOGIN_TTL_MINUTES)
+        });
+        
+        let state = random_token();
+        let nonce = random_token();
+        let code_verifier = random_token();
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+        
+        let mut url = reqwest::Url::parse(&self.metadata.authorization_endpoint)
+            .map_err(|e| DreasError::Configuration(format!("Invalid authorization endpoint: {}", e)))?;
+        
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_uri)
+            .append_pair("scope", &self.config.scopes.join(" "))
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        
+        self.pending_logins.insert(state.clone(), PendingLogin {
+            code_verifier,
+            nonce,
+            created_at: Utc::now(),
+        });
+        
+        Ok(AuthorizationRequest {
+            url: url.to_string(),
+            state,
+        })
+    }
+    
+    /// Complete a login from the IdP callback, returning the validated ID token claims
+    pub async fn complete_login(&mut self, state: &str, code: &str) -> DreasResult<IdTokenClaims> {
+        // State is single-use whether or not the exchange succeeds
+        let pending = self.pending_logins.remove(state)
+            .ok_or_else(|| DreasError::Authentication("Unknown or expired login state".to_string()))?;
+        
+        if Utc::now() - pending.created_at >= Duration::minutes(PENDING_LOGIN_TTL_MINUTES) {
+            return Err(DreasError::Authentication("Login attempt expired".to_string()));
+        }
+        
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_uri.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", pending.code_verifier.as_str()),
+        ];
+        
+        if let Some(client_secret) = &self.config.client_secret {
+            form.push(("client_secret", client_secret.as_str()));
+        }
+        
+        let token_response: TokenResponse = self.http_client
+            .post(&self.metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DreasError::Authentication(format!("OIDC token exchange failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| DreasError::Authentication(format!("Invalid OIDC token response: {}", e)))?;
+        
+        self.validate_id_token(&token_response.id_token, &pending.nonce).await
+    }
+    
+    /// Validate an ID token's signature and claims
+    async fn validate_id_token(&mut self, id_token: &str, expected_nonce: &str) -> DreasResult<IdTokenClaims> {
+        let mut parts = id_token.split('.');
+        let (header, claims, signature) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(header), Some(claims), Some(signature), None) => (header, claims, signature),
+            _ => return Err(DreasError::Authentication("Malformed ID token".to_string())),
+        };
+        
+        let signing_input = &id_token[..header.len() + 1 + claims.len()];
+        let header: IdTokenHeader = decode_segment(header)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature)
+            .map_err(|_| DreasError::Authentication("Malformed ID token signature".to_string()))?;
+        
+        let kid = header.kid
+            .ok_or_else(|| DreasError::Authentication("ID token header missing kid".to_string()))?;
+        
+        // Providers rotate keys, so an unknown kid triggers a rate-limited refetch
+        let unknown_kid = !self.jwks_cache.keys.contains_key(&kid);
+        let can_refetch = self.jwks_cache.fetched_at
+            .is_none_or(|fetched| Utc::now() - fetched >= Duration::seconds(JWKS_MIN_REFRESH_SECONDS));
+        
+        if self.jwks_expired() || (unknown_kid && can_refetch) {
+            self.refresh_jwks().await?;
+        }
+        
+        let key = self.jwks_cache.keys.get(&kid)
+            .ok_or_else(|| DreasError::Authentication(format!("Unknown ID token signing key: {}", kid)))?;
+        
+        let signature_valid = match (header.alg.as_str(), key) {
+            ("RS256", ProviderKey::Rs256(key)) => rsa::pkcs1v15::Signature::try_from(signature.as_slice())
+                .map(|signature| key.verify(signing_input.as_bytes(), &signature).is_ok())
+                .unwrap_or(false),
+            ("EdDSA", ProviderKey::EdDsa(key)) => ed25519_dalek::Signature::from_slice(&signature)
+                .map(|signature| key.verify_strict(signing_input.as_bytes(), &signature).is_ok())
+                .unwrap_or(false),
+            _ => {
+                return Err(DreasError::Authentication(format!(
+                    "ID token algorithm {} does not match signing key", header.alg
+                )));
+            }
+        };
+        
+        if !signature_valid {
+            return Err(DreasError::Authentication("Invalid ID token signature".to_string()));
+        }
+        
+        let claims: IdTokenClaims = decode_segment(claims)?;
+        let now = Utc::now().timestamp();
+        
+        if claims.iss != self.metadata.issuer {
+            return Err(DreasError::Authentication("ID token issuer mismatch".to_string()));
+        }
+        
+        if !claims.aud.contains(&self.config.client_id) {
+            return Err(DreasError::Authentication("ID token audience mismatch".to_string()));
+        }
+        
+        if claims.aud.count() > 1 && claims.azp.as_deref() != Some(self.config.client_id.as_str()) {
+            return Err(DreasError::Authentication("ID token authorized party mismatch".to_string()));
+        }
+        
+        if claims.exp + CLOCK_SKEW_SECONDS < now {
+            return Err(DreasError::Authentication("ID token expired".to_string()));
+        }
+        
+        if claims.iat - CLOCK_SKEW_SECONDS > now {
+            return Err(DreasError::Authentication("ID token issued in the future".to_string()));
+        }
+        
+        if claims.nonce.as_deref() != Some(expected_nonce) {
+            return Err(DreasError::Authentication("ID token nonce mismatch".to_string()));
+        }
+        
+        Ok(claims)
+    }
+    
+    /// Map ID token claims to DREAS role names
+    pub fn map_roles(&self, claims: &IdTokenClaims) -> Vec<String> {
+        let mut roles = self.config.default_roles.clone();
+        
+        for value in claims.claim_values(&self.config.role_claim) {
+            if let Some(mapped) = self.config.role_mappings.get(&value) {
+                for role in mapped {
+                    if !roles.contains(role) {
+                        roles.push(role.clone());
+                    }
+                }
+            }
+        }
+        
+        roles
+    }
+    
+    /// Get the username for the ID token's subject
+    pub fn username(&self, claims: &IdTokenClaims) -> String {
+        claims.claim_values(&self.config.username_claim)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| claims.sub.clone())
+    }
+    
+    /// Get the provider metadata
+    pub fn metadata(&self) -> &ProviderMetadata {
+        &self.metadata
+    }
+    
+    /// Check whether the cached JWKS has outlived its TTL
+    fn jwks_expired(&self) -> bool {
+        self.jwks_cache.fetched_at.is_none_or(|fetched| {
+            Utc::now() - fetched >= Duration::seconds(self.config.jwks_cache_ttl_seconds as i64)
+        })
+    }
+    
+    /// Fetch the provider's signing keys
+    async fn refresh_jwks(&mut self) -> DreasResult<()> {
+        let jwks: serde_json::Value = self.http_client
+            .get(&self.metadata.jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DreasError::Authentication(format!("Failed to fetch OIDC JWKS: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| DreasError::Authentication(format!("Invalid OIDC JWKS: {}", e)))?;
+        
+        let mut keys = HashMap::new();
+        
+        for jwk in jwks["keys"].as_array().into_iter().flatten() {
+            let kid = match jwk["kid"].as_str() {
+                Some(kid) => kid.to_string(),
+                None => continue,
+            };
+            
+            // Skip keys we can't use rather than failing the whole set
+            if jwk["use"].as_str().is_some_and(|key_use| key_use != "sig") {
+                continue;
+            }
+            
+            match parse_provider_key(jwk) {
+                Some(key) => {
+                    keys.insert(kid, key);
+                }
+                None => tracing::debug!("Ignoring unsupported OIDC signing key: {}", kid),
+            }
+        }
+        
+        tracing::debug!("Fetched {} OIDC signing keys", keys.len());
+        
+        self.jwks_cache = JwksCache {
+            keys,
+            fetched_at: Some(Utc::now()),
+        };
+        Ok(())
+    }
+}
+
+/// Parse an RSA or Ed25519 JWK into a verification key
+fn parse_provider_key(jwk: &serde_json::Value) -> Option<ProviderKey> {
+    let decode = |field: &str| jwk[field].as_str().and_then(|value| URL_SAFE_NO_PAD.decode(value).ok());
+    
+    match (jwk["kty"].as_str()?, jwk["crv"].as_str()) {
+        ("RSA", _) => {
+            let public_key = RsaPublicKey::new(
+                BigUint::from_bytes_be(&decode("n")?),
+                BigUint::from_bytes_be(&decode("e")?),
+            ).ok()?;
+            Some(ProviderKey::Rs256(rsa::pkcs1v15::VerifyingKey::new(public_key)))
+        }
+        ("OKP", Some("Ed25519")) => {
+            let bytes: [u8; 32] = decode("x")?.try_into().ok()?;
+            ed25519_dalek::VerifyingKey::from_bytes(&bytes).ok().map(ProviderKey::EdDsa)
+        }
+        _ => None,
+    }
+}
+
+/// Generate a random URL-safe value for state, nonce and PKCE verifiers
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Decode a base64url JSON token segment
+fn decode_segment<T: serde::de::DeserializeOwned>(segment: &str) -> DreasResult<T> {
+    let bytes = URL_SAFE_NO_PAD.decode(segment)
+        .map_err(|_| DreasError::Authentication("Malformed ID token".to_string()))?;
+    
+    serde_json::from_slice(&bytes)
+        .map_err(|_| DreasError::Authentication("Malformed ID token".to_string()))
+}
@@ -0,0 +1,208 @@
+//! Password hashing and password policy enforcement
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module provides Argon2id credential hashing with per-user salts and
+//! tunable cost parameters, plus the password policy applied when users are created.
+
+use crate::{DreasResult, DreasError};
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Argon2id cost parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PasswordHashParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+/// Argon2id hasher for user credentials
+#[derive(Debug, Clone)]
+pub struct CredentialHasher {
+    params: PasswordHashParams,
+    dummy_hash: OnceLock<String>,
+}
+
+/// Password policy applied to new credentials
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    denylist: HashSet<String>,
+}
+
+impl Default for PasswordHashParams {
+    fn default() -> Self {
+        // OWASP recommended minimum for Argon2id
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl CredentialHasher {
+    /// Create a new credential hasher
+    pub fn new(params: PasswordHashParams) -> DreasResult<Self> {
+        let hasher = Self {
+            params,
+            dummy_hash: OnceLock::new(),
+        };
+        
+        // Reject invalid parameters up front rather than on first login
+        hasher.argon2()?;
+        Ok(hasher)
+    }
+    
+    /// Hash a password with a fresh random salt, returning a PHC string
+    pub fn hash(&self, password: &str) -> DreasResult<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        
+        self.argon2()?
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| DreasError::Authentication(format!("Failed to hash password: {}", e)))
+    }
+    
+    /// Verify a password against a stored PHC string in constant time
+    pub fn verify(&self, password: &str, stored_hash: &str) -> DreasResult<bool> {
+        let parsed = PasswordHash::new(stored_hash)
+            .map_err(|e| DreasError::Authentication(format!("Invalid stored password hash: {}", e)))?;
+        
+        // Verify with the parameters recorded in the hash, not the current ones,
+        // so credentials keep working across parameter changes
+        Ok(Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+    }
+    
+    /// Perform a throwaway verification so unknown users take as long as known ones
+    pub fn verify_dummy(&self, password: &str) -> DreasResult<()> {
+        let dummy_hash = match self.dummy_hash.get() {
+            Some(hash) => hash,
+            None => {
+                let hash = self.hash(&uuid::Uuid::new_v4().to_string())?;
+                self.dummy_hash.get_or_init(|| hash)
+            }
+        };
+        
+        self.verify(password, dummy_hash)?;
+        Ok(())
+    }
+    
+    /// Check whether a stored hash was produced with outdated parameters
+    pub fn needs_rehash(&self, stored_hash: &str) -> bool {
+        let parsed = match PasswordHash::new(stored_hash) {
+            Ok(parsed) => parsed,
+            Err(_) => return true,
+        };
+        
+        if parsed.algorithm != Algorithm::Argon2id.ident() || parsed.version != Some(Version::V0x13.into()) {
+            return true;
+        }
+        
+        match Params::try_from(&parsed) {
+            Ok(stored) => {
+                stored.m_cost() != self.params.memory_kib
+                    || stored.t_cost() != self.params.iterations
+                    || stored.p_cost() != self.params.parallelism
+            }
+            Err(_) => true,
+        }
+    }
+    
+    /// Get the configured hashing parameters
+    pub fn params(&self) -> &PasswordHashParams {
+        &self.params
+    }
+    
+    /// Build an Argon2id instance for the configured parameters
+    fn argon2(&self) -> DreasResult<Argon2<'static>> {
+        let params = Params::new(
+            self.params.memory_kib,
+            self.params.iterations,
+            self.params.parallelism,
+            None,
+        )
+        .map_err(|e| DreasError::Configuration(format!("Invalid Argon2 parameters: {}", e)))?;
+        
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl PasswordPolicy {
+    /// Create a new password policy
+    pub fn new(min_length: usize, max_length: usize) -> DreasResult<Self> {
+        if min_length == 0 || min_length > max_length {
+            return Err(DreasError::Configuration(
+                "Password minimum length must be between 1 and the maximum length".to_string()
+            ));
+        }
+        
+        Ok(Self {
+            min_length,
+            max_length,
+            denylist: HashSet::new(),
+        })
+    }
+    
+    /// Load a breached-password denylist, one password per line
+    pub fn with_denylist_file<P: AsRef<Path>>(mut self, path: P) -> DreasResult<Self> {
+        let contents = std::fs::read_to_string(path.as_ref())?;
+        
+        self.denylist.extend(
+            contents
+                .lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| line.to_lowercase()),
+        );
+        
+        tracing::info!("Loaded {} denylisted passwords", self.denylist.len());
+        Ok(self)
+    }
+    
+    /// Validate a candidate password for the given username
+    pub fn validate(&self, username: &str, password: &str) -> DreasResult<()> {
+        let length = password.chars().count();
+        
+        if length < self.min_length {
+            return Err(DreasError::Authentication(
+                format!("Password must be at least {} characters", self.min_length)
+            ));
+        }
+        
+        if length > self.max_length {
+            return Err(DreasError::Authentication(
+                format!("Password must be at most {} characters", self.max_length)
+            ));
+        }
+        
+        let normalized = password.to_lowercase();
+        
+        if normalized == username.to_lowercase() {
+            return Err(DreasError::Authentication("Password cannot match the username".to_string()));
+        }
+        
+        if self.denylist.contains(&normalized) {
+            return Err(DreasError::Authentication("Password appears in a breached-password list".to_string()));
+        }
+        
+        Ok(())
+    }
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            denylist: HashSet::new(),
+        }
+    }
+}
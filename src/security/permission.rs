@@ -0,0 +1,92 @@
+//! Namespaced permissions and permission explanations
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Permissions are colon-separated namespaces such as `kms:encrypt` or
+//! `escrow:recover:tenant-42`. A `*` segment matches any single segment, and a
+//! trailing `*` matches one or more remaining segments, so `kms:*` covers every
+//! KMS permission.
+
+use crate::{DreasResult, DreasError};
+use serde::{Deserialize, Serialize};
+
+/// Separator between permission namespace segments
+pub const PERMISSION_SEPARATOR: char = ':';
+
+/// Wildcard segment
+pub const WILDCARD: &str = "*";
+
+/// Outcome of evaluating a permission against roles and grants
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PermissionDecision {
+    Allowed,
+    Denied,
+    NotGranted,
+}
+
+/// Why a permission was granted or denied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionExplanation {
+    pub permission: String,
+    pub decision: PermissionDecision,
+    /// Subject and roles walked to reach the deciding rule, e.g. `user:alice -> role:operator`
+    pub chain: Vec<String>,
+    /// The allow or deny rule that decided the outcome
+    pub rule: Option<String>,
+}
+
+impl PermissionExplanation {
+    /// Check whether the permission was granted
+    pub fn is_allowed(&self) -> bool {
+        self.decision == PermissionDecision::Allowed
+    }
+    
+    /// Human-readable reason for a refusal
+    pub fn reason(&self) -> Option<String> {
+        match self.decision {
+            PermissionDecision::Allowed => None,
+            PermissionDecision::Denied => Some(format!(
+                "Denied by {} via {}",
+                self.rule.as_deref().unwrap_or_default(),
+                self.chain.join(" -> ")
+            )),
+            PermissionDecision::NotGranted => Some("Insufficient permissions".to_string()),
+        }
+    }
+}
+
+/// Check that a permission or pattern is well formed
+pub fn validate_permission(pattern: &str) -> DreasResult<()> {
+    let malformed = pattern.split(PERMISSION_SEPARATOR).any(|segment| {
+        segment.is_empty() || (segment.contains('*') && segment != WILDCARD)
+    });
+    
+    if malformed {
+        return Err(DreasError::Configuration(format!(
+            "Invalid permission {}: segments must be non-empty and wildcards must be a whole segment",
+            pattern
+        )));
+    }
+    
+    Ok(())
+}
+
+/// Check whether a permission pattern covers a concrete permission
+pub fn permission_matches(pattern: &str, permission: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split(PERMISSION_SEPARATOR).collect();
+    let permission: Vec<&str> = permission.split(PERMISSION_SEPARATOR).collect();
+    
+    for (index, segment) in pattern.iter().enumerate() {
+        let is_last = index == pattern.len() - 1;
+        
+        match permission.get(index) {
+            // A trailing wildcard swallows everything that's left
+            Some(_) if is_last && *segment == WILDCARD => return true,
+            Some(actual) if *segment == WILDCARD || segment == actual => continue,
+            _ => return false,
+        }
+    }
+    
+    pattern.len() == permission.len()
+}
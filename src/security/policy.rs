@@ -0,0 +1,908 @@
+//! Attribute-based access control policy engine
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module evaluates a Cedar-like policy language over principal, action,
+//! resource and context attributes. Policies are loaded from files:
+//! 
+//! ```text
+//! @id("analyst-own-tenant")
+//! forbid (principal in "analyst", action == "kms:decrypt", resource is "prompt")
+//! unless {
+//!     resource.tenant == principal.tenant
+//!     && context.hour >= 9 && context.hour < 17
+//!     && in_cidr(context.ip, "10.0.0.0/8")
+//! };
+//! ```
+//! 
+//! A matching `forbid` always wins. A matching `permit` can grant access that
+//! roles alone do not. A `forbid` whose conditions fail to evaluate (for example
+//! because an attribute is missing) is treated as matching, so errors fail closed.
+
+use crate::{DreasResult, DreasError};
+use super::audit::{actions, AuditEntry, AuditHandle, AuditLogger, AuditResult};
+use super::permission;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use chrono::{Datelike, Timelike, Utc};
+
+/// File extension for policy files loaded from a directory
+pub const POLICY_FILE_EXTENSION: &str = "policy";
+
+/// Policy engine holding a parsed policy set
+#[derive(Debug, Clone)]
+pub struct PolicyEngine {
+    policies: Vec<Policy>,
+    audit_logger: AuditHandle,
+}
+
+/// A principal or resource with attributes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolicyEntity {
+    pub entity_type: String,
+    pub id: String,
+    pub roles: Vec<String>,
+    pub attributes: HashMap<String, Value>,
+}
+
+/// An authorization question put to the policy engine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyRequest {
+    pub principal: PolicyEntity,
+    pub action: String,
+    pub resource: PolicyEntity,
+    pub context: HashMap<String, Value>,
+}
+
+/// Policies that matched a request
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyDecision {
+    pub permitted_by: Vec<String>,
+    pub forbidden_by: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// Policy effect
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PolicyEffect {
+    Permit,
+    Forbid,
+}
+
+/// A single parsed policy
+#[derive(Debug, Clone)]
+pub struct Policy {
+    pub id: String,
+    pub effect: PolicyEffect,
+    principal: PrincipalScope,
+    action: ActionScope,
+    resource: ResourceScope,
+    conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone)]
+enum PrincipalScope {
+    Any,
+    Is(String),
+    In(String),
+}
+
+#[derive(Debug, Clone)]
+enum ActionScope {
+    Any,
+    In(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+enum ResourceScope {
+    Any,
+    Is(String),
+    Type(String),
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    When(Expr),
+    Unless(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Variable(String, Vec<String>),
+    List(Vec<Expr>),
+    Call(String, Vec<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    Has(Box<Expr>, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Like,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Symbol(&'static str),
+}
+
+impl PolicyEngine {
+    /// Create an engine from policy source text
+    pub fn parse(source: &str, origin: &str) -> DreasResult<Self> {
+        Ok(Self {
+            policies: Parser::new(source, origin)?.parse_policies()?,
+            audit_logger: AuditHandle::detached(),
+        })
+    }
+    
+    /// Load policies from a single file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> DreasResult<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)?;
+        Self::parse(&source, &path.display().to_string())
+    }
+    
+    /// Load every policy file in a directory, or a single file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> DreasResult<Self> {
+        let path = path.as_ref();
+        
+        if path.is_file() {
+            return Self::from_file(path);
+        }
+        
+        let mut files: Vec<_> = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.extension().is_some_and(|extension| extension == POLICY_FILE_EXTENSION))
+            .collect();
+        files.sort();
+        
+        let mut engine = Self::parse("", &path.display().to_string())?;
+        for file in files {
+            engine.policies.extend(Self::from_file(&file)?.policies);
+        }
+        
+        tracing::info!("Loaded {} policies from {}", engine.policies.len(), path.display());
+        Ok(engine)
+    }
+    
+    /// Log every decision to the audit log
+    pub fn with_audit_logger(mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> Self {
+        self.audit_logger = AuditHandle::new(audit_logger);
+        self
+    }
+    
+    /// Get the loaded policies
+    pub fn policies(&self) -> &[Policy] {
+        &self.policies
+    }
+    
+    /// Evaluate a request and record the decision in the audit log
+    pub async fn evaluate(&self, request: &PolicyRequest) -> DreasResult<PolicyDecision> {
+        let decision = self.decide(request);
+        
+        if self.audit_logger.logger().is_some() {
+            let mut metadata = HashMap::new();
+            metadata.insert("policy_action".to_string(), request.action.clone());
+            metadata.insert("principal_type".to_string(), request.principal.entity_type.clone());
+            metadata.insert("permitted_by".to_string(), decision.permitted_by.join(","));
+            metadata.insert("forbidden_by".to_string(), decision.forbidden_by.join(","));
+            if !decision.errors.is_empty() {
+                metadata.insert("errors".to_string(), decision.errors.join("; "));
+            }
+            
+            let result = if decision.is_forbidden() { AuditResult::Failure } else { AuditResult::Success };
+            
+            let resource = format!("{}:{}", request.resource.entity_type, request.resource.id);
+            let mut entry = AuditEntry::new(actions::POLICY_DECISION, resource, result).with_user(&request.principal.id);
+            entry.metadata = metadata;
+            if let Some(session_id) = request.context.get("session_id").and_then(|value| value.as_str()) {
+                entry = entry.with_session(session_id);
+            }
+            // Decisions made in an impersonation session are attributed to the support engineer too
+            if let Some(actor_id) = request.context.get("impersonated_by").and_then(|value| value.as_str()) {
+                entry = entry.with_actor(actor_id);
+            }
+            
+            self.audit_logger.record(entry).await?;
+        }
+        
+        Ok(decision)
+    }
+    
+    /// Evaluate a request against every policy
+    pub fn decide(&self, request: &PolicyRequest) -> PolicyDecision {
+        let mut decision = PolicyDecision::default();
+        
+        for policy in &self.policies {
+            if !policy.in_scope(request) {
+                continue;
+            }
+            
+            let matched = match policy.conditions_hold(request) {
+                Ok(matched) => matched,
+                Err(e) => {
+                    decision.errors.push(format!("{}: {}", policy.id, e));
+                    
+                    // Fail closed: a forbid we can't evaluate still forbids
+                    policy.effect == PolicyEffect::Forbid
+                }
+            };
+            
+            if matched {
+                match policy.effect {
+                    PolicyEffect::Permit => decision.permitted_by.push(policy.id.clone()),
+                    PolicyEffect::Forbid => decision.forbidden_by.push(policy.id.clone()),
+                }
+            }
+        }
+        
+        decision
+    }
+}
+
+impl PolicyDecision {
+    /// Check whether any forbid policy matched
+    pub fn is_forbidden(&self) -> bool {
+        !self.forbidden_by.is_empty()
+    }
+    
+    /// Check whether a permit policy matched and no forbid policy did
+    pub fn is_permitted(&self) -> bool {
+        !self.permitted_by.is_empty() && !self.is_forbidden()
+    }
+}
+
+impl PolicyEntity {
+    /// Create an entity with no roles or attributes
+    pub fn new(entity_type: &str, id: &str) -> Self {
+        Self {
+            entity_type: entity_type.to_string(),
+            id: id.to_string(),
+            roles: Vec::new(),
+            attributes: HashMap::new(),
+        }
+    }
+    
+    /// Set the entity's roles
+    pub fn with_roles(mut self, roles: Vec<String>) -> Self {
+        self.roles = roles;
+        self
+    }
+    
+    /// Add an attribute
+    pub fn with_attribute<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.attributes.insert(name.to_string(), value.into());
+        self
+    }
+    
+    /// Add several attributes
+    pub fn with_attributes(mut self, attributes: &HashMap<String, Value>) -> Self {
+        self.attributes.extend(attributes.iter().map(|(name, value)| (name.clone(), value.clone())));
+        self
+    }
+    
+    /// Look up an attribute, including the built-in `id`, `type` and `roles`
+    fn attribute(&self, name: &str) -> Option<Value> {
+        match name {
+            "id" => Some(Value::from(self.id.clone())),
+            "type" => Some(Value::from(self.entity_type.clone())),
+            "roles" => Some(Value::from(self.roles.clone())),
+            _ => self.attributes.get(name).cloned(),
+        }
+    }
+}
+
+impl PolicyRequest {
+    /// Create a request with the current time filled into the context
+    pub fn new(principal: PolicyEntity, action: &str, resource: PolicyEntity) -> Self {
+        let now = Utc::now();
+        let mut context = HashMap::new();
+        context.insert("time".to_string(), Value::from(now.to_rfc3339()));
+        context.insert("hour".to_string(), Value::from(now.hour()));
+        context.insert("weekday".to_string(), Value::from(now.weekday().to_string()));
+        
+        Self {
+            principal,
+            action: action.to_string(),
+            resource,
+            context,
+        }
+    }
+    
+    /// Add a context attribute, such as the client IP
+    pub fn with_context<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.context.insert(name.to_string(), value.into());
+        self
+    }
+}
+
+impl Policy {
+    /// Check the policy head against the request
+    fn in_scope(&self, request: &PolicyRequest) -> bool {
+        let principal = match &self.principal {
+            PrincipalScope::Any => true,
+            PrincipalScope::Is(id) => &request.principal.id == id,
+            PrincipalScope::In(role) => request.principal.roles.contains(role),
+        };
+        
+        let action = match &self.action {
+            ActionScope::Any => true,
+            ActionScope::In(patterns) => patterns.iter()
+                .any(|pattern| permission::permission_matches(pattern, &request.action)),
+        };
+        
+        let resource = match &self.resource {
+            ResourceScope::Any => true,
+            ResourceScope::Is(id) => &request.resource.id == id,
+            ResourceScope::Type(entity_type) => &request.resource.entity_type == entity_type,
+        };
+        
+        principal && action && resource
+    }
+    
+    /// Check the `when` and `unless` clauses
+    fn conditions_hold(&self, request: &PolicyRequest) -> Result<bool, String> {
+        for condition in &self.conditions {
+            let holds = match condition {
+                Condition::When(expr) => as_bool(&expr.evaluate(request)?)?,
+                Condition::Unless(expr) => !as_bool(&expr.evaluate(request)?)?,
+            };
+            
+            if !holds {
+                return Ok(false);
+            }
+        }
+        
+        Ok(true)
+    }
+}
+
+impl Expr {
+    /// Evaluate an expression; missing attributes and type mismatches are errors
+    fn evaluate(&self, request: &PolicyRequest) -> Result<Value, String> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Variable(name, path) => {
+                let (first, rest) = match path.split_first() {
+                    Some((first, rest)) => (Some(first.as_str()), rest),
+                    None => (None, path.as_slice()),
+                };
+                
+                let mut value = match (name.as_str(), first) {
+                    ("action", None) => Value::from(request.action.clone()),
+                    ("principal", Some(attribute)) => request.principal.attribute(attribute)
+                        .ok_or_else(|| format!("principal has no attribute {}", attribute))?,
+                    ("resource", Some(attribute)) => request.resource.attribute(attribute)
+                        .ok_or_else(|| format!("resource has no attribute {}", attribute))?,
+                    ("context", Some(attribute)) => request.context.get(attribute).cloned()
+                        .ok_or_else(|| format!("context has no attribute {}", attribute))?,
+                    _ => return Err(format!("{} cannot be used as a value here", name)),
+                };
+                
+                // Nested attributes index into JSON objects
+                for field in rest {
+                    value = value.get(field).cloned()
+                        .ok_or_else(|| format!("{} has no attribute {}", name, field))?;
+                }
+                
+                Ok(value)
+            }
+            Expr::List(items) => items.iter()
+                .map(|item| item.evaluate(request))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+            Expr::Call(function, args) => {
+                let args = args.iter()
+                    .map(|arg| arg.evaluate(request))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call_function(function, &args)
+            }
+            Expr::Not(inner) => Ok(Value::Bool(!as_bool(&inner.evaluate(request)?)?)),
+            Expr::And(left, right) => Ok(Value::Bool(
+                as_bool(&left.evaluate(request)?)? && as_bool(&right.evaluate(request)?)?
+            )),
+            Expr::Or(left, right) => Ok(Value::Bool(
+                as_bool(&left.evaluate(request)?)? || as_bool(&right.evaluate(request)?)?
+            )),
+            Expr::Compare(op, left, right) => {
+                let left = left.evaluate(request)?;
+                let right = right.evaluate(request)?;
+                compare(*op, &left, &right).map(Value::Bool)
+            }
+            Expr::Has(inner, attribute) => match inner.as_ref() {
+                Expr::Variable(name, path) if path.is_empty() => Ok(Value::Bool(match name.as_str() {
+                    "principal" => request.principal.attribute(attribute).is_some(),
+                    "resource" => request.resource.attribute(attribute).is_some(),
+                    "context" => request.context.contains_key(attribute),
+                    _ => return Err(format!("{} has no attributes", name)),
+                })),
+                other => Ok(Value::Bool(other.evaluate(request)?.get(attribute).is_some())),
+            },
+        }
+    }
+}
+
+/// Coerce a value to a boolean, rejecting anything that isn't one
+fn as_bool(value: &Value) -> Result<bool, String> {
+    value.as_bool().ok_or_else(|| format!("expected a boolean, found {}", value))
+}
+
+/// Apply a comparison operator
+fn compare(op: CompareOp, left: &Value, right: &Value) -> Result<bool, String> {
+    let values_equal = |a: &Value, b: &Value| match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    };
+    
+    match op {
+        CompareOp::Eq => Ok(values_equal(left, right)),
+        CompareOp::Ne => Ok(!values_equal(left, right)),
+        CompareOp::In => match right {
+            Value::Array(items) => Ok(items.iter().any(|item| values_equal(left, item))),
+            _ => Err(format!("`in` needs a list on the right, found {}", right)),
+        },
+        CompareOp::Like => match (left.as_str(), right.as_str()) {
+            (Some(text), Some(pattern)) => Ok(glob_matches(pattern, text)),
+            _ => Err("`like` needs strings on both sides".to_string()),
+        },
+        CompareOp::Lt | CompareOp::Le | CompareOp::Gt | CompareOp::Ge => {
+            let ordering = match (left, right) {
+                (Value::Number(_), Value::Number(_)) => left.as_f64().partial_cmp(&right.as_f64()),
+                (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                _ => None,
+            }
+            .ok_or_else(|| format!("cannot order {} and {}", left, right))?;
+            
+            Ok(match op {
+                CompareOp::Lt => ordering.is_lt(),
+                CompareOp::Le => ordering.is_le(),
+                CompareOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+    }
+}
+
+/// Call a built-in function
+fn call_function(function: &str, args: &[Value]) -> Result<Value, String> {
+    match (function, args) {
+        ("in_cidr", [Value::String(ip), Value::String(cidr)]) => ip_in_cidr(ip, cidr).map(Value::Bool),
+        ("contains", [Value::Array(items), item]) => Ok(Value::Bool(items.contains(item))),
+        ("contains", [Value::String(text), Value::String(part)]) => Ok(Value::Bool(text.contains(part.as_str()))),
+        ("lower", [Value::String(text)]) => Ok(Value::from(text.to_lowercase())),
+        _ => Err(format!("unknown function or bad arguments: {}", function)),
+    }
+}
+
+/// Check whether an IP address falls inside a CIDR block
+fn ip_in_cidr(ip: &str, cidr: &str) -> Result<bool, String> {
+    let ip: IpAddr = ip.parse().map_err(|_| format!("invalid IP address {}", ip))?;
+    let (network, prefix) = parse_cidr(cidr)?;
+    Ok(cidr_contains(network, prefix, ip))
+}
+
+/// Parse a CIDR block into its network address and prefix length
+pub(crate) fn parse_cidr(cidr: &str) -> Result<(IpAddr, u32), String> {
+    let (network, prefix) = cidr.split_once('/').ok_or_else(|| format!("invalid CIDR {}", cidr))?;
+    let network: IpAddr = network.parse().map_err(|_| format!("invalid CIDR {}", cidr))?;
+    let prefix: u32 = prefix.parse().map_err(|_| format!("invalid CIDR {}", cidr))?;
+    
+    let bits = if network.is_ipv4() { 32 } else { 128 };
+    if prefix > bits {
+        return Err(format!("invalid CIDR {}", cidr));
+    }
+    
+    Ok((network, prefix))
+}
+
+/// Check whether an IP address falls inside a parsed CIDR block
+pub(crate) fn cidr_contains(network: IpAddr, prefix: u32, ip: IpAddr) -> bool {
+    let (ip, network, bits) = match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => (u32::from(ip) as u128, u32::from(network) as u128, 32),
+        (IpAddr::V6(ip), IpAddr::V6(network)) => (u128::from(ip), u128::from(network), 128),
+        _ => return false,
+    };
+    
+    let mask = if prefix == 0 { 0 } else { (u128::MAX << (bits - prefix)) & (u128::MAX >> (128 - bits)) };
+    ip & mask == network & mask
+}
+
+/// Match a string against a pattern where `*` matches any run of characters
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || !text[first.len()..].ends_with(last) {
+        return false;
+    }
+    
+    let mut remaining = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match remaining.find(part) {
+            Some(index) => remaining = &remaining[index + part.len()..],
+            None => return false,
+        }
+    }
+    
+    true
+}
+
+/// Recursive-descent parser for the policy language
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    origin: String,
+}
+
+impl Parser {
+    fn new(source: &str, origin: &str) -> DreasResult<Self> {
+        Ok(Self {
+            tokens: tokenize(source, origin)?,
+            position: 0,
+            origin: origin.to_string(),
+        })
+    }
+    
+    fn parse_policies(&mut self) -> DreasResult<Vec<Policy>> {
+        let mut policies = Vec::new();
+        
+        while self.peek().is_some() {
+            let id = if self.eat_symbol("@") {
+                self.expect_ident("id")?;
+                self.expect_symbol("(")?;
+                let id = self.expect_string()?;
+                self.expect_symbol(")")?;
+                id
+            } else {
+                format!("{}#{}", self.origin, policies.len())
+            };
+            
+            policies.push(self.parse_policy(id)?);
+        }
+        
+        Ok(policies)
+    }
+    
+    fn parse_policy(&mut self, id: String) -> DreasResult<Policy> {
+        let effect = match self.next_ident()?.as_str() {
+            "permit" => PolicyEffect::Permit,
+            "forbid" => PolicyEffect::Forbid,
+            other => return Err(self.error(&format!("expected permit or forbid, found {}", other))),
+        };
+        
+        self.expect_symbol("(")?;
+        self.expect_ident("principal")?;
+        let principal = if self.eat_symbol("==") {
+            PrincipalScope::Is(self.expect_string()?)
+        } else if self.eat_ident("in") {
+            PrincipalScope::In(self.expect_string()?)
+        } else {
+            PrincipalScope::Any
+        };
+        
+        self.expect_symbol(",")?;
+        self.expect_ident("action")?;
+        let action = if self.eat_symbol("==") {
+            ActionScope::In(vec![self.expect_string()?])
+        } else if self.eat_ident("in") {
+            self.expect_symbol("[")?;
+            let mut actions = vec![self.expect_string()?];
+            while self.eat_symbol(",") {
+                actions.push(self.expect_string()?);
+            }
+            self.expect_symbol("]")?;
+            ActionScope::In(actions)
+        } else {
+            ActionScope::Any
+        };
+        
+        if let ActionScope::In(patterns) = &action {
+            for pattern in patterns {
+                permission::validate_permission(pattern)?;
+            }
+        }
+        
+        self.expect_symbol(",")?;
+        self.expect_ident("resource")?;
+        let resource = if self.eat_symbol("==") {
+            ResourceScope::Is(self.expect_string()?)
+        } else if self.eat_ident("is") {
+            ResourceScope::Type(self.expect_string()?)
+        } else {
+            ResourceScope::Any
+        };
+        self.expect_symbol(")")?;
+        
+        let mut conditions = Vec::new();
+        loop {
+            let when = if self.eat_ident("when") {
+                true
+            } else if self.eat_ident("unless") {
+                false
+            } else {
+                break;
+            };
+            
+            self.expect_symbol("{")?;
+            let expr = self.parse_or()?;
+            self.expect_symbol("}")?;
+            conditions.push(if when { Condition::When(expr) } else { Condition::Unless(expr) });
+        }
+        
+        self.expect_symbol(";")?;
+        
+        Ok(Policy {
+            id,
+            effect,
+            principal,
+            action,
+            resource,
+            conditions,
+        })
+    }
+    
+    fn parse_or(&mut self) -> DreasResult<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.eat_symbol("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+    
+    fn parse_and(&mut self) -> DreasResult<Expr> {
+        let mut expr = self.parse_unary()?;
+        while self.eat_symbol("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+    
+    fn parse_unary(&mut self) -> DreasResult<Expr> {
+        if self.eat_symbol("!") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_comparison()
+    }
+    
+    fn parse_comparison(&mut self) -> DreasResult<Expr> {
+        let left = self.parse_primary()?;
+        
+        if self.eat_ident("has") {
+            let attribute = self.next_ident()?;
+            return Ok(Expr::Has(Box::new(left), attribute));
+        }
+        
+        let op = [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ]
+        .into_iter()
+        .find(|(symbol, _)| self.eat_symbol(symbol))
+        .map(|(_, op)| op)
+        .or_else(|| self.eat_ident("in").then_some(CompareOp::In))
+        .or_else(|| self.eat_ident("like").then_some(CompareOp::Like));
+        
+        match op {
+            Some(op) => Ok(Expr::Compare(op, Box::new(left), Box::new(self.parse_primary()?))),
+            None => Ok(left),
+        }
+    }
+    
+    fn parse_primary(&mut self) -> DreasResult<Expr> {
+        match self.advance() {
+            Some(Token::Str(value)) => Ok(Expr::Literal(Value::from(value))),
+            Some(Token::Num(value)) => Ok(Expr::Literal(Value::from(value))),
+            Some(Token::Symbol("(")) => {
+                let expr = self.parse_or()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            Some(Token::Symbol("[")) => {
+                let mut items = Vec::new();
+                if !self.eat_symbol("]") {
+                    items.push(self.parse_or()?);
+                    while self.eat_symbol(",") {
+                        items.push(self.parse_or()?);
+                    }
+                    self.expect_symbol("]")?;
+                }
+                Ok(Expr::List(items))
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "principal" | "action" | "resource" | "context" => {
+                    let mut path = Vec::new();
+                    while self.eat_symbol(".") {
+                        path.push(self.next_ident()?);
+                    }
+                    Ok(Expr::Variable(name, path))
+                }
+                _ => {
+                    self.expect_symbol("(")?;
+                    let mut args = Vec::new();
+                    if !self.eat_symbol(")") {
+                        args.push(self.parse_or()?);
+                        while self.eat_symbol(",") {
+                            args.push(self.parse_or()?);
+                        }
+                        self.expect_symbol(")")?;
+                    }
+                    Ok(Expr::Call(name, args))
+                }
+            },
+            other => Err(self.error(&format!("unexpected {:?}", other))),
+        }
+    }
+    
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+    
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+    
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let matched = matches!(self.peek(), Some(Token::Symbol(found)) if *found == symbol);
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+    
+    fn eat_ident(&mut self, ident: &str) -> bool {
+        let matched = matches!(self.peek(), Some(Token::Ident(found)) if found == ident);
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+    
+    fn expect_symbol(&mut self, symbol: &str) -> DreasResult<()> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", symbol)))
+        }
+    }
+    
+    fn expect_ident(&mut self, ident: &str) -> DreasResult<()> {
+        if self.eat_ident(ident) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", ident)))
+        }
+    }
+    
+    fn expect_string(&mut self) -> DreasResult<String> {
+        match self.advance() {
+            Some(Token::Str(value)) => Ok(value),
+            _ => Err(self.error("expected a string")),
+        }
+    }
+    
+    fn next_ident(&mut self) -> DreasResult<String> {
+        match self.advance() {
+            Some(Token::Ident(value)) => Ok(value),
+            _ => Err(self.error("expected an identifier")),
+        }
+    }
+    
+    fn error(&self, message: &str) -> DreasError {
+        let line = self.tokens.get(self.position.saturating_sub(1))
+            .or_else(|| self.tokens.last())
+            .map(|(_, line)| *line)
+            .unwrap_or(1);
+        DreasError::Configuration(format!("Policy syntax error in {} line {}: {}", self.origin, line, message))
+    }
+}
+
+/// Split policy source into tokens, tagging each with its line number
+fn tokenize(source: &str, origin: &str) -> DreasResult<Vec<(Token, usize)>> {
+    const SYMBOLS: [&str; 19] = [
+        "==", "!=", "<=", ">=", "&&", "||", "(", ")", "{", "}", "[", "]", ",", ";", ".", "<", ">", "!", "@",
+    ];
+    
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    let mut line = 1;
+    
+    while let Some(&(start, c)) = chars.peek() {
+        if c == '\n' {
+            line += 1;
+            chars.next();
+        } else if c.is_whitespace() {
+            chars.next();
+        } else if source[start..].starts_with("//") {
+            while chars.peek().is_some_and(|&(_, c)| c != '\n') {
+                chars.next();
+            }
+        } else if c == '"' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, escaped)) => value.push(escaped),
+                        None => break,
+                    },
+                    Some((_, c)) => value.push(c),
+                    None => {
+                        return Err(DreasError::Configuration(format!(
+                            "Policy syntax error in {} line {}: unterminated string", origin, line
+                        )));
+                    }
+                }
+            }
+            tokens.push((Token::Str(value), line));
+        } else if c.is_ascii_digit() {
+            let mut end = start;
+            while let Some(&(index, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                end = index + c.len_utf8();
+                chars.next();
+            }
+            let value = source[start..end].parse()
+                .map_err(|_| DreasError::Configuration(format!(
+                    "Policy syntax error in {} line {}: invalid number", origin, line
+                )))?;
+            tokens.push((Token::Num(value), line));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(index, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = index + c.len_utf8();
+                chars.next();
+            }
+            tokens.push((Token::Ident(source[start..end].to_string()), line));
+        } else {
+            let symbol = SYMBOLS.iter()
+                .find(|symbol| source[start..].starts_with(*symbol))
+                .ok_or_else(|| DreasError::Configuration(format!(
+                    "Policy syntax error in {} line {}: unexpected character {}", origin, line, c
+                )))?;
+            
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push((Token::Symbol(symbol), line));
+        }
+    }
+    
+    Ok(tokens)
+}
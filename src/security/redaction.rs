@@ -0,0 +1,329 @@
+//! Redaction of personal data from audit metadata, logs and API errors
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Prompt fragments, emails and credentials end up in audit metadata, tracing fields
+//! and error messages more often than anyone intends. A `Redactor` removes the values
+//! of denied fields, replaces identifiers with a keyed hash so they can still be
+//! correlated, and blanks out anything its detectors match in the remaining text. It
+//! is applied to `AuditEntry.metadata` by the audit logger, to every tracing event by
+//! the layer from `layer`, and to API error bodies by the API service.
+
+use crate::{DreasResult, DreasError};
+use crate::config::RedactionConfig;
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use regex::Regex;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tracing::field::{Field, Visit};
+use tracing::Subscriber;
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::FormatFields;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Replacement of a denied field's value
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Prefix of hashed identifiers
+const HASH_PREFIX: &str = "hmac:";
+
+/// Hex characters of the HMAC kept in a hashed identifier
+const HASH_LENGTH: usize = 16;
+
+/// Name of the tracing field holding an event's message
+const MESSAGE_FIELD: &str = "message";
+
+/// Check a detector's match really is what it looks for, e.g. a card number's checksum
+type MatchCheck = fn(&str) -> bool;
+
+/// Built-in detectors: name, pattern and optional check of each match
+const BUILTIN_DETECTORS: &[(&str, &str, Option<MatchCheck>)] = &[
+    ("email", r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}", None),
+    ("bearer_token", r"(?i)\bbearer\s+[A-Za-z0-9._~+/-]+=*", None),
+    ("card_number", r"\b\d(?:[ -]?\d){12,18}\b", Some(passes_luhn)),
+    ("us_ssn", r"\b\d{3}-\d{2}-\d{4}\b", None),
+];
+
+/// Removes personal data from field values and free text
+#[derive(Clone)]
+pub struct Redactor {
+    denied_fields: Vec<String>,
+    hashed_fields: Vec<String>,
+    detectors: Vec<Detector>,
+    hash_key: Vec<u8>,
+}
+
+/// Pattern whose matches are removed from text
+#[derive(Clone)]
+struct Detector {
+    name: String,
+    pattern: Regex,
+    validate: Option<MatchCheck>,
+}
+
+/// Field formatter of the tracing layer, redacting every field before it's written
+#[derive(Debug, Clone)]
+pub struct RedactingFields {
+    redactor: Arc<Redactor>,
+}
+
+/// Writes the redacted fields of an event or span
+struct RedactingVisitor<'a, 'writer> {
+    redactor: &'a Redactor,
+    writer: Writer<'writer>,
+    first: bool,
+    result: fmt::Result,
+}
+
+impl Redactor {
+    /// Create a redactor with no rules, hashing identifiers with the given key
+    pub fn new(hash_key: Vec<u8>) -> Self {
+        Self {
+            denied_fields: Vec::new(),
+            hashed_fields: Vec::new(),
+            detectors: Vec::new(),
+            hash_key,
+        }
+    }
+    
+    /// Create a redactor from configuration
+    ///
+    /// Without a configured hash key, a random one is used, so hashes only correlate
+    /// within a single run.
+    pub fn from_config(config: &RedactionConfig) -> DreasResult<Self> {
+        let hash_key = match &config.hash_key_path {
+            Some(path) => hex::decode(std::fs::read_to_string(path)?.trim())
+                .map_err(|e| DreasError::Configuration(format!("Invalid redaction hash key: {}", e)))?,
+            None => {
+                let mut key = vec![0u8; 32];
+                OsRng.fill_bytes(&mut key);
+                key
+            }
+        };
+        
+        let mut redactor = Self::new(hash_key);
+        if !config.enabled {
+            return Ok(redactor);
+        }
+        
+        for field in &config.denied_fields {
+            redactor = redactor.with_denied_field(field);
+        }
+        for field in &config.hashed_fields {
+            redactor = redactor.with_hashed_field(field);
+        }
+        if config.builtin_detectors {
+            redactor = redactor.with_builtin_detectors();
+        }
+        let mut detectors: Vec<(&String, &String)> = config.detectors.iter().collect();
+        detectors.sort();
+        for (name, pattern) in detectors {
+            redactor = redactor.with_detector(name, pattern)?;
+        }
+        
+        Ok(redactor)
+    }
+    
+    /// Remove the value of a field, matched by name regardless of case
+    pub fn with_denied_field(mut self, field: &str) -> Self {
+        self.denied_fields.push(field.to_ascii_lowercase());
+        self
+    }
+    
+    /// Replace the value of a field with its keyed hash
+    pub fn with_hashed_field(mut self, field: &str) -> Self {
+        self.hashed_fields.push(field.to_ascii_lowercase());
+        self
+    }
+    
+    /// Remove emails, bearer tokens, card numbers and US social security numbers from text
+    pub fn with_builtin_detectors(mut self) -> Self {
+        for (name, pattern, validate) in BUILTIN_DETECTORS {
+            self.detectors.push(Detector {
+                name: name.to_string(),
+                pattern: Regex::new(pattern).expect("built-in detector patterns are valid"),
+                validate: *validate,
+            });
+        }
+        self
+    }
+    
+    /// Remove matches of a regular expression from text
+    pub fn with_detector(mut self, name: &str, pattern: &str) -> DreasResult<Self> {
+        let pattern = Regex::new(pattern)
+            .map_err(|e| DreasError::Configuration(format!("Invalid redaction detector {}: {}", name, e)))?;
+        self.detectors.push(Detector {
+            name: name.to_string(),
+            pattern,
+            validate: None,
+        });
+        Ok(self)
+    }
+    
+    /// Redact the value of a named field
+    pub fn redact_field(&self, name: &str, value: &str) -> String {
+        let name = name.to_ascii_lowercase();
+        if self.denied_fields.contains(&name) {
+            REDACTED.to_string()
+        } else if self.hashed_fields.contains(&name) {
+            self.hash_identifier(value)
+        } else {
+            self.redact_text(value)
+        }
+    }
+    
+    /// Replace whatever the detectors match in free text with `[REDACTED:<detector>]`
+    pub fn redact_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for detector in &self.detectors {
+            if !detector.pattern.is_match(&text) {
+                continue;
+            }
+            
+            let replacement = format!("[REDACTED:{}]", detector.name);
+            text = detector.pattern.replace_all(&text, |captures: &regex::Captures<'_>| {
+                let found = &captures[0];
+                match detector.validate {
+                    Some(validate) if !validate(found) => found.to_string(),
+                    _ => replacement.clone(),
+                }
+            }).into_owned();
+        }
+        text
+    }
+    
+    /// Hash an identifier with the redaction key, so equal identifiers hash equally
+    pub fn hash_identifier(&self, value: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.hash_key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(value.as_bytes());
+        let digest = hex::encode(mac.finalize().into_bytes());
+        format!("{}{}", HASH_PREFIX, &digest[..HASH_LENGTH])
+    }
+    
+    /// Redact every value of a metadata map
+    pub fn redact_metadata(&self, metadata: HashMap<String, String>) -> HashMap<String, String> {
+        metadata.into_iter()
+            .map(|(key, value)| {
+                let value = self.redact_field(&key, &value);
+                (key, value)
+            })
+            .collect()
+    }
+    
+    /// Redact every string in a JSON value, treating object keys as field names
+    pub fn redact_json(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(text) => *text = self.redact_text(text),
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|item| self.redact_json(item)),
+            serde_json::Value::Object(fields) => {
+                for (name, field) in fields.iter_mut() {
+                    match field {
+                        serde_json::Value::String(text) => *text = self.redact_field(name, text),
+                        serde_json::Value::Null => {}
+                        _ if self.denied_fields.contains(&name.to_ascii_lowercase()) => {
+                            *field = serde_json::Value::String(REDACTED.to_string());
+                        }
+                        _ => self.redact_json(field),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    /// Redact a response body, as JSON if it parses and as text otherwise
+    pub fn redact_body(&self, body: &str) -> String {
+        match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(mut value) => {
+                self.redact_json(&mut value);
+                value.to_string()
+            }
+            Err(_) => self.redact_text(body),
+        }
+    }
+}
+
+impl fmt::Debug for Redactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Redactor")
+            .field("denied_fields", &self.denied_fields)
+            .field("hashed_fields", &self.hashed_fields)
+            .field("detectors", &self.detectors.iter().map(|detector| &detector.name).collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl RedactingFields {
+    /// Create a field formatter redacting with the given redactor
+    pub fn new(redactor: Arc<Redactor>) -> Self {
+        Self { redactor }
+    }
+}
+
+impl<'writer> FormatFields<'writer> for RedactingFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut visitor = RedactingVisitor {
+            redactor: &self.redactor,
+            writer,
+            first: true,
+            result: Ok(()),
+        };
+        fields.record(&mut visitor);
+        visitor.result
+    }
+}
+
+impl RedactingVisitor<'_, '_> {
+    fn write(&mut self, field: &Field, value: &str) {
+        if self.result.is_err() {
+            return;
+        }
+        
+        let separator = if self.first { "" } else { " " };
+        self.first = false;
+        let value = self.redactor.redact_field(field.name(), value);
+        self.result = if field.name() == MESSAGE_FIELD {
+            write!(self.writer, "{}{}", separator, value)
+        } else {
+            write!(self.writer, "{}{}={}", separator, field.name(), value)
+        };
+    }
+}
+
+impl Visit for RedactingVisitor<'_, '_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.write(field, value);
+    }
+    
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.write(field, &format!("{:?}", value));
+    }
+}
+
+/// Tracing layer writing formatted events like `tracing_subscriber::fmt`, with every field redacted
+pub fn layer<S>(redactor: Arc<Redactor>) -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_subscriber::fmt::layer().fmt_fields(RedactingFields::new(redactor))
+}
+
+/// Check a card number's Luhn checksum
+fn passes_luhn(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits.iter().rev().enumerate()
+        .map(|(position, &digit)| match position % 2 {
+            0 => digit,
+            _ if digit * 2 > 9 => digit * 2 - 9,
+            _ => digit * 2,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
@@ -0,0 +1,164 @@
+//! Request-scoped context for audit attribution
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! The API service builds a `RequestContext` for each request and runs the request
+//! inside it. Agents, KMS and storage calls made while handling the request don't
+//! take the context as a parameter; the audit logger reads it from the current task
+//! and fills in whichever details an entry doesn't already carry.
+
+use super::audit::AuditEntry;
+use super::policy::PolicyEntity;
+use super::session::ClientInfo;
+use super::tenant::{TenantId, TENANT_ATTRIBUTE};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use uuid::Uuid;
+
+/// W3C Trace Context header
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Google Cloud trace header, `TRACE_ID/SPAN_ID;o=OPTIONS`
+pub const CLOUD_TRACE_HEADER: &str = "X-Cloud-Trace-Context";
+
+tokio::task_local! {
+    static CURRENT: RequestContext;
+}
+
+/// Who made a request, from where, and how to correlate it with traces
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestContext {
+    pub request_id: Uuid,
+    /// User or service account ID of the authenticated caller
+    pub principal_id: Option<String>,
+    /// The caller as access policies see it, with its type, roles and attributes
+    pub principal: Option<PolicyEntity>,
+    pub session_id: Option<String>,
+    pub tenant_id: Option<TenantId>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub trace_id: Option<String>,
+}
+
+impl RequestContext {
+    /// Create a context for a request with no known caller
+    pub fn new(request_id: Uuid) -> Self {
+        Self {
+            request_id,
+            principal_id: None,
+            principal: None,
+            session_id: None,
+            tenant_id: None,
+            ip_address: None,
+            user_agent: None,
+            trace_id: None,
+        }
+    }
+    
+    /// Set the authenticated caller
+    pub fn with_principal(mut self, principal_id: impl Into<String>) -> Self {
+        self.principal_id = Some(principal_id.into());
+        self
+    }
+    
+    /// Set the authenticated caller, described for access policies
+    pub fn with_caller(mut self, principal: PolicyEntity) -> Self {
+        self.principal_id = Some(principal.id.clone());
+        self.principal = Some(principal);
+        self
+    }
+    
+    /// Set the caller's session
+    pub fn with_session(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+    
+    /// Set the tenant the request acts for
+    pub fn with_tenant(mut self, tenant_id: TenantId) -> Self {
+        self.tenant_id = Some(tenant_id);
+        self
+    }
+    
+    /// Set the client's IP address and user agent
+    pub fn with_client(mut self, client: ClientInfo) -> Self {
+        self.ip_address = client.ip_address;
+        self.user_agent = client.user_agent;
+        self
+    }
+    
+    /// Set the distributed trace the request belongs to
+    pub fn with_trace_id(mut self, trace_id: impl Into<String>) -> Self {
+        self.trace_id = Some(trace_id.into());
+        self
+    }
+    
+    /// Run a future with this context as the current one
+    ///
+    /// The context is task-local: work handed to a spawned task should capture
+    /// `RequestContext::current()` and re-enter it there.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+    
+    /// Get the context of the request being handled by the current task
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(|context| context.clone()).ok()
+    }
+    
+    /// Describe the authenticated caller for access checks, tagged with the request's tenant
+    ///
+    /// Callers set only by ID have no type or roles. Callers with no tenant of their own belong
+    /// to the default one.
+    pub fn caller(&self) -> Option<PolicyEntity> {
+        let principal = match &self.principal {
+            Some(principal) => principal.clone(),
+            None => PolicyEntity::new("principal", self.principal_id.as_ref()?),
+        };
+        let tenant_id = self.tenant_id.clone().unwrap_or_default();
+        Some(principal.with_attribute(TENANT_ATTRIBUTE, tenant_id.as_str()))
+    }
+    
+    /// Fill in the request details an audit entry doesn't already carry
+    pub fn apply(&self, mut entry: AuditEntry) -> AuditEntry {
+        entry.request_id.get_or_insert(self.request_id);
+        
+        let fill = |field: &mut Option<String>, value: &Option<String>| {
+            if field.is_none() {
+                field.clone_from(value);
+            }
+        };
+        fill(&mut entry.user_id, &self.principal_id);
+        fill(&mut entry.session_id, &self.session_id);
+        fill(&mut entry.ip_address, &self.ip_address);
+        fill(&mut entry.user_agent, &self.user_agent);
+        fill(&mut entry.trace_id, &self.trace_id);
+        
+        if entry.tenant_id.is_none() {
+            entry.tenant_id.clone_from(&self.tenant_id);
+        }
+        
+        entry
+    }
+}
+
+/// Extract the trace ID from W3C `traceparent` or Google Cloud trace headers
+pub fn trace_id_from_headers(headers: &HashMap<String, String>) -> Option<String> {
+    let header = |name: &str| headers.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim());
+    
+    // traceparent is version-traceid-parentid-flags; an all-zero trace ID is invalid
+    let traceparent = header(TRACEPARENT_HEADER)
+        .and_then(|value| value.split('-').nth(1))
+        .filter(|trace_id| trace_id.len() == 32 && trace_id.chars().all(|c| c.is_ascii_hexdigit()))
+        .filter(|trace_id| trace_id.chars().any(|c| c != '0'));
+    
+    let cloud_trace = || header(CLOUD_TRACE_HEADER)
+        .and_then(|value| value.split('/').next())
+        .filter(|trace_id| !trace_id.is_empty() && trace_id.chars().all(|c| c.is_ascii_hexdigit()));
+    
+    traceparent.or_else(cloud_trace).map(|trace_id| trace_id.to_ascii_lowercase())
+}
@@ -0,0 +1,445 @@
+//! Retention policies, legal holds and deletion manifests
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module decides how long audit entries and stored objects are kept, by category,
+//! and lets legal holds suspend deletion of the records of specified users, resources or
+//! date ranges. Every purge is recorded in a signed deletion manifest listing what was
+//! removed, under which retention period, and what was withheld because of a hold.
+//! 
+//! Holds and manifests are kept in files when the audit log is durable, so a restart
+//! neither lifts a hold nor loses the evidence of a purge.
+
+use crate::{DreasResult, DreasError};
+use super::token::{verify_signature, TokenSigner};
+use super::audit_sink::write_durably;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Category of stored objects without a `retention_category` metadata value
+pub const DEFAULT_CATEGORY: &str = "default";
+
+/// Object metadata key naming the object's retention category
+pub const RETENTION_CATEGORY_METADATA: &str = "retention_category";
+
+/// Object metadata key naming the user an object belongs to, for user legal holds
+pub const RETENTION_USER_METADATA: &str = "user_id";
+
+/// File legal holds are kept in, beside the audit entries they protect
+pub const LEGAL_HOLDS_FILE: &str = "legal-holds.json";
+
+/// File deletion manifests are appended to, beside the audit entries they account for
+pub const DELETION_MANIFESTS_FILE: &str = "deletion-manifests.jsonl";
+
+/// Domain separator of deletion manifest signatures
+const MANIFEST_SIGNATURE_CONTEXT: &[u8] = b"dreas-deletion-manifest/v1\n";
+
+/// How long records are kept, by category
+///
+/// Audit entries are categorised by action, e.g. `key_recovery`; stored objects by their
+/// `retention_category` metadata. Categories without their own period use the default.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    default_days: u32,
+    categories: HashMap<String, u32>,
+}
+
+/// Legal hold suspending deletion of the records it covers until released
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegalHold {
+    pub hold_id: Uuid,
+    /// Case or investigation the hold was placed for
+    pub matter: String,
+    pub reason: String,
+    pub placed_by: String,
+    pub placed_at: DateTime<Utc>,
+    pub scope: HoldScope,
+}
+
+/// Records covered by a legal hold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HoldScope {
+    /// Records concerning a user
+    User { user_id: String },
+    /// Records whose resource starts with a prefix, e.g. `gs://bucket/tenants/acme/`
+    Resource { prefix: String },
+    /// Records created within a date range, inclusive
+    ///
+    /// Records of unknown age are treated as inside the range, so a hold is never bypassed
+    /// for lack of information.
+    Period { start: DateTime<Utc>, end: DateTime<Utc> },
+}
+
+/// Record being considered for deletion
+#[derive(Debug, Clone, Copy)]
+pub struct RetainedRecord<'a> {
+    pub user_id: Option<&'a str>,
+    pub resource: &'a str,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Registry of active legal holds, shared by every store that deletes records
+///
+/// A registry opened from a file saves every change to it before the change takes effect.
+#[derive(Debug, Default)]
+pub struct LegalHolds {
+    holds: RwLock<Vec<LegalHold>>,
+    path: Option<PathBuf>,
+}
+
+/// Signed record of a retention purge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletionManifest {
+    pub manifest_id: Uuid,
+    /// Store the records were purged from, e.g. `audit_log:<id>` or a bucket prefix
+    pub store: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub purged: Vec<PurgedRecord>,
+    /// Expired records kept because of a legal hold
+    pub withheld: Vec<WithheldRecord>,
+    /// Destinations that still hold copies of the purged records, e.g. a syslog collector
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retained_by: Vec<String>,
+    /// Key that signed the manifest; unset until it's signed
+    pub key_id: Option<String>,
+    /// Base64-encoded Ed25519 signature over `signing_payload`
+    pub signature: Option<String>,
+}
+
+/// Record removed by a retention purge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgedRecord {
+    /// Audit entry ID or object resource name
+    pub record_id: String,
+    pub category: String,
+    pub created_at: DateTime<Utc>,
+    pub retention_days: u32,
+    /// Hash of the record's contents when purged, where the store keeps one
+    pub digest: Option<String>,
+    /// Hash of what's left of the record after the purge, where the store keeps a tombstone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tombstone_digest: Option<String>,
+}
+
+/// Expired record kept because of a legal hold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithheldRecord {
+    pub record_id: String,
+    pub hold_id: Uuid,
+}
+
+impl RetentionPolicy {
+    /// Create a policy keeping every category for the same number of days
+    pub fn new(default_days: u32) -> Self {
+        Self {
+            default_days,
+            categories: HashMap::new(),
+        }
+    }
+    
+    /// Keep a category for its own number of days
+    pub fn with_category(mut self, category: impl Into<String>, days: u32) -> Self {
+        self.categories.insert(category.into(), days);
+        self
+    }
+    
+    /// Keep several categories for their own number of days
+    pub fn with_categories(mut self, categories: &HashMap<String, u32>) -> Self {
+        self.categories.extend(categories.iter().map(|(category, days)| (category.clone(), *days)));
+        self
+    }
+    
+    /// Get the retention of categories without their own period
+    pub fn default_days(&self) -> u32 {
+        self.default_days
+    }
+    
+    /// Get the number of days a category is kept
+    pub fn retention_days(&self, category: &str) -> u32 {
+        self.categories.get(category).copied().unwrap_or(self.default_days)
+    }
+    
+    /// Check whether a record of a category created at the given time has outlived its retention
+    pub fn is_expired(&self, category: &str, created_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        created_at <= now - Duration::days(self.retention_days(category) as i64)
+    }
+}
+
+impl LegalHold {
+    /// Create a hold placed now
+    pub fn new(matter: impl Into<String>, reason: impl Into<String>, placed_by: impl Into<String>, scope: HoldScope) -> Self {
+        Self {
+            hold_id: Uuid::new_v4(),
+            matter: matter.into(),
+            reason: reason.into(),
+            placed_by: placed_by.into(),
+            placed_at: Utc::now(),
+            scope,
+        }
+    }
+    
+    /// Check whether the hold covers a record
+    pub fn covers(&self, record: &RetainedRecord<'_>) -> bool {
+        match &self.scope {
+            HoldScope::User { user_id } => record.user_id == Some(user_id.as_str()),
+            HoldScope::Resource { prefix } => record.resource.starts_with(prefix.as_str()),
+            HoldScope::Period { start, end } => record.created_at
+                .is_none_or(|created_at| *start <= created_at && created_at <= *end),
+        }
+    }
+    
+    fn validate(&self) -> DreasResult<()> {
+        if self.matter.trim().is_empty() || self.reason.trim().is_empty() || self.placed_by.trim().is_empty() {
+            return Err(DreasError::Configuration("A legal hold needs a matter, a reason and who placed it".to_string()));
+        }
+        
+        match &self.scope {
+            HoldScope::User { user_id } if user_id.is_empty() => {
+                Err(DreasError::Configuration("A user legal hold needs a user ID".to_string()))
+            }
+            HoldScope::Resource { prefix } if prefix.is_empty() => {
+                Err(DreasError::Configuration("A resource legal hold needs a resource prefix".to_string()))
+            }
+            HoldScope::Period { start, end } if start > end => {
+                Err(DreasError::Configuration("A legal hold period must start before it ends".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl HoldScope {
+    /// Describe the scope for audit metadata
+    pub fn describe(&self) -> String {
+        match self {
+            HoldScope::User { user_id } => format!("user:{}", user_id),
+            HoldScope::Resource { prefix } => format!("resource:{}", prefix),
+            HoldScope::Period { start, end } => format!("period:{}..{}", start.to_rfc3339(), end.to_rfc3339()),
+        }
+    }
+}
+
+impl<'a> RetainedRecord<'a> {
+    /// Describe a record by its resource alone
+    pub fn new(resource: &'a str) -> Self {
+        Self {
+            user_id: None,
+            resource,
+            created_at: None,
+        }
+    }
+    
+    /// Set the user the record concerns
+    pub fn with_user(mut self, user_id: Option<&'a str>) -> Self {
+        self.user_id = user_id;
+        self
+    }
+    
+    /// Set when the record was created
+    pub fn with_created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+}
+
+impl LegalHolds {
+    /// Create an empty registry kept in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Open a registry kept in a file, loading the holds already placed
+    pub fn open<P: AsRef<Path>>(path: P) -> DreasResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let holds = Self::read_file(&path)?;
+        tracing::info!("Loaded {} legal holds from {}", holds.len(), path.display());
+        
+        Ok(Self {
+            holds: RwLock::new(holds),
+            path: Some(path),
+        })
+    }
+    
+    /// Check whether holds survive a restart
+    pub fn is_persistent(&self) -> bool {
+        self.path.is_some()
+    }
+    
+    /// Load the holds from the registry's file again, picking up holds placed by other processes
+    pub fn reload(&self) -> DreasResult<()> {
+        if let Some(path) = &self.path {
+            let holds = Self::read_file(path)?;
+            *self.holds.write()
+                .map_err(|_| DreasError::Storage("Legal hold registry lock poisoned".to_string()))? = holds;
+        }
+        Ok(())
+    }
+    
+    /// Place a hold, returning its ID
+    pub fn place(&self, hold: LegalHold) -> DreasResult<Uuid> {
+        hold.validate()?;
+        
+        let hold_id = hold.hold_id;
+        let mut holds = self.holds.write()
+            .map_err(|_| DreasError::Storage("Legal hold registry lock poisoned".to_string()))?;
+        let mut updated = holds.clone();
+        updated.push(hold);
+        self.save(&updated)?;
+        *holds = updated;
+        
+        tracing::info!("Placed legal hold {}", hold_id);
+        Ok(hold_id)
+    }
+    
+    /// Release a hold, returning it
+    pub fn release(&self, hold_id: &Uuid) -> DreasResult<LegalHold> {
+        let mut holds = self.holds.write()
+            .map_err(|_| DreasError::Storage("Legal hold registry lock poisoned".to_string()))?;
+        let position = holds.iter().position(|hold| hold.hold_id == *hold_id)
+            .ok_or_else(|| DreasError::Storage(format!("Legal hold not found: {}", hold_id)))?;
+        let mut updated = holds.clone();
+        let released = updated.remove(position);
+        self.save(&updated)?;
+        *holds = updated;
+        
+        tracing::info!("Released legal hold {}", hold_id);
+        Ok(released)
+    }
+    
+    /// Read the holds saved in a file, none if it doesn't exist yet
+    fn read_file(path: &Path) -> DreasResult<Vec<LegalHold>> {
+        match std::fs::read(path) {
+            Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(DreasError::Storage(format!("Failed to read legal holds from {}: {}", path.display(), e))),
+        }
+    }
+    
+    /// Replace the registry's file with the given holds, if it has one
+    fn save(&self, holds: &[LegalHold]) -> DreasResult<()> {
+        match &self.path {
+            Some(path) => write_durably(path, &serde_json::to_vec_pretty(holds)?),
+            None => Ok(()),
+        }
+    }
+    
+    /// Get the active holds
+    pub fn active(&self) -> DreasResult<Vec<LegalHold>> {
+        Ok(self.holds.read()
+            .map_err(|_| DreasError::Storage("Legal hold registry lock poisoned".to_string()))?
+            .clone())
+    }
+    
+    /// Get the first active hold covering a record, if any
+    pub fn holding(&self, record: &RetainedRecord<'_>) -> DreasResult<Option<Uuid>> {
+        Ok(self.holds.read()
+            .map_err(|_| DreasError::Storage("Legal hold registry lock poisoned".to_string()))?
+            .iter()
+            .find(|hold| hold.covers(record))
+            .map(|hold| hold.hold_id))
+    }
+}
+
+impl DeletionManifest {
+    /// Start an unsigned manifest for a purge of a store
+    pub fn new(store: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            manifest_id: Uuid::new_v4(),
+            store: store.into(),
+            reason: reason.into(),
+            created_at: Utc::now(),
+            purged: Vec::new(),
+            withheld: Vec::new(),
+            retained_by: Vec::new(),
+            key_id: None,
+            signature: None,
+        }
+    }
+    
+    /// Record a purged record
+    pub fn purge(&mut self, record: PurgedRecord) {
+        self.purged.push(record);
+    }
+    
+    /// Record an expired record kept because of a hold
+    pub fn withhold(&mut self, record_id: impl Into<String>, hold_id: Uuid) {
+        self.withheld.push(WithheldRecord { record_id: record_id.into(), hold_id });
+    }
+    
+    /// Check whether the purge removed or withheld anything
+    pub fn is_empty(&self) -> bool {
+        self.purged.is_empty() && self.withheld.is_empty()
+    }
+    
+    /// Bytes covered by the signature: the manifest with its key ID set and no signature
+    pub fn signing_payload(&self) -> DreasResult<Vec<u8>> {
+        let unsigned = Self { signature: None, ..self.clone() };
+        
+        let mut payload = MANIFEST_SIGNATURE_CONTEXT.to_vec();
+        payload.extend(serde_json::to_vec(&unsigned)?);
+        Ok(payload)
+    }
+    
+    /// SHA-256 of the signing payload, as hex, for referencing the manifest from the audit log
+    pub fn digest(&self) -> DreasResult<String> {
+        Ok(hex::encode(Sha256::digest(self.signing_payload()?)))
+    }
+    
+    /// Sign the manifest
+    pub async fn sign(mut self, signer: &dyn TokenSigner) -> DreasResult<Self> {
+        self.key_id = Some(signer.key_id().to_string());
+        let signature = signer.sign(&self.signing_payload()?).await?;
+        self.signature = Some(STANDARD.encode(signature));
+        Ok(self)
+    }
+    
+    /// Append the manifest to a file of manifests, one JSON object per line
+    pub fn append_to<P: AsRef<Path>>(&self, path: P) -> DreasResult<()> {
+        let path = path.as_ref();
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        
+        OpenOptions::new().create(true).append(true).open(path)
+            .and_then(|mut file| file.write_all(&line).and_then(|_| file.sync_data()))
+            .map_err(|e| DreasError::Storage(format!(
+                "Failed to record deletion manifest {} in {}: {}", self.manifest_id, path.display(), e
+            )))
+    }
+    
+    /// Read every manifest appended to a file, none if it doesn't exist yet
+    pub fn read_all<P: AsRef<Path>>(path: P) -> DreasResult<Vec<Self>> {
+        let file = match File::open(path.as_ref()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        
+        let mut manifests = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                manifests.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(manifests)
+    }
+    
+    /// Verify the manifest's signature
+    pub fn verify_signature(&self, verifying_key: &VerifyingKey) -> DreasResult<()> {
+        let signature = self.signature.as_deref()
+            .ok_or_else(|| DreasError::Authentication("Deletion manifest is unsigned".to_string()))?;
+        verify_signature(verifying_key, &self.signing_payload()?, signature, "deletion manifest")
+    }
+}
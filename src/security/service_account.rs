@@ -0,0 +1,151 @@
+//! Service accounts and scoped API keys
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module provides machine identities for pipelines that call DREAS
+//! directly, authenticated with prefixed API keys that are stored only as hashes.
+
+use crate::{DreasResult, DreasError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+use chrono::{DateTime, Utc};
+
+/// Prefix on every API key, so leaked keys are easy to spot in logs and secret scanners
+pub const API_KEY_PREFIX: &str = "dreas_";
+
+/// Length of the random part of an API key in bytes
+const API_KEY_SECRET_LENGTH: usize = 32;
+
+/// Length of the public key identifier in bytes
+const API_KEY_ID_LENGTH: usize = 8;
+
+/// Non-human identity used by pipelines and other services
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceAccount {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub denied_permissions: Vec<String>,
+    #[serde(default)]
+    pub attributes: HashMap<String, serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+    pub is_active: bool,
+}
+
+/// Stored API key; the key itself is never kept, only its hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub key_id: String,
+    pub service_account_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub replaced_by: Option<String>,
+    #[serde(skip_serializing)]
+    key_hash: String,
+}
+
+/// A newly issued API key, returned exactly once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuedApiKey {
+    pub key_id: String,
+    pub api_key: String,
+    pub scopes: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ServiceAccount {
+    /// Create a new, active service account
+    pub fn new(name: String, description: String, roles: Vec<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            description,
+            roles,
+            permissions: Vec::new(),
+            denied_permissions: Vec::new(),
+            attributes: HashMap::new(),
+            created_at: Utc::now(),
+            is_active: true,
+        }
+    }
+}
+
+impl ApiKey {
+    /// Generate a new key for a service account, returning the record and the plaintext key
+    pub fn generate(
+        service_account_id: String,
+        name: String,
+        scopes: Vec<String>,
+        expires_at: DateTime<Utc>,
+    ) -> (Self, IssuedApiKey) {
+        let mut id_bytes = [0u8; API_KEY_ID_LENGTH];
+        let mut secret = [0u8; API_KEY_SECRET_LENGTH];
+        OsRng.fill_bytes(&mut id_bytes);
+        OsRng.fill_bytes(&mut secret);
+        
+        let key_id = hex::encode(id_bytes);
+        let api_key = format!("{}{}_{}", API_KEY_PREFIX, key_id, URL_SAFE_NO_PAD.encode(secret));
+        
+        let record = Self {
+            key_id: key_id.clone(),
+            service_account_id,
+            name,
+            scopes: scopes.clone(),
+            created_at: Utc::now(),
+            expires_at,
+            last_used_at: None,
+            revoked_at: None,
+            replaced_by: None,
+            key_hash: hash_api_key(&api_key),
+        };
+        
+        let issued = IssuedApiKey {
+            key_id,
+            api_key,
+            scopes,
+            expires_at,
+        };
+        
+        (record, issued)
+    }
+    
+    /// Check a presented key against the stored hash in constant time
+    pub fn matches(&self, api_key: &str) -> bool {
+        hash_api_key(api_key).as_bytes().ct_eq(self.key_hash.as_bytes()).into()
+    }
+    
+    /// Check whether the key can currently be used
+    pub fn is_usable(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && now < self.expires_at
+    }
+}
+
+/// Extract the key ID from a presented API key
+pub fn parse_key_id(api_key: &str) -> DreasResult<&str> {
+    api_key
+        .strip_prefix(API_KEY_PREFIX)
+        .and_then(|rest| rest.split_once('_'))
+        .map(|(key_id, _)| key_id)
+        .filter(|key_id| key_id.len() == API_KEY_ID_LENGTH * 2)
+        .ok_or_else(|| DreasError::Authentication("Malformed API key".to_string()))
+}
+
+/// Hash an API key for storage
+///
+/// Keys carry 256 bits of randomness, so a fast hash is enough and keeps
+/// per-request verification cheap.
+fn hash_api_key(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
+}
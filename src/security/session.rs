@@ -0,0 +1,265 @@
+//! Session storage, timeouts and client binding
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module defines where user sessions live. The in-memory store suits single
+//! instances and tests; the file store keeps sessions across restarts. Lifetime rules
+//! (idle and absolute timeouts, per-user caps and client binding) are described by a
+//! `SessionPolicy` and enforced by the identity manager.
+
+use crate::{DreasResult, DreasError};
+use crate::config::SessionConfig;
+use super::identity::UserSession;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use chrono::{DateTime, Duration, Utc};
+
+/// Storage backend for user sessions
+pub trait SessionStore: std::fmt::Debug + Send + Sync {
+    /// Insert or replace a session
+    fn save(&self, session: &UserSession) -> DreasResult<()>;
+    
+    /// Look up a session by ID, whether or not it has expired
+    fn get(&self, session_id: &str) -> DreasResult<Option<UserSession>>;
+    
+    /// Remove a session, returning it if it existed
+    fn remove(&self, session_id: &str) -> DreasResult<Option<UserSession>>;
+    
+    /// List every stored session belonging to a user
+    fn list_for_user(&self, user_id: &str) -> DreasResult<Vec<UserSession>>;
+    
+    /// Remove sessions that are past their absolute or idle timeout
+    fn remove_expired(&self, now: DateTime<Utc>, idle_timeout: Duration) -> DreasResult<Vec<UserSession>>;
+}
+
+/// Session lifetime and binding rules
+#[derive(Debug, Clone)]
+pub struct SessionPolicy {
+    /// Sessions end after this long without activity
+    pub idle_timeout: Duration,
+    /// Sessions end this long after login regardless of activity
+    pub absolute_timeout: Duration,
+    /// Oldest sessions are ended once a user exceeds this; 0 disables the cap
+    pub max_sessions_per_user: usize,
+    pub bind_ip: bool,
+    pub bind_user_agent: bool,
+}
+
+/// Network details of the client making a request
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Session store held in process memory
+#[derive(Debug, Default)]
+pub struct InMemorySessionStore {
+    sessions: RwLock<HashMap<String, UserSession>>,
+}
+
+/// Session store persisted as a JSON file, rewritten on every change
+#[derive(Debug)]
+pub struct FileSessionStore {
+    path: PathBuf,
+    sessions: RwLock<HashMap<String, UserSession>>,
+}
+
+impl SessionPolicy {
+    /// Build a session policy from configuration
+    pub fn from_config(config: &SessionConfig) -> Self {
+        Self {
+            idle_timeout: Duration::minutes(config.idle_timeout_minutes),
+            absolute_timeout: Duration::hours(config.absolute_timeout_hours),
+            max_sessions_per_user: config.max_sessions_per_user,
+            bind_ip: config.bind_ip,
+            bind_user_agent: config.bind_user_agent,
+        }
+    }
+    
+    /// Check whether a request from the client may use the session
+    pub fn binding_matches(&self, session: &UserSession, client: &ClientInfo) -> bool {
+        // A value recorded at login must be presented again; omitting it doesn't bypass the binding
+        let ip_matches = !self.bind_ip
+            || session.ip_address.is_none()
+            || session.ip_address == client.ip_address;
+        let user_agent_matches = !self.bind_user_agent
+            || session.user_agent.is_none()
+            || session.user_agent == client.user_agent;
+        
+        ip_matches && user_agent_matches
+    }
+}
+
+impl Default for SessionPolicy {
+    fn default() -> Self {
+        Self::from_config(&SessionConfig::default())
+    }
+}
+
+impl ClientInfo {
+    /// Describe a client by its source IP alone
+    pub fn from_ip(ip_address: Option<&str>) -> Self {
+        Self {
+            ip_address: ip_address.map(|ip| ip.to_string()),
+            user_agent: None,
+        }
+    }
+    
+    /// Set the client's user agent
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+}
+
+impl UserSession {
+    /// Check whether the session is past its absolute or idle timeout
+    pub fn is_expired(&self, now: DateTime<Utc>, idle_timeout: Duration) -> bool {
+        now > self.expires_at || now - self.last_active_at > idle_timeout
+    }
+}
+
+impl InMemorySessionStore {
+    /// Create an empty in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for InMemorySessionStore {
+    fn save(&self, session: &UserSession) -> DreasResult<()> {
+        write_sessions(&self.sessions)?.insert(session.session_id.clone(), session.clone());
+        Ok(())
+    }
+    
+    fn get(&self, session_id: &str) -> DreasResult<Option<UserSession>> {
+        Ok(read_sessions(&self.sessions)?.get(session_id).cloned())
+    }
+    
+    fn remove(&self, session_id: &str) -> DreasResult<Option<UserSession>> {
+        Ok(write_sessions(&self.sessions)?.remove(session_id))
+    }
+    
+    fn list_for_user(&self, user_id: &str) -> DreasResult<Vec<UserSession>> {
+        let sessions = read_sessions(&self.sessions)?;
+        Ok(sessions_for_user(&sessions, user_id))
+    }
+    
+    fn remove_expired(&self, now: DateTime<Utc>, idle_timeout: Duration) -> DreasResult<Vec<UserSession>> {
+        let mut sessions = write_sessions(&self.sessions)?;
+        Ok(drain_expired(&mut sessions, now, idle_timeout))
+    }
+}
+
+impl FileSessionStore {
+    /// Open a file-backed store, loading any sessions already saved at the path
+    pub fn open<P: AsRef<Path>>(path: P) -> DreasResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        
+        let sessions = if path.exists() {
+            let contents = std::fs::read_to_string(&path)?;
+            serde_json::from_str(&contents)?
+        } else {
+            HashMap::new()
+        };
+        
+        tracing::info!("Session store opened at {} with {} sessions", path.display(), sessions.len());
+        
+        Ok(Self {
+            path,
+            sessions: RwLock::new(sessions),
+        })
+    }
+    
+    /// Write all sessions to disk, replacing the file atomically
+    fn persist(&self, sessions: &HashMap<String, UserSession>) -> DreasResult<()> {
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_vec(sessions)?)?;
+        std::fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn save(&self, session: &UserSession) -> DreasResult<()> {
+        let mut sessions = write_sessions(&self.sessions)?;
+        sessions.insert(session.session_id.clone(), session.clone());
+        self.persist(&sessions)
+    }
+    
+    fn get(&self, session_id: &str) -> DreasResult<Option<UserSession>> {
+        Ok(read_sessions(&self.sessions)?.get(session_id).cloned())
+    }
+    
+    fn remove(&self, session_id: &str) -> DreasResult<Option<UserSession>> {
+        let mut sessions = write_sessions(&self.sessions)?;
+        let removed = sessions.remove(session_id);
+        
+        if removed.is_some() {
+            self.persist(&sessions)?;
+        }
+        
+        Ok(removed)
+    }
+    
+    fn list_for_user(&self, user_id: &str) -> DreasResult<Vec<UserSession>> {
+        let sessions = read_sessions(&self.sessions)?;
+        Ok(sessions_for_user(&sessions, user_id))
+    }
+    
+    fn remove_expired(&self, now: DateTime<Utc>, idle_timeout: Duration) -> DreasResult<Vec<UserSession>> {
+        let mut sessions = write_sessions(&self.sessions)?;
+        let expired = drain_expired(&mut sessions, now, idle_timeout);
+        
+        if !expired.is_empty() {
+            self.persist(&sessions)?;
+        }
+        
+        Ok(expired)
+    }
+}
+
+/// Acquire a read lock on a session map
+fn read_sessions(
+    sessions: &RwLock<HashMap<String, UserSession>>,
+) -> DreasResult<RwLockReadGuard<'_, HashMap<String, UserSession>>> {
+    sessions.read()
+        .map_err(|_| DreasError::Storage("Session store lock poisoned".to_string()))
+}
+
+/// Acquire a write lock on a session map
+fn write_sessions(
+    sessions: &RwLock<HashMap<String, UserSession>>,
+) -> DreasResult<RwLockWriteGuard<'_, HashMap<String, UserSession>>> {
+    sessions.write()
+        .map_err(|_| DreasError::Storage("Session store lock poisoned".to_string()))
+}
+
+/// Collect a user's sessions, oldest first
+fn sessions_for_user(sessions: &HashMap<String, UserSession>, user_id: &str) -> Vec<UserSession> {
+    let mut user_sessions: Vec<UserSession> = sessions.values()
+        .filter(|session| session.user_id == user_id)
+        .cloned()
+        .collect();
+    user_sessions.sort_by_key(|session| session.created_at);
+    user_sessions
+}
+
+/// Remove and return expired sessions from a session map
+fn drain_expired(
+    sessions: &mut HashMap<String, UserSession>,
+    now: DateTime<Utc>,
+    idle_timeout: Duration,
+) -> Vec<UserSession> {
+    let expired_ids: Vec<String> = sessions.values()
+        .filter(|session| session.is_expired(now, idle_timeout))
+        .map(|session| session.session_id.clone())
+        .collect();
+    
+    expired_ids.iter()
+        .filter_map(|session_id| sessions.remove(session_id))
+        .collect()
+}
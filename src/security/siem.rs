@@ -0,0 +1,536 @@
+//! SIEM export of audit entries
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Security operations tooling ingests OCSF events or CEF lines rather than the
+//! audit log's own JSON. Entries are mapped onto the OCSF Authentication, Entity
+//! Management and API Activity classes, or formatted as CEF; the syslog sink sends
+//! them to a collector as they are logged, and `export_entries` writes a date range
+//! of entries in bulk.
+
+use crate::{DreasResult, DreasError};
+use crate::config::SyslogSinkConfig;
+use super::audit::{actions, AuditEntry, AuditResult};
+use super::audit_sink::AuditSink;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::io::Write;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+/// OCSF schema version of exported events
+pub const OCSF_VERSION: &str = "1.1.0";
+
+/// Product and vendor name reported to the SIEM
+const PRODUCT_NAME: &str = "DREAS";
+
+/// Product version reported to the SIEM
+const PRODUCT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Syslog APP-NAME of audit messages
+const SYSLOG_APP_NAME: &str = "dreas";
+
+/// Syslog facility 13, "log audit" (RFC 5424 section 6.2.1)
+const SYSLOG_FACILITY: u8 = 13;
+
+/// Maximum length of a syslog MSGID
+const MAX_MSGID_LENGTH: usize = 32;
+
+/// Format of audit entries sent to a SIEM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SiemFormat {
+    /// OCSF event as a single line of JSON
+    Ocsf,
+    /// ArcSight Common Event Format
+    Cef,
+}
+
+/// OCSF event classes audit entries are mapped onto
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OcsfClass {
+    Authentication,
+    EntityManagement,
+    ApiActivity,
+}
+
+/// Sink sending entries to a syslog collector as RFC 5424 messages over TCP or TLS
+///
+/// Messages are framed by octet counting (RFC 6587, RFC 5425). A write succeeds once the
+/// message is handed to the connection. Connecting and sending are each bounded by the
+/// configured timeout; a connection that fails or times out is dropped and re-established.
+#[derive(Debug)]
+pub struct SyslogAuditSink {
+    address: String,
+    tls: Option<native_tls::TlsConnector>,
+    format: SiemFormat,
+    hostname: String,
+    timeout: Duration,
+    connection: Mutex<Option<SyslogConnection>>,
+}
+
+/// Open connection to a syslog collector
+#[derive(Debug)]
+enum SyslogConnection {
+    Tcp(TcpStream),
+    Tls(Box<tokio_native_tls::TlsStream<TcpStream>>),
+}
+
+impl SiemFormat {
+    /// Format an entry as a single line
+    pub fn format(&self, entry: &AuditEntry) -> DreasResult<String> {
+        match self {
+            SiemFormat::Ocsf => Ok(serde_json::to_string(&to_ocsf(entry))?),
+            SiemFormat::Cef => Ok(to_cef(entry)),
+        }
+    }
+}
+
+impl std::str::FromStr for SiemFormat {
+    type Err = DreasError;
+    
+    fn from_str(format: &str) -> DreasResult<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "ocsf" => Ok(SiemFormat::Ocsf),
+            "cef" => Ok(SiemFormat::Cef),
+            _ => Err(DreasError::Configuration(format!("Unknown SIEM format {:?}: use ocsf or cef", format))),
+        }
+    }
+}
+
+impl OcsfClass {
+    /// Get the class an audit action is reported as
+    pub fn of(action: &str) -> Self {
+        ocsf_activity(action).0
+    }
+    
+    /// OCSF class UID
+    pub fn uid(&self) -> u32 {
+        match self {
+            OcsfClass::Authentication => 3002,
+            OcsfClass::EntityManagement => 3004,
+            OcsfClass::ApiActivity => 6003,
+        }
+    }
+    
+    /// OCSF class name
+    pub fn name(&self) -> &'static str {
+        match self {
+            OcsfClass::Authentication => "Authentication",
+            OcsfClass::EntityManagement => "Entity Management",
+            OcsfClass::ApiActivity => "API Activity",
+        }
+    }
+    
+    /// UID of the OCSF category the class belongs to
+    pub fn category_uid(&self) -> u32 {
+        self.uid() / 1000
+    }
+    
+    /// Name of the OCSF category the class belongs to
+    pub fn category_name(&self) -> &'static str {
+        match self {
+            OcsfClass::Authentication | OcsfClass::EntityManagement => "Identity & Access Management",
+            OcsfClass::ApiActivity => "Application Activity",
+        }
+    }
+}
+
+/// OCSF class, activity ID and activity name of an audit action
+fn ocsf_activity(action: &str) -> (OcsfClass, u32, &'static str) {
+    match action {
+        actions::USER_AUTHENTICATION | actions::IMPERSONATION_START => (OcsfClass::Authentication, 1, "Logon"),
+        actions::IMPERSONATION_END => (OcsfClass::Authentication, 2, "Logoff"),
+        actions::KEY_ESCROW => (OcsfClass::EntityManagement, 1, "Create"),
+        actions::KEY_RECOVERY => (OcsfClass::EntityManagement, 2, "Read"),
+        actions::PERMISSION_CHANGE => (OcsfClass::EntityManagement, 3, "Update"),
+        actions::AUDIT_LOG_PRUNED => (OcsfClass::EntityManagement, 4, "Delete"),
+        actions::STORAGE_WRITE => (OcsfClass::ApiActivity, 1, "Create"),
+        actions::STORAGE_READ => (OcsfClass::ApiActivity, 2, "Read"),
+        actions::STORAGE_DELETE => (OcsfClass::ApiActivity, 4, "Delete"),
+        _ => (OcsfClass::ApiActivity, 99, "Other"),
+    }
+}
+
+/// Map an audit entry onto an OCSF event
+///
+/// The user the operation was performed as is the actor, unless a support engineer was
+/// impersonating them; then the engineer is the actor and the subject is kept in `unmapped`.
+pub fn to_ocsf(entry: &AuditEntry) -> Value {
+    let (class, activity_id, activity_name) = ocsf_activity(&entry.action);
+    let (severity_id, severity, status_id, status) = match entry.result {
+        AuditResult::Success => (1, "Informational", 1, "Success"),
+        AuditResult::Partial => (2, "Low", 99, "Partial"),
+        AuditResult::Failure => (3, "Medium", 2, "Failure"),
+    };
+    
+    let mut event = json!({
+        "class_uid": class.uid(),
+        "class_name": class.name(),
+        "category_uid": class.category_uid(),
+        "category_name": class.category_name(),
+        "activity_id": activity_id,
+        "activity_name": activity_name,
+        "type_uid": class.uid() * 100 + activity_id,
+        "time": entry.timestamp.timestamp_millis(),
+        "severity_id": severity_id,
+        "severity": severity,
+        "status_id": status_id,
+        "status": status,
+        "message": format!("{} on {}", entry.action, entry.resource),
+        "metadata": {
+            "version": OCSF_VERSION,
+            "uid": entry.entry_id,
+            "sequence": entry.sequence,
+            "log_name": "audit",
+            "product": {
+                "name": PRODUCT_NAME,
+                "vendor_name": PRODUCT_NAME,
+                "version": PRODUCT_VERSION,
+            },
+        },
+    });
+    let event_fields = event.as_object_mut().expect("OCSF event is an object");
+    
+    let mut unmapped = Map::new();
+    let mut actor = Map::new();
+    if let Some(acting_user) = entry.actor_id.as_ref().or(entry.user_id.as_ref()) {
+        actor.insert("user".to_string(), json!({ "uid": acting_user }));
+    }
+    if let (Some(_), Some(subject)) = (&entry.actor_id, &entry.user_id) {
+        unmapped.insert("impersonated_user_uid".to_string(), json!(subject));
+    }
+    if let Some(session_id) = &entry.session_id {
+        actor.insert("session".to_string(), json!({ "uid": session_id }));
+    }
+    if !actor.is_empty() {
+        event_fields.insert("actor".to_string(), Value::Object(actor));
+    }
+    
+    // Authentication entries record the login address in metadata when there's no request context
+    if let Some(ip) = entry.ip_address.as_ref().or(entry.metadata.get("source_ip")) {
+        event_fields.insert("src_endpoint".to_string(), json!({ "ip": ip }));
+    }
+    if let Some(user_agent) = &entry.user_agent {
+        event_fields.insert("http_request".to_string(), json!({ "user_agent": user_agent }));
+    }
+    if let Some(trace_id) = &entry.trace_id {
+        event_fields["metadata"]["correlation_uid"] = json!(trace_id);
+    }
+    
+    match class {
+        OcsfClass::Authentication => {
+            let mut user = Map::new();
+            if let Some(user_id) = &entry.user_id {
+                user.insert("uid".to_string(), json!(user_id));
+            }
+            if let Some(username) = entry.metadata.get("username") {
+                user.insert("name".to_string(), json!(username));
+            }
+            event_fields.insert("user".to_string(), Value::Object(user));
+        }
+        OcsfClass::EntityManagement => {
+            event_fields.insert("entity".to_string(), json!({ "uid": entry.resource }));
+        }
+        OcsfClass::ApiActivity => {
+            let mut api = json!({ "operation": entry.action });
+            if let Some(request_id) = &entry.request_id {
+                api["request"] = json!({ "uid": request_id });
+            }
+            event_fields.insert("api".to_string(), api);
+            event_fields.insert("resources".to_string(), json!([{ "uid": entry.resource }]));
+        }
+    }
+    
+    unmapped.insert("resource".to_string(), json!(entry.resource));
+    unmapped.insert("hash".to_string(), json!(entry.hash));
+    if let Some(tenant_id) = &entry.tenant_id {
+        unmapped.insert("tenant_id".to_string(), json!(tenant_id));
+    }
+    if let Some(request_id) = &entry.request_id {
+        unmapped.insert("request_id".to_string(), json!(request_id));
+    }
+    if !entry.metadata.is_empty() {
+        unmapped.insert("metadata".to_string(), json!(entry.metadata));
+    }
+    event_fields.insert("unmapped".to_string(), Value::Object(unmapped));
+    
+    event
+}
+
+/// Format an audit entry as a CEF line
+///
+/// The acting user is `suid`; when a support engineer was impersonating a user, the
+/// engineer is `suid` and the user is `duid`.
+pub fn to_cef(entry: &AuditEntry) -> String {
+    let severity = match entry.result {
+        AuditResult::Success => 3,
+        AuditResult::Partial => 5,
+        AuditResult::Failure => 7,
+    };
+    let outcome = match entry.result {
+        AuditResult::Success => "success",
+        AuditResult::Partial => "partial",
+        AuditResult::Failure => "failure",
+    };
+    
+    let mut extensions: Vec<(&str, String)> = vec![
+        ("rt", entry.timestamp.timestamp_millis().to_string()),
+        ("externalId", entry.entry_id.to_string()),
+        ("act", entry.action.clone()),
+        ("outcome", outcome.to_string()),
+    ];
+    match (&entry.actor_id, &entry.user_id) {
+        (Some(actor_id), Some(user_id)) => {
+            extensions.push(("suid", actor_id.clone()));
+            extensions.push(("duid", user_id.clone()));
+        }
+        (Some(user_id), None) | (None, Some(user_id)) => extensions.push(("suid", user_id.clone())),
+        (None, None) => {}
+    }
+    if let Some(username) = entry.metadata.get("username") {
+        extensions.push(("suser", username.clone()));
+    }
+    // src must be an IP address, so anything else is left out
+    if let Some(ip) = entry.ip_address.as_ref().or(entry.metadata.get("source_ip"))
+        .filter(|ip| ip.parse::<IpAddr>().is_ok())
+    {
+        extensions.push(("src", ip.clone()));
+    }
+    if let Some(user_agent) = &entry.user_agent {
+        extensions.push(("requestClientApplication", user_agent.clone()));
+    }
+    
+    let custom_strings = [
+        ("resource", Some(entry.resource.clone())),
+        ("tenant", entry.tenant_id.as_ref().map(|tenant_id| tenant_id.to_string())),
+        ("session", entry.session_id.clone()),
+        ("requestId", entry.request_id.map(|request_id| request_id.to_string())),
+        ("traceId", entry.trace_id.clone()),
+    ];
+    let labels = ["cs1Label", "cs2Label", "cs3Label", "cs4Label", "cs5Label"];
+    let keys = ["cs1", "cs2", "cs3", "cs4", "cs5"];
+    for (((label, value), label_key), key) in custom_strings.into_iter().zip(labels).zip(keys) {
+        if let Some(value) = value {
+            extensions.push((label_key, label.to_string()));
+            extensions.push((key, value));
+        }
+    }
+    extensions.push(("cn1Label", "sequence".to_string()));
+    extensions.push(("cn1", entry.sequence.to_string()));
+    
+    let extension = extensions.iter()
+        .map(|(key, value)| format!("{}={}", key, escape_cef_extension(value)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    
+    format!(
+        "CEF:0|{}|{}|{}|{}|{}|{}|{}",
+        PRODUCT_NAME,
+        PRODUCT_NAME,
+        PRODUCT_VERSION,
+        escape_cef_header(&entry.action),
+        escape_cef_header(&cef_event_name(&entry.action)),
+        severity,
+        extension
+    )
+}
+
+/// Human-readable event name of an action, e.g. "Storage read" for `storage_read`
+fn cef_event_name(action: &str) -> String {
+    let name = action.replace('_', " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+/// Escape a CEF header field, which may not contain pipes or line breaks
+fn escape_cef_header(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+/// Escape a CEF extension value
+fn escape_cef_extension(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+/// Write the entries logged in `[start, end)` to `out` in a SIEM format, one per line
+///
+/// The range is half-open so consecutive exports neither overlap nor leave gaps.
+/// Returns the number of entries written.
+pub fn export_entries<W: Write>(
+    entries: &[AuditEntry],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    format: SiemFormat,
+    out: &mut W,
+) -> DreasResult<usize> {
+    if start >= end {
+        return Err(DreasError::Configuration("Export range must end after it starts".to_string()));
+    }
+    
+    let mut exported = 0;
+    for entry in entries.iter().filter(|entry| entry.timestamp >= start && entry.timestamp < end) {
+        writeln!(out, "{}", format.format(entry)?)?;
+        exported += 1;
+    }
+    out.flush()?;
+    
+    Ok(exported)
+}
+
+impl SyslogAuditSink {
+    /// Create a sink for the collector in the configuration; it connects on the first write
+    pub fn from_config(config: &SyslogSinkConfig) -> DreasResult<Self> {
+        let tls = if config.tls {
+            let mut builder = native_tls::TlsConnector::builder();
+            if let Some(path) = &config.ca_certificate_path {
+                let certificate = native_tls::Certificate::from_pem(&std::fs::read(path)?)
+                    .map_err(|e| DreasError::Configuration(format!("Invalid syslog CA certificate {}: {}", path, e)))?;
+                builder.add_root_certificate(certificate);
+            }
+            Some(builder.build().map_err(|e| DreasError::Configuration(format!("Failed to set up syslog TLS: {}", e)))?)
+        } else {
+            None
+        };
+        
+        Ok(Self {
+            address: config.address.clone(),
+            tls,
+            format: config.format,
+            hostname: config.hostname.clone().unwrap_or_else(|| "-".to_string()),
+            timeout: Duration::from_secs(config.timeout_seconds),
+            connection: Mutex::new(None),
+        })
+    }
+    
+    /// Build the RFC 5424 message for an entry
+    pub fn message(&self, entry: &AuditEntry) -> DreasResult<String> {
+        let severity = match entry.result {
+            AuditResult::Success => 6,
+            AuditResult::Partial => 5,
+            AuditResult::Failure => 4,
+        };
+        let msgid: String = entry.action.chars()
+            .filter(|c| c.is_ascii_graphic())
+            .take(MAX_MSGID_LENGTH)
+            .collect();
+        
+        // The BOM marks the message body as UTF-8
+        Ok(format!(
+            "<{}>1 {} {} {} {} {} [origin software=\"{}\" swVersion=\"{}\"] \u{feff}{}",
+            SYSLOG_FACILITY * 8 + severity,
+            entry.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.hostname,
+            SYSLOG_APP_NAME,
+            std::process::id(),
+            if msgid.is_empty() { "-" } else { msgid.as_str() },
+            PRODUCT_NAME,
+            PRODUCT_VERSION,
+            self.format.format(entry)?
+        ))
+    }
+    
+    /// Open a connection to the collector, giving up after the timeout
+    async fn connect(&self) -> DreasResult<SyslogConnection> {
+        timeout(self.timeout, self.handshake()).await.map_err(|_| DreasError::AuditLogging(format!(
+            "Timed out connecting to syslog collector {}", self.address
+        )))?
+    }
+    
+    /// Connect to the collector and, if configured, negotiate TLS
+    async fn handshake(&self) -> DreasResult<SyslogConnection> {
+        let stream = TcpStream::connect(&self.address).await.map_err(|e| DreasError::AuditLogging(format!(
+            "Failed to connect to syslog collector {}: {}", self.address, e
+        )))?;
+        
+        let Some(connector) = &self.tls else {
+            return Ok(SyslogConnection::Tcp(stream));
+        };
+        let host = self.address.rsplit_once(':')
+            .map_or(self.address.as_str(), |(host, _)| host)
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let stream = tokio_native_tls::TlsConnector::from(connector.clone())
+            .connect(host, stream)
+            .await
+            .map_err(|e| DreasError::AuditLogging(format!(
+                "TLS handshake with syslog collector {} failed: {}", self.address, e
+            )))?;
+        
+        Ok(SyslogConnection::Tls(Box::new(stream)))
+    }
+}
+
+impl SyslogConnection {
+    /// Write a framed message and flush it
+    async fn send(&mut self, frame: &[u8]) -> std::io::Result<()> {
+        match self {
+            SyslogConnection::Tcp(stream) => {
+                stream.write_all(frame).await?;
+                stream.flush().await
+            }
+            SyslogConnection::Tls(stream) => {
+                stream.write_all(frame).await?;
+                stream.flush().await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AuditSink for SyslogAuditSink {
+    fn name(&self) -> &str {
+        "syslog"
+    }
+    
+    async fn write(&self, entry: &AuditEntry) -> DreasResult<()> {
+        let message = self.message(entry)?;
+        let frame = format!("{} {}", message.len(), message);
+        
+        let mut connection = self.connection.lock().await;
+        
+        // A collector restart drops the connection, so retry once on a fresh one
+        let mut last_error = String::new();
+        for _ in 0..2 {
+            let stream = match connection.as_mut() {
+                Some(stream) => stream,
+                None => match self.connect().await {
+                    Ok(stream) => connection.insert(stream),
+                    Err(e) => {
+                        tracing::warn!("{}", e);
+                        last_error = e.to_string();
+                        continue;
+                    }
+                },
+            };
+            
+            let error = match timeout(self.timeout, stream.send(frame.as_bytes())).await {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => "timed out".to_string(),
+            };
+            tracing::warn!("Syslog collector {} connection failed: {}", self.address, error);
+            *connection = None;
+            last_error = error;
+        }
+        
+        Err(DreasError::AuditLogging(format!(
+            "Failed to send audit entry {} to syslog collector {}: {}", entry.entry_id, self.address, last_error
+        )))
+    }
+}
@@ -0,0 +1,160 @@
+//! Tenant identifiers and cross-tenant access checks
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! Several business units share one DREAS deployment. Each tenant gets its own
+//! KMS key ring and storage prefix, and the `TenantGuard` refuses any object access
+//! or key use across tenants unless an access policy explicitly permits it.
+
+use crate::{DreasResult, DreasError};
+use super::policy::{PolicyEngine, PolicyEntity, PolicyRequest};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Tenant of data that predates multi-tenancy or has no explicit owner
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Policy attribute carrying an entity's tenant
+pub const TENANT_ATTRIBUTE: &str = "tenant";
+
+/// Maximum tenant ID length, keeping derived KMS key ring names within limits
+const MAX_TENANT_ID_LENGTH: usize = 32;
+
+/// Identifier of a tenant, safe to embed in KMS resource names and storage paths
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TenantId(String);
+
+/// Enforces that principals only access and decrypt their own tenant's data
+#[derive(Debug, Clone, Default)]
+pub struct TenantGuard {
+    policy_engine: Option<Arc<PolicyEngine>>,
+}
+
+impl TenantId {
+    /// Create a tenant ID of lowercase letters, digits and hyphens
+    pub fn new(id: &str) -> DreasResult<Self> {
+        let valid = !id.is_empty()
+            && id.len() <= MAX_TENANT_ID_LENGTH
+            && !id.starts_with('-')
+            && !id.ends_with('-')
+            && id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        
+        if !valid {
+            return Err(DreasError::Configuration(format!(
+                "Invalid tenant ID {:?}: use 1-{} lowercase letters, digits or inner hyphens",
+                id, MAX_TENANT_ID_LENGTH
+            )));
+        }
+        
+        Ok(Self(id.to_string()))
+    }
+    
+    /// Get the tenant ID as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    
+    /// Object name prefix under which the tenant's stored data lives
+    pub fn storage_prefix(&self) -> String {
+        format!("tenants/{}/", self.0)
+    }
+    
+    /// Name of the tenant's KMS key ring, derived from the deployment's key ring
+    pub fn key_ring(&self, base_key_ring: &str) -> String {
+        format!("{}-{}", base_key_ring, self.0)
+    }
+}
+
+impl Default for TenantId {
+    fn default() -> Self {
+        Self(DEFAULT_TENANT.to_string())
+    }
+}
+
+impl std::fmt::Display for TenantId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for TenantId {
+    type Error = DreasError;
+    
+    fn try_from(id: String) -> DreasResult<Self> {
+        Self::new(&id)
+    }
+}
+
+impl From<TenantId> for String {
+    fn from(tenant_id: TenantId) -> Self {
+        tenant_id.0
+    }
+}
+
+impl TenantGuard {
+    /// Create a guard that refuses all cross-tenant access
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Allow cross-tenant access that an access policy explicitly permits
+    pub fn with_policy_engine(mut self, policy_engine: Arc<PolicyEngine>) -> Self {
+        self.policy_engine = Some(policy_engine);
+        self
+    }
+    
+    /// Check that a principal may perform an action on a resource owned by a tenant
+    ///
+    /// The principal's tenant is read from its `tenant` attribute. Access within a tenant
+    /// is always allowed here; across tenants it needs a matching `permit` policy and no `forbid`.
+    pub async fn check_access(
+        &self,
+        principal: &PolicyEntity,
+        action: &str,
+        resource: &str,
+        owner: &TenantId,
+    ) -> DreasResult<()> {
+        if tenant_of(principal).as_ref() == Some(owner) {
+            return Ok(());
+        }
+        
+        let permitted = match &self.policy_engine {
+            Some(policy_engine) => {
+                let request = PolicyRequest::new(
+                    principal.clone(),
+                    action,
+                    PolicyEntity::new("tenant_resource", resource).with_attribute(TENANT_ATTRIBUTE, owner.as_str()),
+                )
+                .with_context("cross_tenant", true);
+                
+                policy_engine.evaluate(&request).await?.is_permitted()
+            }
+            None => false,
+        };
+        
+        if !permitted {
+            tracing::warn!(
+                "Cross-tenant {} refused: {} {} on {} owned by tenant {}",
+                action, principal.entity_type, principal.id, resource, owner
+            );
+            return Err(DreasError::Authentication(format!(
+                "Cross-tenant access to {} is not permitted", resource
+            )));
+        }
+        
+        tracing::info!(
+            "Cross-tenant {} permitted by policy: {} {} on {} owned by tenant {}",
+            action, principal.entity_type, principal.id, resource, owner
+        );
+        Ok(())
+    }
+}
+
+/// Read the tenant attribute of a policy entity
+pub fn tenant_of(entity: &PolicyEntity) -> Option<TenantId> {
+    entity.attributes.get(TENANT_ATTRIBUTE)
+        .and_then(|tenant| tenant.as_str())
+        .and_then(|tenant| TenantId::new(tenant).ok())
+}
@@ -0,0 +1,547 @@
+//! Signed access tokens, refresh tokens and JWKS publication
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module issues EdDSA-signed JWT access tokens that any DREAS service can verify
+//! offline from the published JWKS, along with rotating refresh tokens with reuse detection.
+
+use crate::{DreasResult, DreasError};
+use crate::config::TokenConfig;
+use super::kms::KmsClient;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// JWS algorithm used for all DREAS tokens
+pub const TOKEN_ALGORITHM: &str = "EdDSA";
+
+/// DER prefix of an Ed25519 SubjectPublicKeyInfo, followed by the 32-byte key
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// Allowed clock skew when validating token timestamps
+const CLOCK_SKEW_SECONDS: i64 = 30;
+
+/// Signer for token payloads
+#[async_trait]
+pub trait TokenSigner: std::fmt::Debug + Send + Sync {
+    /// Key ID published in the JWKS and token headers
+    fn key_id(&self) -> &str;
+    
+    /// Public half of the signing key
+    fn verifying_key(&self) -> VerifyingKey;
+    
+    /// Produce an Ed25519 signature over the message
+    async fn sign(&self, message: &[u8]) -> DreasResult<Vec<u8>>;
+}
+
+/// Token signer backed by a locally held Ed25519 key
+#[derive(Debug)]
+pub struct LocalTokenSigner {
+    key_id: String,
+    signing_key: SigningKey,
+}
+
+/// Token signer backed by a KMS EC_SIGN_ED25519 key version
+#[derive(Debug)]
+pub struct KmsTokenSigner {
+    key_id: String,
+    kms_client: KmsClient,
+    verifying_key: VerifyingKey,
+}
+
+/// Claims carried by an access token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessClaims {
+    pub iss: String,
+    pub aud: String,
+    pub sub: String,
+    pub sid: String,
+    pub jti: String,
+    pub roles: Vec<String>,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// JWS header
+#[derive(Debug, Serialize, Deserialize)]
+struct TokenHeader {
+    alg: String,
+    typ: String,
+    kid: String,
+}
+
+/// JSON Web Key (Ed25519 public key)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    pub kid: String,
+    pub alg: String,
+    #[serde(rename = "use")]
+    pub key_use: String,
+}
+
+/// JSON Web Key Set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+/// Access and refresh token pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
+/// Stored refresh token state, keyed by token hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshTokenRecord {
+    pub family_id: Uuid,
+    pub user_id: String,
+    pub session_id: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used: bool,
+}
+
+/// Result of presenting a refresh token
+#[derive(Debug, Clone)]
+pub enum RefreshOutcome {
+    /// Token was valid and is now spent; issue its successor
+    Rotated(RefreshTokenRecord),
+    /// Token had already been rotated; its whole family has been revoked
+    Reused(RefreshTokenRecord),
+}
+
+/// Issues access tokens and manages refresh token rotation
+#[derive(Debug, Clone)]
+pub struct TokenService {
+    signer: Arc<dyn TokenSigner>,
+    issuer: String,
+    audience: String,
+    access_token_ttl: Duration,
+    refresh_token_ttl: Duration,
+    refresh_tokens: HashMap<String, RefreshTokenRecord>,
+    additional_keys: Vec<Jwk>,
+}
+
+/// Offline verifier for access tokens, built from a JWKS
+#[derive(Debug, Clone)]
+pub struct TokenVerifier {
+    issuer: String,
+    audience: String,
+    keys: HashMap<String, VerifyingKey>,
+}
+
+impl LocalTokenSigner {
+    /// Create a signer from an existing key
+    pub fn new(signing_key: SigningKey) -> Self {
+        Self {
+            key_id: jwk_thumbprint(&signing_key.verifying_key()),
+            signing_key,
+        }
+    }
+    
+    /// Generate a signer with a fresh random key
+    pub fn generate() -> Self {
+        Self::new(SigningKey::generate(&mut OsRng))
+    }
+    
+    /// Load a signer from a file containing a hex-encoded 32-byte Ed25519 seed
+    pub fn from_seed_file<P: AsRef<Path>>(path: P) -> DreasResult<Self> {
+        let contents = std::fs::read_to_string(path.as_ref())?;
+        
+        let seed: [u8; 32] = hex::decode(contents.trim())
+            .map_err(|e| DreasError::Configuration(format!("Invalid signing key seed: {}", e)))?
+            .try_into()
+            .map_err(|_| DreasError::Configuration("Signing key seed must be 32 bytes".to_string()))?;
+        
+        Ok(Self::new(SigningKey::from_bytes(&seed)))
+    }
+}
+
+#[async_trait]
+impl TokenSigner for LocalTokenSigner {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+    
+    fn verifying_key(&self) -> VerifyingKey {
+        self.signing_key.verifying_key()
+    }
+    
+    async fn sign(&self, message: &[u8]) -> DreasResult<Vec<u8>> {
+        Ok(self.signing_key.sign(message).to_bytes().to_vec())
+    }
+}
+
+impl KmsTokenSigner {
+    /// Create a signer for the KMS client's key version, fetching its public key
+    pub async fn new(kms_client: KmsClient) -> DreasResult<Self> {
+        let pem = kms_client.get_public_key().await?;
+        let verifying_key = parse_ed25519_pem(&pem)?;
+        
+        Ok(Self {
+            key_id: jwk_thumbprint(&verifying_key),
+            kms_client,
+            verifying_key,
+        })
+    }
+}
+
+#[async_trait]
+impl TokenSigner for KmsTokenSigner {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+    
+    fn verifying_key(&self) -> VerifyingKey {
+        self.verifying_key
+    }
+    
+    async fn sign(&self, message: &[u8]) -> DreasResult<Vec<u8>> {
+        self.kms_client.asymmetric_sign(message).await
+    }
+}
+
+impl Jwk {
+    /// Build a JWK for an Ed25519 public key
+    pub fn from_verifying_key(verifying_key: &VerifyingKey) -> Self {
+        Self {
+            kty: "OKP".to_string(),
+            crv: "Ed25519".to_string(),
+            x: URL_SAFE_NO_PAD.encode(verifying_key.as_bytes()),
+            kid: jwk_thumbprint(verifying_key),
+            alg: TOKEN_ALGORITHM.to_string(),
+            key_use: "sig".to_string(),
+        }
+    }
+    
+    /// Decode the Ed25519 public key
+    pub fn verifying_key(&self) -> DreasResult<VerifyingKey> {
+        if self.kty != "OKP" || self.crv != "Ed25519" {
+            return Err(DreasError::Authentication(format!("Unsupported JWK type: {}/{}", self.kty, self.crv)));
+        }
+        
+        let bytes: [u8; 32] = URL_SAFE_NO_PAD.decode(&self.x)
+            .map_err(|e| DreasError::Authentication(format!("Invalid JWK key material: {}", e)))?
+            .try_into()
+            .map_err(|_| DreasError::Authentication("JWK key must be 32 bytes".to_string()))?;
+        
+        VerifyingKey::from_bytes(&bytes)
+            .map_err(|e| DreasError::Authentication(format!("Invalid Ed25519 public key: {}", e)))
+    }
+}
+
+impl TokenService {
+    /// Create a new token service
+    pub fn new(
+        signer: Arc<dyn TokenSigner>,
+        issuer: String,
+        audience: String,
+        access_token_ttl: Duration,
+        refresh_token_ttl: Duration,
+    ) -> Self {
+        Self {
+            signer,
+            issuer,
+            audience,
+            access_token_ttl,
+            refresh_token_ttl,
+            refresh_tokens: HashMap::new(),
+            additional_keys: Vec::new(),
+        }
+    }
+    
+    /// Create a token service from configuration, if a signing key is configured
+    pub async fn from_config(config: &TokenConfig) -> DreasResult<Option<Self>> {
+        let signer: Arc<dyn TokenSigner> = match (&config.signing_key_path, &config.kms_signing_key_uri) {
+            (Some(_), Some(_)) => {
+                return Err(DreasError::Configuration(
+                    "Configure either a local or a KMS token signing key, not both".to_string()
+                ));
+            }
+            (Some(path), None) => Arc::new(LocalTokenSigner::from_seed_file(path)?),
+            (None, Some(uri)) => Arc::new(KmsTokenSigner::new(KmsClient::from_key_version_uri(uri)?).await?),
+            (None, None) => return Ok(None),
+        };
+        
+        Ok(Some(Self::new(
+            signer,
+            config.issuer.clone(),
+            config.audience.clone(),
+            Duration::seconds(config.access_token_ttl_seconds),
+            Duration::seconds(config.refresh_token_ttl_seconds),
+        )))
+    }
+    
+    /// Keep publishing a retired key so tokens it signed verify until they expire
+    pub fn with_retired_key(mut self, verifying_key: &VerifyingKey) -> Self {
+        self.additional_keys.push(Jwk::from_verifying_key(verifying_key));
+        self
+    }
+    
+    /// Issue a signed access token
+    pub async fn issue_access_token(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        roles: Vec<String>,
+    ) -> DreasResult<String> {
+        let now = Utc::now();
+        
+        let claims = AccessClaims {
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            sub: user_id.to_string(),
+            sid: session_id.to_string(),
+            jti: Uuid::new_v4().to_string(),
+            roles,
+            iat: now.timestamp(),
+            exp: (now + self.access_token_ttl).timestamp(),
+        };
+        
+        let header = TokenHeader {
+            alg: TOKEN_ALGORITHM.to_string(),
+            typ: "JWT".to_string(),
+            kid: self.signer.key_id().to_string(),
+        };
+        
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?)
+        );
+        
+        let signature = self.signer.sign(signing_input.as_bytes()).await?;
+        
+        Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature)))
+    }
+    
+    /// Issue a refresh token, starting a new rotation family if none is given
+    pub fn issue_refresh_token(
+        &mut self,
+        user_id: &str,
+        session_id: &str,
+        family_id: Option<Uuid>,
+    ) -> String {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token = format!("rt_{}", URL_SAFE_NO_PAD.encode(bytes));
+        let now = Utc::now();
+        
+        self.refresh_tokens.insert(hash_refresh_token(&token), RefreshTokenRecord {
+            family_id: family_id.unwrap_or_else(Uuid::new_v4),
+            user_id: user_id.to_string(),
+            session_id: session_id.to_string(),
+            issued_at: now,
+            expires_at: now + self.refresh_token_ttl,
+            used: false,
+        });
+        
+        token
+    }
+    
+    /// Consume a refresh token so a successor can be issued
+    ///
+    /// Presenting a token that was already rotated means it leaked, so the whole
+    /// family is revoked and the caller should end the session.
+    pub fn consume_refresh_token(&mut self, token: &str) -> DreasResult<RefreshOutcome> {
+        let token_hash = hash_refresh_token(token);
+        
+        let record = self.refresh_tokens.get_mut(&token_hash)
+            .ok_or_else(|| DreasError::Authentication("Invalid refresh token".to_string()))?;
+        
+        if record.used {
+            let record = record.clone();
+            let revoked = self.revoke_family(record.family_id);
+            
+            tracing::warn!(
+                "Refresh token reuse detected for session {}; revoked {} tokens",
+                record.session_id, revoked
+            );
+            return Ok(RefreshOutcome::Reused(record));
+        }
+        
+        if Utc::now() > record.expires_at {
+            return Err(DreasError::Authentication("Refresh token expired".to_string()));
+        }
+        
+        record.used = true;
+        Ok(RefreshOutcome::Rotated(record.clone()))
+    }
+    
+    /// Revoke every refresh token in a rotation family
+    pub fn revoke_family(&mut self, family_id: Uuid) -> usize {
+        let initial_count = self.refresh_tokens.len();
+        self.refresh_tokens.retain(|_, record| record.family_id != family_id);
+        initial_count - self.refresh_tokens.len()
+    }
+    
+    /// Revoke every refresh token issued for a session
+    pub fn revoke_session(&mut self, session_id: &str) -> usize {
+        let initial_count = self.refresh_tokens.len();
+        self.refresh_tokens.retain(|_, record| record.session_id != session_id);
+        initial_count - self.refresh_tokens.len()
+    }
+    
+    /// Drop expired refresh tokens
+    pub fn cleanup_expired(&mut self) -> usize {
+        let now = Utc::now();
+        let initial_count = self.refresh_tokens.len();
+        self.refresh_tokens.retain(|_, record| record.expires_at > now);
+        initial_count - self.refresh_tokens.len()
+    }
+    
+    /// Access token lifetime in seconds
+    pub fn access_token_ttl_seconds(&self) -> i64 {
+        self.access_token_ttl.num_seconds()
+    }
+    
+    /// Get the JWKS for the active and retired signing keys
+    pub fn jwks(&self) -> Jwks {
+        let mut keys = vec![Jwk::from_verifying_key(&self.signer.verifying_key())];
+        keys.extend(self.additional_keys.iter().cloned());
+        Jwks { keys }
+    }
+    
+    /// Build a verifier for tokens issued by this service
+    pub fn verifier(&self) -> DreasResult<TokenVerifier> {
+        TokenVerifier::from_jwks(&self.jwks(), self.issuer.clone(), self.audience.clone())
+    }
+}
+
+impl TokenVerifier {
+    /// Create a verifier from a JWKS
+    pub fn from_jwks(jwks: &Jwks, issuer: String, audience: String) -> DreasResult<Self> {
+        let mut keys = HashMap::new();
+        
+        for jwk in &jwks.keys {
+            if jwk.alg != TOKEN_ALGORITHM {
+                continue;
+            }
+            keys.insert(jwk.kid.clone(), jwk.verifying_key()?);
+        }
+        
+        if keys.is_empty() {
+            return Err(DreasError::Configuration("JWKS contains no usable signing keys".to_string()));
+        }
+        
+        Ok(Self { issuer, audience, keys })
+    }
+    
+    /// Verify a token's signature, issuer, audience and lifetime
+    pub fn verify(&self, token: &str) -> DreasResult<AccessClaims> {
+        let mut parts = token.split('.');
+        let (header, claims, signature) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(header), Some(claims), Some(signature), None) => (header, claims, signature),
+            _ => return Err(DreasError::Authentication("Malformed access token".to_string())),
+        };
+        
+        let signing_input = &token[..header.len() + 1 + claims.len()];
+        let header: TokenHeader = decode_segment(header)?;
+        
+        if header.alg != TOKEN_ALGORITHM {
+            return Err(DreasError::Authentication(format!("Unsupported token algorithm: {}", header.alg)));
+        }
+        
+        let key = self.keys.get(&header.kid)
+            .ok_or_else(|| DreasError::Authentication(format!("Unknown signing key: {}", header.kid)))?;
+        
+        let signature_bytes = URL_SAFE_NO_PAD.decode(signature)
+            .map_err(|_| DreasError::Authentication("Malformed token signature".to_string()))?;
+        let signature = Signature::from_slice(&signature_bytes)
+            .map_err(|_| DreasError::Authentication("Malformed token signature".to_string()))?;
+        
+        key.verify_strict(signing_input.as_bytes(), &signature)
+            .map_err(|_| DreasError::Authentication("Invalid token signature".to_string()))?;
+        
+        let claims: AccessClaims = decode_segment(claims)?;
+        let now = Utc::now().timestamp();
+        
+        if claims.iss != self.issuer {
+            return Err(DreasError::Authentication("Token issuer mismatch".to_string()));
+        }
+        
+        if claims.aud != self.audience {
+            return Err(DreasError::Authentication("Token audience mismatch".to_string()));
+        }
+        
+        if claims.exp + CLOCK_SKEW_SECONDS < now {
+            return Err(DreasError::Authentication("Access token expired".to_string()));
+        }
+        
+        if claims.iat - CLOCK_SKEW_SECONDS > now {
+            return Err(DreasError::Authentication("Access token issued in the future".to_string()));
+        }
+        
+        Ok(claims)
+    }
+}
+
+/// Verify a base64-encoded Ed25519 signature over a payload
+///
+/// `subject` names what was signed in error messages, e.g. "tree head".
+pub fn verify_signature(verifying_key: &VerifyingKey, payload: &[u8], signature: &str, subject: &str) -> DreasResult<()> {
+    let signature_bytes: [u8; 64] = STANDARD.decode(signature)
+        .map_err(|e| DreasError::Authentication(format!("Invalid {} signature encoding: {}", subject, e)))?
+        .try_into()
+        .map_err(|_| DreasError::Authentication(format!("The {} signature must be 64 bytes", subject)))?;
+    
+    verifying_key.verify(payload, &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| DreasError::Authentication(format!("The {} signature is invalid", subject)))
+}
+
+/// Compute the RFC 7638 thumbprint of an Ed25519 key, used as its key ID
+fn jwk_thumbprint(verifying_key: &VerifyingKey) -> String {
+    let canonical = format!(
+        r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#,
+        URL_SAFE_NO_PAD.encode(verifying_key.as_bytes())
+    );
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+/// Parse a PEM-encoded Ed25519 SubjectPublicKeyInfo
+fn parse_ed25519_pem(pem: &str) -> DreasResult<VerifyingKey> {
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    
+    let der = STANDARD.decode(body.trim())
+        .map_err(|e| DreasError::KmsSigning(format!("Invalid public key PEM: {}", e)))?;
+    
+    let key_bytes: [u8; 32] = der
+        .strip_prefix(&ED25519_SPKI_PREFIX[..])
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| DreasError::KmsSigning("KMS key is not an Ed25519 public key".to_string()))?;
+    
+    VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|e| DreasError::KmsSigning(format!("Invalid Ed25519 public key: {}", e)))
+}
+
+/// Hash a refresh token for storage
+fn hash_refresh_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Decode a base64url JSON token segment
+fn decode_segment<T: serde::de::DeserializeOwned>(segment: &str) -> DreasResult<T> {
+    let bytes = URL_SAFE_NO_PAD.decode(segment)
+        .map_err(|_| DreasError::Authentication("Malformed access token".to_string()))?;
+    
+    serde_json::from_slice(&bytes)
+        .map_err(|_| DreasError::Authentication("Malformed access token".to_string()))
+}
@@ -7,8 +7,23 @@
 //! handling HTTP requests and responses with proper authentication and authorization.
 
 use crate::{DreasResult, DreasError};
+use crate::security::audit::AuditLogger;
+use crate::security::audit_query::{AuditFilter, AuditSearch, AUDIT_READ_ALL_TENANTS_PERMISSION, AUDIT_READ_PERMISSION};
+use crate::security::audit_stream::{AuditEvent, AuditSubscription};
+use crate::security::identity::{IdentityManager, Principal};
+use crate::security::policy::{cidr_contains, parse_cidr, PolicyEngine, PolicyEntity, PolicyRequest};
+use crate::security::redaction::Redactor;
+use crate::security::request_context::{trace_id_from_headers, RequestContext};
+use crate::security::service_account::API_KEY_PREFIX;
+use crate::security::session::ClientInfo;
+use crate::security::token::{Jwks, TokenVerifier};
+use super::scim::{ScimProvisioner, SCIM_CONTENT_TYPE, SCIM_PERMISSION};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
@@ -19,8 +34,32 @@ pub struct ApiService {
     port: u16,
     endpoints: HashMap<String, ApiEndpoint>,
     middleware: Vec<MiddlewareFunction>,
+    jwks: Option<Jwks>,
+    token_verifier: Option<TokenVerifier>,
+    identity_manager: Option<Arc<RwLock<IdentityManager>>>,
+    policy_engine: Option<Arc<PolicyEngine>>,
+    scim: Option<ScimProvisioner>,
+    audit_logger: Option<Arc<Mutex<AuditLogger>>>,
+    audit_stream_recheck_interval: Duration,
+    trusted_proxies: Vec<(IpAddr, u32)>,
+    redactor: Option<Arc<Redactor>>,
 }
 
+/// Path prefix of the SCIM 2.0 provisioning endpoints
+pub const SCIM_BASE_PATH: &str = "/scim/v2";
+
+/// Path of the audit log search endpoint
+pub const AUDIT_SEARCH_PATH: &str = "/audit/entries";
+
+/// Path of the audit event stream endpoint
+pub const AUDIT_STREAM_PATH: &str = "/audit/stream";
+
+/// Content type of Server-Sent Events
+pub const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream";
+
+/// How often an open audit stream checks its subscriber may still read the audit log, by default
+pub const AUDIT_STREAM_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
 /// API endpoint definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiEndpoint {
@@ -53,6 +92,9 @@ pub struct ApiRequest {
     pub body: Option<String>,
     pub query_params: HashMap<String, String>,
     pub timestamp: DateTime<Utc>,
+    /// Address of the connection the request arrived on
+    #[serde(default)]
+    pub peer_address: Option<IpAddr>,
 }
 
 /// API response structure
@@ -66,6 +108,22 @@ pub struct ApiResponse {
     pub timestamp: DateTime<Utc>,
 }
 
+/// Server-Sent Events stream of audit entries, for a principal allowed to read the audit log
+///
+/// Each entry is sent as an `audit_entry` event with its sequence number as the event ID, so
+/// a client reconnecting with `Last-Event-ID` resumes where it left off. A `lagged` event
+/// tells the client it fell behind and entries were dropped. Only entries of the principal's
+/// own tenant are sent unless it may read every tenant's.
+#[derive(Debug)]
+pub struct AuditEventStream {
+    subscription: AuditSubscription,
+    principal: Principal,
+    identity_manager: Arc<RwLock<IdentityManager>>,
+    headers: HashMap<String, String>,
+    recheck_interval: Duration,
+    checked_at: Instant,
+}
+
 /// Middleware function type
 pub type MiddlewareFunction = fn(&mut ApiRequest) -> DreasResult<()>;
 
@@ -77,7 +135,119 @@ impl ApiService {
             port,
             endpoints: HashMap::new(),
             middleware: Vec::new(),
+            jwks: None,
+            token_verifier: None,
+            identity_manager: None,
+            policy_engine: None,
+            scim: None,
+            audit_logger: None,
+            audit_stream_recheck_interval: AUDIT_STREAM_RECHECK_INTERVAL,
+            trusted_proxies: Vec::new(),
+            redactor: None,
+        }
+    }
+    
+    /// Verify bearer tokens against a JWKS and publish it on the `jwks` handler
+    pub fn set_token_verification(&mut self, jwks: Jwks, issuer: String, audience: String) -> DreasResult<()> {
+        self.token_verifier = Some(TokenVerifier::from_jwks(&jwks, issuer, audience)?);
+        self.jwks = Some(jwks);
+        Ok(())
+    }
+    
+    /// Resolve service account API keys through the identity manager
+    pub fn set_identity_manager(&mut self, identity_manager: Arc<RwLock<IdentityManager>>) {
+        self.identity_manager = Some(identity_manager);
+    }
+    
+    /// Evaluate access policies for every request
+    pub fn set_policy_engine(&mut self, policy_engine: Arc<PolicyEngine>) {
+        self.policy_engine = Some(policy_engine);
+    }
+    
+    /// Trust `X-Forwarded-For` hops added by proxies in these CIDR blocks
+    ///
+    /// Without trusted proxies the client address is always the connection's peer.
+    pub fn set_trusted_proxies(&mut self, proxies: &[String]) -> DreasResult<()> {
+        self.trusted_proxies = proxies.iter()
+            .map(|cidr| parse_cidr(cidr)
+                .map_err(|e| DreasError::Configuration(format!("Invalid trusted proxy: {}", e))))
+            .collect::<DreasResult<_>>()?;
+        Ok(())
+    }
+    
+    /// Redact personal data from error response bodies
+    pub fn set_redactor(&mut self, redactor: Arc<Redactor>) {
+        self.redactor = Some(redactor);
+    }
+    
+    /// Serve SCIM 2.0 `/Users` and `/Groups` from the identity manager under `/scim/v2`
+    pub async fn enable_scim(&mut self, base_url: &str) -> DreasResult<()> {
+        let identity_manager = self.identity_manager.clone()
+            .ok_or_else(|| DreasError::Configuration("SCIM requires an identity manager".to_string()))?;
+        self.scim = Some(ScimProvisioner::new(identity_manager, &format!("{}{}", base_url.trim_end_matches('/'), SCIM_BASE_PATH)));
+        
+        for resource in ["Users", "Groups"] {
+            let collection = format!("{}/{}", SCIM_BASE_PATH, resource);
+            let item = format!("{}/*", collection);
+            let routes = [
+                (&collection, HttpMethod::GET),
+                (&collection, HttpMethod::POST),
+                (&item, HttpMethod::GET),
+                (&item, HttpMethod::PATCH),
+                (&item, HttpMethod::DELETE),
+            ];
+            
+            for (path, method) in routes {
+                self.register_endpoint(ApiEndpoint {
+                    path: path.clone(),
+                    method,
+                    handler: "scim".to_string(),
+                    requires_auth: true,
+                    rate_limit: None,
+                    timeout_seconds: Some(30),
+                }).await?;
+            }
         }
+        
+        Ok(())
+    }
+    
+    /// Serve searches of the audit log on `GET /audit/entries` to principals allowed to read it
+    ///
+    /// Takes the query parameters of `AuditSearch::from_query_params` and returns an `AuditPage`.
+    pub async fn enable_audit_search(&mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> DreasResult<()> {
+        self.audit_logger = Some(audit_logger);
+        
+        self.register_endpoint(ApiEndpoint {
+            path: AUDIT_SEARCH_PATH.to_string(),
+            method: HttpMethod::GET,
+            handler: "audit_search".to_string(),
+            requires_auth: true,
+            rate_limit: Some(10),
+            timeout_seconds: Some(30),
+        }).await
+    }
+    
+    /// Stream audit entries as Server-Sent Events on `GET /audit/stream` to principals allowed to read the log
+    ///
+    /// Streams are opened with `open_audit_stream`, taking the query parameters of
+    /// `AuditFilter::from_query_params`.
+    pub async fn enable_audit_streaming(&mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> DreasResult<()> {
+        self.audit_logger = Some(audit_logger);
+        
+        self.register_endpoint(ApiEndpoint {
+            path: AUDIT_STREAM_PATH.to_string(),
+            method: HttpMethod::GET,
+            handler: "audit_stream".to_string(),
+            requires_auth: true,
+            rate_limit: Some(10),
+            timeout_seconds: None,
+        }).await
+    }
+    
+    /// Set how often open audit streams check their subscribers may still read the audit log
+    pub fn set_audit_stream_recheck_interval(&mut self, interval: Duration) {
+        self.audit_stream_recheck_interval = interval;
     }
     
     /// Register an API endpoint
@@ -117,66 +287,247 @@ impl ApiService {
     }
     
     /// Process HTTP request
+    ///
+    /// Requests that fail are answered with an error status and a body built by `error_body`,
+    /// so error details are redacted before they leave the service.
     pub async fn process_request(&mut self, request: ApiRequest) -> DreasResult<ApiResponse> {
         let start_time = std::time::Instant::now();
+        let request_id = request.request_id;
+        let method = request.method.clone();
+        let path = request.path.clone();
+        
+        let (status_code, headers, response_body) = match self.respond(request).await {
+            Ok(response) => response,
+            Err((status_code, error)) => {
+                tracing::warn!("API request failed with status {}: {}", status_code, error);
+                (status_code, self.get_default_headers(), Some(self.error_body(&error)))
+            }
+        };
         
+        let processing_time = start_time.elapsed().as_millis() as u64;
+        
+        let response = ApiResponse {
+            request_id,
+            status_code,
+            headers,
+            body: response_body,
+            processing_time_ms: processing_time,
+            timestamp: Utc::now(),
+        };
+        
+        tracing::info!("API request processed: {} {} in {}ms", 
+                      method as u8, 
+                      path, 
+                      processing_time);
+        
+        Ok(response)
+    }
+    
+    /// Handle a request, returning the status code, headers and body of its response
+    ///
+    /// Failures come with the status code to answer them with.
+    async fn respond(&self, request: ApiRequest) -> Result<(u16, HashMap<String, String>, Option<String>), (u16, DreasError)> {
         // Apply middleware
-        let mut processed_request = request.clone();
+        let mut processed_request = request;
         for middleware in &self.middleware {
-            middleware(&mut processed_request)?;
+            middleware(&mut processed_request).map_err(|e| (400, e))?;
         }
         
         // Find matching endpoint
-        let endpoint_key = format!("{}:{}", processed_request.method.clone() as u8, processed_request.path);
-        let endpoint = self.endpoints.get(&endpoint_key)
-            .ok_or_else(|| DreasError::Generic(format!("Endpoint not found: {} {}", 
+        let endpoint = self.find_endpoint(&processed_request)
+            .ok_or_else(|| (404, DreasError::Generic(format!("Endpoint not found: {} {}", 
                                                       processed_request.method.clone() as u8, 
-                                                      processed_request.path)))?;
+                                                      processed_request.path))))?;
         
         // Check authentication if required
-        if endpoint.requires_auth {
-            self.validate_authentication(&processed_request)?;
+        let principal = if endpoint.requires_auth {
+            Some(self.validate_authentication(&processed_request).await.map_err(|e| (401, e))?)
+        } else {
+            None
+        };
+        
+        // Everything audited while handling the request is attributed to it
+        let context = self.request_context(&processed_request, principal.as_ref()).await;
+        let (status_code, response_body) = context.scope(async {
+            // Apply access policies, including to unauthenticated endpoints
+            self.check_policies(&processed_request, endpoint, principal.as_ref()).await?;
+            
+            // Check rate limiting
+            if let Some(rate_limit) = endpoint.rate_limit {
+                self.check_rate_limit(&processed_request, rate_limit)?;
+            }
+            
+            // Process the request
+            self.handle_request(&processed_request, endpoint, principal.as_ref()).await
+        }).await.map_err(|e| (Self::error_status(&e), e))?;
+        
+        let mut headers = self.get_default_headers();
+        if endpoint.handler == "scim" {
+            headers.insert("Content-Type".to_string(), SCIM_CONTENT_TYPE.to_string());
         }
         
-        // Check rate limiting
-        if let Some(rate_limit) = endpoint.rate_limit {
-            self.check_rate_limit(&processed_request, rate_limit)?;
+        Ok((status_code, headers, response_body))
+    }
+    
+    /// Status code answering an error raised while handling a request
+    ///
+    /// A feature that isn't enabled, or something that isn't there, is answered as not found.
+    fn error_status(error: &DreasError) -> u16 {
+        match error {
+            DreasError::Authentication(_) => 403,
+            DreasError::Configuration(message)
+                if ["not enabled", "not found", "does not exist"].iter().any(|phrase| message.contains(phrase)) => 404,
+            _ => 500,
+        }
+    }
+    
+    /// Find the endpoint for a request, falling back to the longest `/*` prefix endpoint
+    fn find_endpoint(&self, request: &ApiRequest) -> Option<&ApiEndpoint> {
+        let endpoint_key = format!("{}:{}", request.method.clone() as u8, request.path);
+        if let Some(endpoint) = self.endpoints.get(&endpoint_key) {
+            return Some(endpoint);
         }
         
-        // Process the request
-        let response_body = self.handle_request(&processed_request, endpoint).await?;
+        self.endpoints.values()
+            .filter(|endpoint| endpoint.method == request.method)
+            .filter(|endpoint| {
+                endpoint.path.strip_suffix('*')
+                    .is_some_and(|prefix| prefix.ends_with('/') && request.path.starts_with(prefix))
+            })
+            .max_by_key(|endpoint| endpoint.path.len())
+    }
+    
+    /// Validate authentication and resolve the caller
+    async fn validate_authentication(&self, request: &ApiRequest) -> DreasResult<Principal> {
+        let token = request.header("Authorization")
+            .and_then(|auth_header| auth_header.strip_prefix("Bearer "))
+            .ok_or_else(|| DreasError::Authentication("Missing or invalid authorization header".to_string()))?;
         
-        let processing_time = start_time.elapsed().as_millis() as u64;
+        // API keys carry a fixed prefix; anything else is treated as an access token
+        if token.starts_with(API_KEY_PREFIX) {
+            let identity_manager = self.identity_manager.as_ref()
+                .ok_or_else(|| DreasError::Authentication("API key authentication not configured".to_string()))?;
+            return identity_manager.write().await.authenticate_api_key(token);
+        }
         
-        let response = ApiResponse {
-            request_id: processed_request.request_id,
-            status_code: 200,
-            headers: self.get_default_headers(),
-            body: Some(response_body),
-            processing_time_ms: processing_time,
-            timestamp: Utc::now(),
-        };
+        let verifier = self.token_verifier.as_ref()
+            .ok_or_else(|| DreasError::Authentication("Access token verification not configured".to_string()))?;
+        let claims = verifier.verify(token)?;
         
-        tracing::info!("API request processed: {} {} in {}ms", 
-                      processed_request.method.clone() as u8, 
-                      processed_request.path, 
-                      processing_time);
+        // Tokens outlive revocation, so check the session too when sessions are managed here
+        if let Some(identity_manager) = &self.identity_manager {
+            identity_manager.read().await.validate_session(&claims.sid, &self.client_info(request))?;
+        }
         
-        Ok(response)
+        Ok(Principal::User {
+            user_id: claims.sub,
+            session_id: claims.sid,
+            roles: claims.roles,
+        })
     }
     
-    /// Validate authentication
-    fn validate_authentication(&self, request: &ApiRequest) -> DreasResult<()> {
-        // TODO: Implement actual authentication validation
-        // This is a placeholder implementation
+    /// Describe the client that sent a request
+    fn client_info(&self, request: &ApiRequest) -> ClientInfo {
+        let client = ClientInfo::from_ip(self.client_ip(request).map(|ip| ip.to_string()).as_deref());
+        match request.header("User-Agent") {
+            Some(user_agent) => client.with_user_agent(user_agent),
+            None => client,
+        }
+    }
+    
+    /// Find the address of the client that sent a request
+    ///
+    /// `X-Forwarded-For` is read from the nearest hop back, and only while each hop was added by
+    /// a trusted proxy; anything further along can be forged by the client.
+    fn client_ip(&self, request: &ApiRequest) -> Option<IpAddr> {
+        let mut client = request.peer_address?;
+        let forwarded = request.header("X-Forwarded-For").unwrap_or_default();
         
-        if let Some(auth_header) = request.headers.get("Authorization") {
-            if auth_header.starts_with("Bearer ") {
-                return Ok(());
+        for hop in forwarded.rsplit(',') {
+            if !self.is_trusted_proxy(client) {
+                break;
+            }
+            match hop.trim().parse() {
+                Ok(ip) => client = ip,
+                Err(_) => break,
             }
         }
         
-        Err(DreasError::Authentication("Missing or invalid authorization header".to_string()))
+        Some(client)
+    }
+    
+    /// Check whether an address belongs to a trusted proxy
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|&(network, prefix)| cidr_contains(network, prefix, ip))
+    }
+    
+    /// Build the audit context of a request from its headers and caller
+    async fn request_context(&self, request: &ApiRequest, principal: Option<&Principal>) -> RequestContext {
+        let mut context = RequestContext::new(request.request_id).with_client(self.client_info(request));
+        if let Some(trace_id) = trace_id_from_headers(&request.headers) {
+            context = context.with_trace_id(trace_id);
+        }
+        
+        let principal = match principal {
+            Some(principal) => principal,
+            None => return context,
+        };
+        
+        if let Principal::User { session_id, .. } = principal {
+            context = context.with_session(session_id.clone());
+        }
+        let identity_manager = match &self.identity_manager {
+            Some(identity_manager) => identity_manager.read().await,
+            None => return context.with_caller(principal.to_policy_entity()),
+        };
+        
+        context = context.with_caller(identity_manager.principal_entity(principal));
+        if let Some(tenant_id) = identity_manager.principal_tenant(principal) {
+            context = context.with_tenant(tenant_id);
+        }
+        
+        context
+    }
+    
+    /// Evaluate access policies for a request as action `api:<handler>` on the endpoint
+    async fn check_policies(
+        &self,
+        request: &ApiRequest,
+        endpoint: &ApiEndpoint,
+        principal: Option<&Principal>,
+    ) -> DreasResult<()> {
+        let policy_engine = match &self.policy_engine {
+            Some(policy_engine) => policy_engine,
+            None => return Ok(()),
+        };
+        
+        let principal = match (principal, &self.identity_manager) {
+            (Some(principal), Some(identity_manager)) => identity_manager.read().await.principal_entity(principal),
+            (Some(principal), None) => principal.to_policy_entity(),
+            (None, _) => PolicyEntity::new("anonymous", "anonymous"),
+        };
+        
+        let mut policy_request = PolicyRequest::new(
+            principal,
+            &format!("api:{}", endpoint.handler),
+            PolicyEntity::new("endpoint", &endpoint.path),
+        )
+        .with_context("method", format!("{:?}", request.method))
+        .with_context("path", request.path.clone())
+        .with_context("request_id", request.request_id.to_string());
+        
+        if let Some(client_ip) = self.client_ip(request) {
+            policy_request = policy_request.with_context("ip", client_ip.to_string());
+        }
+        
+        let decision = policy_engine.evaluate(&policy_request).await?;
+        if decision.is_forbidden() {
+            return Err(DreasError::Authentication(format!(
+                "Access denied by policy {}", decision.forbidden_by.join(", ")
+            )));
+        }
+        
+        Ok(())
     }
     
     /// Check rate limiting
@@ -188,25 +539,198 @@ impl ApiService {
     }
     
     /// Handle the actual request
-    async fn handle_request(&self, request: &ApiRequest, endpoint: &ApiEndpoint) -> DreasResult<String> {
+    async fn handle_request(
+        &self,
+        request: &ApiRequest,
+        endpoint: &ApiEndpoint,
+        principal: Option<&Principal>,
+    ) -> DreasResult<(u16, Option<String>)> {
         // TODO: Implement actual request handling based on endpoint handler
         // This is a placeholder implementation
         
-        match endpoint.handler.as_str() {
-            "health_check" => Ok(serde_json::json!({
+        let body = match endpoint.handler.as_str() {
+            "health_check" => serde_json::json!({
                 "status": "healthy",
                 "service_id": self.service_id,
                 "timestamp": Utc::now()
-            }).to_string()),
-            "get_stats" => Ok(self.get_service_stats().to_string()),
-            _ => Ok(serde_json::json!({
+            }).to_string(),
+            "get_stats" => self.get_service_stats().to_string(),
+            "jwks" => {
+                let jwks = self.jwks.as_ref()
+                    .ok_or_else(|| DreasError::Configuration("Token signing keys not configured".to_string()))?;
+                serde_json::to_string(jwks)?
+            }
+            "scim" => return self.handle_scim_request(request, principal).await,
+            "audit_search" => return self.handle_audit_search(request, principal).await,
+            "audit_stream" => {
+                let error = DreasError::Generic("Audit streams are only served as Server-Sent Events".to_string());
+                return Ok((406, Some(self.error_body(&error))));
+            }
+            _ => serde_json::json!({
                 "message": "Request processed",
                 "handler": endpoint.handler,
-                "request_id": request.request_id
-            }).to_string()),
+                "request_id": request.request_id,
+                "principal": principal.map(|principal| principal.id())
+            }).to_string(),
+        };
+        
+        Ok((200, Some(body)))
+    }
+    
+    /// Handle a SCIM provisioning request from a principal holding the provisioning permission
+    async fn handle_scim_request(
+        &self,
+        request: &ApiRequest,
+        principal: Option<&Principal>,
+    ) -> DreasResult<(u16, Option<String>)> {
+        let scim = self.scim.as_ref()
+            .ok_or_else(|| DreasError::Configuration("SCIM provisioning not enabled".to_string()))?;
+        self.require_permission(principal, SCIM_PERMISSION, "SCIM").await?;
+        
+        let path = request.path.strip_prefix(SCIM_BASE_PATH).unwrap_or(&request.path);
+        let response = scim.handle(&request.method, path, &request.query_params, request.body.as_deref()).await;
+        
+        // SCIM error messages can echo back request data
+        let body = response.body.map(|body| match &self.redactor {
+            Some(redactor) if response.status_code >= 400 => redactor.redact_body(&body.to_string()),
+            _ => body.to_string(),
+        });
+        Ok((response.status_code, body))
+    }
+    
+    /// Search the audit log for a principal allowed to read it, within its own tenant unless it may read every tenant's
+    async fn handle_audit_search(
+        &self,
+        request: &ApiRequest,
+        principal: Option<&Principal>,
+    ) -> DreasResult<(u16, Option<String>)> {
+        let audit_logger = self.audit_logger.as_ref()
+            .ok_or_else(|| DreasError::Configuration("Audit search not enabled".to_string()))?;
+        self.require_permission(principal, AUDIT_READ_PERMISSION, "Audit search").await?;
+        
+        // Malformed queries and cursors are the caller's mistake
+        let bad_request = |e: DreasError| (400, Some(self.error_body(&e)));
+        let mut search = match AuditSearch::from_query_params(&request.query_params) {
+            Ok(search) => search,
+            Err(e) => return Ok(bad_request(e)),
+        };
+        let principal = principal
+            .ok_or_else(|| DreasError::Authentication("Audit search requests must be authenticated".to_string()))?;
+        search.filter = self.scope_audit_filter(principal, search.filter).await?;
+        
+        match audit_logger.lock().await.search(&search) {
+            Ok(page) => Ok((200, Some(serde_json::to_string(&page)?))),
+            Err(e) => Ok(bad_request(e)),
         }
     }
     
+    /// Open an audit event stream for a request to the audit stream endpoint
+    ///
+    /// The request is authenticated and checked against access policies like any other, and
+    /// its principal must hold `audit:read`, and `audit:read_all_tenants` to see entries of
+    /// tenants other than its own. A `Last-Event-ID` header replays the retained matching
+    /// entries logged after that sequence number.
+    pub async fn open_audit_stream(&self, request: ApiRequest) -> DreasResult<AuditEventStream> {
+        let mut request = request;
+        for middleware in &self.middleware {
+            middleware(&mut request)?;
+        }
+        
+        let endpoint = self.find_endpoint(&request)
+            .filter(|endpoint| endpoint.handler == "audit_stream")
+            .ok_or_else(|| DreasError::Generic(format!("Not an audit stream endpoint: {}", request.path)))?;
+        let audit_logger = self.audit_logger.as_ref()
+            .ok_or_else(|| DreasError::Configuration("Audit streaming not enabled".to_string()))?;
+        let identity_manager = self.identity_manager.clone()
+            .ok_or_else(|| DreasError::Configuration("Audit streaming requires an identity manager".to_string()))?;
+        let principal = self.validate_authentication(&request).await?;
+        
+        let context = self.request_context(&request, Some(&principal)).await;
+        let subscription = context.scope(async {
+            self.check_policies(&request, endpoint, Some(&principal)).await?;
+            if let Some(rate_limit) = endpoint.rate_limit {
+                self.check_rate_limit(&request, rate_limit)?;
+            }
+            self.require_permission(Some(&principal), AUDIT_READ_PERMISSION, "Audit streaming").await?;
+            
+            let filter = self.scope_audit_filter(
+                &principal,
+                AuditFilter::from_query_params(&request.query_params)?,
+            ).await?;
+            let last_event_id = request.header("Last-Event-ID")
+                .map(|value| value.trim().parse::<u64>()
+                    .map_err(|_| DreasError::Generic(format!("Invalid Last-Event-ID {:?}", value))))
+                .transpose()?;
+            
+            let audit_logger = audit_logger.lock().await;
+            Ok::<_, DreasError>(match last_event_id {
+                Some(sequence) => audit_logger.subscribe_after(filter, sequence),
+                None => audit_logger.subscribe(filter),
+            })
+        }).await?;
+        
+        let mut headers = self.get_default_headers();
+        headers.insert("Content-Type".to_string(), EVENT_STREAM_CONTENT_TYPE.to_string());
+        headers.insert("Cache-Control".to_string(), "no-cache".to_string());
+        
+        tracing::info!("Audit stream opened for {}", principal.id());
+        Ok(AuditEventStream {
+            subscription,
+            principal,
+            identity_manager,
+            headers,
+            recheck_interval: self.audit_stream_recheck_interval,
+            checked_at: Instant::now(),
+        })
+    }
+    
+    /// Restrict an audit filter to the principal's own tenant unless it may read every tenant's entries
+    async fn scope_audit_filter(&self, principal: &Principal, filter: AuditFilter) -> DreasResult<AuditFilter> {
+        let identity_manager = self.identity_manager.as_ref()
+            .ok_or_else(|| DreasError::Configuration("Audit access requires an identity manager".to_string()))?
+            .read()
+            .await;
+        
+        let all_tenants = identity_manager
+            .check_principal_permission(principal, AUDIT_READ_ALL_TENANTS_PERMISSION)
+            .await?;
+        if all_tenants.allowed {
+            return Ok(filter);
+        }
+        
+        // Principals without a tenant of their own belong to the default one
+        let tenant_id = identity_manager.principal_tenant(principal).unwrap_or_default();
+        Ok(AuditFilter::all_of(vec![filter, AuditFilter::Tenant(tenant_id)]))
+    }
+    
+    /// Check an authenticated principal holds a permission
+    async fn require_permission(&self, principal: Option<&Principal>, permission: &str, feature: &str) -> DreasResult<()> {
+        let identity_manager = self.identity_manager.as_ref()
+            .ok_or_else(|| DreasError::Configuration(format!("{} requires an identity manager", feature)))?;
+        let principal = principal
+            .ok_or_else(|| DreasError::Authentication(format!("{} requests must be authenticated", feature)))?;
+        
+        let permission = identity_manager.read().await
+            .check_principal_permission(principal, permission)
+            .await?;
+        if !permission.allowed {
+            return Err(DreasError::Authentication(format!(
+                "{} access denied: {}", feature, permission.reason.unwrap_or_default()
+            )));
+        }
+        
+        Ok(())
+    }
+    
+    /// Render an error as a JSON response body, redacted if a redactor is configured
+    pub fn error_body(&self, error: &DreasError) -> String {
+        let message = match &self.redactor {
+            Some(redactor) => redactor.redact_text(&error.to_string()),
+            None => error.to_string(),
+        };
+        serde_json::json!({ "error": message }).to_string()
+    }
+    
     /// Get default HTTP headers
     fn get_default_headers(&self) -> HashMap<String, String> {
         let mut headers = HashMap::new();
@@ -272,3 +796,72 @@ impl ApiService {
         Ok(())
     }
 }
+
+impl ApiRequest {
+    /// Get a header value, matching the name regardless of case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl AuditEventStream {
+    /// Headers of the streaming response
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+    
+    /// Filter entries must match to be sent
+    pub fn filter(&self) -> &AuditFilter {
+        self.subscription.filter()
+    }
+    
+    /// Wait for the next event, encoded as a Server-Sent Events frame
+    ///
+    /// Returns `None` once the audit logger is gone. Fails, ending the stream, once the
+    /// subscriber may no longer read the audit log, whether or not entries are arriving.
+    pub async fn next_frame(&mut self) -> DreasResult<Option<String>> {
+        // Permissions revoked while the stream is open take effect within the interval
+        let event = loop {
+            let recheck_at = tokio::time::Instant::from_std(self.checked_at + self.recheck_interval);
+            tokio::select! {
+                biased;
+                _ = tokio::time::sleep_until(recheck_at) => self.recheck_permission().await?,
+                event = self.subscription.next() => match event {
+                    Some(event) => break event,
+                    None => return Ok(None),
+                },
+            }
+        };
+        
+        let frame = match event {
+            AuditEvent::Entry(entry) => format!(
+                "id: {}\nevent: audit_entry\ndata: {}\n\n",
+                entry.sequence,
+                serde_json::to_string(entry.as_ref())?
+            ),
+            AuditEvent::Lagged { missed } => format!(
+                "event: lagged\ndata: {}\n\n",
+                serde_json::json!({ "missed": missed })
+            ),
+        };
+        Ok(Some(frame))
+    }
+    
+    /// Check the subscriber still holds the permission to read the audit log
+    async fn recheck_permission(&mut self) -> DreasResult<()> {
+        let permission = self.identity_manager.read().await
+            .check_principal_permission(&self.principal, AUDIT_READ_PERMISSION)
+            .await?;
+        if !permission.allowed {
+            tracing::warn!("Audit stream closed for {}: permission revoked", self.principal.id());
+            return Err(DreasError::Authentication(format!(
+                "Audit streaming access denied: {}", permission.reason.unwrap_or_default()
+            )));
+        }
+        
+        self.checked_at = Instant::now();
+        Ok(())
+    }
+}
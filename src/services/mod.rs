@@ -10,6 +10,7 @@ pub mod storage;
 pub mod model;
 pub mod api;
 pub mod observer;
+pub mod scim;
 
 pub use storage::StorageService;
 pub use model::ModelService;
@@ -7,8 +7,11 @@
 //! managing model configurations, and ensuring secure communication.
 
 use crate::{DreasResult, DreasError};
+use crate::security::audit::{actions, AuditEntry, AuditHandle, AuditLogger, AuditResult};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
@@ -18,6 +21,7 @@ pub struct ModelService {
     service_id: Uuid,
     available_models: HashMap<String, ModelConfig>,
     active_connections: HashMap<String, ModelConnection>,
+    audit_logger: AuditHandle,
 }
 
 /// Model configuration
@@ -76,9 +80,16 @@ impl ModelService {
             service_id: Uuid::new_v4(),
             available_models: HashMap::new(),
             active_connections: HashMap::new(),
+            audit_logger: AuditHandle::detached(),
         }
     }
     
+    /// Record every model request in the audit log
+    pub fn with_audit_logger(mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> Self {
+        self.audit_logger = AuditHandle::new(audit_logger);
+        self
+    }
+    
     /// Register a model configuration
     pub async fn register_model(&mut self, config: ModelConfig) -> DreasResult<()> {
         let name = config.name.clone();
@@ -122,11 +133,14 @@ impl ModelService {
         let start_time = std::time::Instant::now();
         
         // Get model configuration
-        let config = self.available_models.get(&request.model_name)
-            .ok_or_else(|| DreasError::Generic(format!("Model {} not found", request.model_name)))?;
-        
-        if !config.enabled {
-            return Err(DreasError::Generic(format!("Model {} is disabled", request.model_name)));
+        let available = match self.available_models.get(&request.model_name) {
+            Some(config) if config.enabled => Ok(()),
+            Some(_) => Err(DreasError::Generic(format!("Model {} is disabled", request.model_name))),
+            None => Err(DreasError::Generic(format!("Model {} not found", request.model_name))),
+        };
+        if let Err(e) = available {
+            self.audit_request(&request, None, Some(&e)).await?;
+            return Err(e);
         }
         
         // Establish or update connection
@@ -154,10 +168,39 @@ impl ModelService {
             connection.request_count += 1;
         }
         
+        self.audit_request(&request, Some(&response), None).await?;
+        
         tracing::info!("Model request completed: {} in {}ms", request.model_name, processing_time);
         Ok(response)
     }
     
+    /// Record a model request in the audit log, failed if `error` is set
+    async fn audit_request(
+        &self,
+        request: &ModelRequest,
+        response: Option<&ModelResponse>,
+        error: Option<&DreasError>,
+    ) -> DreasResult<()> {
+        let result = if error.is_some() { AuditResult::Failure } else { AuditResult::Success };
+        
+        let mut entry = AuditEntry::new(actions::MODEL_REQUEST, format!("model:{}", request.model_name), result)
+            .with_metadata("request_id", request.request_id.to_string())
+            .with_metadata("prompt_length", request.prompt.len().to_string());
+        if let Some(user_id) = request.metadata.get("user_id") {
+            entry = entry.with_user(user_id);
+        }
+        if let Some(response) = response {
+            entry = entry
+                .with_metadata("tokens_used", response.tokens_used.to_string())
+                .with_metadata("processing_time_ms", response.processing_time_ms.to_string());
+        }
+        if let Some(error) = error {
+            entry = entry.with_metadata("error", error.to_string());
+        }
+        
+        self.audit_logger.record(entry).await
+    }
+    
     /// Establish connection to a model
     async fn establish_connection(&mut self, model_name: &str) -> DreasResult<String> {
         let connection_id = Uuid::new_v4().to_string();
@@ -7,6 +7,7 @@
 //! for the DREAS framework, enabling comprehensive system health monitoring.
 
 use crate::{DreasResult, DreasError};
+use crate::security::audit::AuditEntry;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -41,6 +42,9 @@ pub struct Alert {
     pub triggered_at: DateTime<Utc>,
     pub resolved_at: Option<DateTime<Utc>>,
     pub metadata: HashMap<String, String>,
+    /// Audit entries supporting the alert
+    #[serde(default)]
+    pub evidence: Vec<AuditEntry>,
 }
 
 /// Alert severity levels
@@ -158,6 +162,18 @@ impl ObserverService {
         name: String,
         severity: AlertSeverity,
         message: String,
+    ) -> DreasResult<Uuid> {
+        self.create_alert_with_evidence(name, severity, message, HashMap::new(), Vec::new()).await
+    }
+    
+    /// Create an alert with details and the audit entries that support it
+    pub async fn create_alert_with_evidence(
+        &mut self,
+        name: String,
+        severity: AlertSeverity,
+        message: String,
+        metadata: HashMap<String, String>,
+        evidence: Vec<AuditEntry>,
     ) -> DreasResult<Uuid> {
         let alert_id = Uuid::new_v4();
         
@@ -168,7 +184,8 @@ impl ObserverService {
             message,
             triggered_at: Utc::now(),
             resolved_at: None,
-            metadata: HashMap::new(),
+            metadata,
+            evidence,
         };
         
         self.alerts.push(alert.clone());
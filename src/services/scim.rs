@@ -0,0 +1,943 @@
+//! SCIM 2.0 user and group provisioning
+//! 
+//! Author: Kiran Kumar Balijepalli
+//! Date: October 2026
+//! 
+//! This module maps the SCIM 2.0 `/Users` and `/Groups` resources (RFC 7643/7644) onto
+//! the identity manager's users and roles, so an identity provider can create, update,
+//! deactivate and list DREAS accounts without custom scripts.
+
+use crate::DreasError;
+use crate::security::identity::{IdentityManager, Role, User};
+use super::api::HttpMethod;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use chrono::{DateTime, Utc};
+
+/// Content type of SCIM request and response bodies
+pub const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+/// Permission a caller needs to use the SCIM endpoints
+pub const SCIM_PERMISSION: &str = "identity:provision";
+
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+const LIST_RESPONSE_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const PATCH_OP_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+
+/// User attributes holding SCIM fields that have no dedicated `User` field
+const EXTERNAL_ID_ATTRIBUTE: &str = "scim_external_id";
+const DISPLAY_NAME_ATTRIBUTE: &str = "display_name";
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// SCIM User resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUser {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    pub user_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub emails: Vec<ScimEmail>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    /// Accepted on create, never returned
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    /// Read-only; membership is managed through `/Groups`
+    #[serde(default)]
+    pub groups: Vec<ScimMember>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+/// Email address of a SCIM user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScimEmail {
+    pub value: String,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub email_type: Option<String>,
+    #[serde(default)]
+    pub primary: bool,
+}
+
+/// Reference from a group to a member, or from a user to a group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScimMember {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(rename = "$ref", default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+}
+
+/// Resource metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimMeta {
+    pub resource_type: String,
+    pub created: DateTime<Utc>,
+    pub location: String,
+}
+
+/// SCIM Group resource, backed by a role
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroup {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub display_name: String,
+    #[serde(default)]
+    pub members: Vec<ScimMember>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+/// SCIM PATCH request body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScimPatchRequest {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
+
+/// A single PATCH operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScimPatchOperation {
+    pub op: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub value: Option<Value>,
+}
+
+/// Paged list of resources
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListResponse {
+    pub schemas: Vec<String>,
+    pub total_results: usize,
+    pub start_index: usize,
+    pub items_per_page: usize,
+    #[serde(rename = "Resources")]
+    pub resources: Vec<Value>,
+}
+
+/// Status code and body of a SCIM response
+#[derive(Debug, Clone)]
+pub struct ScimResponse {
+    pub status_code: u16,
+    pub body: Option<Value>,
+}
+
+/// SCIM error, returned to the client in the SCIM error format
+#[derive(Debug, Clone)]
+struct ScimError {
+    status_code: u16,
+    scim_type: Option<&'static str>,
+    detail: String,
+}
+
+/// Parsed `filter` query parameter: comparisons joined by `and`, with `or` between groups
+#[derive(Debug, Clone)]
+struct ScimFilter {
+    any_of: Vec<Vec<Comparison>>,
+}
+
+/// A single `attribute operator value` comparison
+#[derive(Debug, Clone)]
+struct Comparison {
+    attribute: String,
+    operator: String,
+    value: Value,
+}
+
+/// SCIM provisioning handler for the `/Users` and `/Groups` endpoints
+#[derive(Debug, Clone)]
+pub struct ScimProvisioner {
+    identity_manager: Arc<RwLock<IdentityManager>>,
+    base_url: String,
+}
+
+type ScimResult<T> = Result<T, ScimError>;
+
+impl ScimProvisioner {
+    /// Create a provisioner whose resource locations are relative to `base_url`
+    pub fn new(identity_manager: Arc<RwLock<IdentityManager>>, base_url: &str) -> Self {
+        Self {
+            identity_manager,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+    
+    /// Handle a request for a path below the SCIM base, e.g. `/Users/{id}`
+    pub async fn handle(
+        &self,
+        method: &HttpMethod,
+        path: &str,
+        query_params: &HashMap<String, String>,
+        body: Option<&str>,
+    ) -> ScimResponse {
+        self.route(method, path, query_params, body)
+            .await
+            .unwrap_or_else(|error| error.into_response())
+    }
+    
+    /// Dispatch a request to the resource handler for its path and method
+    async fn route(
+        &self,
+        method: &HttpMethod,
+        path: &str,
+        query_params: &HashMap<String, String>,
+        body: Option<&str>,
+    ) -> ScimResult<ScimResponse> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        
+        match (segments.as_slice(), method) {
+            (["Users"], HttpMethod::GET) => self.list_users(query_params).await,
+            (["Users"], HttpMethod::POST) => self.create_user(parse_body(body)?).await,
+            (["Users", id], HttpMethod::GET) => self.get_user(id).await,
+            (["Users", id], HttpMethod::PATCH) => self.patch_user(id, parse_body(body)?).await,
+            (["Users", id], HttpMethod::DELETE) => self.deactivate_user(id).await,
+            (["Groups"], HttpMethod::GET) => self.list_groups(query_params).await,
+            (["Groups"], HttpMethod::POST) => self.create_group(parse_body(body)?).await,
+            (["Groups", id], HttpMethod::GET) => self.get_group(id).await,
+            (["Groups", id], HttpMethod::PATCH) => self.patch_group(id, parse_body(body)?).await,
+            (["Groups", id], HttpMethod::DELETE) => self.delete_group(id).await,
+            (["Users" | "Groups", ..], _) => Err(ScimError::new(405, None, "Method not supported for this resource")),
+            _ => Err(ScimError::not_found(path)),
+        }
+    }
+    
+    /// List users, optionally filtered and paged
+    async fn list_users(&self, query_params: &HashMap<String, String>) -> ScimResult<ScimResponse> {
+        let identity_manager = self.identity_manager.read().await;
+        let resources = identity_manager.list_users()
+            .iter()
+            .map(|user| to_value(&self.user_resource(&identity_manager, user)))
+            .collect::<ScimResult<Vec<Value>>>()?;
+        
+        list_response(resources, query_params)
+    }
+    
+    /// Get a single user
+    async fn get_user(&self, user_id: &str) -> ScimResult<ScimResponse> {
+        let identity_manager = self.identity_manager.read().await;
+        let user = identity_manager.get_user(user_id)
+            .ok_or_else(|| ScimError::not_found(user_id))?;
+        
+        ScimResponse::ok(200, &self.user_resource(&identity_manager, &user))
+    }
+    
+    /// Create a user, with a local password if one was supplied
+    async fn create_user(&self, resource: ScimUser) -> ScimResult<ScimResponse> {
+        if resource.user_name.trim().is_empty() {
+            return Err(ScimError::new(400, Some("invalidValue"), "userName is required"));
+        }
+        
+        let mut identity_manager = self.identity_manager.write().await;
+        if identity_manager.list_users().iter().any(|user| user.username == resource.user_name) {
+            return Err(ScimError::new(409, Some("uniqueness"), "userName is already in use"));
+        }
+        
+        let email = primary_email(&resource.emails).unwrap_or_default();
+        let user = match resource.password.clone() {
+            Some(password) => identity_manager
+                .create_user(resource.user_name.clone(), email, password, Vec::new())
+                .await
+                .map_err(ScimError::invalid_value)?,
+            None => identity_manager
+                .provision_user(resource.user_name.clone(), email, Vec::new())
+                .map_err(ScimError::invalid_value)?,
+        };
+        
+        let mut updated = user.clone();
+        set_optional_attribute(&mut updated, EXTERNAL_ID_ATTRIBUTE, resource.external_id);
+        set_optional_attribute(&mut updated, DISPLAY_NAME_ATTRIBUTE, resource.display_name);
+        updated.is_active = resource.active;
+        let user = identity_manager.update_user(updated).await.map_err(ScimError::invalid_value)?;
+        
+        tracing::info!("SCIM provisioned user: {}", user.username);
+        ScimResponse::ok(201, &self.user_resource(&identity_manager, &user))
+    }
+    
+    /// Apply PATCH operations to a user
+    async fn patch_user(&self, user_id: &str, request: ScimPatchRequest) -> ScimResult<ScimResponse> {
+        check_patch_schema(&request)?;
+        
+        let mut identity_manager = self.identity_manager.write().await;
+        let mut user = identity_manager.get_user(user_id)
+            .ok_or_else(|| ScimError::not_found(user_id))?;
+        
+        for operation in &request.operations {
+            let op = operation.op.to_lowercase();
+            match (&operation.path, &operation.value) {
+                (Some(path), value) => apply_user_change(&mut user, &op, path, value.as_ref())?,
+                // Without a path, the value is an object of attribute changes
+                (None, Some(Value::Object(changes))) => {
+                    for (path, value) in changes {
+                        apply_user_change(&mut user, &op, path, Some(value))?;
+                    }
+                }
+                (None, _) => return Err(ScimError::new(400, Some("noTarget"), "PATCH operation needs a path or an object value")),
+            }
+        }
+        
+        if user.username.trim().is_empty() {
+            return Err(ScimError::new(400, Some("invalidValue"), "userName is required"));
+        }
+        
+        let user = identity_manager.update_user(user).await.map_err(ScimError::conflict_or_invalid)?;
+        ScimResponse::ok(200, &self.user_resource(&identity_manager, &user))
+    }
+    
+    /// Deactivate a user and revoke their sessions; accounts are never hard-deleted
+    async fn deactivate_user(&self, user_id: &str) -> ScimResult<ScimResponse> {
+        let mut identity_manager = self.identity_manager.write().await;
+        if identity_manager.get_user(user_id).is_none() {
+            return Err(ScimError::not_found(user_id));
+        }
+        
+        let user = identity_manager.deactivate_user(user_id).await.map_err(ScimError::invalid_value)?;
+        tracing::info!("SCIM deactivated user: {}", user.username);
+        
+        Ok(ScimResponse {
+            status_code: 204,
+            body: None,
+        })
+    }
+    
+    /// List groups, optionally filtered and paged
+    async fn list_groups(&self, query_params: &HashMap<String, String>) -> ScimResult<ScimResponse> {
+        let identity_manager = self.identity_manager.read().await;
+        let users = identity_manager.list_users();
+        let resources = identity_manager.list_roles()
+            .iter()
+            .map(|role| to_value(&self.group_resource(role, &users)))
+            .collect::<ScimResult<Vec<Value>>>()?;
+        
+        list_response(resources, query_params)
+    }
+    
+    /// Get a single group
+    async fn get_group(&self, name: &str) -> ScimResult<ScimResponse> {
+        let identity_manager = self.identity_manager.read().await;
+        let role = identity_manager.get_role(name)
+            .ok_or_else(|| ScimError::not_found(name))?;
+        
+        ScimResponse::ok(200, &self.group_resource(&role, &identity_manager.list_users()))
+    }
+    
+    /// Create a group as a role without permissions, adding the listed members
+    async fn create_group(&self, resource: ScimGroup) -> ScimResult<ScimResponse> {
+        let name = resource.display_name.trim().to_string();
+        if name.is_empty() {
+            return Err(ScimError::new(400, Some("invalidValue"), "displayName is required"));
+        }
+        
+        let mut identity_manager = self.identity_manager.write().await;
+        if identity_manager.get_role(&name).is_some() {
+            return Err(ScimError::new(409, Some("uniqueness"), "A group with this displayName already exists"));
+        }
+        
+        let member_ids: Vec<String> = resource.members.into_iter().map(|member| member.value).collect();
+        check_members_exist(&identity_manager, &member_ids)?;
+        
+        let role = identity_manager
+            .create_role(name.clone(), Vec::new(), "Provisioned through SCIM".to_string())
+            .await
+            .map_err(ScimError::invalid_value)?;
+        set_role_members(&mut identity_manager, &name, &member_ids, &[]).await?;
+        
+        tracing::info!("SCIM provisioned group: {}", name);
+        ScimResponse::ok(201, &self.group_resource(&role, &identity_manager.list_users()))
+    }
+    
+    /// Apply PATCH operations to a group's membership
+    async fn patch_group(&self, name: &str, request: ScimPatchRequest) -> ScimResult<ScimResponse> {
+        check_patch_schema(&request)?;
+        
+        let mut identity_manager = self.identity_manager.write().await;
+        let role = identity_manager.get_role(name)
+            .ok_or_else(|| ScimError::not_found(name))?;
+        
+        for operation in &request.operations {
+            let op = operation.op.to_lowercase();
+            
+            // Without a path, the value is an object of attribute changes
+            let changes: Vec<(String, Option<Value>)> = match (&operation.path, &operation.value) {
+                (Some(path), value) => vec![(path.to_lowercase(), value.clone())],
+                (None, Some(Value::Object(changes))) => changes.iter()
+                    .map(|(path, value)| (path.to_lowercase(), Some(value.clone())))
+                    .collect(),
+                (None, _) => return Err(ScimError::new(400, Some("noTarget"), "PATCH operation needs a path or an object value")),
+            };
+            
+            for (path, value) in changes {
+                apply_group_change(&mut identity_manager, name, &op, &path, value.as_ref()).await?;
+            }
+        }
+        
+        ScimResponse::ok(200, &self.group_resource(&role, &identity_manager.list_users()))
+    }
+    
+    /// Delete a group's role, removing it from all members
+    async fn delete_group(&self, name: &str) -> ScimResult<ScimResponse> {
+        let mut identity_manager = self.identity_manager.write().await;
+        if identity_manager.get_role(name).is_none() {
+            return Err(ScimError::not_found(name));
+        }
+        
+        identity_manager.delete_role(name).map_err(ScimError::invalid_value)?;
+        tracing::info!("SCIM deleted group: {}", name);
+        
+        Ok(ScimResponse {
+            status_code: 204,
+            body: None,
+        })
+    }
+    
+    /// Describe a user as a SCIM resource
+    fn user_resource(&self, identity_manager: &IdentityManager, user: &User) -> ScimUser {
+        let emails = if user.email.is_empty() {
+            Vec::new()
+        } else {
+            vec![ScimEmail {
+                value: user.email.clone(),
+                email_type: Some("work".to_string()),
+                primary: true,
+            }]
+        };
+        
+        let groups = user.roles.iter()
+            .filter(|role| identity_manager.get_role(role).is_some())
+            .map(|role| ScimMember {
+                value: role.clone(),
+                display: Some(role.clone()),
+                reference: Some(format!("{}/Groups/{}", self.base_url, role)),
+            })
+            .collect();
+        
+        ScimUser {
+            schemas: vec![USER_SCHEMA.to_string()],
+            id: Some(user.id.clone()),
+            external_id: string_attribute(user, EXTERNAL_ID_ATTRIBUTE),
+            user_name: user.username.clone(),
+            display_name: string_attribute(user, DISPLAY_NAME_ATTRIBUTE),
+            emails,
+            active: user.is_active,
+            password: None,
+            groups,
+            meta: Some(ScimMeta {
+                resource_type: "User".to_string(),
+                created: user.created_at,
+                location: format!("{}/Users/{}", self.base_url, user.id),
+            }),
+        }
+    }
+    
+    /// Describe a role as a SCIM group
+    fn group_resource(&self, role: &Role, users: &[User]) -> ScimGroup {
+        let members = users.iter()
+            .filter(|user| user.roles.contains(&role.name))
+            .map(|user| ScimMember {
+                value: user.id.clone(),
+                display: Some(user.username.clone()),
+                reference: Some(format!("{}/Users/{}", self.base_url, user.id)),
+            })
+            .collect();
+        
+        ScimGroup {
+            schemas: vec![GROUP_SCHEMA.to_string()],
+            id: Some(role.name.clone()),
+            display_name: role.name.clone(),
+            members,
+            meta: Some(ScimMeta {
+                resource_type: "Group".to_string(),
+                created: role.created_at,
+                location: format!("{}/Groups/{}", self.base_url, role.name),
+            }),
+        }
+    }
+}
+
+impl ScimResponse {
+    /// Build a response with a serialized resource body
+    fn ok<T: Serialize>(status_code: u16, resource: &T) -> ScimResult<Self> {
+        Ok(Self {
+            status_code,
+            body: Some(to_value(resource)?),
+        })
+    }
+}
+
+impl ScimError {
+    fn new(status_code: u16, scim_type: Option<&'static str>, detail: &str) -> Self {
+        Self {
+            status_code,
+            scim_type,
+            detail: detail.to_string(),
+        }
+    }
+    
+    fn not_found(id: &str) -> Self {
+        Self::new(404, None, &format!("Resource {} not found", id))
+    }
+    
+    fn invalid_value(error: DreasError) -> Self {
+        Self::new(400, Some("invalidValue"), &error.to_string())
+    }
+    
+    fn invalid_op(op: &str) -> Self {
+        Self::new(400, Some("invalidSyntax"), &format!("Unsupported PATCH operation: {}", op))
+    }
+    
+    /// Renames onto an existing username are conflicts; anything else is a bad value
+    fn conflict_or_invalid(error: DreasError) -> Self {
+        match &error {
+            DreasError::Authentication(message) if message.ends_with("already exists") => {
+                Self::new(409, Some("uniqueness"), message)
+            }
+            _ => Self::invalid_value(error),
+        }
+    }
+    
+    fn into_response(self) -> ScimResponse {
+        let mut body = serde_json::json!({
+            "schemas": [ERROR_SCHEMA],
+            "status": self.status_code.to_string(),
+            "detail": self.detail,
+        });
+        if let Some(scim_type) = self.scim_type {
+            body["scimType"] = scim_type.into();
+        }
+        
+        ScimResponse {
+            status_code: self.status_code,
+            body: Some(body),
+        }
+    }
+}
+
+impl ScimFilter {
+    /// Parse a filter such as `userName eq "alice" and active eq true`
+    fn parse(filter: &str) -> ScimResult<Self> {
+        let tokens = tokenize_filter(filter)?;
+        let mut any_of = vec![Vec::new()];
+        let mut position = 0;
+        
+        while position < tokens.len() {
+            let attribute = tokens[position].to_lowercase();
+            let operator = tokens.get(position + 1)
+                .map(|operator| operator.to_lowercase())
+                .ok_or_else(|| ScimError::new(400, Some("invalidFilter"), "Filter is missing an operator"))?;
+            position += 2;
+            
+            let value = if operator == "pr" {
+                Value::Null
+            } else {
+                let token = tokens.get(position)
+                    .ok_or_else(|| ScimError::new(400, Some("invalidFilter"), "Filter is missing a value"))?;
+                position += 1;
+                parse_filter_value(token)?
+            };
+            
+            if !["eq", "ne", "co", "sw", "ew", "pr"].contains(&operator.as_str()) {
+                return Err(ScimError::new(400, Some("invalidFilter"), &format!("Unsupported filter operator: {}", operator)));
+            }
+            
+            if let Some(group) = any_of.last_mut() {
+                group.push(Comparison { attribute, operator, value });
+            }
+            
+            match tokens.get(position).map(|token| token.to_lowercase()).as_deref() {
+                None => break,
+                Some("and") => {}
+                Some("or") => any_of.push(Vec::new()),
+                Some(other) => {
+                    return Err(ScimError::new(400, Some("invalidFilter"), &format!("Unexpected filter token: {}", other)));
+                }
+            }
+            position += 1;
+        }
+        
+        if any_of.iter().any(|group| group.is_empty()) {
+            return Err(ScimError::new(400, Some("invalidFilter"), "Filter is incomplete"));
+        }
+        
+        Ok(Self { any_of })
+    }
+    
+    /// Check whether a serialized resource satisfies the filter
+    fn matches(&self, resource: &Value) -> bool {
+        self.any_of.iter().any(|group| group.iter().all(|comparison| comparison.matches(resource)))
+    }
+}
+
+impl Comparison {
+    /// Compare every value at the attribute path; multi-valued attributes match if any value does
+    fn matches(&self, resource: &Value) -> bool {
+        let values = resolve_path(resource, &self.attribute);
+        
+        match self.operator.as_str() {
+            "pr" => values.iter().any(|value| !value.is_null() && value.as_str() != Some("")),
+            "ne" => !values.iter().any(|value| values_equal(value, &self.value)),
+            "eq" => values.iter().any(|value| values_equal(value, &self.value)),
+            operator => {
+                let expected = match self.value.as_str() {
+                    Some(expected) => expected.to_lowercase(),
+                    None => return false,
+                };
+                values.iter()
+                    .filter_map(|value| value.as_str())
+                    .map(str::to_lowercase)
+                    .any(|actual| match operator {
+                        "co" => actual.contains(&expected),
+                        "sw" => actual.starts_with(&expected),
+                        _ => actual.ends_with(&expected),
+                    })
+            }
+        }
+    }
+}
+
+/// Deserialize a request body
+fn parse_body<T: serde::de::DeserializeOwned>(body: Option<&str>) -> ScimResult<T> {
+    let body = body.ok_or_else(|| ScimError::new(400, Some("invalidSyntax"), "Request body is required"))?;
+    serde_json::from_str(body)
+        .map_err(|e| ScimError::new(400, Some("invalidSyntax"), &format!("Invalid request body: {}", e)))
+}
+
+/// Serialize a resource
+fn to_value<T: Serialize>(resource: &T) -> ScimResult<Value> {
+    serde_json::to_value(resource)
+        .map_err(|e| ScimError::new(500, None, &format!("Failed to serialize resource: {}", e)))
+}
+
+fn default_active() -> bool {
+    true
+}
+
+/// Require the PatchOp message schema when the client declares schemas
+fn check_patch_schema(request: &ScimPatchRequest) -> ScimResult<()> {
+    if !request.schemas.is_empty() && !request.schemas.iter().any(|schema| schema == PATCH_OP_SCHEMA) {
+        return Err(ScimError::new(400, Some("invalidSyntax"), "PATCH requests must use the PatchOp schema"));
+    }
+    Ok(())
+}
+
+/// Filter and page a list of resources according to the query parameters
+fn list_response(resources: Vec<Value>, query_params: &HashMap<String, String>) -> ScimResult<ScimResponse> {
+    let filter = query_params.get("filter")
+        .map(|filter| ScimFilter::parse(filter))
+        .transpose()?;
+    
+    let matching: Vec<Value> = resources.into_iter()
+        .filter(|resource| filter.as_ref().is_none_or(|filter| filter.matches(resource)))
+        .collect();
+    
+    // startIndex is 1-based; values below 1 are treated as 1
+    let start_index = query_params.get("startIndex")
+        .and_then(|start_index| start_index.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let count = query_params.get("count")
+        .and_then(|count| count.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .min(MAX_PAGE_SIZE);
+    
+    let total_results = matching.len();
+    let page: Vec<Value> = matching.into_iter().skip(start_index - 1).take(count).collect();
+    
+    ScimResponse::ok(200, &ScimListResponse {
+        schemas: vec![LIST_RESPONSE_SCHEMA.to_string()],
+        total_results,
+        start_index,
+        items_per_page: page.len(),
+        resources: page,
+    })
+}
+
+/// Apply one PATCH change to a user
+fn apply_user_change(user: &mut User, op: &str, path: &str, value: Option<&Value>) -> ScimResult<()> {
+    if !["add", "replace", "remove"].contains(&op) {
+        return Err(ScimError::invalid_op(op));
+    }
+    
+    let attribute = path.to_lowercase();
+    let remove = op == "remove";
+    
+    match attribute.as_str() {
+        "active" if !remove => user.is_active = parse_bool(value)?,
+        "username" if !remove => user.username = required_string(value)?,
+        "externalid" => set_optional_attribute(user, EXTERNAL_ID_ATTRIBUTE, optional_string(value, remove)?),
+        "displayname" => set_optional_attribute(user, DISPLAY_NAME_ATTRIBUTE, optional_string(value, remove)?),
+        // Covers `emails`, `emails.value` and `emails[type eq "work"].value`; users have one address
+        attribute if attribute.starts_with("emails") => {
+            user.email = if remove {
+                String::new()
+            } else {
+                match value {
+                    Some(Value::Array(emails)) => {
+                        let emails: Vec<ScimEmail> = serde_json::from_value(Value::Array(emails.clone()))
+                            .map_err(|e| ScimError::new(400, Some("invalidValue"), &format!("Invalid emails: {}", e)))?;
+                        primary_email(&emails).unwrap_or_default()
+                    }
+                    Some(Value::Object(email)) => email.get("value")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                        .unwrap_or_default(),
+                    _ => required_string(value)?,
+                }
+            };
+        }
+        "active" | "username" => {
+            return Err(ScimError::new(400, Some("mutability"), &format!("{} cannot be removed", path)));
+        }
+        _ => return Err(ScimError::new(400, Some("invalidPath"), &format!("Unsupported user attribute: {}", path))),
+    }
+    
+    Ok(())
+}
+
+/// Apply one PATCH change to a group
+async fn apply_group_change(
+    identity_manager: &mut IdentityManager,
+    name: &str,
+    op: &str,
+    path: &str,
+    value: Option<&Value>,
+) -> ScimResult<()> {
+    match path {
+        "displayname" => {
+            // Roles are referenced by name from policies and permissions, so they can't be renamed
+            if value.and_then(Value::as_str) != Some(name) {
+                return Err(ScimError::new(400, Some("mutability"), "Groups cannot be renamed"));
+            }
+        }
+        "members" => {
+            let member_ids = member_ids(value)?;
+            check_members_exist(identity_manager, &member_ids)?;
+            
+            match op {
+                "add" => set_role_members(identity_manager, name, &member_ids, &[]).await?,
+                "remove" => set_role_members(identity_manager, name, &[], &member_ids).await?,
+                "replace" => {
+                    let removed: Vec<String> = role_member_ids(&identity_manager.list_users(), name)
+                        .into_iter()
+                        .filter(|user_id| !member_ids.contains(user_id))
+                        .collect();
+                    set_role_members(identity_manager, name, &member_ids, &removed).await?;
+                }
+                _ => return Err(ScimError::invalid_op(op)),
+            }
+        }
+        // `members[value eq "id"]` selects members to remove
+        path if path.starts_with("members[") && path.ends_with(']') && op == "remove" => {
+            let filter = ScimFilter::parse(&path["members[".len()..path.len() - 1])?;
+            let removed: Vec<String> = role_member_ids(&identity_manager.list_users(), name)
+                .into_iter()
+                .filter(|user_id| filter.matches(&serde_json::json!({ "value": user_id })))
+                .collect();
+            set_role_members(identity_manager, name, &[], &removed).await?;
+        }
+        _ => return Err(ScimError::new(400, Some("invalidPath"), "Unsupported group attribute")),
+    }
+    
+    Ok(())
+}
+
+/// Add and remove a role for the given users
+async fn set_role_members(
+    identity_manager: &mut IdentityManager,
+    role: &str,
+    added: &[String],
+    removed: &[String],
+) -> ScimResult<()> {
+    for user_id in added.iter().chain(removed) {
+        let mut user = identity_manager.get_user(user_id)
+            .ok_or_else(|| ScimError::not_found(user_id))?;
+        
+        user.roles.retain(|existing| existing != role);
+        if added.contains(user_id) {
+            user.roles.push(role.to_string());
+        }
+        
+        identity_manager.update_user(user).await.map_err(ScimError::invalid_value)?;
+    }
+    
+    Ok(())
+}
+
+/// Reject memberships that reference unknown users
+fn check_members_exist(identity_manager: &IdentityManager, member_ids: &[String]) -> ScimResult<()> {
+    match member_ids.iter().find(|user_id| identity_manager.get_user(user_id).is_none()) {
+        Some(user_id) => Err(ScimError::new(400, Some("invalidValue"), &format!("Unknown member: {}", user_id))),
+        None => Ok(()),
+    }
+}
+
+/// IDs of the users holding a role
+fn role_member_ids(users: &[User], role: &str) -> Vec<String> {
+    users.iter()
+        .filter(|user| user.roles.iter().any(|existing| existing == role))
+        .map(|user| user.id.clone())
+        .collect()
+}
+
+/// Extract member IDs from a PATCH value listing members
+fn member_ids(value: Option<&Value>) -> ScimResult<Vec<String>> {
+    let members = value.cloned().unwrap_or(Value::Array(Vec::new()));
+    let members: Vec<ScimMember> = serde_json::from_value(members)
+        .map_err(|e| ScimError::new(400, Some("invalidValue"), &format!("Invalid members: {}", e)))?;
+    Ok(members.into_iter().map(|member| member.value).collect())
+}
+
+/// Pick the primary email, or the first one listed
+fn primary_email(emails: &[ScimEmail]) -> Option<String> {
+    emails.iter()
+        .find(|email| email.primary)
+        .or_else(|| emails.first())
+        .map(|email| email.value.clone())
+}
+
+/// Read a string-valued user attribute
+fn string_attribute(user: &User, name: &str) -> Option<String> {
+    user.attributes.get(name).and_then(Value::as_str).map(str::to_string)
+}
+
+/// Set or clear a string-valued user attribute
+fn set_optional_attribute(user: &mut User, name: &str, value: Option<String>) {
+    match value {
+        Some(value) => user.attributes.insert(name.to_string(), value.into()),
+        None => user.attributes.remove(name),
+    };
+}
+
+/// Read a boolean, accepting the string forms some identity providers send
+fn parse_bool(value: Option<&Value>) -> ScimResult<bool> {
+    match value {
+        Some(Value::Bool(value)) => Ok(*value),
+        Some(Value::String(value)) if value.eq_ignore_ascii_case("true") => Ok(true),
+        Some(Value::String(value)) if value.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(ScimError::new(400, Some("invalidValue"), "Expected a boolean")),
+    }
+}
+
+/// Read a required string value
+fn required_string(value: Option<&Value>) -> ScimResult<String> {
+    value.and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| ScimError::new(400, Some("invalidValue"), "Expected a string"))
+}
+
+/// Read a string value, or nothing for a removal
+fn optional_string(value: Option<&Value>, remove: bool) -> ScimResult<Option<String>> {
+    if remove {
+        return Ok(None);
+    }
+    required_string(value).map(Some)
+}
+
+/// Split a filter into attribute, operator, value and logical tokens
+fn tokenize_filter(filter: &str) -> ScimResult<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = filter.chars().peekable();
+    
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            // Keep quotes so values can be told apart from keywords
+            chars.next();
+            let mut token = String::from('"');
+            loop {
+                match chars.next() {
+                    Some('\\') => token.extend(chars.next()),
+                    Some('"') => break,
+                    Some(c) => token.push(c),
+                    None => return Err(ScimError::new(400, Some("invalidFilter"), "Unterminated string in filter")),
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    
+    Ok(tokens)
+}
+
+/// Parse a filter comparison value
+fn parse_filter_value(token: &str) -> ScimResult<Value> {
+    if let Some(string) = token.strip_prefix('"') {
+        return Ok(Value::String(string.to_string()));
+    }
+    
+    match token.to_lowercase().as_str() {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        "null" => Ok(Value::Null),
+        _ => serde_json::from_str::<serde_json::Number>(token)
+            .map(Value::Number)
+            .map_err(|_| ScimError::new(400, Some("invalidFilter"), &format!("Invalid filter value: {}", token))),
+    }
+}
+
+/// Collect the values at a dotted attribute path, matching names case-insensitively
+fn resolve_path(resource: &Value, path: &str) -> Vec<Value> {
+    let mut current = vec![resource.clone()];
+    
+    for segment in path.split('.') {
+        current = current.into_iter()
+            .flat_map(|value| match value {
+                Value::Array(items) => items,
+                value => vec![value],
+            })
+            .filter_map(|value| match value {
+                Value::Object(object) => object.into_iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(segment))
+                    .map(|(_, value)| value),
+                _ => None,
+            })
+            .collect();
+    }
+    
+    current.into_iter()
+        .flat_map(|value| match value {
+            Value::Array(items) => items,
+            value => vec![value],
+        })
+        .collect()
+}
+
+/// Compare values, ignoring case for strings as SCIM does for most attributes
+fn values_equal(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::String(actual), Value::String(expected)) => actual.eq_ignore_ascii_case(expected),
+        (actual, expected) => actual == expected,
+    }
+}
@@ -5,20 +5,52 @@
 //! 
 //! This module provides secure storage services using Google Cloud Storage
 //! and BigQuery with CMEK encryption for enterprise-grade data protection.
+//! 
+//! Objects are deleted once their retention period expires unless a legal hold covers
+//! them; see the `retention` module.
 
 use crate::{DreasResult, DreasError};
+use crate::security::audit::{actions, AuditEntry, AuditHandle, AuditLogger, AuditResult};
+use crate::security::kms::{service_account_token, METADATA_TOKEN_URL};
+use crate::security::policy::PolicyEntity;
+use crate::security::request_context::RequestContext;
+use crate::security::retention::{
+    DeletionManifest, LegalHolds, PurgedRecord, RetainedRecord, RetentionPolicy, DEFAULT_CATEGORY,
+    RETENTION_CATEGORY_METADATA, RETENTION_USER_METADATA,
+};
+use crate::security::tenant::{TenantGuard, TenantId};
+use crate::security::token::TokenSigner;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+/// BigQuery REST API base URL
+const BIGQUERY_API_BASE: &str = "https://bigquery.googleapis.com/bigquery/v2";
+
+/// Table audit entries are streamed to in the BigQuery dataset
+const AUDIT_LOG_TABLE: &str = "audit_logs";
+
 /// Storage service for secure data persistence
 #[derive(Debug, Clone)]
 pub struct StorageService {
     service_id: Uuid,
     gcs_bucket: String,
     bigquery_dataset: String,
+    /// Project of the BigQuery dataset; audit logs can't be stored without one
+    project_id: Option<String>,
+    http_client: reqwest::Client,
     encryption_enabled: bool,
+    tenant_id: TenantId,
+    tenant_guard: TenantGuard,
+    /// Principal object access is checked for instead of the caller of the current request
+    principal: Option<PolicyEntity>,
+    audit_logger: AuditHandle,
+    retention: Option<RetentionPolicy>,
+    legal_holds: Arc<LegalHolds>,
+    manifest_signer: Option<Arc<dyn TokenSigner>>,
 }
 
 /// Storage operation result
@@ -53,6 +85,8 @@ pub struct StorageItem {
     pub modified_at: DateTime<Utc>,
     pub metadata: HashMap<String, String>,
     pub encrypted: bool,
+    #[serde(default)]
+    pub tenant_id: TenantId,
 }
 
 impl StorageService {
@@ -62,10 +96,109 @@ impl StorageService {
             service_id: Uuid::new_v4(),
             gcs_bucket,
             bigquery_dataset,
+            project_id: None,
+            http_client: reqwest::Client::new(),
             encryption_enabled: true,
+            tenant_id: TenantId::default(),
+            tenant_guard: TenantGuard::new(),
+            principal: None,
+            audit_logger: AuditHandle::detached(),
+            retention: None,
+            legal_holds: Arc::new(LegalHolds::new()),
+            manifest_signer: None,
+        }
+    }
+    
+    /// Record object writes, reads and deletes in the audit log
+    ///
+    /// Audit log exports to BigQuery are not themselves audited, so this service can back an
+    /// audit sink of the same logger.
+    pub fn with_audit_logger(mut self, audit_logger: Arc<Mutex<AuditLogger>>) -> Self {
+        self.audit_logger = AuditHandle::new(audit_logger);
+        self
+    }
+    
+    /// Store audit logs in the BigQuery dataset of this project
+    pub fn with_project_id(mut self, project_id: impl Into<String>) -> Self {
+        self.project_id = Some(project_id.into());
+        self
+    }
+    
+    /// Get a handle scoped to a tenant's storage prefix
+    ///
+    /// Every object access through the handle is checked by the tenant guard against the
+    /// caller, so a principal of another tenant needs an explicit permit.
+    pub fn for_tenant(&self, tenant_id: TenantId) -> Self {
+        Self {
+            tenant_id,
+            ..self.clone()
         }
     }
     
+    /// Set the guard deciding which cross-tenant access is allowed
+    pub fn with_tenant_guard(mut self, tenant_guard: TenantGuard) -> Self {
+        self.tenant_guard = tenant_guard;
+        self
+    }
+    
+    /// Check object access against a principal rather than the caller of the current request
+    pub fn with_principal(mut self, principal: PolicyEntity) -> Self {
+        self.principal = Some(principal);
+        self
+    }
+    
+    /// Delete objects once they outlive a retention policy, by their `retention_category` metadata
+    pub fn with_retention_policy(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+    
+    /// Refuse to delete objects covered by a hold in a shared registry
+    pub fn with_legal_holds(mut self, legal_holds: Arc<LegalHolds>) -> Self {
+        self.legal_holds = legal_holds;
+        self
+    }
+    
+    /// Sign deletion manifests with the given key
+    pub fn with_manifest_signer(mut self, signer: Arc<dyn TokenSigner>) -> Self {
+        self.manifest_signer = Some(signer);
+        self
+    }
+    
+    /// Get the tenant this handle stores data for
+    pub fn tenant_id(&self) -> &TenantId {
+        &self.tenant_id
+    }
+    
+    /// Resolve an item name to its object name under a tenant's prefix
+    fn object_name(tenant_id: &TenantId, name: &str) -> DreasResult<String> {
+        // Names are relative to the tenant prefix and can't climb out of it
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|segment| segment == "..") {
+            return Err(DreasError::Storage(format!("Invalid item name: {}", name)));
+        }
+        
+        Ok(format!("{}{}", tenant_id.storage_prefix(), name))
+    }
+    
+    /// Check the caller may perform an action on an object of this handle's tenant
+    ///
+    /// The caller is the principal set on the handle, else the one of the request being handled.
+    /// Work outside any request is the service's own and isn't checked. Refusals are audited
+    /// as failed `audit_action`s. Returns the principal checked.
+    async fn authorize(&self, action: &str, audit_action: &str, resource_id: &str) -> DreasResult<Option<PolicyEntity>> {
+        let principal = match self.principal.clone().or_else(|| RequestContext::current()?.caller()) {
+            Some(principal) => principal,
+            None => return Ok(None),
+        };
+        
+        if let Err(e) = self.tenant_guard.check_access(&principal, action, resource_id, &self.tenant_id).await {
+            self.audit_object_access(audit_action, resource_id, &self.tenant_id, Some(&principal), Some(&e)).await?;
+            return Err(e);
+        }
+        
+        Ok(Some(principal))
+    }
+    
     /// Store encrypted data in Google Cloud Storage
     pub async fn store_data(
         &self,
@@ -75,7 +208,8 @@ impl StorageService {
         metadata: Option<HashMap<String, String>>,
     ) -> DreasResult<StorageResult> {
         let operation_id = Uuid::new_v4();
-        let resource_id = format!("gs://{}/{}", self.gcs_bucket, name);
+        let resource_id = format!("gs://{}/{}", self.gcs_bucket, Self::object_name(&self.tenant_id, &name)?);
+        let principal = self.authorize("storage:write", actions::STORAGE_WRITE, &resource_id).await?;
         
         // TODO: Implement actual GCS storage with CMEK encryption
         // This is a placeholder implementation
@@ -84,11 +218,14 @@ impl StorageService {
         result_metadata.insert("bucket".to_string(), self.gcs_bucket.clone());
         result_metadata.insert("content_type".to_string(), content_type);
         result_metadata.insert("size".to_string(), data.len().to_string());
+        result_metadata.insert("tenant_id".to_string(), self.tenant_id.to_string());
         
         if self.encryption_enabled {
             result_metadata.insert("encrypted".to_string(), "true".to_string());
         }
         
+        self.audit_object_access(actions::STORAGE_WRITE, &resource_id, &self.tenant_id, principal.as_ref(), None).await?;
+        
         let result = StorageResult {
             operation_id,
             resource_id,
@@ -104,7 +241,56 @@ impl StorageService {
     
     /// Retrieve data from Google Cloud Storage
     pub async fn retrieve_data(&self, name: String) -> DreasResult<Vec<u8>> {
-        let resource_id = format!("gs://{}/{}", self.gcs_bucket, name);
+        let resource_id = format!("gs://{}/{}", self.gcs_bucket, Self::object_name(&self.tenant_id, &name)?);
+        let principal = self.authorize("storage:read", actions::STORAGE_READ, &resource_id).await?;
+        
+        let data = self.read_object(&self.tenant_id, &name).await?;
+        self.audit_object_access(actions::STORAGE_READ, &resource_id, &self.tenant_id, principal.as_ref(), None).await?;
+        Ok(data)
+    }
+    
+    /// Retrieve another tenant's data, if the tenant guard allows the principal to read it
+    pub async fn retrieve_tenant_data(
+        &self,
+        principal: &PolicyEntity,
+        owner: &TenantId,
+        name: String,
+    ) -> DreasResult<Vec<u8>> {
+        self.for_tenant(owner.clone())
+            .with_principal(principal.clone())
+            .retrieve_data(name)
+            .await
+    }
+    
+    /// Record an object access in the audit log, failed if `error` is set
+    async fn audit_object_access(
+        &self,
+        action: &str,
+        resource_id: &str,
+        owner: &TenantId,
+        principal: Option<&PolicyEntity>,
+        error: Option<&DreasError>,
+    ) -> DreasResult<()> {
+        let result = if error.is_some() { AuditResult::Failure } else { AuditResult::Success };
+        
+        let mut entry = AuditEntry::new(action, resource_id, result)
+            .with_tenant(owner.clone())
+            .with_metadata("bucket", &self.gcs_bucket);
+        if let Some(principal) = principal {
+            entry = entry
+                .with_user(&principal.id)
+                .with_metadata("principal_type", &principal.entity_type);
+        }
+        if let Some(error) = error {
+            entry = entry.with_metadata("error", error.to_string());
+        }
+        
+        self.audit_logger.record(entry).await
+    }
+    
+    /// Read an object from a tenant's prefix
+    async fn read_object(&self, tenant_id: &TenantId, name: &str) -> DreasResult<Vec<u8>> {
+        let resource_id = format!("gs://{}/{}", self.gcs_bucket, Self::object_name(tenant_id, name)?);
         
         // TODO: Implement actual GCS retrieval with decryption
         // This is a placeholder implementation
@@ -115,14 +301,56 @@ impl StorageService {
         Ok(b"retrieved data".to_vec())
     }
     
+    /// Look up the metadata of an object in a tenant's prefix
+    async fn stat_object(&self, tenant_id: &TenantId, name: &str) -> DreasResult<StorageItem> {
+        let resource_id = format!("gs://{}/{}", self.gcs_bucket, Self::object_name(tenant_id, name)?);
+        
+        // TODO: Implement actual GCS metadata lookup
+        // This is a placeholder implementation
+        
+        tracing::debug!("Reading object metadata: {}", resource_id);
+        
+        Ok(StorageItem {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            content_type: "application/octet-stream".to_string(),
+            size: 0,
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+            metadata: HashMap::new(),
+            encrypted: self.encryption_enabled,
+            tenant_id: tenant_id.clone(),
+        })
+    }
+    
+    /// Describe an object for legal hold checks by its resource name, owner and age
+    fn retained_record<'a>(resource_id: &'a str, item: &'a StorageItem) -> RetainedRecord<'a> {
+        RetainedRecord::new(resource_id)
+            .with_user(item.metadata.get(RETENTION_USER_METADATA).map(String::as_str))
+            .with_created_at(item.created_at)
+    }
+    
     /// Delete data from storage
+    ///
+    /// Fails if a legal hold covers the object.
     pub async fn delete_data(&self, name: String) -> DreasResult<StorageResult> {
         let operation_id = Uuid::new_v4();
-        let resource_id = format!("gs://{}/{}", self.gcs_bucket, name);
+        let resource_id = format!("gs://{}/{}", self.gcs_bucket, Self::object_name(&self.tenant_id, &name)?);
+        let principal = self.authorize("storage:delete", actions::STORAGE_DELETE, &resource_id).await?;
+        
+        let item = self.stat_object(&self.tenant_id, &name).await?;
+        self.legal_holds.reload()?;
+        if let Some(hold_id) = self.legal_holds.holding(&Self::retained_record(&resource_id, &item))? {
+            let error = DreasError::Storage(format!("{} is under legal hold {}", resource_id, hold_id));
+            self.audit_object_access(actions::STORAGE_DELETE, &resource_id, &self.tenant_id, principal.as_ref(), Some(&error)).await?;
+            return Err(error);
+        }
         
         // TODO: Implement actual GCS deletion
         // This is a placeholder implementation
         
+        self.audit_object_access(actions::STORAGE_DELETE, &resource_id, &self.tenant_id, principal.as_ref(), None).await?;
+        
         let result = StorageResult {
             operation_id,
             resource_id,
@@ -136,8 +364,13 @@ impl StorageService {
         Ok(result)
     }
     
-    /// List stored items
+    /// List stored items in this tenant's prefix
     pub async fn list_items(&self, prefix: Option<String>) -> DreasResult<Vec<StorageItem>> {
+        let list_prefix = format!("{}{}", self.tenant_id.storage_prefix(), prefix.unwrap_or_default());
+        let resource_id = format!("gs://{}/{}", self.gcs_bucket, list_prefix);
+        self.authorize("storage:list", actions::STORAGE_READ, &resource_id).await?;
+        tracing::debug!("Listing items: {}", resource_id);
+        
         // TODO: Implement actual GCS listing
         // This is a placeholder implementation
         
@@ -151,6 +384,7 @@ impl StorageService {
                 modified_at: Utc::now(),
                 metadata: HashMap::new(),
                 encrypted: self.encryption_enabled,
+                tenant_id: self.tenant_id.clone(),
             },
             StorageItem {
                 id: Uuid::new_v4().to_string(),
@@ -161,22 +395,93 @@ impl StorageService {
                 modified_at: Utc::now(),
                 metadata: HashMap::new(),
                 encrypted: self.encryption_enabled,
+                tenant_id: self.tenant_id.clone(),
             },
         ];
         
         Ok(items)
     }
     
-    /// Store audit logs in BigQuery
+    /// Delete this tenant's objects that have outlived the retention policy
+    ///
+    /// Expired objects covered by a legal hold are kept and listed as withheld. Returns the
+    /// deletion manifest, which is also logged as a `retention_purge` entry.
+    pub async fn apply_retention(&self) -> DreasResult<DeletionManifest> {
+        let retention = self.retention.as_ref()
+            .ok_or_else(|| DreasError::Configuration("No storage retention policy configured".to_string()))?;
+        let signer = self.manifest_signer.as_deref()
+            .ok_or_else(|| DreasError::Configuration("Storage retention needs a signing key for deletion manifests".to_string()))?;
+        
+        let store = format!("gs://{}/{}", self.gcs_bucket, self.tenant_id.storage_prefix());
+        let mut manifest = DeletionManifest::new(store.clone(), "retention policy");
+        self.legal_holds.reload()?;
+        
+        let items = self.list_items(None).await?;
+        let now = Utc::now();
+        for item in items {
+            let category = item.metadata.get(RETENTION_CATEGORY_METADATA).map_or(DEFAULT_CATEGORY, String::as_str);
+            if !retention.is_expired(category, item.created_at, now) {
+                continue;
+            }
+            
+            let resource_id = format!("gs://{}/{}", self.gcs_bucket, Self::object_name(&self.tenant_id, &item.name)?);
+            if let Some(hold_id) = self.legal_holds.holding(&Self::retained_record(&resource_id, &item))? {
+                manifest.withhold(resource_id, hold_id);
+                continue;
+            }
+            
+            self.delete_data(item.name.clone()).await?;
+            manifest.purge(PurgedRecord {
+                record_id: resource_id,
+                category: category.to_string(),
+                created_at: item.created_at,
+                retention_days: retention.retention_days(category),
+                digest: None,
+                tombstone_digest: None,
+            });
+        }
+        
+        let manifest = manifest.sign(signer).await?;
+        if !manifest.is_empty() {
+            let entry = AuditEntry::new(actions::RETENTION_PURGE, store, AuditResult::Success)
+                .with_tenant(self.tenant_id.clone())
+                .with_metadata("purged_objects", manifest.purged.len().to_string())
+                .with_metadata("withheld_objects", manifest.withheld.len().to_string())
+                .with_metadata("deletion_manifest", manifest.manifest_id.to_string())
+                .with_metadata("manifest_digest", manifest.digest()?);
+            self.audit_logger.record(entry).await?;
+        }
+        
+        tracing::info!("Retention purged {} objects, withholding {}", manifest.purged.len(), manifest.withheld.len());
+        Ok(manifest)
+    }
+    
+    /// Store audit logs in BigQuery, streaming them into the dataset's `audit_logs` table
+    ///
+    /// Rows are deduplicated on their `entry_id`, so a retried write doesn't store an entry
+    /// twice. Fails unless BigQuery accepted every row.
     pub async fn store_audit_logs(
         &self,
         logs: Vec<serde_json::Value>,
     ) -> DreasResult<StorageResult> {
         let operation_id = Uuid::new_v4();
-        let table_name = format!("{}.audit_logs", self.bigquery_dataset);
+        let (project_id, table_name) = self.audit_log_table()?;
         
-        // TODO: Implement actual BigQuery insertion with CMEK encryption
-        // This is a placeholder implementation
+        let url = format!(
+            "{}/projects/{}/datasets/{}/tables/{}/insertAll",
+            BIGQUERY_API_BASE, project_id, self.bigquery_dataset, AUDIT_LOG_TABLE
+        );
+        let rows: Vec<_> = logs.iter()
+            .map(|row| serde_json::json!({ "insertId": row["entry_id"], "json": row }))
+            .collect();
+        let response = self.bigquery_request(&url, serde_json::json!({ "rows": rows })).await?;
+        
+        // Rejected rows are reported in the body of a successful response
+        if let Some(errors) = response["insertErrors"].as_array().filter(|errors| !errors.is_empty()) {
+            return Err(DreasError::Storage(format!(
+                "BigQuery rejected {} of {} audit log rows: {}", errors.len(), logs.len(), errors[0]
+            )));
+        }
         
         let mut metadata = HashMap::new();
         metadata.insert("dataset".to_string(), self.bigquery_dataset.clone());
@@ -196,6 +501,70 @@ impl StorageService {
         Ok(result)
     }
     
+    /// Replace audit log rows in BigQuery with the tombstones left by a retention purge
+    pub async fn replace_audit_logs(
+        &self,
+        tombstones: Vec<serde_json::Value>,
+    ) -> DreasResult<StorageResult> {
+        let (project_id, table_name) = self.audit_log_table()?;
+        let entry_ids: Vec<_> = tombstones.iter()
+            .map(|tombstone| serde_json::json!({ "value": tombstone["entry_id"] }))
+            .collect();
+        
+        let url = format!("{}/projects/{}/queries", BIGQUERY_API_BASE, project_id);
+        let response = self.bigquery_request(&url, serde_json::json!({
+            "query": format!("DELETE FROM `{}` WHERE entry_id IN UNNEST(@entry_ids)", table_name),
+            "useLegacySql": false,
+            "parameterMode": "NAMED",
+            "queryParameters": [{
+                "name": "entry_ids",
+                "parameterType": { "type": "ARRAY", "arrayType": { "type": "STRING" } },
+                "parameterValue": { "arrayValues": entry_ids },
+            }],
+        })).await?;
+        
+        if response["jobComplete"].as_bool() != Some(true) {
+            return Err(DreasError::Storage(format!("Deleting purged audit log rows from {} did not complete", table_name)));
+        }
+        
+        self.store_audit_logs(tombstones).await
+    }
+    
+    /// Get the project and fully qualified name of the BigQuery audit log table
+    fn audit_log_table(&self) -> DreasResult<(&str, String)> {
+        let project_id = self.project_id.as_deref()
+            .ok_or_else(|| DreasError::Configuration("Storing audit logs in BigQuery needs a project ID".to_string()))?;
+        
+        // The table is named in SQL, where it can't be a query parameter
+        let valid_project = !project_id.is_empty() && project_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        let valid_dataset = !self.bigquery_dataset.is_empty()
+            && self.bigquery_dataset.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_project || !valid_dataset {
+            return Err(DreasError::Configuration(format!(
+                "Invalid BigQuery dataset: {}.{}", project_id, self.bigquery_dataset
+            )));
+        }
+        
+        Ok((project_id, format!("{}.{}.{}", project_id, self.bigquery_dataset, AUDIT_LOG_TABLE)))
+    }
+    
+    /// Call a BigQuery API
+    async fn bigquery_request(&self, url: &str, body: serde_json::Value) -> DreasResult<serde_json::Value> {
+        let token = service_account_token(&self.http_client, METADATA_TOKEN_URL).await?;
+        
+        self.http_client
+            .post(url)
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DreasError::Storage(format!("BigQuery request failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| DreasError::Storage(format!("Invalid BigQuery response: {}", e)))
+    }
+    
     /// Query audit logs from BigQuery
     pub async fn query_audit_logs(
         &self,
@@ -229,6 +598,7 @@ impl StorageService {
             "gcs_bucket": self.gcs_bucket,
             "bigquery_dataset": self.bigquery_dataset,
             "encryption_enabled": self.encryption_enabled,
+            "tenant_id": self.tenant_id,
             "created_at": Utc::now()
         })
     }
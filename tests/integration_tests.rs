@@ -6,6 +6,7 @@
 use dreas::{
     agents::{AgentCoordinator, PromptAgent, ResponseAgent, shared::AgentContext},
     security::{KmsClient, KeyEscrow, IdentityManager, AuditLogger},
+    security::key_provider::{KeyProvider, LocalKeyProvider},
     services::{StorageService, ModelService, ApiService, ObserverService},
     config::AppConfig,
 };
@@ -19,10 +20,11 @@ async fn test_agent_coordination() {
     // Create test context
     let session_id = Uuid::new_v4();
     let context = AgentContext::new(session_id, "test-key-id".to_string());
+    let key_provider = std::sync::Arc::new(LocalKeyProvider::new().with_generated_key("test-key-id"));
     
     // Create and register agents
-    let prompt_agent = PromptAgent::new(context.clone());
-    let response_agent = ResponseAgent::new(context);
+    let prompt_agent = PromptAgent::new(context.clone(), key_provider.clone());
+    let response_agent = ResponseAgent::new(context, key_provider.clone());
     
     let prompt_agent_id = coordinator.register_prompt_agent(prompt_agent).await.unwrap();
     let response_agent_id = coordinator.register_response_agent(response_agent).await.unwrap();
@@ -32,19 +34,89 @@ async fn test_agent_coordination() {
     assert!(prompt_result.is_ok());
     
     // Test response processing
-    let response_result = coordinator.process_response(response_agent_id, "Test response".to_string()).await;
+    let sealed_response = key_provider.encrypt("test-key-id", b"Test response").await.unwrap();
+    let response_result = coordinator.process_response(response_agent_id, sealed_response).await;
     assert!(response_result.is_ok());
 }
 
+/// Serve a minimal Cloud KMS (token, encrypt and decrypt endpoints) on a local port
+///
+/// Ciphertexts name the key that sealed them, and only open with that key.
+async fn start_mock_kms() -> String {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            
+            // Read headers, then the body according to Content-Length
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            let header_end = loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+            let head = String::from_utf8_lossy(&request[..header_end]).to_string();
+            let content_length = head.lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                .unwrap_or(0);
+            while request.len() < header_end + content_length {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let body: serde_json::Value = serde_json::from_slice(&request[header_end..]).unwrap_or_default();
+            let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+            let authorized = head.lines().any(|line| line.eq_ignore_ascii_case("authorization: Bearer mock-token"));
+            
+            let call = path.strip_prefix("/v1/").and_then(|call| call.rsplit_once(':'));
+            let (status, response) = match call {
+                _ if path == "/token" => ("200 OK", serde_json::json!({ "access_token": "mock-token", "expires_in": 3600 })),
+                Some(_) if !authorized => ("401 Unauthorized", serde_json::json!({})),
+                Some((key_version, "encrypt")) => {
+                    let crypto_key = key_version.split("/cryptoKeyVersions/").next().unwrap();
+                    let mut ciphertext = format!("{}\n", crypto_key).into_bytes();
+                    ciphertext.extend(STANDARD.decode(body["plaintext"].as_str().unwrap()).unwrap());
+                    ("200 OK", serde_json::json!({ "name": key_version, "ciphertext": STANDARD.encode(ciphertext) }))
+                }
+                Some((crypto_key, "decrypt")) => {
+                    let ciphertext = STANDARD.decode(body["ciphertext"].as_str().unwrap()).unwrap();
+                    match ciphertext.strip_prefix(format!("{}\n", crypto_key).as_bytes()) {
+                        Some(plaintext) => ("200 OK", serde_json::json!({ "plaintext": STANDARD.encode(plaintext) })),
+                        None => ("400 Bad Request", serde_json::json!({ "error": { "status": "INVALID_ARGUMENT" } })),
+                    }
+                }
+                _ => ("404 Not Found", serde_json::json!({})),
+            };
+            
+            let response = response.to_string();
+            let http = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status, response.len(), response
+            );
+            stream.write_all(http.as_bytes()).await.unwrap();
+        }
+    });
+    
+    address
+}
+
 #[tokio::test]
 async fn test_kms_client() {
+    let kms = start_mock_kms().await;
     let kms_client = KmsClient::new(
         "test-project".to_string(),
         "us-central1".to_string(),
         "test-keyring".to_string(),
         "test-key".to_string(),
         "1".to_string(),
-    );
+    ).with_endpoints(format!("{}/v1", kms), format!("{}/token", kms));
     
     // Test configuration validation
     assert!(kms_client.validate_config().is_ok());
@@ -55,6 +127,50 @@ async fn test_kms_client() {
     let decrypted = kms_client.decrypt(&encrypted.ciphertext).await.unwrap();
     
     assert_eq!(test_data, decrypted.plaintext.as_slice());
+    assert!(kms_client.test_connection().await.is_ok());
+    
+    // Test ciphertexts don't open with another key
+    let other_key = kms_client.for_key_version(
+        "projects/test-project/locations/us-central1/keyRings/test-keyring/cryptoKeys/other-key/cryptoKeyVersions/1"
+    ).unwrap();
+    assert!(other_key.decrypt(&encrypted.ciphertext).await.is_err());
+    
+    // Test calls fail closed when the KMS or the token endpoint can't be reached
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let unreachable = format!("http://{}", closed.local_addr().unwrap());
+    drop(closed);
+    assert!(kms_client.clone().with_endpoints(format!("{}/v1", unreachable), format!("{}/token", kms))
+        .encrypt(test_data).await.is_err());
+    assert!(kms_client.clone().with_endpoints(format!("{}/v1", kms), format!("{}/token", unreachable))
+        .decrypt(&encrypted.ciphertext).await.is_err());
+}
+
+#[tokio::test]
+async fn test_kms_key_namespace() {
+    use dreas::security::TenantId;
+    
+    let kms_client = KmsClient::new(
+        "test-project".to_string(),
+        "us-central1".to_string(),
+        "test-keyring".to_string(),
+        "test-key".to_string(),
+        "1".to_string(),
+    );
+    
+    // Test other key versions must live in the client's key ring, or its tenant's
+    let key_uri = |project: &str, location: &str, key_ring: &str| format!(
+        "projects/{}/locations/{}/keyRings/{}/cryptoKeys/prompts/cryptoKeyVersions/2", project, location, key_ring
+    );
+    let acme = TenantId::new("acme").unwrap();
+    let acme_kms = kms_client.for_tenant(acme.clone());
+    assert!(kms_client.for_key_version(&key_uri("test-project", "us-central1", "test-keyring")).is_ok());
+    assert_eq!(acme_kms.for_key_version(&key_uri("test-project", "us-central1", "test-keyring")).unwrap().tenant_id(), Some(&acme));
+    assert!(acme_kms.for_key_version(&key_uri("test-project", "us-central1", "test-keyring-acme")).is_ok());
+    assert!(acme_kms.for_key_version(&key_uri("test-project", "us-central1", "test-keyring-globex")).is_err());
+    assert!(acme_kms.for_key_version(&key_uri("test-project", "us-central1", "other-keyring")).is_err());
+    assert!(acme_kms.for_key_version(&key_uri("other-project", "us-central1", "test-keyring-acme")).is_err());
+    assert!(acme_kms.for_key_version(&key_uri("test-project", "europe-west1", "test-keyring-acme")).is_err());
+    assert!(kms_client.for_key_version(&key_uri("test-project", "us-central1", "test-keyring-acme")).is_err());
 }
 
 #[tokio::test]
@@ -124,34 +240,3478 @@ async fn test_identity_manager() {
 }
 
 #[tokio::test]
-async fn test_audit_logger() {
-    let mut audit_logger = AuditLogger::new(30);
+async fn test_password_hashing() {
+    use dreas::security::password::{CredentialHasher, PasswordHashParams, PasswordPolicy};
     
-    // Test audit logging
-    let entry_id = audit_logger.log_operation(
-        Some("user123".to_string()),
-        Some("session456".to_string()),
-        "data_access".to_string(),
-        "sensitive_file.txt".to_string(),
-        dreas::security::audit::AuditResult::Success,
-        None,
+    let mut identity_manager = IdentityManager::new()
+        .with_password_policy(PasswordPolicy::new(10, 64).unwrap());
+    
+    // Test password policy enforcement
+    assert!(identity_manager.create_user(
+        "shortpw".to_string(),
+        "short@example.com".to_string(),
+        "abc123".to_string(),
+        vec![],
+    ).await.is_err());
+    
+    identity_manager.create_user(
+        "alice".to_string(),
+        "alice@example.com".to_string(),
+        "correct horse battery".to_string(),
+        vec![],
     ).await.unwrap();
     
-    assert!(!entry_id.to_string().is_empty());
+    // Test wrong password and unknown user are rejected identically
+    let wrong_password = identity_manager.authenticate("alice", "password123").await.unwrap();
+    let unknown_user = identity_manager.authenticate("mallory", "correct horse battery").await.unwrap();
+    assert!(!wrong_password.success);
+    assert!(!unknown_user.success);
+    assert_eq!(wrong_password.error, unknown_user.error);
     
-    // Test audit query
-    let query = dreas::security::audit::AuditQuery {
-        start_date: Some(chrono::Utc::now() - chrono::Duration::hours(1)),
-        end_date: Some(chrono::Utc::now()),
-        user_id: Some("user123".to_string()),
-        action: Some("data_access".to_string()),
+    // Test login still works after the hashing parameters change
+    let new_params = PasswordHashParams { memory_kib: 8192, iterations: 3, parallelism: 1 };
+    identity_manager.set_hash_params(new_params.clone()).unwrap();
+    assert!(identity_manager.authenticate("alice", "correct horse battery").await.unwrap().success);
+    assert!(identity_manager.authenticate("alice", "correct horse battery").await.unwrap().success);
+    
+    // Test rehash detection
+    let old_hasher = CredentialHasher::new(PasswordHashParams::default()).unwrap();
+    let new_hasher = CredentialHasher::new(new_params).unwrap();
+    let old_hash = old_hasher.hash("correct horse battery").unwrap();
+    assert!(new_hasher.verify("correct horse battery", &old_hash).unwrap());
+    assert!(new_hasher.needs_rehash(&old_hash));
+    assert!(!old_hasher.needs_rehash(&old_hash));
+}
+
+#[tokio::test]
+async fn test_access_tokens() {
+    use dreas::security::token::{LocalTokenSigner, TokenService, TokenVerifier};
+    use std::sync::Arc;
+    
+    let token_service = TokenService::new(
+        Arc::new(LocalTokenSigner::generate()),
+        "dreas-test".to_string(),
+        "dreas-services".to_string(),
+        chrono::Duration::minutes(15),
+        chrono::Duration::days(30),
+    );
+    let mut identity_manager = IdentityManager::new().with_token_service(token_service);
+    
+    identity_manager.create_user(
+        "tokenuser".to_string(),
+        "token@example.com".to_string(),
+        "password123".to_string(),
+        vec!["analyst".to_string()],
+    ).await.unwrap();
+    
+    let auth_result = identity_manager.authenticate("tokenuser", "password123").await.unwrap();
+    let access_token = auth_result.access_token.unwrap();
+    let refresh_token = auth_result.refresh_token.unwrap();
+    
+    // Test offline verification from the published JWKS
+    let verifier = TokenVerifier::from_jwks(
+        &identity_manager.jwks().unwrap(),
+        "dreas-test".to_string(),
+        "dreas-services".to_string(),
+    ).unwrap();
+    let claims = verifier.verify(&access_token).unwrap();
+    assert_eq!(claims.sid, auth_result.session_id.unwrap());
+    assert_eq!(claims.roles, vec!["analyst".to_string()]);
+    
+    // Test tampered tokens are rejected
+    let tampered = format!("{}x", access_token);
+    assert!(verifier.verify(&tampered).is_err());
+    
+    // Test refresh token rotation
+    let rotated = identity_manager.refresh_tokens(&refresh_token).await.unwrap();
+    assert_ne!(rotated.refresh_token, refresh_token);
+    assert!(identity_manager.verify_access_token(&rotated.access_token).is_ok());
+    
+    // Test reuse detection revokes the session and the whole token family
+    assert!(identity_manager.refresh_tokens(&refresh_token).await.is_err());
+    assert!(identity_manager.refresh_tokens(&rotated.refresh_token).await.is_err());
+    assert!(identity_manager.verify_access_token(&rotated.access_token).is_err());
+}
+
+/// State shared between a test and the mock identity provider
+#[derive(Default)]
+struct MockIdpState {
+    nonce: String,
+    code_challenge: String,
+    groups: Vec<String>,
+}
+
+/// Serve a minimal OIDC provider (discovery, JWKS, token endpoint) on a local port
+async fn start_mock_idp(state: std::sync::Arc<std::sync::Mutex<MockIdpState>>) -> String {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    use rsa::pkcs8::DecodePrivateKey;
+    use rsa::signature::{SignatureEncoding, Signer};
+    use rsa::traits::PublicKeyParts;
+    use sha2::{Digest, Sha256};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    
+    let private_key = rsa::RsaPrivateKey::from_pkcs8_pem(include_str!("fixtures/oidc_idp_key.pem")).unwrap();
+    let jwks = serde_json::json!({
+        "keys": [{
+            "kty": "RSA",
+            "kid": "mock-key-1",
+            "use": "sig",
+            "alg": "RS256",
+            "n": URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+            "e": URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+        }]
+    });
+    let signing_key = rsa::pkcs1v15::SigningKey::<Sha256>::new(private_key);
+    
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let issuer = format!("http://{}", listener.local_addr().unwrap());
+    let server_issuer = issuer.clone();
+    
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            
+            // Read headers, then the body according to Content-Length
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            let header_end = loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+            let head = String::from_utf8_lossy(&request[..header_end]).to_string();
+            let content_length = head.lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                .unwrap_or(0);
+            while request.len() < header_end + content_length {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let body = String::from_utf8_lossy(&request[header_end..]).to_string();
+            let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+            
+            let (status, response) = match path.as_str() {
+                "/.well-known/openid-configuration" => ("200 OK", serde_json::json!({
+                    "issuer": server_issuer,
+                    "authorization_endpoint": format!("{}/authorize", server_issuer),
+                    "token_endpoint": format!("{}/token", server_issuer),
+                    "jwks_uri": format!("{}/jwks", server_issuer),
+                })),
+                "/jwks" => ("200 OK", jwks.clone()),
+                "/token" => {
+                    let form: std::collections::HashMap<&str, &str> = body.split('&')
+                        .filter_map(|pair| pair.split_once('='))
+                        .collect();
+                    let state = state.lock().unwrap();
+                    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(form["code_verifier"].as_bytes()));
+                    
+                    if challenge != state.code_challenge || form["code"] != "mock-code" {
+                        ("400 Bad Request", serde_json::json!({ "error": "invalid_grant" }))
+                    } else {
+                        let now = chrono::Utc::now().timestamp();
+                        let header = serde_json::json!({ "alg": "RS256", "kid": "mock-key-1", "typ": "JWT" });
+                        let claims = serde_json::json!({
+                            "iss": server_issuer,
+                            "sub": "idp-user-42",
+                            "aud": "dreas",
+                            "iat": now,
+                            "exp": now + 300,
+                            "nonce": state.nonce,
+                            "email": "jane@corp.example.com",
+                            "groups": state.groups,
+                        });
+                        let signing_input = format!(
+                            "{}.{}",
+                            URL_SAFE_NO_PAD.encode(header.to_string()),
+                            URL_SAFE_NO_PAD.encode(claims.to_string())
+                        );
+                        let signature = signing_key.sign(signing_input.as_bytes()).to_bytes();
+                        let id_token = format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature));
+                        ("200 OK", serde_json::json!({ "id_token": id_token, "token_type": "Bearer" }))
+                    }
+                }
+                _ => ("404 Not Found", serde_json::json!({})),
+            };
+            
+            let response = response.to_string();
+            let http = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status, response.len(), response
+            );
+            stream.write_all(http.as_bytes()).await.unwrap();
+        }
+    });
+    
+    issuer
+}
+
+#[tokio::test]
+async fn test_oidc_login() {
+    use dreas::config::OidcConfig;
+    use dreas::security::audit::{actions, AuditResult};
+    use dreas::security::oidc::OidcClient;
+    use std::sync::{Arc, Mutex};
+    
+    let idp_state = Arc::new(Mutex::new(MockIdpState {
+        groups: vec!["corp-security".to_string(), "corp-everyone".to_string()],
+        ..Default::default()
+    }));
+    let issuer = start_mock_idp(idp_state.clone()).await;
+    
+    let mut role_mappings = std::collections::HashMap::new();
+    role_mappings.insert("corp-security".to_string(), vec!["auditor".to_string(), "analyst".to_string()]);
+    
+    let mut oidc_client = OidcClient::discover(OidcConfig {
+        issuer_url: issuer,
+        client_id: "dreas".to_string(),
+        client_secret: None,
+        redirect_uri: "https://dreas.example.com/callback".to_string(),
+        scopes: vec!["openid".to_string(), "email".to_string()],
+        username_claim: "email".to_string(),
+        role_claim: "groups".to_string(),
+        role_mappings,
+        default_roles: vec!["user".to_string()],
+        jwks_cache_ttl_seconds: 3600,
+    }).await.unwrap();
+    let auth_log = Arc::new(tokio::sync::Mutex::new(AuditLogger::new(30)));
+    let mut identity_manager = IdentityManager::new().with_audit_logger(auth_log.clone());
+    
+    // Simulate the browser redirect: the IdP sees the nonce and PKCE challenge
+    let authorization = oidc_client.begin_login().unwrap();
+    let url = reqwest::Url::parse(&authorization.url).unwrap();
+    let params: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+    assert_eq!(params["code_challenge_method"], "S256");
+    {
+        let mut idp = idp_state.lock().unwrap();
+        idp.nonce = params["nonce"].clone();
+        idp.code_challenge = params["code_challenge"].clone();
+    }
+    
+    // Test first login provisions the user with mapped roles
+    let auth_result = identity_manager
+        .authenticate_oidc(&mut oidc_client, &authorization.state, "mock-code")
+        .await
+        .unwrap();
+    assert!(auth_result.success);
+    let user = auth_result.user.unwrap();
+    assert_eq!(user.username, "jane@corp.example.com");
+    assert_eq!(user.roles, vec!["user".to_string(), "auditor".to_string(), "analyst".to_string()]);
+    
+    // Test state is single-use
+    assert!(identity_manager
+        .authenticate_oidc(&mut oidc_client, &authorization.state, "mock-code")
+        .await
+        .is_err());
+    
+    // Test a nonce mismatch is rejected
+    let authorization = oidc_client.begin_login().unwrap();
+    let url = reqwest::Url::parse(&authorization.url).unwrap();
+    let params: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+    {
+        let mut idp = idp_state.lock().unwrap();
+        idp.nonce = "replayed-nonce".to_string();
+        idp.code_challenge = params["code_challenge"].clone();
+    }
+    assert!(identity_manager
+        .authenticate_oidc(&mut oidc_client, &authorization.state, "mock-code")
+        .await
+        .is_err());
+    
+    // Test role changes at the IdP apply on next login
+    let authorization = oidc_client.begin_login().unwrap();
+    let url = reqwest::Url::parse(&authorization.url).unwrap();
+    let params: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+    {
+        let mut idp = idp_state.lock().unwrap();
+        idp.nonce = params["nonce"].clone();
+        idp.code_challenge = params["code_challenge"].clone();
+        idp.groups = vec!["corp-everyone".to_string()];
+    }
+    let auth_result = identity_manager
+        .authenticate_oidc(&mut oidc_client, &authorization.state, "mock-code")
+        .await
+        .unwrap();
+    assert_eq!(auth_result.user.unwrap().roles, vec!["user".to_string()]);
+    
+    // Test every attempt is audited, rejected callbacks against the identity provider
+    let attempts: Vec<(String, String, AuditResult)> = auth_log.lock().await.entries().iter()
+        .filter(|entry| entry.action == actions::USER_AUTHENTICATION)
+        .map(|entry| (entry.resource.clone(), entry.metadata["outcome"].clone(), entry.result.clone()))
+        .collect();
+    let user_resource = "user:jane@corp.example.com".to_string();
+    let provider_resource = format!("user:oidc:{}", oidc_client.metadata().issuer);
+    assert_eq!(attempts, vec![
+        (user_resource.clone(), "oidc".to_string(), AuditResult::Success),
+        (provider_resource.clone(), "oidc_login_rejected".to_string(), AuditResult::Failure),
+        (provider_resource, "oidc_login_rejected".to_string(), AuditResult::Failure),
+        (user_resource, "oidc".to_string(), AuditResult::Success),
+    ]);
+}
+
+#[tokio::test]
+async fn test_mfa_step_up() {
+    use dreas::config::LockoutConfig;
+    use dreas::security::audit::{actions, AuditResult};
+    use dreas::security::mfa::TotpAuthenticator;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    
+    // Test RFC 6238 SHA-1 vectors
+    let rfc_totp = TotpAuthenticator::new("DREAS".to_string(), 8, 30, 1).unwrap();
+    let rfc_secret = b"12345678901234567890";
+    let at = |seconds| chrono::DateTime::from_timestamp(seconds, 0).unwrap();
+    assert_eq!(rfc_totp.code_at(rfc_secret, at(59)).unwrap(), "94287082");
+    assert_eq!(rfc_totp.code_at(rfc_secret, at(1111111109)).unwrap(), "07081804");
+    assert_eq!(rfc_totp.code_at(rfc_secret, at(2000000000)).unwrap(), "69279037");
+    
+    let audit_logger = AuditLogger::new(30);
+    let auth_log = Arc::new(Mutex::new(AuditLogger::new(30)));
+    let mut identity_manager = IdentityManager::new()
+        .with_step_up_permissions(audit_logger.sensitive_operations().iter().cloned())
+        .with_lockout_config(&LockoutConfig { max_failures_per_user: 3, free_failures: 3, ..LockoutConfig::default() })
+        .with_audit_logger(auth_log.clone());
+    
+    identity_manager.create_role(
+        "escrow_agent".to_string(),
+        vec!["key_recovery".to_string(), "read_data".to_string()],
+        "Escrow recovery agent".to_string(),
+    ).await.unwrap();
+    
+    identity_manager.create_user(
+        "bob".to_string(),
+        "bob@example.com".to_string(),
+        "correct horse battery".to_string(),
+        vec!["escrow_agent".to_string()],
+    ).await.unwrap();
+    
+    let auth_result = identity_manager.authenticate("bob", "correct horse battery").await.unwrap();
+    let session_id = auth_result.session_id.unwrap();
+    
+    // Test ordinary permissions don't need step-up but sensitive ones do
+    assert!(identity_manager.check_permission(&session_id, "read_data").await.unwrap().allowed);
+    let permission_result = identity_manager.check_permission(&session_id, "key_recovery").await.unwrap();
+    assert!(!permission_result.allowed);
+    assert!(permission_result.mfa_required);
+    assert!(identity_manager.verify_mfa(&session_id, "123456").await.is_err());
+    
+    // Test enrollment with a code from the authenticator
+    let enrollment = identity_manager.begin_totp_enrollment(&session_id).unwrap();
+    assert!(enrollment.provisioning_uri.starts_with("otpauth://totp/DREAS:bob?secret="));
+    
+    let totp = TotpAuthenticator::default();
+    let secret = data_encoding::BASE32_NOPAD.decode(enrollment.secret.as_bytes()).unwrap();
+    let code = totp.code_at(&secret, chrono::Utc::now()).unwrap();
+    
+    let recovery_codes = identity_manager.confirm_totp_enrollment(&session_id, &code).unwrap();
+    assert_eq!(recovery_codes.len(), 10);
+    assert!(identity_manager.is_mfa_enrolled(&auth_result.user.unwrap().id));
+    assert!(identity_manager.check_permission(&session_id, "key_recovery").await.unwrap().allowed);
+    assert!(identity_manager.begin_totp_enrollment(&session_id).is_err());
+    
+    // Test a fresh session needs its own step-up, and codes can't be replayed
+    let second_session = identity_manager.authenticate("bob", "correct horse battery").await.unwrap()
+        .session_id.unwrap();
+    assert!(identity_manager.check_permission(&second_session, "key_recovery").await.unwrap().mfa_required);
+    assert!(!identity_manager.verify_mfa(&second_session, &code).await.unwrap());
+    
+    // Test recovery codes are single-use and accepted without the dash
+    let recovery_code = recovery_codes[0].replace('-', "").to_uppercase();
+    assert!(identity_manager.verify_mfa(&second_session, &recovery_code).await.unwrap());
+    assert!(!identity_manager.verify_mfa(&second_session, &recovery_codes[0]).await.unwrap());
+    assert!(identity_manager.check_permission(&second_session, "key_recovery").await.unwrap().allowed);
+    
+    // Test repeated wrong codes lock MFA out, refusing even a valid code without using it up
+    assert!(!identity_manager.verify_mfa(&second_session, "000000").await.unwrap());
+    assert!(!identity_manager.verify_mfa(&second_session, "AAAA-BBBB").await.unwrap());
+    assert!(identity_manager.verify_mfa(&second_session, &recovery_codes[1]).await.is_err());
+    
+    let outcomes: Vec<String> = auth_log.lock().await.entries().iter()
+        .filter(|entry| entry.action == actions::USER_AUTHENTICATION && entry.result == AuditResult::Failure)
+        .map(|entry| entry.metadata["outcome"].clone())
+        .collect();
+    assert_eq!(outcomes, vec!["invalid_mfa_code", "invalid_mfa_code", "invalid_mfa_code", "invalid_mfa_code", "mfa_locked", "mfa_throttled"]);
+    
+    // Test an administrator can lift the lockout, after which the unused code still works
+    assert!(identity_manager.unlock_account("bob").await.unwrap());
+    assert!(identity_manager.verify_mfa(&second_session, &recovery_codes[1]).await.unwrap());
+    
+    // Test disabling MFA requires the step-up it protects
+    let third_session = identity_manager.authenticate("bob", "correct horse battery").await.unwrap()
+        .session_id.unwrap();
+    assert!(identity_manager.disable_totp(&third_session).is_err());
+    assert!(identity_manager.disable_totp(&second_session).is_ok());
+}
+
+#[tokio::test]
+async fn test_service_account_api_keys() {
+    use dreas::security::identity::Principal;
+    use dreas::services::api::{ApiEndpoint, ApiRequest, HttpMethod};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    
+    let mut identity_manager = IdentityManager::new()
+        .with_step_up_permissions(vec!["key_recovery".to_string()]);
+    
+    identity_manager.create_role(
+        "pipeline".to_string(),
+        vec!["read_data".to_string(), "data_encryption".to_string(), "key_recovery".to_string()],
+        "Batch pipeline".to_string(),
+    ).await.unwrap();
+    
+    let account = identity_manager.create_service_account(
+        "nightly-etl".to_string(),
+        "Nightly ETL pipeline".to_string(),
+        vec!["pipeline".to_string()],
+    ).await.unwrap();
+    
+    // Test scopes must be a non-step-up subset of the account's permissions
+    assert!(identity_manager.create_api_key(&account.id, "etl".to_string(), vec!["admin".to_string()], None).is_err());
+    assert!(identity_manager.create_api_key(&account.id, "etl".to_string(), vec!["key_recovery".to_string()], None).is_err());
+    assert!(identity_manager.create_api_key(
+        &account.id, "etl".to_string(), vec!["read_data".to_string()], Some(chrono::Duration::days(3650)),
+    ).is_err());
+    
+    let issued = identity_manager.create_api_key(&account.id, "etl".to_string(), vec!["read_data".to_string()], None).unwrap();
+    assert!(issued.api_key.starts_with("dreas_"));
+    
+    // Test key resolution and scope enforcement
+    let principal = identity_manager.authenticate_api_key(&issued.api_key).unwrap();
+    assert_eq!(principal.id(), account.id);
+    assert!(matches!(principal, Principal::ServiceAccount { .. }));
+    assert!(identity_manager.check_principal_permission(&principal, "read_data").await.unwrap().allowed);
+    assert!(!identity_manager.check_principal_permission(&principal, "data_encryption").await.unwrap().allowed);
+    assert!(identity_manager.list_api_keys(&account.id)[0].last_used_at.is_some());
+    
+    let mut tampered = issued.api_key.clone();
+    tampered.pop();
+    tampered.push(if issued.api_key.ends_with('A') { 'B' } else { 'A' });
+    assert!(identity_manager.authenticate_api_key(&tampered).is_err());
+    
+    // Test rotation keeps the old key working during the overlap
+    let rotated = identity_manager.rotate_api_key(&issued.key_id, Some(chrono::Duration::hours(1))).unwrap();
+    assert!(identity_manager.authenticate_api_key(&issued.api_key).is_ok());
+    assert!(identity_manager.authenticate_api_key(&rotated.api_key).is_ok());
+    assert_eq!(rotated.scopes, issued.scopes);
+    
+    identity_manager.rotate_api_key(&rotated.key_id, Some(chrono::Duration::zero())).unwrap();
+    assert!(identity_manager.authenticate_api_key(&rotated.api_key).is_err());
+    
+    identity_manager.revoke_api_key(&issued.key_id).unwrap();
+    assert!(identity_manager.authenticate_api_key(&issued.api_key).is_err());
+    
+    // Test the API resolves keys to principals and rejects unverifiable bearer tokens
+    let live_key = identity_manager.create_api_key(&account.id, "etl-2".to_string(), vec!["read_data".to_string()], None).unwrap();
+    let identity_manager = Arc::new(RwLock::new(identity_manager));
+    let mut api_service = ApiService::new(8080);
+    api_service.set_identity_manager(identity_manager.clone());
+    
+    api_service.register_endpoint(ApiEndpoint {
+        path: "/data".to_string(),
+        method: HttpMethod::GET,
+        handler: "read_data".to_string(),
+        requires_auth: true,
+        rate_limit: None,
+        timeout_seconds: None,
+    }).await.unwrap();
+    
+    let request = |token: &str| ApiRequest {
+        request_id: Uuid::new_v4(),
+        method: HttpMethod::GET,
+        path: "/data".to_string(),
+        headers: [("Authorization".to_string(), format!("Bearer {}", token))].into_iter().collect(),
+        body: None,
+        query_params: std::collections::HashMap::new(),
+        timestamp: chrono::Utc::now(),
+        peer_address: None,
+    };
+    
+    let response = api_service.process_request(request(&live_key.api_key)).await.unwrap();
+    let body: serde_json::Value = serde_json::from_str(&response.body.unwrap()).unwrap();
+    assert_eq!(body["principal"], account.id.as_str());
+    assert_eq!(api_service.process_request(request("anything")).await.unwrap().status_code, 401);
+    
+    identity_manager.write().await.deactivate_service_account(&account.id).unwrap();
+    let response = api_service.process_request(request(&live_key.api_key)).await.unwrap();
+    assert_eq!(response.status_code, 401);
+    let body: serde_json::Value = serde_json::from_str(&response.body.unwrap()).unwrap();
+    assert!(body["error"].as_str().unwrap().contains("Authentication error"));
+}
+
+#[tokio::test]
+async fn test_role_hierarchy() {
+    use dreas::security::permission::{permission_matches, PermissionDecision};
+    
+    // Test namespaced wildcard matching
+    assert!(permission_matches("kms:*", "kms:encrypt"));
+    assert!(permission_matches("kms:*", "kms:key:rotate"));
+    assert!(!permission_matches("kms:*", "kms"));
+    assert!(permission_matches("escrow:*:tenant-42", "escrow:recover:tenant-42"));
+    assert!(!permission_matches("escrow:*:tenant-42", "escrow:recover:tenant-7"));
+    assert!(!permission_matches("kms:encrypt", "kms:encrypt:extra"));
+    
+    let mut identity_manager = IdentityManager::new();
+    assert!(identity_manager.create_role("bad".to_string(), vec!["kms:enc*".to_string()], String::new()).await.is_err());
+    
+    identity_manager.create_role("kms_user".to_string(), vec!["kms:*".to_string()], "KMS access".to_string()).await.unwrap();
+    identity_manager.create_role("analyst".to_string(), vec!["read_data".to_string()], "Analyst".to_string()).await.unwrap();
+    identity_manager.create_role("operator".to_string(), vec![], "Operator".to_string()).await.unwrap();
+    identity_manager.create_role("admin".to_string(), vec!["escrow:recover:*".to_string()], "Admin".to_string()).await.unwrap();
+    
+    identity_manager.set_role_parents("operator", vec!["analyst".to_string(), "kms_user".to_string()]).unwrap();
+    identity_manager.set_role_parents("admin", vec!["operator".to_string()]).unwrap();
+    
+    // Test inheritance cycles are rejected, including self-inheritance
+    assert!(identity_manager.set_role_parents("kms_user", vec!["admin".to_string()]).is_err());
+    assert!(identity_manager.set_role_parents("analyst", vec!["analyst".to_string()]).is_err());
+    assert!(identity_manager.set_role_parents("analyst", vec!["missing".to_string()]).is_err());
+    
+    identity_manager.create_user(
+        "carol".to_string(),
+        "carol@example.com".to_string(),
+        "correct horse battery".to_string(),
+        vec!["admin".to_string()],
+    ).await.unwrap();
+    
+    let session_id = identity_manager.authenticate("carol", "correct horse battery").await.unwrap()
+        .session_id.unwrap();
+    
+    // Test permissions inherited through two levels of roles
+    assert!(identity_manager.check_permission(&session_id, "read_data").await.unwrap().allowed);
+    assert!(identity_manager.check_permission(&session_id, "kms:decrypt").await.unwrap().allowed);
+    assert!(identity_manager.check_permission(&session_id, "escrow:recover:tenant-42").await.unwrap().allowed);
+    assert!(!identity_manager.check_permission(&session_id, "escrow:approve:tenant-42").await.unwrap().allowed);
+    
+    let explanation = identity_manager.explain_permission(&session_id, "kms:decrypt").unwrap();
+    assert_eq!(explanation.decision, PermissionDecision::Allowed);
+    assert_eq!(explanation.chain, vec!["user:carol", "role:admin", "role:operator", "role:kms_user"]);
+    assert_eq!(explanation.rule.as_deref(), Some("allow kms:*"));
+    
+    // Test explicit denies override inherited allows
+    identity_manager.deny_role_permission("operator", "kms:decrypt").unwrap();
+    let permission_result = identity_manager.check_permission(&session_id, "kms:decrypt").await.unwrap();
+    assert!(!permission_result.allowed);
+    assert!(permission_result.reason.unwrap().contains("deny kms:decrypt"));
+    assert!(identity_manager.check_permission(&session_id, "kms:encrypt").await.unwrap().allowed);
+    
+    let explanation = identity_manager.explain_permission(&session_id, "kms:decrypt").unwrap();
+    assert_eq!(explanation.decision, PermissionDecision::Denied);
+    assert_eq!(explanation.chain, vec!["user:carol", "role:admin", "role:operator"]);
+    
+    identity_manager.deny_user_permission("carol", "escrow:*:tenant-42").unwrap();
+    identity_manager.grant_user_permission("carol", "escrow:approve:tenant-42").unwrap();
+    let explanation = identity_manager.explain_permission(&session_id, "escrow:approve:tenant-42").unwrap();
+    assert_eq!(explanation.decision, PermissionDecision::Denied);
+    assert_eq!(explanation.chain, vec!["user:carol"]);
+    
+    let explanation = identity_manager.explain_permission(&session_id, "write_data").unwrap();
+    assert_eq!(explanation.decision, PermissionDecision::NotGranted);
+}
+
+#[tokio::test]
+async fn test_policy_engine() {
+    use dreas::security::audit::AuditQuery;
+    use dreas::security::policy::{PolicyEngine, PolicyEntity, PolicyRequest};
+    use dreas::services::api::{ApiEndpoint, ApiRequest, HttpMethod};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::{Mutex, RwLock};
+    
+    // Test syntax errors report the file and line
+    let error = PolicyEngine::parse("permit (principal,\n action ==, resource);", "bad.policy").unwrap_err();
+    assert!(error.to_string().contains("bad.policy line 2"));
+    
+    // Test the shipped policy files parse
+    let shipped = PolicyEngine::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/config/policies")).unwrap();
+    assert!(shipped.policies().iter().any(|policy| policy.id == "analyst-own-tenant-business-hours"));
+    
+    let source = r#"
+        @id("analyst-own-tenant")
+        forbid (principal in "analyst", action == "kms:decrypt", resource is "prompt")
+        unless {
+            resource.tenant == principal.tenant
+            && context.hour >= 9 && context.hour < 17
+            && in_cidr(context.ip, "10.0.0.0/8")
+        };
+        
+        @id("auditors-read-reports")
+        permit (principal in "auditor", action == "reports:read", resource)
+        when { principal has tenant && !context.mfa_verified };
+        
+        @id("stats-from-corp-network")
+        forbid (principal, action == "api:get_stats", resource == "/stats")
+        unless { context has ip && in_cidr(context.ip, "10.0.0.0/8") };
+    "#;
+    
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::new(30)));
+    let policy_engine = Arc::new(PolicyEngine::parse(source, "inline").unwrap().with_audit_logger(audit_logger.clone()));
+    assert_eq!(policy_engine.policies().len(), 3);
+    
+    let mut identity_manager = IdentityManager::new().with_policy_engine(policy_engine.clone());
+    identity_manager.create_role("analyst".to_string(), vec!["kms:*".to_string()], "Analyst".to_string()).await.unwrap();
+    identity_manager.create_role("auditor".to_string(), vec![], "Auditor".to_string()).await.unwrap();
+    identity_manager.create_user(
+        "dana".to_string(),
+        "dana@example.com".to_string(),
+        "correct horse battery".to_string(),
+        vec!["analyst".to_string(), "auditor".to_string()],
+    ).await.unwrap();
+    identity_manager.set_user_attribute("dana", "tenant", serde_json::json!("tenant-42")).await.unwrap();
+    
+    let session_id = identity_manager.authenticate("dana", "correct horse battery").await.unwrap()
+        .session_id.unwrap();
+    
+    let prompt = |tenant: &str| Some(PolicyEntity::new("prompt", "prompt-1").with_attribute("tenant", tenant));
+    let context = |hour: u32, ip: &str| -> HashMap<String, serde_json::Value> {
+        [("hour".to_string(), hour.into()), ("ip".to_string(), ip.into())].into_iter().collect()
+    };
+    
+    // Test tenant, business-hours and network conditions
+    let check = |tenant: &'static str, hour: u32, ip: &'static str| {
+        let identity_manager = &identity_manager;
+        let session_id = session_id.clone();
+        async move {
+            identity_manager.check_permission_in_context(&session_id, "kms:decrypt", prompt(tenant), context(hour, ip))
+                .await.unwrap()
+        }
+    };
+    
+    assert!(check("tenant-42", 10, "10.1.2.3").await.allowed);
+    assert!(!check("tenant-7", 10, "10.1.2.3").await.allowed);
+    assert!(!check("tenant-42", 20, "10.1.2.3").await.allowed);
+    let outside = check("tenant-42", 10, "203.0.113.5").await;
+    assert!(!outside.allowed);
+    assert_eq!(outside.reason.as_deref(), Some("Forbidden by policy analyst-own-tenant"));
+    
+    // Test missing attributes fail closed, and policies don't affect other actions
+    let untagged = Some(PolicyEntity::new("prompt", "prompt-2"));
+    assert!(!identity_manager.check_permission_in_context(&session_id, "kms:decrypt", untagged, context(10, "10.1.2.3"))
+        .await.unwrap().allowed);
+    assert!(identity_manager.check_permission(&session_id, "kms:encrypt").await.unwrap().allowed);
+    
+    // Test permit policies grant access roles alone don't
+    assert!(identity_manager.check_permission(&session_id, "reports:read").await.unwrap().allowed);
+    identity_manager.deny_role_permission("auditor", "reports:*").unwrap();
+    assert!(!identity_manager.check_permission(&session_id, "reports:read").await.unwrap().allowed);
+    
+    // Test decisions are recorded in the audit log
+    let entries = audit_logger.lock().await.query_audit_entries(AuditQuery {
+        start_date: None,
+        end_date: None,
+        user_id: None,
+        action: Some("policy_decision".to_string()),
         resource: None,
         result: None,
-        limit: Some(10),
+        limit: None,
+    }).unwrap();
+    assert_eq!(entries.len(), 8);
+    assert!(entries.iter().any(|entry| entry.metadata.get("forbidden_by").map(|ids| ids.as_str()) == Some("analyst-own-tenant")));
+    
+    // Test the KMS usage check
+    let kms_client = KmsClient::new(
+        "test-project".to_string(),
+        "us-central1".to_string(),
+        "test-keyring".to_string(),
+        "test-key".to_string(),
+        "1".to_string(),
+    ).with_policy_engine(policy_engine.clone());
+    
+    let analyst = PolicyEntity::new("user", "dana").with_roles(vec!["analyst".to_string()])
+        .with_attribute("tenant", "tenant-42");
+    let usage = |tenant: &str| PolicyRequest::new(analyst.clone(), "kms:decrypt", prompt(tenant).unwrap())
+        .with_context("hour", 11)
+        .with_context("ip", "10.0.0.8");
+    assert!(kms_client.check_usage(usage("tenant-42")).await.is_ok());
+    assert!(kms_client.check_usage(usage("tenant-7")).await.is_err());
+    
+    // Test the API applies policies using the client address
+    let mut api_service = ApiService::new(8080);
+    api_service.set_identity_manager(Arc::new(RwLock::new(identity_manager)));
+    api_service.set_policy_engine(policy_engine);
+    api_service.register_endpoint(ApiEndpoint {
+        path: "/stats".to_string(),
+        method: HttpMethod::GET,
+        handler: "get_stats".to_string(),
+        requires_auth: false,
+        rate_limit: None,
+        timeout_seconds: None,
+    }).await.unwrap();
+    
+    let request = |peer: &str, header: &str, forwarded_for: &str| ApiRequest {
+        request_id: Uuid::new_v4(),
+        method: HttpMethod::GET,
+        path: "/stats".to_string(),
+        headers: [(header.to_string(), forwarded_for.to_string())].into_iter().collect(),
+        body: None,
+        query_params: HashMap::new(),
+        timestamp: chrono::Utc::now(),
+        peer_address: Some(peer.parse().unwrap()),
     };
     
-    let entries = audit_logger.query_audit_entries(query).unwrap();
-    assert!(!entries.is_empty());
+    // Without trusted proxies only the peer address counts
+    assert_eq!(api_service.process_request(request("10.4.0.1", "X-Forwarded-For", "198.51.100.7")).await.unwrap().status_code, 200);
+    assert_eq!(api_service.process_request(request("198.51.100.7", "X-Forwarded-For", "10.4.0.1")).await.unwrap().status_code, 403);
+    
+    // Forwarded hops are trusted only as far back as they were added by trusted proxies
+    assert!(api_service.set_trusted_proxies(&["192.0.2.0/33".to_string()]).is_err());
+    api_service.set_trusted_proxies(&["192.0.2.0/24".to_string()]).unwrap();
+    assert_eq!(api_service.process_request(request("192.0.2.1", "X-Forwarded-For", "10.4.0.1")).await.unwrap().status_code, 200);
+    assert_eq!(api_service.process_request(request("192.0.2.1", "x-forwarded-for", "198.51.100.7, 10.4.0.1, 192.0.2.9")).await.unwrap().status_code, 200);
+    assert_eq!(api_service.process_request(request("192.0.2.1", "x-forwarded-for", "10.4.0.1, 198.51.100.7")).await.unwrap().status_code, 403);
+    assert_eq!(api_service.process_request(request("198.51.100.7", "X-Forwarded-For", "10.4.0.1")).await.unwrap().status_code, 403);
+    
+    // Test API policies see the caller's tenant and attributes, not just its credentials
+    let mut identity_manager = IdentityManager::new();
+    identity_manager.create_role("reader".to_string(), vec!["read_data".to_string()], "Reader".to_string()).await.unwrap();
+    let mut api_keys = Vec::new();
+    for (name, tenant) in [("acme-reports", Some("acme")), ("shared-reports", None)] {
+        let account = identity_manager.create_service_account(name.to_string(), "Reports".to_string(), vec!["reader".to_string()])
+            .await.unwrap();
+        if let Some(tenant) = tenant {
+            identity_manager.set_service_account_tenant(&account.id, dreas::security::TenantId::new(tenant).unwrap()).unwrap();
+        }
+        api_keys.push(identity_manager.create_api_key(&account.id, name.to_string(), vec!["read_data".to_string()], None).unwrap());
+    }
+    
+    let source = r#"
+        @id("stats-for-acme")
+        forbid (principal, action == "api:get_stats", resource)
+        unless { principal.type == "service_account" && principal.tenant == "acme" && principal has key_id };
+    "#;
+    let mut api_service = ApiService::new(8080);
+    api_service.set_identity_manager(Arc::new(RwLock::new(identity_manager)));
+    api_service.set_policy_engine(Arc::new(PolicyEngine::parse(source, "inline").unwrap()));
+    api_service.register_endpoint(ApiEndpoint {
+        path: "/stats".to_string(),
+        method: HttpMethod::GET,
+        handler: "get_stats".to_string(),
+        requires_auth: true,
+        rate_limit: None,
+        timeout_seconds: None,
+    }).await.unwrap();
+    
+    let authorized = |api_key: &str| ApiRequest {
+        headers: [("Authorization".to_string(), format!("Bearer {}", api_key))].into_iter().collect(),
+        ..request("10.4.0.1", "X-Forwarded-For", "10.4.0.1")
+    };
+    assert_eq!(api_service.process_request(authorized(&api_keys[0].api_key)).await.unwrap().status_code, 200);
+    assert_eq!(api_service.process_request(authorized(&api_keys[1].api_key)).await.unwrap().status_code, 403);
+}
+
+#[tokio::test]
+async fn test_account_lockout() {
+    use dreas::config::LockoutConfig;
+    use dreas::security::audit::{AuditQuery, AuditResult};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    
+    let lockout = LockoutConfig {
+        max_failures_per_user: 3,
+        max_failures_per_ip: 5,
+        failure_window_seconds: 900,
+        lockout_seconds: 900,
+        free_failures: 1,
+        base_delay_ms: 0,
+        max_delay_seconds: 30,
+        spike_window_seconds: 60,
+        spike_threshold: 5,
+    };
+    
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::new(30)));
+    let observer = Arc::new(Mutex::new(ObserverService::new()));
+    let mut identity_manager = IdentityManager::new()
+        .with_lockout_config(&lockout)
+        .with_audit_logger(audit_logger.clone())
+        .with_observer(observer.clone());
+    
+    for username in ["erin", "frank"] {
+        identity_manager.create_user(
+            username.to_string(),
+            format!("{}@example.com", username),
+            "correct horse battery".to_string(),
+            vec![],
+        ).await.unwrap();
+    }
+    
+    // Test the first failure is free and the next one is delayed
+    let mut delayed_manager = IdentityManager::new()
+        .with_lockout_config(&LockoutConfig { base_delay_ms: 60_000, ..lockout.clone() });
+    delayed_manager.create_user(
+        "dana".to_string(),
+        "dana@example.com".to_string(),
+        "correct horse battery".to_string(),
+        vec![],
+    ).await.unwrap();
+    assert!(delayed_manager.authenticate("dana", "wrong").await.unwrap().retry_after.is_none());
+    assert!(delayed_manager.authenticate("dana", "wrong").await.unwrap().retry_after.is_none());
+    let throttled = delayed_manager.authenticate("dana", "correct horse battery").await.unwrap();
+    assert!(!throttled.success);
+    assert!(throttled.retry_after.is_some());
+    
+    // Test lockout after the configured number of failures, even with the right password
+    assert!(!identity_manager.authenticate_from("erin", "wrong", Some("198.51.100.1")).await.unwrap().success);
+    assert!(!identity_manager.authenticate_from("erin", "wrong", Some("198.51.100.1")).await.unwrap().success);
+    assert!(identity_manager.account_locked_until("erin").is_none());
+    assert!(!identity_manager.authenticate_from("erin", "wrong", Some("198.51.100.1")).await.unwrap().success);
+    assert!(identity_manager.account_locked_until("erin").is_some());
+    assert!(identity_manager.account_locked_until("ERIN").is_some());
+    assert!(!identity_manager.authenticate_from("erin", "correct horse battery", Some("203.0.113.9")).await.unwrap().success);
+    
+    // Test admin unlock
+    assert!(identity_manager.unlock_account("erin").await.unwrap());
+    assert!(identity_manager.authenticate_from("erin", "correct horse battery", Some("203.0.113.9")).await.unwrap().success);
+    
+    // Test per-IP lockout across different usernames
+    for username in ["frank", "ghost"] {
+        identity_manager.authenticate_from(username, "wrong", Some("198.51.100.1")).await.unwrap();
+    }
+    let ip_locked = identity_manager.authenticate_from("frank", "correct horse battery", Some("198.51.100.1")).await.unwrap();
+    assert!(!ip_locked.success);
+    assert!(ip_locked.retry_after.is_some());
+    assert!(identity_manager.authenticate_from("frank", "correct horse battery", Some("203.0.113.9")).await.unwrap().success);
+    assert!(identity_manager.unlock_ip("198.51.100.1"));
+    
+    // Test failures are audited as user_authentication failures
+    let failures = audit_logger.lock().await.query_audit_entries(AuditQuery {
+        start_date: None,
+        end_date: None,
+        user_id: None,
+        action: Some("user_authentication".to_string()),
+        resource: None,
+        result: Some(AuditResult::Failure),
+        limit: None,
+    }).unwrap();
+    assert!(failures.iter().any(|entry| entry.metadata.get("outcome").map(|outcome| outcome.as_str()) == Some("account_locked")));
+    assert!(failures.iter().any(|entry| entry.metadata.get("source_ip").map(|ip| ip.as_str()) == Some("198.51.100.1")));
+    
+    // Test the failure spike raised a single observer alert
+    let observer = observer.lock().await;
+    let spike_alerts = observer.get_active_alerts().into_iter()
+        .filter(|alert| alert.name == "Authentication Failure Spike")
+        .count();
+    assert_eq!(spike_alerts, 1);
+    assert!(observer.get_metrics().iter().any(|metric| metric.name == "auth_failures_per_minute"));
+}
+
+#[tokio::test]
+async fn test_session_store() {
+    use dreas::config::SessionConfig;
+    use dreas::security::session::{ClientInfo, FileSessionStore, SessionStore};
+    
+    let store_path = std::env::temp_dir().join(format!("dreas-sessions-{}.json", Uuid::new_v4()));
+    let session_config = SessionConfig {
+        path: Some(store_path.to_string_lossy().to_string()),
+        idle_timeout_minutes: 30,
+        absolute_timeout_hours: 24,
+        max_sessions_per_user: 2,
+        bind_ip: true,
+        bind_user_agent: true,
+    };
+    
+    let mut identity_manager = IdentityManager::new().with_session_config(&session_config).unwrap();
+    let user = identity_manager.create_user(
+        "grace".to_string(),
+        "grace@example.com".to_string(),
+        "correct horse battery".to_string(),
+        vec![],
+    ).await.unwrap();
+    
+    let laptop = ClientInfo::from_ip(Some("198.51.100.7")).with_user_agent("laptop-browser");
+    let phone = ClientInfo::from_ip(Some("203.0.113.20")).with_user_agent("phone-app");
+    
+    let mut session_ids = Vec::new();
+    for client in [&laptop, &phone, &laptop] {
+        let auth_result = identity_manager.authenticate_with_client("grace", "correct horse battery", client).await.unwrap();
+        session_ids.push(auth_result.session_id.unwrap());
+    }
+    
+    // Test the per-user cap ended the oldest session
+    assert!(identity_manager.get_user_by_session(&session_ids[0]).unwrap().is_none());
+    let sessions = identity_manager.list_sessions(&user.id).unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions.iter().any(|session| session.user_agent.as_deref() == Some("phone-app")));
+    
+    // Test sessions are bound to the IP and user agent that logged in
+    assert!(identity_manager.validate_session(&session_ids[1], &phone).is_ok());
+    assert!(identity_manager.validate_session(&session_ids[1], &laptop).is_err());
+    assert!(identity_manager.validate_session(&session_ids[2], &ClientInfo::from_ip(Some("198.51.100.7"))).is_err());
+    assert!(identity_manager.validate_session(
+        &session_ids[2],
+        &ClientInfo::from_ip(Some("192.0.2.1")).with_user_agent("laptop-browser"),
+    ).is_err());
+    
+    // Test sessions persist to the file store
+    let reopened = FileSessionStore::open(&store_path).unwrap();
+    let persisted = reopened.get(&session_ids[2]).unwrap().unwrap();
+    assert_eq!(persisted.ip_address.as_deref(), Some("198.51.100.7"));
+    assert!(reopened.get(&session_ids[0]).unwrap().is_none());
+    
+    // Test revoking every session for a user
+    assert_eq!(identity_manager.revoke_all_sessions(&user.id).await.unwrap(), 2);
+    assert!(identity_manager.list_sessions(&user.id).unwrap().is_empty());
+    assert!(FileSessionStore::open(&store_path).unwrap().list_for_user(&user.id).unwrap().is_empty());
+    std::fs::remove_file(&store_path).unwrap();
+    
+    // Test absolute timeouts end sessions and cleanup purges them
+    let mut short_lived = IdentityManager::new()
+        .with_session_config(&SessionConfig { path: None, absolute_timeout_hours: 0, ..session_config.clone() })
+        .unwrap();
+    short_lived.create_user(
+        "heidi".to_string(),
+        "heidi@example.com".to_string(),
+        "correct horse battery".to_string(),
+        vec![],
+    ).await.unwrap();
+    let auth_result = short_lived.authenticate("heidi", "correct horse battery").await.unwrap();
+    let session_id = auth_result.session_id.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    
+    let permission_result = short_lived.check_permission(&session_id, "read_data").await.unwrap();
+    assert!(!permission_result.allowed);
+    assert_eq!(permission_result.reason.as_deref(), Some("Session expired"));
+    assert!(short_lived.get_user_by_session(&session_id).unwrap().is_none());
+    
+    short_lived.authenticate("heidi", "correct horse battery").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    assert_eq!(short_lived.cleanup_expired_sessions().unwrap(), 1);
+    
+    // Test idle timeouts end sessions that go unused
+    let mut idle = IdentityManager::new()
+        .with_session_config(&SessionConfig { path: None, idle_timeout_minutes: 0, ..session_config })
+        .unwrap();
+    idle.create_user(
+        "ivan".to_string(),
+        "ivan@example.com".to_string(),
+        "correct horse battery".to_string(),
+        vec![],
+    ).await.unwrap();
+    let auth_result = idle.authenticate("ivan", "correct horse battery").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    assert!(idle.get_user_by_session(&auth_result.session_id.unwrap()).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_scim_provisioning() {
+    use dreas::services::api::{ApiRequest, ApiResponse, HttpMethod};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    
+    let mut identity_manager = IdentityManager::new();
+    identity_manager.create_role(
+        "provisioner".to_string(),
+        vec!["identity:provision".to_string()],
+        "Identity provider sync".to_string(),
+    ).await.unwrap();
+    let account = identity_manager.create_service_account(
+        "okta-scim".to_string(),
+        "Okta SCIM connector".to_string(),
+        vec!["provisioner".to_string()],
+    ).await.unwrap();
+    let scim_key = identity_manager
+        .create_api_key(&account.id, "scim".to_string(), vec!["identity:provision".to_string()], None)
+        .unwrap();
+    identity_manager.create_role(
+        "reader".to_string(),
+        vec!["read_data".to_string()],
+        "Read-only access".to_string(),
+    ).await.unwrap();
+    let reporting = identity_manager.create_service_account(
+        "reporting".to_string(),
+        "Reporting job".to_string(),
+        vec!["reader".to_string()],
+    ).await.unwrap();
+    let reporting_key = identity_manager
+        .create_api_key(&reporting.id, "reports".to_string(), vec!["read_data".to_string()], None)
+        .unwrap();
+    
+    let identity_manager = Arc::new(RwLock::new(identity_manager));
+    let mut api_service = ApiService::new(8080);
+    api_service.set_identity_manager(identity_manager.clone());
+    api_service.enable_scim("https://dreas.example.com/").await.unwrap();
+    
+    let request_as = |api_key: &str, method: HttpMethod, path: &str, query: &[(&str, &str)], body: Option<Value>| ApiRequest {
+        request_id: Uuid::new_v4(),
+        method,
+        path: path.to_string(),
+        headers: [("Authorization".to_string(), format!("Bearer {}", api_key))].into_iter().collect(),
+        body: body.map(|body| body.to_string()),
+        query_params: query.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<HashMap<_, _>>(),
+        timestamp: chrono::Utc::now(),
+        peer_address: None,
+    };
+    let request = |method: HttpMethod, path: &str, query: &[(&str, &str)], body: Option<Value>| {
+        request_as(&scim_key.api_key, method, path, query, body)
+    };
+    let body = |response: &ApiResponse| -> Value { serde_json::from_str(response.body.as_ref().unwrap()).unwrap() };
+    
+    // Test creating a user with a password so they can log in locally
+    let response = api_service.process_request(request(HttpMethod::POST, "/scim/v2/Users", &[], Some(json!({
+        "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+        "userName": "judy@example.com",
+        "externalId": "00u1abcd",
+        "displayName": "Judy",
+        "password": "correct horse battery",
+        "emails": [{ "value": "judy@example.com", "type": "work", "primary": true }]
+    })))).await.unwrap();
+    assert_eq!(response.status_code, 201);
+    assert_eq!(response.headers["Content-Type"], "application/scim+json");
+    let judy = body(&response);
+    let judy_id = judy["id"].as_str().unwrap().to_string();
+    assert_eq!(judy["externalId"], "00u1abcd");
+    assert_eq!(judy["meta"]["location"], format!("https://dreas.example.com/scim/v2/Users/{}", judy_id));
+    assert!(judy.get("password").is_none());
+    
+    let duplicate = api_service.process_request(request(HttpMethod::POST, "/scim/v2/Users", &[], Some(json!({
+        "userName": "judy@example.com"
+    })))).await.unwrap();
+    assert_eq!(duplicate.status_code, 409);
+    assert_eq!(body(&duplicate)["scimType"], "uniqueness");
+    
+    let response = api_service.process_request(request(HttpMethod::POST, "/scim/v2/Users", &[], Some(json!({
+        "userName": "ken@example.com",
+        "emails": [{ "value": "ken@example.com" }]
+    })))).await.unwrap();
+    let ken_id = body(&response)["id"].as_str().unwrap().to_string();
+    
+    // Test list filters and paging
+    let response = api_service.process_request(request(
+        HttpMethod::GET, "/scim/v2/Users", &[("filter", "userName eq \"JUDY@example.com\"")], None,
+    )).await.unwrap();
+    let list = body(&response);
+    assert_eq!(list["totalResults"], 1);
+    assert_eq!(list["Resources"][0]["id"], judy_id.as_str());
+    
+    let response = api_service.process_request(request(
+        HttpMethod::GET, "/scim/v2/Users", &[("filter", "emails.value ew \"@example.com\" and active eq true"), ("count", "1")], None,
+    )).await.unwrap();
+    let list = body(&response);
+    assert_eq!(list["totalResults"], 2);
+    assert_eq!(list["itemsPerPage"], 1);
+    
+    let invalid_filter = api_service.process_request(request(
+        HttpMethod::GET, "/scim/v2/Users", &[("filter", "userName regex \"j.*\"")], None,
+    )).await.unwrap();
+    assert_eq!(invalid_filter.status_code, 400);
+    
+    // Test groups map onto roles and their members
+    let response = api_service.process_request(request(HttpMethod::POST, "/scim/v2/Groups", &[], Some(json!({
+        "schemas": ["urn:ietf:params:scim:schemas:core:2.0:Group"],
+        "displayName": "analysts",
+        "members": [{ "value": judy_id }]
+    })))).await.unwrap();
+    assert_eq!(response.status_code, 201);
+    assert!(identity_manager.read().await.get_user(&judy_id).unwrap().roles.contains(&"analysts".to_string()));
+    
+    let response = api_service.process_request(request(HttpMethod::PATCH, "/scim/v2/Groups/analysts", &[], Some(json!({
+        "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+        "Operations": [
+            { "op": "add", "path": "members", "value": [{ "value": ken_id }] },
+            { "op": "remove", "path": format!("members[value eq \"{}\"]", judy_id) }
+        ]
+    })))).await.unwrap();
+    let group = body(&response);
+    assert_eq!(group["members"].as_array().unwrap().len(), 1);
+    assert_eq!(group["members"][0]["value"], ken_id.as_str());
+    
+    let response = api_service.process_request(request(
+        HttpMethod::GET, "/scim/v2/Groups", &[("filter", "displayName eq \"analysts\"")], None,
+    )).await.unwrap();
+    assert_eq!(body(&response)["totalResults"], 1);
+    
+    let response = api_service.process_request(request(HttpMethod::GET, &format!("/scim/v2/Users/{}", ken_id), &[], None)).await.unwrap();
+    assert_eq!(body(&response)["groups"][0]["value"], "analysts");
+    
+    // Test patching a user, including the string booleans some IdPs send
+    let auth_result = identity_manager.write().await.authenticate("judy@example.com", "correct horse battery").await.unwrap();
+    let session_id = auth_result.session_id.unwrap();
+    
+    let response = api_service.process_request(request(HttpMethod::PATCH, &format!("/scim/v2/Users/{}", judy_id), &[], Some(json!({
+        "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+        "Operations": [
+            { "op": "Replace", "path": "emails[type eq \"work\"].value", "value": "judy.new@example.com" },
+            { "op": "Replace", "value": { "displayName": "Judy N.", "active": "False" } }
+        ]
+    })))).await.unwrap();
+    assert_eq!(response.status_code, 200);
+    let judy = body(&response);
+    assert_eq!(judy["emails"][0]["value"], "judy.new@example.com");
+    assert_eq!(judy["displayName"], "Judy N.");
+    assert_eq!(judy["active"], false);
+    
+    // Test deactivation revokes the user's sessions
+    assert!(identity_manager.read().await.get_user_by_session(&session_id).unwrap().is_none());
+    
+    let response = api_service.process_request(request(HttpMethod::DELETE, &format!("/scim/v2/Users/{}", ken_id), &[], None)).await.unwrap();
+    assert_eq!(response.status_code, 204);
+    assert!(response.body.is_none());
+    assert!(!identity_manager.read().await.get_user(&ken_id).unwrap().is_active);
+    
+    let missing = api_service.process_request(request(HttpMethod::GET, "/scim/v2/Users/unknown", &[], None)).await.unwrap();
+    assert_eq!(missing.status_code, 404);
+    
+    // Test callers without the provisioning permission are refused
+    let refused = api_service.process_request(request_as(
+        &reporting_key.api_key, HttpMethod::GET, "/scim/v2/Users", &[], None,
+    )).await.unwrap();
+    assert_eq!(refused.status_code, 403);
+}
+
+#[tokio::test]
+async fn test_tenant_isolation() {
+    use dreas::security::audit::AuditQuery;
+    use dreas::security::policy::{PolicyEngine, PolicyEntity, PolicyRequest};
+    use dreas::security::request_context::RequestContext;
+    use dreas::security::session::ClientInfo;
+    use dreas::security::tenant::{tenant_of, TenantGuard, TenantId};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    
+    // Test tenant IDs are safe to embed in key and storage names
+    let acme = TenantId::new("acme").unwrap();
+    let globex = TenantId::new("globex").unwrap();
+    assert_eq!(acme.storage_prefix(), "tenants/acme/");
+    assert_eq!(TenantId::default().as_str(), "default");
+    assert!(TenantId::new("Acme").is_err());
+    assert!(TenantId::new("../globex").is_err());
+    assert!(serde_json::from_str::<TenantId>("\"acme/x\"").is_err());
+    
+    let context = AgentContext::new(Uuid::new_v4(), "test-key-id".to_string()).with_tenant(acme.clone());
+    assert_eq!(context.tenant_id, acme);
+    
+    // Test users and their sessions carry their tenant
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::new(30)));
+    let mut identity_manager = IdentityManager::new().with_audit_logger(audit_logger.clone());
+    identity_manager.create_role("reader".to_string(), vec!["storage:read".to_string()], "Reader".to_string())
+        .await.unwrap();
+    let alice = identity_manager.create_user(
+        "alice".to_string(),
+        "alice@acme.example".to_string(),
+        "correct horse battery".to_string(),
+        vec!["reader".to_string()],
+    ).await.unwrap();
+    assert_eq!(alice.tenant_id, TenantId::default());
+    identity_manager.set_user_tenant(&alice.id, acme.clone()).await.unwrap();
+    
+    let session_id = identity_manager.authenticate("alice", "correct horse battery").await.unwrap()
+        .session_id.unwrap();
+    let session = identity_manager.validate_session(&session_id, &ClientInfo::default()).unwrap();
+    assert_eq!(session.tenant_id, acme);
+    
+    // Test permission checks refuse resources of another tenant
+    let report = |tenant: &TenantId| Some(PolicyEntity::new("report", "q3").with_attribute("tenant", tenant.as_str()));
+    let own = identity_manager.check_permission_in_context(&session_id, "storage:read", report(&acme), HashMap::new())
+        .await.unwrap();
+    assert!(own.allowed);
+    let other = identity_manager.check_permission_in_context(&session_id, "storage:read", report(&globex), HashMap::new())
+        .await.unwrap();
+    assert!(!other.allowed);
+    assert_eq!(other.reason.as_deref(), Some("Cross-tenant access requires an explicit policy permit"));
+    
+    // Test service accounts are kept to their own tenant's resources the same way
+    let etl = identity_manager.create_service_account("etl".to_string(), "Nightly ETL".to_string(), vec!["reader".to_string()])
+        .await.unwrap();
+    identity_manager.set_service_account_tenant(&etl.id, acme.clone()).unwrap();
+    let etl_key = identity_manager.create_api_key(&etl.id, "etl".to_string(), vec!["storage:read".to_string()], None).unwrap();
+    let etl = identity_manager.authenticate_api_key(&etl_key.api_key).unwrap();
+    let own = identity_manager.check_principal_permission_in_context(&etl, "storage:read", report(&acme), HashMap::new())
+        .await.unwrap();
+    assert!(own.allowed);
+    let other = identity_manager.check_principal_permission_in_context(&etl, "storage:read", report(&globex), HashMap::new())
+        .await.unwrap();
+    assert!(!other.allowed);
+    assert_eq!(other.reason.as_deref(), Some("Cross-tenant access requires an explicit policy permit"));
+    
+    // Test the caller of a request is described with its type, roles and tenant
+    let etl_entity = identity_manager.principal_entity(&etl);
+    assert_eq!(etl_entity.entity_type, "service_account");
+    assert_eq!(etl_entity.roles, vec!["reader".to_string()]);
+    let caller = RequestContext::new(Uuid::new_v4()).with_caller(etl_entity).with_tenant(acme.clone()).caller().unwrap();
+    assert_eq!(caller.entity_type, "service_account");
+    assert_eq!(caller.roles, vec!["reader".to_string()]);
+    assert_eq!(tenant_of(&caller), Some(acme.clone()));
+    
+    // Test moving a user to another tenant ends their sessions
+    identity_manager.set_user_tenant(&alice.id, globex.clone()).await.unwrap();
+    assert!(identity_manager.validate_session(&session_id, &ClientInfo::default()).is_err());
+    
+    // Test audit entries can be queried per tenant
+    let query = || AuditQuery {
+        start_date: None,
+        end_date: None,
+        user_id: None,
+        action: Some("user_authentication".to_string()),
+        resource: None,
+        result: None,
+        limit: None,
+    };
+    let audit_logger = audit_logger.lock().await;
+    let acme_entries = audit_logger.query_tenant_entries(&acme, query()).unwrap();
+    assert_eq!(acme_entries.len(), 1);
+    assert_eq!(acme_entries[0].user_id.as_deref(), Some(alice.id.as_str()));
+    assert!(audit_logger.query_tenant_entries(&globex, query()).unwrap().is_empty());
+    
+    // Test each tenant stores data under its own prefix
+    let storage_service = StorageService::new("test-bucket".to_string(), "test_dataset".to_string());
+    let acme_storage = storage_service.for_tenant(acme.clone());
+    let stored = acme_storage.store_data("report.csv".to_string(), b"q3".to_vec(), "text/csv".to_string(), None)
+        .await.unwrap();
+    assert_eq!(stored.resource_id, "gs://test-bucket/tenants/acme/report.csv");
+    assert!(acme_storage.store_data("../globex/report.csv".to_string(), Vec::new(), "text/csv".to_string(), None)
+        .await.is_err());
+    assert!(acme_storage.list_items(None).await.unwrap().iter().all(|item| item.tenant_id == acme));
+    
+    // Test cross-tenant reads need an explicit permit
+    let shipped = Arc::new(PolicyEngine::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/config/policies")).unwrap());
+    let guarded_storage = acme_storage.clone().with_tenant_guard(TenantGuard::new().with_policy_engine(shipped));
+    let acme_user = PolicyEntity::new("user", "alice").with_attribute("tenant", "acme");
+    let compliance = PolicyEntity::new("user", "carol")
+        .with_roles(vec!["platform-compliance".to_string()])
+        .with_attribute("tenant", "default");
+    
+    assert!(guarded_storage.retrieve_tenant_data(&acme_user, &acme, "report.csv".to_string()).await.is_ok());
+    assert!(guarded_storage.retrieve_tenant_data(&acme_user, &globex, "report.csv".to_string()).await.is_err());
+    assert!(guarded_storage.retrieve_tenant_data(&compliance, &globex, "report.csv".to_string()).await.is_ok());
+    assert!(acme_storage.retrieve_tenant_data(&compliance, &globex, "report.csv".to_string()).await.is_err());
+    
+    // Test every object access through a tenant's handle is refused to other tenants' principals
+    let globex_storage = acme_storage.clone().with_principal(PolicyEntity::new("user", "bob").with_attribute("tenant", "globex"));
+    assert!(globex_storage.store_data("report.csv".to_string(), b"q3".to_vec(), "text/csv".to_string(), None).await.is_err());
+    assert!(globex_storage.retrieve_data("report.csv".to_string()).await.is_err());
+    assert!(globex_storage.delete_data("report.csv".to_string()).await.is_err());
+    assert!(globex_storage.list_items(None).await.is_err());
+    assert!(acme_storage.clone().with_principal(acme_user.clone()).retrieve_data("report.csv".to_string()).await.is_ok());
+    
+    // A policy permit covers only the actions it names
+    let compliance_storage = guarded_storage.for_tenant(globex.clone()).with_principal(compliance.clone());
+    assert!(compliance_storage.retrieve_data("report.csv".to_string()).await.is_ok());
+    assert!(compliance_storage.delete_data("report.csv".to_string()).await.is_err());
+    
+    // Test the caller of the request being handled is checked when no principal is set
+    let globex_request = RequestContext::new(Uuid::new_v4()).with_principal("bob").with_tenant(globex.clone());
+    globex_request.clone().scope(async {
+        assert!(acme_storage.store_data("report.csv".to_string(), b"q3".to_vec(), "text/csv".to_string(), None).await.is_err());
+        assert!(acme_storage.retrieve_data("report.csv".to_string()).await.is_err());
+        assert!(acme_storage.delete_data("report.csv".to_string()).await.is_err());
+        assert!(acme_storage.list_items(None).await.is_err());
+        assert!(storage_service.for_tenant(globex.clone()).list_items(None).await.is_ok());
+    }).await;
+    
+    // Test each tenant has its own KMS key namespace
+    let kms = start_mock_kms().await;
+    let kms_client = KmsClient::new(
+        "test-project".to_string(),
+        "us-central1".to_string(),
+        "test-keyring".to_string(),
+        "test-key".to_string(),
+        "1".to_string(),
+    ).with_endpoints(format!("{}/v1", kms), format!("{}/token", kms));
+    let acme_kms = kms_client.for_tenant(acme.clone());
+    let encrypted = acme_kms.encrypt(b"q3").await.unwrap();
+    assert!(encrypted.key_id.contains("/keyRings/test-keyring-acme/"));
+    
+    // Test encrypt and decrypt calls check the caller may use the tenant's key
+    globex_request.clone().scope(async {
+        assert!(acme_kms.encrypt(b"q3").await.unwrap_err().to_string().contains("Cross-tenant use of KMS key"));
+        assert!(acme_kms.decrypt(&encrypted.ciphertext).await.unwrap_err().to_string().contains("Cross-tenant"));
+        assert!(kms_client.for_tenant(globex.clone()).encrypt(b"q3").await.is_ok());
+    }).await;
+    RequestContext::new(Uuid::new_v4()).with_principal("alice").with_tenant(acme.clone()).scope(async {
+        assert!(acme_kms.decrypt(&encrypted.ciphertext).await.is_ok());
+    }).await;
+    
+    let decrypt = |principal: &PolicyEntity| PolicyRequest::new(principal.clone(), "kms:decrypt", PolicyEntity::new("prompt", "p-1"));
+    let globex_user = PolicyEntity::new("user", "bob").with_attribute("tenant", "globex");
+    assert!(acme_kms.check_usage(decrypt(&acme_user)).await.is_ok());
+    assert!(acme_kms.check_usage(decrypt(&globex_user)).await.is_err());
+    assert!(kms_client.check_usage(decrypt(&globex_user)).await.is_ok());
+    
+    let source = r#"
+        @id("support-decrypts-across-tenants")
+        permit (principal in "support", action == "kms:decrypt", resource)
+        when { context.cross_tenant == true };
+    "#;
+    let acme_kms = acme_kms.with_policy_engine(Arc::new(PolicyEngine::parse(source, "inline").unwrap()));
+    let support = globex_user.clone().with_roles(vec!["support".to_string()]);
+    assert!(acme_kms.check_usage(decrypt(&globex_user)).await.is_err());
+    assert!(acme_kms.check_usage(decrypt(&support)).await.is_ok());
+    
+    // Test a request's caller keeps its roles, so the permit applies to requests from support too
+    let support_request = RequestContext::new(Uuid::new_v4()).with_caller(support.clone()).with_tenant(globex.clone());
+    support_request.scope(async {
+        assert!(acme_kms.decrypt(&encrypted.ciphertext).await.is_ok());
+    }).await;
+    globex_request.scope(async {
+        assert!(acme_kms.decrypt(&encrypted.ciphertext).await.is_err());
+    }).await;
+    
+    drop(audit_logger);
+    
+    // Test setting the tenant attribute moves the user the same way, ending their sessions
+    let session_id = identity_manager.authenticate("alice", "correct horse battery").await.unwrap()
+        .session_id.unwrap();
+    identity_manager.set_user_attribute("alice", "tenant", serde_json::json!("acme")).await.unwrap();
+    assert!(identity_manager.validate_session(&session_id, &ClientInfo::default()).is_err());
+    assert_eq!(identity_manager.get_user(&alice.id).unwrap().tenant_id, acme);
+}
+
+#[tokio::test]
+async fn test_impersonation() {
+    use dreas::security::audit::{AuditQuery, AuditResult};
+    use dreas::security::impersonation::InMemoryNotifier;
+    use dreas::security::session::ClientInfo;
+    use dreas::security::tenant::TenantId;
+    use chrono::Duration;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::new(30)));
+    let notifier = Arc::new(InMemoryNotifier::new());
+    let mut identity_manager = IdentityManager::new()
+        .with_audit_logger(audit_logger.clone())
+        .with_notifier(notifier.clone());
+    identity_manager.create_role("support".to_string(), vec!["identity:impersonate".to_string()], "Support".to_string())
+        .await.unwrap();
+    identity_manager.create_role("member".to_string(), vec!["reports:read".to_string()], "Member".to_string())
+        .await.unwrap();
+    
+    let password = "correct horse battery";
+    let sam = identity_manager.create_user(
+        "sam".to_string(), "sam@example.com".to_string(), password.to_string(), vec!["support".to_string()],
+    ).await.unwrap();
+    let uma = identity_manager.create_user(
+        "uma".to_string(), "uma@example.com".to_string(), password.to_string(), vec!["member".to_string()],
+    ).await.unwrap();
+    let sam_session = identity_manager.authenticate("sam", password).await.unwrap().session_id.unwrap();
+    let uma_session = identity_manager.authenticate("uma", password).await.unwrap().session_id.unwrap();
+    
+    // Test requests need a reason, a bounded duration and the impersonate permission
+    let reason = "Reproducing ticket 4711 export failure";
+    assert!(identity_manager.start_impersonation(&sam_session, &uma.id, "debug", Duration::minutes(30)).await.is_err());
+    assert!(identity_manager.start_impersonation(&sam_session, &uma.id, reason, Duration::hours(2)).await.is_err());
+    assert!(identity_manager.start_impersonation(&sam_session, &sam.id, reason, Duration::minutes(30)).await.is_err());
+    assert!(identity_manager.start_impersonation(&uma_session, &sam.id, reason, Duration::minutes(30)).await.is_err());
+    
+    // Test the session acts as the subject and records the actor
+    let session = identity_manager.start_impersonation(&sam_session, &uma.id, reason, Duration::minutes(30))
+        .await.unwrap();
+    let impersonation = session.impersonation.clone().unwrap();
+    assert_eq!(session.user_id, uma.id);
+    assert_eq!(impersonation.actor_id, sam.id);
+    assert_eq!(impersonation.reason, reason);
+    assert!(session.expires_at <= chrono::Utc::now() + Duration::minutes(30));
+    assert!(identity_manager.validate_session(&uma_session, &ClientInfo::default()).is_ok());
+    
+    assert!(identity_manager.check_permission(&session.session_id, "reports:read").await.unwrap().allowed);
+    assert!(!identity_manager.check_permission(&session.session_id, "identity:impersonate").await.unwrap().allowed);
+    assert!(identity_manager.start_impersonation(&session.session_id, &sam.id, reason, Duration::minutes(5))
+        .await.is_err());
+    
+    // Test the subject is notified
+    let notifications = notifier.take_for_user(&uma.id).unwrap();
+    assert_eq!(notifications.len(), 1);
+    assert!(notifications[0].message.contains("sam"));
+    assert!(notifications[0].message.contains(reason));
+    
+    // Test actions in the session are attributed to both identities
+    let entry = identity_manager.session_audit_entry(&session.session_id, "report_export", "report:q3", AuditResult::Success)
+        .unwrap();
+    assert_eq!(entry.user_id.as_deref(), Some(uma.id.as_str()));
+    assert_eq!(entry.actor_id.as_deref(), Some(sam.id.as_str()));
+    assert!(identity_manager.session_audit_entry(&uma_session, "report_export", "report:q3", AuditResult::Success)
+        .unwrap().actor_id.is_none());
+    
+    let query = |action: &str| AuditQuery {
+        start_date: None,
+        end_date: None,
+        user_id: Some(uma.id.clone()),
+        action: Some(action.to_string()),
+        resource: None,
+        result: None,
+        limit: None,
+    };
+    let started = audit_logger.lock().await.query_audit_entries(query("impersonation_start")).unwrap();
+    assert_eq!(started.len(), 1);
+    assert_eq!(started[0].actor_id.as_deref(), Some(sam.id.as_str()));
+    assert_eq!(started[0].metadata.get("impersonation_reason").map(|reason| reason.as_str()), Some(reason));
+    
+    // Test ending the session audits and notifies
+    assert!(identity_manager.end_impersonation(&uma_session).await.is_err());
+    identity_manager.end_impersonation(&session.session_id).await.unwrap();
+    assert!(identity_manager.validate_session(&session.session_id, &ClientInfo::default()).is_err());
+    assert_eq!(notifier.take_for_user(&uma.id).unwrap().len(), 1);
+    assert_eq!(audit_logger.lock().await.query_audit_entries(query("impersonation_end")).unwrap().len(), 1);
+    
+    // Test impersonation ends with the actor's own session
+    let session = identity_manager.start_impersonation(&sam_session, &uma.id, reason, Duration::minutes(30))
+        .await.unwrap();
+    identity_manager.logout(&sam_session).await.unwrap();
+    assert!(identity_manager.validate_session(&session.session_id, &ClientInfo::default()).is_err());
+    
+    // Test support staff can't impersonate users of another tenant
+    let sam_session = identity_manager.authenticate("sam", password).await.unwrap().session_id.unwrap();
+    identity_manager.set_user_tenant(&uma.id, TenantId::new("acme").unwrap()).await.unwrap();
+    assert!(identity_manager.start_impersonation(&sam_session, &uma.id, reason, Duration::minutes(30)).await.is_err());
+}
+
+#[tokio::test]
+async fn test_audit_logger() {
+    let mut audit_logger = AuditLogger::new(30);
+    
+    // Test audit logging
+    let entry_id = audit_logger.log_operation(
+        Some("user123".to_string()),
+        Some("session456".to_string()),
+        "data_access".to_string(),
+        "sensitive_file.txt".to_string(),
+        dreas::security::audit::AuditResult::Success,
+        None,
+    ).await.unwrap();
+    
+    assert!(!entry_id.to_string().is_empty());
+    
+    // Test audit query
+    let query = dreas::security::audit::AuditQuery {
+        start_date: Some(chrono::Utc::now() - chrono::Duration::hours(1)),
+        end_date: Some(chrono::Utc::now()),
+        user_id: Some("user123".to_string()),
+        action: Some("data_access".to_string()),
+        resource: None,
+        result: None,
+        limit: Some(10),
+    };
+    
+    let entries = audit_logger.query_audit_entries(query).unwrap();
+    assert!(!entries.is_empty());
+}
+
+#[tokio::test]
+async fn test_audit_chain() {
+    use dreas::security::audit::{AuditEntry, AuditResult, ChainAnchor, ChainViolationKind, GENESIS_HASH};
+    
+    let mut audit_logger = AuditLogger::new(30);
+    
+    // Test an old entry ahead of recent ones, to exercise retention below
+    let mut expired = AuditEntry::new("data_access", "file:archive.txt", AuditResult::Success).with_user("user123");
+    expired.timestamp = chrono::Utc::now() - chrono::Duration::days(40);
+    audit_logger.log_entry(expired).await.unwrap();
+    for resource in ["file:a.txt", "file:b.txt", "file:c.txt"] {
+        audit_logger.log_entry(AuditEntry::new("data_access", resource, AuditResult::Success).with_user("user123"))
+            .await.unwrap();
+    }
+    
+    // Test entries are sequenced and linked
+    let entries = audit_logger.entries().to_vec();
+    assert_eq!(entries.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    assert_eq!(entries[0].previous_hash, GENESIS_HASH);
+    assert_eq!(entries[2].previous_hash, entries[1].hash);
+    let verification = audit_logger.verify_chain().unwrap();
+    assert!(verification.valid);
+    assert_eq!(verification.entries_checked, 4);
+    
+    // Test the chain survives export and import
+    let exported = serde_json::to_string(&entries).unwrap();
+    let imported: Vec<AuditEntry> = serde_json::from_str(&exported).unwrap();
+    assert!(AuditLogger::verify_entries(&imported, &ChainAnchor::genesis()).unwrap().valid);
+    
+    // Test modification, gaps and reordering are reported at the first bad entry
+    let first_violation = |entries: &[AuditEntry]| {
+        let verification = AuditLogger::verify_entries(entries, &ChainAnchor::genesis()).unwrap();
+        assert!(!verification.valid);
+        let violation = verification.first_invalid.unwrap();
+        (violation.index, violation.kind)
+    };
+    
+    let mut modified = entries.clone();
+    modified[1].resource = "file:other.txt".to_string();
+    assert_eq!(first_violation(&modified), (1, ChainViolationKind::HashMismatch));
+    
+    let mut rehashed = modified.clone();
+    rehashed[1].hash = rehashed[1].compute_hash().unwrap();
+    assert_eq!(first_violation(&rehashed), (2, ChainViolationKind::PreviousHashMismatch));
+    
+    let mut truncated = entries.clone();
+    truncated.remove(2);
+    assert_eq!(first_violation(&truncated), (2, ChainViolationKind::UnexpectedSequence { expected: 3 }));
+    
+    let mut reordered = entries.clone();
+    reordered.swap(1, 2);
+    assert_eq!(first_violation(&reordered), (1, ChainViolationKind::UnexpectedSequence { expected: 2 }));
+    
+    // Test retention cleanup keeps the chain verifiable and records the pruning
+    assert!(audit_logger.cleanup_old_entries().await.is_err());
+    let mut audit_logger = audit_logger.with_checkpoint_signer(std::sync::Arc::new(dreas::security::token::LocalTokenSigner::generate()));
+    assert_eq!(audit_logger.cleanup_old_entries().await.unwrap(), 1);
+    assert_eq!(audit_logger.chain_anchor(), &ChainAnchor { sequence: 1, hash: entries[0].hash.clone() });
+    assert!(audit_logger.verify_chain().unwrap().valid);
+    
+    let pruned = audit_logger.entries().last().unwrap();
+    assert_eq!(pruned.action, "audit_log_pruned");
+    assert_eq!(pruned.sequence, 5);
+    assert_eq!(pruned.metadata.get("removed_entries").map(|count| count.as_str()), Some("1"));
+    assert_eq!(audit_logger.head().hash, pruned.hash);
+    assert!(!AuditLogger::verify_entries(audit_logger.entries(), &ChainAnchor::genesis()).unwrap().valid);
+}
+
+#[tokio::test]
+async fn test_audit_checkpoints() {
+    use dreas::security::audit::{AuditEntry, AuditHandle, AuditResult};
+    use dreas::security::merkle;
+    use dreas::security::token::{LocalTokenSigner, TokenSigner};
+    use std::sync::Arc;
+    
+    // Test checkpoints need a signing key
+    assert!(AuditLogger::new(30).checkpoint().await.is_err());
+    
+    let signer = Arc::new(LocalTokenSigner::generate());
+    let verifying_key = signer.verifying_key();
+    let mut audit_logger = AuditLogger::new(30).with_checkpoint_signer(signer);
+    
+    for i in 0..11 {
+        if i == 5 {
+            audit_logger.checkpoint().await.unwrap();
+        }
+        let entry = AuditEntry::new("data_access", format!("file:{}.txt", i), AuditResult::Success);
+        audit_logger.log_entry(entry).await.unwrap();
+    }
+    let first_head = audit_logger.checkpoints()[0].clone();
+    let second_head = audit_logger.checkpoint().await.unwrap();
+    assert_eq!((first_head.tree_size, second_head.tree_size), (5, 11));
+    assert_eq!(audit_logger.checkpoints().len(), 2);
+    first_head.verify_signature(&verifying_key).unwrap();
+    second_head.verify_signature(&verifying_key).unwrap();
+    
+    // Test every entry has a valid inclusion proof in each tree containing it
+    let entries = audit_logger.entries().to_vec();
+    for entry in &entries {
+        let proof = audit_logger.inclusion_proof(entry.sequence, second_head.tree_size).unwrap();
+        proof.verify(entry, &second_head).unwrap();
+        if entry.sequence <= first_head.tree_size {
+            let proof = audit_logger.inclusion_proof(entry.sequence, first_head.tree_size).unwrap();
+            proof.verify(entry, &first_head).unwrap();
+        }
+    }
+    assert!(audit_logger.inclusion_proof(7, first_head.tree_size).is_err());
+    
+    let mut modified = entries[2].clone();
+    modified.resource = "file:other.txt".to_string();
+    let proof = audit_logger.inclusion_proof(3, second_head.tree_size).unwrap();
+    assert!(proof.verify(&modified, &second_head).is_err());
+    assert!(proof.verify(&entries[3], &second_head).is_err());
+    
+    // Test the later checkpoint extends the earlier one
+    let proof = audit_logger.consistency_proof(first_head.tree_size, second_head.tree_size).unwrap();
+    proof.verify(&first_head, &second_head).unwrap();
+    assert!(audit_logger.consistency_proof(second_head.tree_size, first_head.tree_size).is_err());
+    
+    // Test consistency proofs between every pair of tree sizes
+    let leaves: Vec<merkle::MerkleHash> = entries.iter().map(|entry| merkle::entry_leaf_hash(entry).unwrap()).collect();
+    for new_size in 1..=leaves.len() {
+        let new_root = merkle::root_hash(&leaves[..new_size]);
+        for old_size in 1..=new_size {
+            let old_root = merkle::root_hash(&leaves[..old_size]);
+            let proof = merkle::consistency_path(&leaves[..new_size], old_size);
+            assert!(merkle::verify_consistency(old_size as u64, new_size as u64, &old_root, &new_root, &proof));
+            if old_size < new_size {
+                assert!(!merkle::verify_consistency(old_size as u64, new_size as u64, &new_root, &new_root, &proof));
+            }
+        }
+    }
+    
+    // Test rewritten history and forged tree heads are rejected
+    let mut rewritten = leaves.clone();
+    rewritten[1] = merkle::leaf_hash(b"forged");
+    let mut forged_head = first_head.clone();
+    forged_head.root_hash = hex::encode(merkle::root_hash(&rewritten[..5]));
+    assert!(forged_head.verify_signature(&verifying_key).is_err());
+    let proof = audit_logger.consistency_proof(5, 11).unwrap();
+    assert!(proof.verify(&forged_head, &second_head).is_err());
+    assert!(AuditLogger::new(30).with_checkpoint_signer(Arc::new(LocalTokenSigner::generate()))
+        .checkpoint().await.unwrap()
+        .verify_signature(&verifying_key).is_err());    
+    // Test a shared log keeps logging while a snapshotted tree head is signed
+    let shared = Arc::new(tokio::sync::Mutex::new(audit_logger));
+    let pending = shared.lock().await.prepare_checkpoint().unwrap();
+    let logged_meanwhile = AuditEntry::new("data_access", "file:meanwhile.txt", AuditResult::Success);
+    shared.try_lock().unwrap().log_entry(logged_meanwhile).await.unwrap();
+    let snapshot_head = pending.sign().await.unwrap();
+    assert_eq!(snapshot_head.tree_size, 11);
+    shared.lock().await.record_checkpoint(snapshot_head.clone()).unwrap();
+    
+    let handle_head = AuditHandle::new(shared.clone()).checkpoint().await.unwrap();
+    assert_eq!(handle_head.tree_size, 12);
+    handle_head.verify_signature(&verifying_key).unwrap();
+    assert_eq!(shared.lock().await.checkpoints().len(), 4);
+    
+    // Test stale and foreign tree heads aren't kept
+    assert!(shared.lock().await.record_checkpoint(snapshot_head).is_err());
+    let mut foreign_head = handle_head.clone();
+    foreign_head.root_hash = first_head.root_hash.clone();
+    assert!(shared.lock().await.record_checkpoint(foreign_head).is_err());
+    assert!(AuditHandle::detached().checkpoint().await.is_err());
+}
+
+#[tokio::test]
+async fn test_audit_sinks() {
+    use dreas::security::audit::{AuditEntry, AuditResult, ChainAnchor};
+    use dreas::security::audit_sink::{AuditSink, FanOutAuditSink, FileAuditSink, SinkMode, StorageAuditSink};
+    use dreas::security::merkle::CHECKPOINTS_FILE;
+    use dreas::security::redaction::Redactor;
+    use dreas::security::token::LocalTokenSigner;
+    use dreas::DreasResult;
+    use std::sync::Arc;
+    
+    #[derive(Debug)]
+    struct FailingSink;
+    
+    #[async_trait::async_trait]
+    impl AuditSink for FailingSink {
+        fn name(&self) -> &str {
+            "failing"
+        }
+        
+        async fn write(&self, _entry: &AuditEntry) -> DreasResult<()> {
+            Err(dreas::DreasError::AuditLogging("disk full".to_string()))
+        }
+    }
+    
+    #[derive(Debug, Default)]
+    struct FlakySink {
+        failing: std::sync::atomic::AtomicBool,
+    }
+    
+    #[async_trait::async_trait]
+    impl AuditSink for FlakySink {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+        
+        async fn write(&self, _entry: &AuditEntry) -> DreasResult<()> {
+            if self.failing.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(dreas::DreasError::AuditLogging("connection reset".to_string()));
+            }
+            Ok(())
+        }
+    }
+    
+    let directory = std::env::temp_dir().join(format!("dreas-audit-{}", Uuid::new_v4()));
+    
+    // Test entries are appended as JSON lines and rotated by size
+    let file_sink = Arc::new(FileAuditSink::open(&directory).unwrap().with_rotation(1024, chrono::Duration::hours(1)));
+    let mut audit_logger = AuditLogger::new(30)
+        .with_sink(file_sink.clone(), SinkMode::Mandatory)
+        .with_sink(Arc::new(StorageAuditSink::new(StorageService::new(String::new(), "test_dataset".to_string()))), SinkMode::BestEffort)
+        .with_sink(Arc::new(FailingSink), SinkMode::BestEffort);
+    
+    for i in 0..10 {
+        let entry = AuditEntry::new("data_access", format!("file:{}.txt", i), AuditResult::Success).with_user("user123");
+        audit_logger.log_entry(entry).await.unwrap();
+    }
+    
+    let segments = FileAuditSink::segments(&directory).unwrap();
+    assert!(segments.len() > 1);
+    for segment in &segments {
+        assert!(std::fs::metadata(segment).unwrap().len() <= 1024);
+    }
+    
+    let written = FileAuditSink::read_entries(&directory).unwrap();
+    assert_eq!(written.len(), 10);
+    assert!(AuditLogger::verify_entries(&written, &ChainAnchor::genesis()).unwrap().valid);
+    assert_eq!(written.last().unwrap().hash, audit_logger.head().hash);
+    
+    // Test a restarted logger picks up the chain from disk and continues it in a new file
+    let mut restarted = AuditLogger::new(30);
+    assert_eq!(restarted.restore(FileAuditSink::read_entries(&directory).unwrap()).unwrap(), 10);
+    let mut restarted = restarted.with_sink(Arc::new(FileAuditSink::open(&directory).unwrap()), SinkMode::Mandatory);
+    restarted.log_entry(AuditEntry::new("data_access", "file:after-restart.txt", AuditResult::Success)).await.unwrap();
+    
+    let written = FileAuditSink::read_entries(&directory).unwrap();
+    assert_eq!(written.len(), 11);
+    assert_eq!(written.last().unwrap().sequence, 11);
+    assert!(AuditLogger::verify_entries(&written, &ChainAnchor::genesis()).unwrap().valid);
+    assert!(FileAuditSink::segments(&directory).unwrap().len() > segments.len());
+    
+    // Test a broken chain on disk is refused rather than silently continued
+    let mut tampered = written.clone();
+    tampered[4].resource = "file:tampered.txt".to_string();
+    assert!(AuditLogger::new(30).restore(tampered).is_err());
+    assert!(restarted.restore(written).is_err());
+    
+    // Test a failing mandatory sink fails the operation closed
+    let mut strict_logger = AuditLogger::new(30)
+        .with_sink(file_sink.clone(), SinkMode::Mandatory)
+        .with_sink(Arc::new(FailingSink), SinkMode::Mandatory);
+    let mut subscription = strict_logger.subscribe(dreas::security::audit_query::AuditFilter::All);
+    let result = strict_logger.log_entry(AuditEntry::new("key_recovery", "key:escrowed", AuditResult::Success)).await;
+    assert!(matches!(result, Err(dreas::DreasError::AuditLogging(_))));
+    
+    // Test subscribers aren't sent an entry the mandatory sinks didn't record
+    let streamed = tokio::time::timeout(std::time::Duration::from_millis(100), subscription.next()).await;
+    assert!(streamed.is_err());
+    
+    // Test the refused entry is left out of the chain even though another sink recorded it
+    assert_eq!(FileAuditSink::read_entries(&directory).unwrap().last().unwrap().action, "key_recovery");
+    assert!(strict_logger.entries().is_empty());
+    
+    // Test the entry logged after a refused one takes its place, and a restart drops the orphaned copy
+    let flaky_directory = std::env::temp_dir().join(format!("dreas-audit-{}", Uuid::new_v4()));
+    let flaky_sink = Arc::new(FlakySink::default());
+    let mut flaky_logger = AuditLogger::new(30)
+        .with_sink(Arc::new(FileAuditSink::open(&flaky_directory).unwrap()), SinkMode::Mandatory)
+        .with_sink(flaky_sink.clone(), SinkMode::Mandatory);
+    flaky_logger.log_entry(AuditEntry::new("data_access", "file:a.txt", AuditResult::Success)).await.unwrap();
+    flaky_sink.failing.store(true, std::sync::atomic::Ordering::SeqCst);
+    assert!(flaky_logger.log_entry(AuditEntry::new("data_access", "file:b.txt", AuditResult::Success)).await.is_err());
+    flaky_sink.failing.store(false, std::sync::atomic::Ordering::SeqCst);
+    let logged = flaky_logger.log_entry(AuditEntry::new("data_access", "file:c.txt", AuditResult::Success)).await.unwrap();
+    assert_eq!(flaky_logger.entries().last().unwrap().entry_id, logged);
+    assert_eq!(flaky_logger.entries().last().unwrap().sequence, 2);
+    assert!(flaky_logger.verify_chain().unwrap().valid);
+    
+    let written = FileAuditSink::read_entries(&flaky_directory).unwrap();
+    assert_eq!(written.len(), 3);
+    let mut restarted = AuditLogger::new(30);
+    assert_eq!(restarted.restore(written).unwrap(), 2);
+    assert_eq!(restarted.entries()[1].resource, "file:c.txt");
+    assert!(restarted.verify_chain().unwrap().valid);
+    std::fs::remove_dir_all(&flaky_directory).unwrap();
+    
+    // Test fan-out sinks only fail for mandatory failures
+    let entry = AuditEntry::new("key_recovery", "key:escrowed", AuditResult::Success);
+    let best_effort = FanOutAuditSink::new().with_sink(Arc::new(FailingSink), SinkMode::BestEffort);
+    best_effort.write(&entry).await.unwrap();
+    let mandatory = best_effort.with_sink(Arc::new(FailingSink), SinkMode::Mandatory);
+    assert!(mandatory.write(&entry).await.is_err());
+    
+    // Test the BigQuery sink fails rather than claiming to store entries it can't
+    let unconfigured = StorageAuditSink::new(StorageService::new(String::new(), "test_dataset".to_string()));
+    assert!(unconfigured.write(&entry).await.is_err());
+    assert!(unconfigured.purge(std::slice::from_ref(&entry)).await.is_err());
+    let injected = StorageService::new(String::new(), "audit` WHERE true; --".to_string()).with_project_id("test-project");
+    assert!(StorageAuditSink::new(injected).write(&entry).await.is_err());
+    
+    std::fs::remove_dir_all(&directory).unwrap();
+    
+    // Test each service gets its own chain from the configuration, continued after a restart
+    let root = std::env::temp_dir().join(format!("dreas-audit-{}", Uuid::new_v4()));
+    let mut config = dreas::config::AppConfig::default();
+    config.security.audit_sinks.file_directory = Some(root.to_string_lossy().into_owned());
+    let redactor = Arc::new(Redactor::from_config(&config.security.redaction).unwrap());
+    let mut log_ids = Vec::new();
+    for component in ["api_service", "coordinator"] {
+        let mut logger = AuditLogger::from_config(&config, component, redactor.clone()).await.unwrap();
+        logger.log_entry(AuditEntry::new("data_access", format!("file:{}.txt", component), AuditResult::Success)).await.unwrap();
+        log_ids.push(logger.log_id());
+    }
+    assert_ne!(log_ids[0], log_ids[1]);
+    let mut coordinator = AuditLogger::from_config(&config, "coordinator", redactor.clone()).await.unwrap();
+    assert_eq!(coordinator.entries().len(), 1);
+    assert_eq!(coordinator.log_id(), log_ids[1]);
+    coordinator.log_entry(AuditEntry::new("data_access", "file:later.txt", AuditResult::Success)).await.unwrap();
+    
+    let coordinator_directory = config.security.audit_sinks.component_directory("coordinator").unwrap();
+    assert_eq!(coordinator_directory, root.join("coordinator"));
+    let written = FileAuditSink::read_entries(&coordinator_directory).unwrap();
+    assert_eq!(written.len(), 2);
+    assert_eq!(written[0].resource, "file:coordinator.txt");
+    assert!(AuditLogger::verify_entries(&written, &ChainAnchor::genesis()).unwrap().valid);
+    assert_eq!(FileAuditSink::read_entries(root.join("api_service")).unwrap().len(), 1);
+    assert!(coordinator.legal_holds().is_persistent());
+    
+    // Test checkpoints are reloaded after a restart and checked against the restored entries
+    let mut coordinator = coordinator.with_checkpoint_signer(Arc::new(LocalTokenSigner::generate()));
+    let tree_head = coordinator.checkpoint().await.unwrap();
+    let restarted = AuditLogger::from_config(&config, "coordinator", redactor.clone()).await.unwrap();
+    assert_eq!(restarted.log_id(), log_ids[1]);
+    assert_eq!(restarted.checkpoints(), std::slice::from_ref(&tree_head));
+    
+    let checkpoint_file = coordinator_directory.join(CHECKPOINTS_FILE);
+    let mut tampered = tree_head;
+    tampered.root_hash = "00".repeat(32);
+    std::fs::write(&checkpoint_file, format!("{}\n", serde_json::to_string(&tampered).unwrap())).unwrap();
+    assert!(AuditLogger::from_config(&config, "coordinator", redactor.clone()).await.is_err());
+    
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_shared_audit_logger() {
+    use dreas::security::audit::{actions, AuditEntry, AuditResult};
+    use dreas::security::audit_sink::{AuditSink, SinkMode};
+    use dreas::security::escrow::{EscrowSignature, RecoveryRequest};
+    use dreas::security::TenantId;
+    use dreas::services::model::{ModelConfig, ModelRequest};
+    use dreas::DreasResult;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    
+    #[derive(Debug)]
+    struct FailingSink;
+    
+    #[async_trait::async_trait]
+    impl AuditSink for FailingSink {
+        fn name(&self) -> &str {
+            "failing"
+        }
+        
+        async fn write(&self, _entry: &AuditEntry) -> DreasResult<()> {
+            Err(dreas::DreasError::AuditLogging("disk full".to_string()))
+        }
+    }
+    
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::new(30)));
+    let acme = TenantId::new("acme").unwrap();
+    
+    // Test escrow and both granted and refused recoveries are audited
+    let mut escrow = KeyEscrow::new(vec!["admin1".to_string(), "admin2".to_string()], 2).unwrap()
+        .with_audit_logger(audit_logger.clone());
+    escrow.escrow_key("escrowed-key".to_string(), b"wrapped key".to_vec(), None).await.unwrap();
+    let signature = |signer: &str| EscrowSignature {
+        signer: signer.to_string(),
+        signature: format!("signature-{}", signer),
+        timestamp: chrono::Utc::now(),
+    };
+    let recovery_request = |signatures: Vec<EscrowSignature>| RecoveryRequest {
+        request_id: Uuid::new_v4(),
+        requester: "admin1".to_string(),
+        key_id: "escrowed-key".to_string(),
+        reason: "Disaster recovery drill".to_string(),
+        signatures,
+        timestamp: chrono::Utc::now(),
+    };
+    escrow.recover_key(recovery_request(vec![signature("admin1"), signature("admin2")])).await.unwrap();
+    assert!(escrow.recover_key(recovery_request(vec![signature("admin1")])).await.is_err());
+    
+    // Test KMS calls are audited under the key's tenant
+    let kms = start_mock_kms().await;
+    let kms_client = KmsClient::new(
+        "test-project".to_string(),
+        "us-central1".to_string(),
+        "test-ring".to_string(),
+        "test-key".to_string(),
+        "1".to_string(),
+    ).with_endpoints(format!("{}/v1", kms), format!("{}/token", kms))
+        .with_audit_logger(audit_logger.clone()).for_tenant(acme.clone());
+    let encrypted = kms_client.encrypt(b"secret").await.unwrap();
+    kms_client.decrypt(&encrypted.ciphertext).await.unwrap();
+    assert!(kms_client.decrypt(b"not base64!").await.is_err());
+    
+    // Test storage operations are audited
+    let storage = StorageService::new("test-bucket".to_string(), "test_dataset".to_string())
+        .with_audit_logger(audit_logger.clone())
+        .for_tenant(acme.clone());
+    storage.store_data("report.txt".to_string(), b"data".to_vec(), "text/plain".to_string(), None).await.unwrap();
+    storage.retrieve_data("report.txt".to_string()).await.unwrap();
+    storage.delete_data("report.txt".to_string()).await.unwrap();
+    
+    // Test model requests are audited, including ones for unknown models
+    let mut model_service = ModelService::new().with_audit_logger(audit_logger.clone());
+    model_service.register_model(ModelConfig {
+        name: "test-model".to_string(),
+        provider: "openai".to_string(),
+        version: "1.0".to_string(),
+        endpoint: "https://api.openai.com/v1/chat/completions".to_string(),
+        api_key_encrypted: b"encrypted_api_key".to_vec(),
+        max_tokens: 2048,
+        temperature: 0.7,
+        capabilities: vec!["chat".to_string()],
+        enabled: true,
+    }).await.unwrap();
+    let model_request = |model_name: &str| ModelRequest {
+        request_id: Uuid::new_v4(),
+        model_name: model_name.to_string(),
+        prompt: "Hello, world!".to_string(),
+        max_tokens: None,
+        temperature: None,
+        metadata: [("user_id".to_string(), "user123".to_string())].into_iter().collect(),
+    };
+    model_service.send_request(model_request("test-model")).await.unwrap();
+    assert!(model_service.send_request(model_request("missing-model")).await.is_err());
+    
+    // Test agents audit under their context's user, session and tenant
+    let session_id = Uuid::new_v4();
+    let context = AgentContext::new(session_id, "test-key-id".to_string())
+        .with_user_id("user123".to_string())
+        .with_tenant(acme.clone());
+    let key_provider = Arc::new(LocalKeyProvider::new().with_generated_key("test-key-id"));
+    PromptAgent::new(context.clone(), key_provider.clone()).with_audit_logger(audit_logger.clone())
+        .process_prompt("Summarise this".to_string()).await.unwrap();
+    let sealed_response = key_provider.encrypt("test-key-id", b"Summary").await.unwrap();
+    ResponseAgent::new(context, key_provider).with_audit_logger(audit_logger.clone())
+        .process_response(sealed_response).await.unwrap();
+    
+    let logger = audit_logger.lock().await;
+    let recorded: Vec<(&str, AuditResult)> = logger.entries().iter()
+        .map(|entry| (entry.action.as_str(), entry.result.clone()))
+        .collect();
+    assert_eq!(recorded, vec![
+        (actions::KEY_ESCROW, AuditResult::Success),
+        (actions::KEY_RECOVERY, AuditResult::Success),
+        (actions::KEY_RECOVERY, AuditResult::Failure),
+        (actions::DATA_ENCRYPTION, AuditResult::Success),
+        (actions::DATA_DECRYPTION, AuditResult::Success),
+        (actions::DATA_DECRYPTION, AuditResult::Failure),
+        (actions::STORAGE_WRITE, AuditResult::Success),
+        (actions::STORAGE_READ, AuditResult::Success),
+        (actions::STORAGE_DELETE, AuditResult::Success),
+        (actions::MODEL_REQUEST, AuditResult::Success),
+        (actions::MODEL_REQUEST, AuditResult::Failure),
+        (actions::PROMPT_PROCESSED, AuditResult::Success),
+        (actions::RESPONSE_PROCESSED, AuditResult::Success),
+    ]);
+    assert!(logger.verify_chain().unwrap().valid);
+    
+    let entries = logger.entries();
+    assert_eq!(entries[2].user_id.as_deref(), Some("admin1"));
+    assert!(entries[2].metadata["error"].contains("Insufficient signatures"));
+    assert_eq!(entries[3].tenant_id.as_ref(), Some(&acme));
+    assert!(entries[3].resource.contains("keyRings/test-ring-acme/"));
+    assert_eq!(entries[6].resource, "gs://test-bucket/tenants/acme/report.txt");
+    assert_eq!(entries[9].user_id.as_deref(), Some("user123"));
+    assert_eq!(entries[11].session_id, Some(session_id.to_string()));
+    assert_eq!(entries[11].tenant_id.as_ref(), Some(&acme));
+    drop(logger);
+    
+    // Test operations fail closed when the audit log can't record them
+    let strict_logger = Arc::new(Mutex::new(AuditLogger::new(30).with_sink(Arc::new(FailingSink), SinkMode::Mandatory)));
+    let strict_kms = KmsClient::new(
+        "test-project".to_string(),
+        "us-central1".to_string(),
+        "test-ring".to_string(),
+        "test-key".to_string(),
+        "1".to_string(),
+    ).with_endpoints(format!("{}/v1", kms), format!("{}/token", kms)).with_audit_logger(strict_logger.clone());
+    assert!(strict_kms.encrypt(b"secret").await.is_err());
+    let mut strict_escrow = KeyEscrow::new(vec!["admin1".to_string()], 1).unwrap().with_audit_logger(strict_logger);
+    assert!(strict_escrow.escrow_key("escrowed-key".to_string(), b"wrapped key".to_vec(), None).await.is_err());
+    assert!(strict_escrow.list_escrowed_keys().is_empty());
+}
+
+#[tokio::test]
+async fn test_request_context() {
+    use dreas::security::audit::actions;
+    use dreas::security::policy::PolicyEngine;
+    use dreas::security::request_context::{trace_id_from_headers, RequestContext};
+    use dreas::security::session::ClientInfo;
+    use dreas::security::TenantId;
+    use dreas::services::api::{ApiEndpoint, ApiRequest, HttpMethod};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::{Mutex, RwLock};
+    
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::new(30)));
+    let acme = TenantId::new("acme").unwrap();
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    
+    // Test trace IDs are read from W3C and Google Cloud trace headers
+    let headers = |name: &str, value: &str| -> HashMap<String, String> {
+        [(name.to_string(), value.to_string())].into_iter().collect()
+    };
+    assert_eq!(
+        trace_id_from_headers(&headers("traceparent", &format!("00-{}-00f067aa0ba902b7-01", trace_id))).as_deref(),
+        Some(trace_id)
+    );
+    assert_eq!(
+        trace_id_from_headers(&headers("X-Cloud-Trace-Context", "105445AA7843BC8BF206B12000100000/1;o=1")).as_deref(),
+        Some("105445aa7843bc8bf206b12000100000")
+    );
+    assert!(trace_id_from_headers(&headers("traceparent", "00-00000000000000000000000000000000-00f067aa0ba902b7-01")).is_none());
+    
+    // Test agents, KMS and storage calls pick up the request they run in
+    let kms = start_mock_kms().await;
+    let kms_client = KmsClient::new(
+        "test-project".to_string(),
+        "us-central1".to_string(),
+        "test-ring".to_string(),
+        "test-key".to_string(),
+        "1".to_string(),
+    ).with_endpoints(format!("{}/v1", kms), format!("{}/token", kms)).with_audit_logger(audit_logger.clone());
+    let storage = StorageService::new("test-bucket".to_string(), "test_dataset".to_string())
+        .with_audit_logger(audit_logger.clone())
+        .for_tenant(acme.clone());
+    let key_provider = Arc::new(LocalKeyProvider::new().with_generated_key("test-key-id"));
+    let prompt_agent = PromptAgent::new(AgentContext::new(Uuid::new_v4(), "test-key-id".to_string()), key_provider)
+        .with_audit_logger(audit_logger.clone());
+    
+    let request_id = Uuid::new_v4();
+    let context = RequestContext::new(request_id)
+        .with_principal("user123")
+        .with_session("session456")
+        .with_tenant(acme.clone())
+        .with_client(ClientInfo::from_ip(Some("10.1.2.3")).with_user_agent("dreas-cli/1.0"))
+        .with_trace_id(trace_id);
+    context.clone().scope(async {
+        assert_eq!(RequestContext::current(), Some(context.clone()));
+        kms_client.encrypt(b"secret").await.unwrap();
+        storage.store_data("report.txt".to_string(), b"data".to_vec(), "text/plain".to_string(), None).await.unwrap();
+        prompt_agent.process_prompt("Summarise this".to_string()).await.unwrap();
+    }).await;
+    
+    // Test calls outside a request carry no request details
+    assert!(RequestContext::current().is_none());
+    kms_client.encrypt(b"secret").await.unwrap();
+    
+    {
+        let logger = audit_logger.lock().await;
+        let entries = logger.entries();
+        assert_eq!(entries.len(), 4);
+        for entry in &entries[..3] {
+            assert_eq!(entry.request_id, Some(request_id));
+            assert_eq!(entry.user_id.as_deref(), Some("user123"));
+            assert_eq!(entry.ip_address.as_deref(), Some("10.1.2.3"));
+            assert_eq!(entry.user_agent.as_deref(), Some("dreas-cli/1.0"));
+            assert_eq!(entry.trace_id.as_deref(), Some(trace_id));
+        }
+        assert_eq!(entries[0].action, actions::DATA_ENCRYPTION);
+        assert_eq!(entries[0].tenant_id.as_ref(), Some(&acme));
+        assert_eq!(entries[0].session_id.as_deref(), Some("session456"));
+        
+        assert_eq!(entries[1].resource, "gs://test-bucket/tenants/acme/report.txt");
+        
+        // Test details the entry sets itself take precedence
+        assert_ne!(entries[2].session_id.as_deref(), Some("session456"));
+        
+        assert!(entries[3].request_id.is_none());
+        assert!(entries[3].ip_address.is_none());
+        
+        // Test the request details are covered by the hash chain
+        assert!(logger.verify_chain().unwrap().valid);
+        let mut tampered = entries.to_vec();
+        tampered[0].ip_address = Some("192.0.2.1".to_string());
+        assert!(!AuditLogger::verify_entries(&tampered, logger.chain_anchor()).unwrap().valid);
+    }
+    
+    // Test API requests run in a context built from their headers and caller
+    let mut identity_manager = IdentityManager::new();
+    identity_manager.create_role(
+        "reader".to_string(),
+        vec!["read_data".to_string()],
+        "Read-only access".to_string(),
+    ).await.unwrap();
+    let account = identity_manager.create_service_account(
+        "reporting".to_string(),
+        "Reporting job".to_string(),
+        vec!["reader".to_string()],
+    ).await.unwrap();
+    let api_key = identity_manager
+        .create_api_key(&account.id, "reports".to_string(), vec!["read_data".to_string()], None)
+        .unwrap();
+    
+    let mut api_service = ApiService::new(8080);
+    api_service.set_identity_manager(Arc::new(RwLock::new(identity_manager)));
+    api_service.set_policy_engine(Arc::new(PolicyEngine::parse("", "inline").unwrap().with_audit_logger(audit_logger.clone())));
+    api_service.set_trusted_proxies(&["192.0.2.0/24".to_string()]).unwrap();
+    api_service.register_endpoint(ApiEndpoint {
+        path: "/stats".to_string(),
+        method: HttpMethod::GET,
+        handler: "get_stats".to_string(),
+        requires_auth: true,
+        rate_limit: None,
+        timeout_seconds: None,
+    }).await.unwrap();
+    
+    let request = ApiRequest {
+        request_id: Uuid::new_v4(),
+        method: HttpMethod::GET,
+        path: "/stats".to_string(),
+        headers: [
+            ("Authorization".to_string(), format!("Bearer {}", api_key.api_key)),
+            ("X-Forwarded-For".to_string(), "10.4.0.1, 192.0.2.1".to_string()),
+            ("user-agent".to_string(), "reporting-job/2.3".to_string()),
+            ("traceparent".to_string(), format!("00-{}-00f067aa0ba902b7-01", trace_id)),
+        ].into_iter().collect(),
+        body: None,
+        query_params: HashMap::new(),
+        timestamp: chrono::Utc::now(),
+        peer_address: Some("192.0.2.2".parse().unwrap()),
+    };
+    let api_request_id = request.request_id;
+    api_service.process_request(request).await.unwrap();
+    
+    let logger = audit_logger.lock().await;
+    let decision = logger.entries().last().unwrap();
+    assert_eq!(decision.action, actions::POLICY_DECISION);
+    assert_eq!(decision.request_id, Some(api_request_id));
+    assert_eq!(decision.user_id.as_deref(), Some(account.id.as_str()));
+    assert_eq!(decision.ip_address.as_deref(), Some("10.4.0.1"));
+    assert_eq!(decision.user_agent.as_deref(), Some("reporting-job/2.3"));
+    assert_eq!(decision.trace_id.as_deref(), Some(trace_id));
+}
+
+#[tokio::test]
+async fn test_siem_export() {
+    use dreas::config::SyslogSinkConfig;
+    use dreas::security::audit::{actions, AuditEntry, AuditResult};
+    use dreas::security::audit_sink::AuditSink;
+    use dreas::security::siem::{self, OcsfClass, SiemFormat, SyslogAuditSink};
+    use dreas::security::TenantId;
+    use tokio::io::AsyncReadExt;
+    
+    let mut audit_logger = AuditLogger::new(30);
+    let login = AuditEntry::new(actions::USER_AUTHENTICATION, "user:alice", AuditResult::Failure)
+        .with_user("user123")
+        .with_metadata("username", "alice")
+        .with_metadata("source_ip", "10.1.2.3");
+    let read = AuditEntry::new(actions::STORAGE_READ, "data/report.csv", AuditResult::Success)
+        .with_user("user123")
+        .with_actor("support456")
+        .with_tenant(TenantId::new("finance").unwrap());
+    let escrow = AuditEntry::new(actions::KEY_ESCROW, "escrow_key:key789", AuditResult::Success);
+    let odd = AuditEntry::new("custom|action", "a=b\\c\nd", AuditResult::Partial);
+    for entry in [login, read, escrow, odd] {
+        audit_logger.log_entry(entry).await.unwrap();
+    }
+    let entries = audit_logger.entries().to_vec();
+    
+    // Test actions map onto OCSF classes and activities
+    let login_event = siem::to_ocsf(&entries[0]);
+    assert_eq!(OcsfClass::of(actions::USER_AUTHENTICATION), OcsfClass::Authentication);
+    assert_eq!(login_event["class_uid"], 3002);
+    assert_eq!(login_event["category_uid"], 3);
+    assert_eq!(login_event["type_uid"], 300201);
+    assert_eq!(login_event["status_id"], 2);
+    assert_eq!(login_event["user"]["name"], "alice");
+    assert_eq!(login_event["src_endpoint"]["ip"], "10.1.2.3");
+    assert_eq!(login_event["metadata"]["uid"], entries[0].entry_id.to_string());
+    
+    let read_event = siem::to_ocsf(&entries[1]);
+    assert_eq!(read_event["class_uid"], 6003);
+    assert_eq!(read_event["activity_id"], 2);
+    assert_eq!(read_event["resources"][0]["uid"], "data/report.csv");
+    assert_eq!(read_event["actor"]["user"]["uid"], "support456");
+    assert_eq!(read_event["unmapped"]["impersonated_user_uid"], "user123");
+    assert_eq!(read_event["unmapped"]["tenant_id"], "finance");
+    
+    let escrow_event = siem::to_ocsf(&entries[2]);
+    assert_eq!(escrow_event["class_uid"], 3004);
+    assert_eq!(escrow_event["entity"]["uid"], "escrow_key:key789");
+    
+    // Test CEF lines escape header and extension delimiters
+    let cef = siem::to_cef(&entries[1]);
+    assert!(cef.starts_with("CEF:0|DREAS|DREAS|"));
+    assert!(cef.contains("|storage_read|Storage read|3|"));
+    assert!(cef.contains("suid=support456 duid=user123"));
+    assert!(cef.contains("cs2Label=tenant cs2=finance"));
+    
+    let odd_cef = siem::to_cef(&entries[3]);
+    assert!(odd_cef.contains("|custom\\|action|Custom\\|action|5|"));
+    assert!(odd_cef.contains("cs1=a\\=b\\\\c\\nd "));
+    assert!(!odd_cef.contains('\n'));
+    
+    // Test a date range is exported one entry per line, excluding its end
+    let start = chrono::Utc::now() - chrono::Duration::days(1);
+    let mut dated = entries.clone();
+    for (hour, entry) in dated.iter_mut().enumerate() {
+        entry.timestamp = start + chrono::Duration::hours(hour as i64);
+    }
+    
+    let mut exported = Vec::new();
+    let count = siem::export_entries(&dated, start, start + chrono::Duration::hours(2), SiemFormat::Cef, &mut exported).unwrap();
+    let exported = String::from_utf8(exported).unwrap();
+    assert_eq!(count, 2);
+    assert_eq!(exported.lines().count(), 2);
+    assert!(exported.lines().all(|line| line.starts_with("CEF:0|")));
+    assert!(!exported.contains("key_escrow"));
+    
+    let mut ocsf_lines = Vec::new();
+    let all = siem::export_entries(&dated, start, start + chrono::Duration::days(1), SiemFormat::Ocsf, &mut ocsf_lines).unwrap();
+    assert_eq!(all, 4);
+    for line in String::from_utf8(ocsf_lines).unwrap().lines() {
+        let event: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(event["metadata"]["version"], siem::OCSF_VERSION);
+    }
+    assert!(siem::export_entries(&entries, start, start, SiemFormat::Ocsf, &mut Vec::new()).is_err());
+    assert_eq!("CEF".parse::<SiemFormat>().unwrap(), SiemFormat::Cef);
+    assert!("leef".parse::<SiemFormat>().is_err());
+    
+    // Test the syslog sink sends octet-counted RFC 5424 messages
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let sink = SyslogAuditSink::from_config(&SyslogSinkConfig {
+        address: listener.local_addr().unwrap().to_string(),
+        tls: false,
+        ca_certificate_path: None,
+        format: SiemFormat::Cef,
+        hostname: Some("dreas-test".to_string()),
+        timeout_seconds: 5,
+    }).unwrap();
+    
+    let collector = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 4096];
+        while !String::from_utf8_lossy(&received).contains("storage_read") {
+            let read = stream.read(&mut buffer).await.unwrap();
+            assert!(read > 0);
+            received.extend_from_slice(&buffer[..read]);
+        }
+        String::from_utf8(received).unwrap()
+    });
+    
+    sink.write(&entries[0]).await.unwrap();
+    sink.write(&entries[1]).await.unwrap();
+    let received = collector.await.unwrap();
+    
+    // Facility 13 (log audit) with warning severity for the failed login
+    let (length, rest) = received.split_once(' ').unwrap();
+    let first = &rest[..length.parse::<usize>().unwrap()];
+    assert!(first.starts_with("<108>1 "));
+    assert!(first.contains(" dreas-test dreas "));
+    assert!(first.contains(" user_authentication [origin software=\"DREAS\""));
+    assert!(first.contains("\u{feff}CEF:0|DREAS|DREAS|"));
+    assert_eq!(first, sink.message(&entries[0]).unwrap());
+    assert!(received.contains("<110>1 "));
+    
+    // Test an unreachable collector fails the write
+    let unreachable = SyslogAuditSink::from_config(&SyslogSinkConfig {
+        address: "127.0.0.1:1".to_string(),
+        tls: false,
+        ca_certificate_path: None,
+        format: SiemFormat::Ocsf,
+        hostname: None,
+        timeout_seconds: 5,
+    }).unwrap();
+    assert!(unreachable.write(&entries[0]).await.is_err());
+    
+    // Test a collector that accepts but never completes the TLS handshake times out
+    let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let stalled = SyslogAuditSink::from_config(&SyslogSinkConfig {
+        address: silent.local_addr().unwrap().to_string(),
+        tls: true,
+        ca_certificate_path: None,
+        format: SiemFormat::Ocsf,
+        hostname: None,
+        timeout_seconds: 1,
+    }).unwrap();
+    let held = tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((stream, _)) = silent.accept().await {
+            connections.push(stream);
+        }
+    });
+    let started = std::time::Instant::now();
+    let result = stalled.write(&entries[0]).await;
+    assert!(result.unwrap_err().to_string().contains("Timed out"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    held.abort();
+}
+
+#[tokio::test]
+async fn test_audit_search() {
+    use dreas::security::audit::{actions, AuditEntry, AuditResult};
+    use dreas::security::audit_query::{AuditFilter, AuditSearch, Pattern, SortOrder};
+    use dreas::security::TenantId;
+    use dreas::services::api::{ApiRequest, HttpMethod};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::{Mutex, RwLock};
+    
+    let mut audit_logger = AuditLogger::new(30);
+    let finance = TenantId::new("finance").unwrap();
+    for i in 0..12 {
+        let (action, result) = match i % 4 {
+            0 => (actions::STORAGE_READ, AuditResult::Success),
+            1 => (actions::STORAGE_WRITE, AuditResult::Success),
+            2 => (actions::STORAGE_DELETE, AuditResult::Failure),
+            _ => (actions::KEY_ESCROW, AuditResult::Partial),
+        };
+        let mut entry = AuditEntry::new(action, format!("data/report-{}.csv", i), result)
+            .with_user(if i % 2 == 0 { "alice" } else { "bob" });
+        if i < 6 {
+            entry = entry.with_tenant(finance.clone());
+        }
+        audit_logger.log_entry(entry).await.unwrap();
+    }
+    audit_logger.log_entry(
+        AuditEntry::new(actions::USER_AUTHENTICATION, "user:mallory", AuditResult::Failure)
+            .with_metadata("outcome", "Invalid password")
+            .with_metadata("username", "mallory"),
+    ).await.unwrap();
+    
+    // Test query strings parse into filters
+    assert_eq!(AuditFilter::parse("").unwrap(), AuditFilter::All);
+    assert_eq!(AuditFilter::parse("action:storage_*").unwrap(), AuditFilter::Action(Pattern::Prefix("storage_".to_string())));
+    assert_eq!(
+        AuditFilter::parse("resource:\"data/*\"").unwrap(),
+        AuditFilter::Resource(Pattern::Exact("data/*".to_string()))
+    );
+    assert_eq!(
+        AuditFilter::parse("user:alice OR NOT (result:success user:bob)").unwrap(),
+        AuditFilter::Or(vec![
+            AuditFilter::User("alice".to_string()),
+            AuditFilter::Not(Box::new(AuditFilter::And(vec![
+                AuditFilter::Result(AuditResult::Success),
+                AuditFilter::User("bob".to_string()),
+            ]))),
+        ])
+    );
+    for invalid in ["colour:red", "(user:alice", "result:maybe", "since:yesterday", "user:alice AND", "\"open"] {
+        assert!(AuditFilter::parse(invalid).is_err(), "{} should not parse", invalid);
+    }
+    
+    // Test times given as dates cover the whole day
+    let day_end = dreas::security::audit_query::parse_time("2026-10-01", true).unwrap();
+    assert_eq!(day_end.to_rfc3339(), "2026-10-01T23:59:59.999999999+00:00");
+    assert_eq!(dreas::security::audit_query::parse_time("2026-10-01", false).unwrap().to_rfc3339(), "2026-10-01T00:00:00+00:00");
+    assert!(dreas::security::audit_query::parse_time("01/10/2026", false).is_err());
+    
+    // Test indexed searches return exactly the entries a full scan would, across pages
+    let queries = [
+        "",
+        "action:storage_*",
+        "action:storage_??ad",
+        "action:*_write OR action:key_escrow",
+        "resource:data/report-1*",
+        "user:alice AND NOT action:storage_read",
+        "tenant:finance result:failure",
+        "NOT tenant:finance",
+        "(user:bob OR result:failure) AND action:storage_*",
+        "invalid PASSWORD",
+        "metadata.username:mall*",
+        "since:2000-01-01 until:2000-01-01",
+        "since:2000-01-01",
+    ];
+    for query in queries {
+        let filter = AuditFilter::parse(query).unwrap();
+        let expected: Vec<u64> = audit_logger.entries().iter()
+            .filter(|entry| filter.matches(entry))
+            .map(|entry| entry.sequence)
+            .rev()
+            .collect();
+        
+        let mut found = Vec::new();
+        let mut search = AuditSearch::new(filter).with_page_size(2);
+        loop {
+            let page = audit_logger.search(&search).unwrap();
+            assert!(page.entries.len() <= 2);
+            found.extend(page.entries.iter().map(|entry| entry.sequence));
+            match page.next_cursor {
+                Some(cursor) => search = search.with_cursor(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(found, expected, "query {:?}", query);
+    }
+    assert_eq!(audit_logger.search(&AuditSearch::new(AuditFilter::parse("action:storage_*").unwrap())).unwrap().entries.len(), 9);
+    assert_eq!(audit_logger.search(&AuditSearch::new(AuditFilter::parse("invalid password").unwrap())).unwrap().entries.len(), 1);
+    
+    // Test oldest-first pages and cursors bound to their log and order
+    let oldest = AuditSearch::new(AuditFilter::All).with_order(SortOrder::OldestFirst).with_page_size(5);
+    let first_page = audit_logger.search(&oldest).unwrap();
+    assert_eq!(first_page.entries.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    let cursor = first_page.next_cursor.unwrap();
+    let second_page = audit_logger.search(&oldest.clone().with_cursor(cursor.clone())).unwrap();
+    assert_eq!(second_page.entries[0].sequence, 6);
+    
+    assert!(audit_logger.search(&AuditSearch::new(AuditFilter::All).with_cursor(cursor.clone())).is_err());
+    assert!(AuditLogger::new(30).search(&oldest.clone().with_cursor(cursor)).is_err());
+    assert!(audit_logger.search(&oldest.clone().with_cursor("not-a-cursor")).is_err());
+    
+    // Test the legacy query API is served from the same indexes
+    let failures = audit_logger.query_tenant_entries(&finance, dreas::security::audit::AuditQuery {
+        start_date: None,
+        end_date: None,
+        user_id: None,
+        action: None,
+        resource: None,
+        result: Some(AuditResult::Failure),
+        limit: None,
+    }).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].resource, "data/report-2.csv");
+    
+    // Test pruned entries leave the indexes
+    let mut pruning_logger = AuditLogger::new(0)
+        .with_checkpoint_signer(Arc::new(dreas::security::token::LocalTokenSigner::generate()));
+    for i in 0..3 {
+        pruning_logger.log_entry(AuditEntry::new(actions::STORAGE_READ, format!("data/{}", i), AuditResult::Success)).await.unwrap();
+    }
+    assert_eq!(pruning_logger.cleanup_old_entries().await.unwrap(), 3);
+    let remaining = pruning_logger.search(&AuditSearch::new(AuditFilter::All)).unwrap();
+    assert_eq!(remaining.entries.len(), 1);
+    assert_eq!(remaining.entries[0].action, actions::AUDIT_LOG_PRUNED);
+    assert!(pruning_logger.search(&AuditSearch::new(AuditFilter::parse("action:storage_read").unwrap())).unwrap().entries.is_empty());
+    
+    // Test the API serves searches to principals allowed to read the audit log
+    let mut identity_manager = IdentityManager::new();
+    identity_manager.create_role(
+        "auditor".to_string(),
+        vec!["audit:read".to_string(), "audit:read_all_tenants".to_string()],
+        "Audit review".to_string(),
+    ).await.unwrap();
+    identity_manager.create_role("tenant_auditor".to_string(), vec!["audit:read".to_string()], "Tenant audit review".to_string()).await.unwrap();
+    identity_manager.create_role("reader".to_string(), vec!["read_data".to_string()], "Read-only access".to_string()).await.unwrap();
+    let auditor = identity_manager.create_service_account(
+        "soc".to_string(),
+        "SOC tooling".to_string(),
+        vec!["auditor".to_string()],
+    ).await.unwrap();
+    let auditor_key = identity_manager
+        .create_api_key(&auditor.id, "soc".to_string(), vec!["audit:read".to_string(), "audit:read_all_tenants".to_string()], None)
+        .unwrap();
+    let finance_auditor = identity_manager.create_service_account(
+        "finance-soc".to_string(),
+        "Finance's SOC tooling".to_string(),
+        vec!["tenant_auditor".to_string()],
+    ).await.unwrap();
+    identity_manager.set_service_account_tenant(&finance_auditor.id, finance.clone()).unwrap();
+    let finance_key = identity_manager
+        .create_api_key(&finance_auditor.id, "finance".to_string(), vec!["audit:read".to_string()], None)
+        .unwrap();
+    let reporting = identity_manager.create_service_account(
+        "reporting".to_string(),
+        "Reporting job".to_string(),
+        vec!["reader".to_string()],
+    ).await.unwrap();
+    let reporting_key = identity_manager
+        .create_api_key(&reporting.id, "reports".to_string(), vec!["read_data".to_string()], None)
+        .unwrap();
+    
+    let mut api_service = ApiService::new(8080);
+    api_service.set_identity_manager(Arc::new(RwLock::new(identity_manager)));
+    api_service.enable_audit_search(Arc::new(Mutex::new(audit_logger))).await.unwrap();
+    
+    let request = |api_key: &str, query: &[(&str, &str)]| ApiRequest {
+        request_id: Uuid::new_v4(),
+        method: HttpMethod::GET,
+        path: "/audit/entries".to_string(),
+        headers: [("Authorization".to_string(), format!("Bearer {}", api_key))].into_iter().collect(),
+        body: None,
+        query_params: query.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<HashMap<_, _>>(),
+        timestamp: chrono::Utc::now(),
+        peer_address: None,
+    };
+    
+    let response = api_service.process_request(request(&auditor_key.api_key, &[
+        ("q", "action:storage_* AND user:alice"),
+        ("order", "oldest"),
+        ("limit", "4"),
+    ])).await.unwrap();
+    assert_eq!(response.status_code, 200);
+    let page: serde_json::Value = serde_json::from_str(response.body.as_ref().unwrap()).unwrap();
+    assert_eq!(page["entries"].as_array().unwrap().len(), 4);
+    assert_eq!(page["entries"][0]["resource"], "data/report-0.csv");
+    
+    let response = api_service.process_request(request(&auditor_key.api_key, &[
+        ("q", "action:storage_* AND user:alice"),
+        ("order", "oldest"),
+        ("limit", "4"),
+        ("cursor", page["next_cursor"].as_str().unwrap()),
+    ])).await.unwrap();
+    let page: serde_json::Value = serde_json::from_str(response.body.as_ref().unwrap()).unwrap();
+    assert_eq!(page["entries"].as_array().unwrap().len(), 2);
+    assert!(page["next_cursor"].is_null());
+    
+    let response = api_service.process_request(request(&auditor_key.api_key, &[("q", "user:(alice")])).await.unwrap();
+    assert_eq!(response.status_code, 400);
+    assert_eq!(api_service.process_request(request(&reporting_key.api_key, &[])).await.unwrap().status_code, 403);
+    
+    // Test principals without the cross-tenant permission only find their own tenant's entries
+    let response = api_service.process_request(request(&finance_key.api_key, &[("q", "action:storage_* AND user:alice")])).await.unwrap();
+    assert_eq!(response.status_code, 200);
+    let page: serde_json::Value = serde_json::from_str(response.body.as_ref().unwrap()).unwrap();
+    let entries = page["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert!(entries.iter().all(|entry| entry["tenant_id"] == "finance"));
+    
+    let response = api_service.process_request(request(&finance_key.api_key, &[("q", "tenant:default OR tenant:finance")])).await.unwrap();
+    let page: serde_json::Value = serde_json::from_str(response.body.as_ref().unwrap()).unwrap();
+    assert!(page["entries"].as_array().unwrap().iter().all(|entry| entry["tenant_id"] == "finance"));
+    let response = api_service.process_request(request(&finance_key.api_key, &[("q", "NOT tenant:finance")])).await.unwrap();
+    let page: serde_json::Value = serde_json::from_str(response.body.as_ref().unwrap()).unwrap();
+    assert!(page["entries"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_retention_legal_hold() {
+    use dreas::security::audit::{actions, AuditEntry, AuditHandle, AuditResult, ChainViolationKind};
+    use dreas::security::audit_query::{AuditFilter, AuditSearch};
+    use dreas::security::audit_sink::{AuditSink, FileAuditSink, SinkMode};
+    use dreas::security::retention::{
+        DeletionManifest, HoldScope, LegalHold, LegalHolds, RetentionPolicy, DELETION_MANIFESTS_FILE, LEGAL_HOLDS_FILE,
+    };
+    use dreas::security::token::{LocalTokenSigner, TokenSigner};
+    use std::sync::Arc;
+    
+    let signer = Arc::new(LocalTokenSigner::generate());
+    let holds = Arc::new(LegalHolds::new());
+    
+    // Everything expires at once except key recoveries and the log's own housekeeping
+    let retention = RetentionPolicy::new(0)
+        .with_category(actions::KEY_RECOVERY, 2555)
+        .with_category(actions::LEGAL_HOLD_PLACED, 2555)
+        .with_category(actions::LEGAL_HOLD_RELEASED, 2555)
+        .with_category(actions::AUDIT_LOG_PRUNED, 2555);
+    assert_eq!(retention.retention_days(actions::KEY_RECOVERY), 2555);
+    assert_eq!(retention.retention_days(actions::STORAGE_READ), 0);
+    
+    let mut audit_logger = AuditLogger::new(365)
+        .with_retention_policy(retention)
+        .with_legal_holds(holds.clone())
+        .with_checkpoint_signer(signer.clone());
+    for (action, user_id) in [
+        (actions::STORAGE_READ, "alice"),
+        (actions::KEY_RECOVERY, "bob"),
+        (actions::STORAGE_READ, "carol"),
+        (actions::STORAGE_WRITE, "dave"),
+    ] {
+        audit_logger.log_entry(
+            AuditEntry::new(action, format!("data/{}.csv", user_id), AuditResult::Success)
+                .with_user(user_id)
+                .with_metadata("client", "batch-job"),
+        ).await.unwrap();
+    }
+    
+    // Test holds need a reason and a sensible scope
+    let unexplained = LegalHold::new("Case 42", "", "counsel", HoldScope::User { user_id: "dave".to_string() });
+    assert!(audit_logger.place_legal_hold(unexplained).await.is_err());
+    let backwards = LegalHold::new("Case 42", "Litigation", "counsel", HoldScope::Period {
+        start: chrono::Utc::now(),
+        end: chrono::Utc::now() - chrono::Duration::days(1),
+    });
+    assert!(audit_logger.place_legal_hold(backwards).await.is_err());
+    
+    let hold = LegalHold::new("Case 42", "Litigation", "counsel", HoldScope::User { user_id: "dave".to_string() });
+    let hold_id = audit_logger.place_legal_hold(hold).await.unwrap();
+    assert_eq!(holds.active().unwrap().len(), 1);
+    let placed = audit_logger.entries().last().unwrap();
+    assert_eq!(placed.action, actions::LEGAL_HOLD_PLACED);
+    assert_eq!(placed.metadata.get("scope").unwrap(), "user:dave");
+    
+    // Test expired entries are purged unless held, keeping the chain intact
+    let purged = audit_logger.cleanup_old_entries().await.unwrap();
+    assert_eq!(purged, 2);
+    assert_eq!(audit_logger.chain_anchor().sequence, 1);
+    assert!(audit_logger.verify_chain().unwrap().valid);
+    
+    let entries = audit_logger.entries();
+    assert_eq!(entries[0].action, actions::KEY_RECOVERY);
+    assert!(!entries[0].purged);
+    assert!(entries[1].purged);
+    assert_eq!(entries[1].action, actions::STORAGE_READ);
+    assert_eq!(entries[1].user_id, None);
+    assert_eq!(entries[1].resource, "[purged]");
+    assert_eq!(entries[2].user_id.as_deref(), Some("dave"));
+    assert!(!entries[2].purged);
+    
+    let manifest = audit_logger.deletion_manifests()[0].clone();
+    assert_eq!(manifest.purged.len(), 2);
+    assert_eq!(manifest.withheld.len(), 1);
+    assert_eq!(manifest.withheld[0].hold_id, hold_id);
+    assert_eq!(manifest.withheld[0].record_id, entries[2].entry_id.to_string());
+    assert_eq!(manifest.purged[1].record_id, entries[1].entry_id.to_string());
+    assert_eq!(manifest.purged[1].digest.as_deref(), Some(entries[1].hash.as_str()));
+    assert_eq!(entries[1].metadata.get("deletion_manifest").unwrap(), &manifest.manifest_id.to_string());
+    assert_eq!(manifest.key_id.as_deref(), Some(signer.key_id()));
+    manifest.verify_signature(&signer.verifying_key()).unwrap();
+    
+    let mut forged = manifest.clone();
+    forged.purged.pop();
+    assert!(forged.verify_signature(&signer.verifying_key()).is_err());
+    
+    // Test marking a live entry purged, or editing what's left of a purged one, is detected
+    let verify = |entries: &[dreas::security::audit::AuditEntry]| AuditLogger::verify_entries_with_manifests(
+        entries, audit_logger.chain_anchor(), audit_logger.deletion_manifests(), &signer.verifying_key(),
+    ).unwrap();
+    assert!(verify(audit_logger.entries()).valid);
+    assert!(!AuditLogger::verify_entries(audit_logger.entries(), audit_logger.chain_anchor()).unwrap().valid);
+    let mut hidden = audit_logger.entries().to_vec();
+    hidden[0].purged = true;
+    hidden[0].resource = "data/nothing-to-see.csv".to_string();
+    let verification = verify(&hidden);
+    assert_eq!(verification.first_invalid.unwrap().kind, ChainViolationKind::UnaccountedPurge);
+    let mut edited = audit_logger.entries().to_vec();
+    edited[1].action = actions::KEY_RECOVERY.to_string();
+    assert_eq!(verify(&edited).first_invalid.unwrap().kind, ChainViolationKind::UnaccountedPurge);
+    assert!(!AuditLogger::verify_entries_with_manifests(
+        audit_logger.entries(), audit_logger.chain_anchor(), &[forged.clone()], &signer.verifying_key(),
+    ).unwrap().valid);
+    
+    let pruned = audit_logger.entries().last().unwrap();
+    assert_eq!(pruned.action, actions::AUDIT_LOG_PRUNED);
+    assert_eq!(pruned.metadata.get("purged_entries").unwrap(), "2");
+    assert_eq!(pruned.metadata.get("withheld_entries").unwrap(), "1");
+    assert_eq!(pruned.metadata.get("removed_entries").unwrap(), "1");
+    assert_eq!(pruned.metadata.get("manifest_digest").unwrap(), &manifest.digest().unwrap());
+    
+    // Test purged contents can no longer be searched for
+    let by_user = AuditSearch::new(AuditFilter::parse("user:carol").unwrap());
+    assert!(audit_logger.search(&by_user).unwrap().entries.is_empty());
+    let by_client = AuditSearch::new(AuditFilter::parse("batch-job").unwrap());
+    assert_eq!(audit_logger.search(&by_client).unwrap().entries.len(), 2);
+    
+    // Test releasing the hold lets the entry go at the next cleanup
+    let released = audit_logger.release_legal_hold(&hold_id, "counsel", "Case settled").await.unwrap();
+    assert_eq!(released.matter, "Case 42");
+    assert!(holds.active().unwrap().is_empty());
+    assert!(audit_logger.release_legal_hold(&hold_id, "counsel", "Case settled").await.is_err());
+    
+    assert_eq!(audit_logger.cleanup_old_entries().await.unwrap(), 1);
+    assert!(audit_logger.entries()[2].purged);
+    assert_eq!(audit_logger.chain_anchor().sequence, 1);
+    assert!(audit_logger.verify_chain().unwrap().valid);
+    let pruned = audit_logger.entries().last().unwrap();
+    assert_eq!(pruned.metadata.get("removed_entries").unwrap(), "0");
+    assert!(!pruned.metadata.contains_key("from_sequence"));
+    
+    // Test a shared log keeps logging while sinks purge their copies and the manifest is signed
+    let shared = Arc::new(tokio::sync::Mutex::new(audit_logger));
+    shared.lock().await.log_entry(AuditEntry::new(actions::STORAGE_READ, "data/erin.csv", AuditResult::Success)).await.unwrap();
+    let pending = shared.lock().await.prepare_purge().unwrap().unwrap();
+    assert_eq!(pending.len(), 1);
+    let logged_meanwhile = AuditEntry::new(actions::STORAGE_READ, "data/frank.csv", AuditResult::Success);
+    shared.try_lock().unwrap().log_entry(logged_meanwhile).await.unwrap();
+    let purge = pending.execute().await.unwrap();
+    assert_eq!(shared.lock().await.record_purge(purge.clone()).await.unwrap(), 1);
+    assert!(shared.lock().await.record_purge(purge).await.is_err());
+    
+    assert_eq!(AuditHandle::new(shared.clone()).apply_retention().await.unwrap(), 1);
+    assert!(shared.lock().await.prepare_purge().unwrap().is_none());
+    assert!(shared.lock().await.verify_chain().unwrap().valid);
+    assert!(AuditHandle::detached().apply_retention().await.is_err());
+    
+    // Test stored objects follow the same model
+    let storage = StorageService::new("test-bucket".to_string(), "test-dataset".to_string());
+    assert!(storage.apply_retention().await.is_err());
+    
+    let storage = storage
+        .with_retention_policy(RetentionPolicy::new(0))
+        .with_legal_holds(holds.clone());
+    assert!(storage.apply_retention().await.is_err());
+    let storage = storage.with_manifest_signer(signer.clone());
+    let held_object = format!("gs://test-bucket/{}example-item-2", storage.tenant_id().storage_prefix());
+    let object_hold = holds.place(LegalHold::new("Case 43", "Regulator inquiry", "counsel", HoldScope::Resource {
+        prefix: held_object.clone(),
+    })).unwrap();
+    
+    assert!(storage.delete_data("example-item-2".to_string()).await.is_err());
+    assert!(storage.delete_data("example-item-1".to_string()).await.is_ok());
+    
+    // Test holds on a period only cover objects created within it
+    let last_year = holds.place(LegalHold::new("Case 44", "Audit of last year", "counsel", HoldScope::Period {
+        start: chrono::Utc::now() - chrono::Duration::days(400),
+        end: chrono::Utc::now() - chrono::Duration::days(300),
+    })).unwrap();
+    assert!(storage.delete_data("example-item-1".to_string()).await.is_ok());
+    let this_week = holds.place(LegalHold::new("Case 45", "Incident review", "counsel", HoldScope::Period {
+        start: chrono::Utc::now() - chrono::Duration::days(7),
+        end: chrono::Utc::now() + chrono::Duration::days(1),
+    })).unwrap();
+    assert!(storage.delete_data("example-item-1".to_string()).await.is_err());
+    holds.release(&last_year).unwrap();
+    holds.release(&this_week).unwrap();
+    
+    let manifest = storage.apply_retention().await.unwrap();
+    assert_eq!(manifest.purged.len(), 1);
+    assert!(manifest.purged[0].record_id.ends_with("example-item-1"));
+    assert_eq!(manifest.purged[0].category, "default");
+    assert_eq!(manifest.withheld.len(), 1);
+    assert_eq!(manifest.withheld[0].record_id, held_object);
+    assert_eq!(manifest.withheld[0].hold_id, object_hold);
+    manifest.verify_signature(&signer.verifying_key()).unwrap();
+    
+    // Test a durable log isn't purged while its holds and manifests would be lost on restart
+    #[derive(Debug)]
+    struct ForwardingSink;
+    
+    #[async_trait::async_trait]
+    impl AuditSink for ForwardingSink {
+        fn name(&self) -> &str {
+            "forwarder"
+        }
+        
+        async fn write(&self, _entry: &AuditEntry) -> dreas::DreasResult<()> {
+            Ok(())
+        }
+    }
+    
+    let directory = std::env::temp_dir().join(format!("dreas-retention-{}", Uuid::new_v4()));
+    let file_sink = Arc::new(FileAuditSink::open(&directory).unwrap());
+    let durable_logger = |holds: Arc<LegalHolds>| AuditLogger::new(365)
+        .with_retention_policy(RetentionPolicy::new(0)
+            .with_category(actions::LEGAL_HOLD_PLACED, 2555)
+            .with_category(actions::AUDIT_LOG_PRUNED, 2555))
+        .with_legal_holds(holds)
+        .with_checkpoint_signer(signer.clone())
+        .with_sink(Arc::new(ForwardingSink), SinkMode::BestEffort);
+    let mut volatile = durable_logger(Arc::new(LegalHolds::new()));
+    volatile.log_entry(AuditEntry::new(actions::STORAGE_READ, "data/erin.csv", AuditResult::Success).with_user("erin")).await.unwrap();
+    assert!(volatile.cleanup_old_entries().await.is_err());
+    assert!(!volatile.entries()[0].purged);
+    
+    // Test holds and manifests are kept in files and survive a restart
+    let holds_path = directory.join(LEGAL_HOLDS_FILE);
+    let manifests_path = directory.join(DELETION_MANIFESTS_FILE);
+    let durable_holds = Arc::new(LegalHolds::open(&holds_path).unwrap());
+    assert!(durable_holds.is_persistent());
+    let mut durable = durable_logger(durable_holds.clone())
+        .with_sink(file_sink.clone(), SinkMode::Mandatory)
+        .with_deletion_manifests(&manifests_path)
+        .unwrap();
+    let erin_hold = durable.place_legal_hold(LegalHold::new("Case 46", "Litigation", "counsel", HoldScope::User {
+        user_id: "erin".to_string(),
+    })).await.unwrap();
+    durable.log_entry(AuditEntry::new(actions::STORAGE_READ, "data/erin.csv", AuditResult::Success).with_user("erin")).await.unwrap();
+    durable.log_entry(AuditEntry::new(actions::STORAGE_READ, "data/frank.csv", AuditResult::Success).with_user("frank")).await.unwrap();
+    
+    let reopened = LegalHolds::open(&holds_path).unwrap();
+    assert_eq!(reopened.active().unwrap()[0].hold_id, erin_hold);
+    assert_eq!(durable.cleanup_old_entries().await.unwrap(), 1);
+    let reloaded = AuditLogger::new(365).with_deletion_manifests(&manifests_path).unwrap();
+    assert_eq!(reloaded.deletion_manifests().len(), 1);
+    assert_eq!(reloaded.deletion_manifests()[0].withheld[0].hold_id, erin_hold);
+    assert_eq!(reloaded.deletion_manifests()[0].retained_by, vec!["forwarder".to_string()]);
+    reloaded.deletion_manifests()[0].verify_signature(&signer.verifying_key()).unwrap();
+    assert_eq!(durable.entries().last().unwrap().metadata.get("retained_by").unwrap(), "forwarder");
+    
+    // Test the file sink keeps only the tombstone, and the restored log accounts for it
+    let on_disk = FileAuditSink::read_entries(&directory).unwrap();
+    let frank = on_disk.iter().find(|entry| entry.purged).unwrap();
+    assert_eq!(frank.resource, "[purged]");
+    assert!(on_disk.iter().all(|entry| entry.user_id.as_deref() != Some("frank")));
+    assert!(!AuditLogger::verify_entries(&on_disk, &dreas::security::audit::ChainAnchor::genesis()).unwrap().valid);
+    let mut restarted = reloaded.with_checkpoint_signer(signer.clone());
+    assert_eq!(restarted.restore(on_disk.clone()).unwrap(), on_disk.len());
+    assert!(restarted.verify_chain().unwrap().valid);
+    assert!(AuditLogger::new(365).with_checkpoint_signer(signer.clone()).restore(on_disk).is_err());
+    
+    // Test a hold released by another process is picked up before the next purge
+    reopened.release(&erin_hold).unwrap();
+    assert_eq!(durable_holds.active().unwrap().len(), 1);
+    assert_eq!(durable.cleanup_old_entries().await.unwrap(), 1);
+    assert!(durable_holds.active().unwrap().is_empty());
+    assert_eq!(DeletionManifest::read_all(&manifests_path).unwrap().len(), 2);
+    
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_audit_reports() {
+    use dreas::security::audit::{actions, AuditEntry, AuditResult};
+    use dreas::security::audit_report::{AuditReport, ReportFormat, ReportRows, ReportSignature, ReportTemplate};
+    use dreas::security::token::{LocalTokenSigner, TokenSigner};
+    use chrono::{Duration, Utc};
+    
+    let mut audit_logger = AuditLogger::new(365);
+    let failed_login = |username: &str, ip_address: &str| {
+        let mut entry = AuditEntry::new(actions::USER_AUTHENTICATION, format!("user:{}", username), AuditResult::Failure)
+            .with_metadata("username", username)
+            .with_metadata("outcome", "Invalid password");
+        entry.ip_address = Some(ip_address.to_string());
+        entry
+    };
+    let entries = vec![
+        AuditEntry::new(actions::USER_AUTHENTICATION, "user:alice", AuditResult::Success)
+            .with_user("alice")
+            .with_metadata("username", "alice"),
+        failed_login("alice", "10.0.0.1").with_user("alice"),
+        failed_login("mallory", "203.0.113.7"),
+        failed_login("mallory", "203.0.113.8"),
+        failed_login("=HYPERLINK(\"http://evil\")", "203.0.113.9"),
+        failed_login("<script>alert(1)</script>", "203.0.113.9"),
+        AuditEntry::new(actions::PERMISSION_CHANGE, "user:bob", AuditResult::Success).with_user("bob"),
+        AuditEntry::new(actions::POLICY_DECISION, "data/payroll.csv", AuditResult::Failure)
+            .with_user("bob")
+            .with_actor("support-1"),
+        AuditEntry::new(actions::DATA_ENCRYPTION, "kms_key:k1", AuditResult::Success).with_user("alice"),
+        AuditEntry::new(actions::DATA_ENCRYPTION, "kms_key:k1", AuditResult::Success).with_user("alice"),
+        AuditEntry::new(actions::DATA_DECRYPTION, "kms_key:k1", AuditResult::Failure).with_user("bob"),
+        AuditEntry::new(actions::KEY_RECOVERY, "escrow_key:k1", AuditResult::Success)
+            .with_user("carol")
+            .with_metadata("reason", "Custodian left the company")
+            .with_metadata("signers", "custodian-1,custodian-2"),
+    ];
+    for entry in entries {
+        audit_logger.log_entry(entry).await.unwrap();
+    }
+    let start = Utc::now() - Duration::hours(1);
+    let end = Utc::now() + Duration::hours(1);
+    
+    // Test the activity summary
+    let report = audit_logger.generate_report(ReportTemplate::Activity, start, end).unwrap();
+    assert_eq!(report.template(), ReportTemplate::Activity);
+    assert_eq!(report.summary.total_operations, 12);
+    assert_eq!(report.summary.failed_operations, 7);
+    assert_eq!(report.chain_head, audit_logger.head());
+    let ReportRows::Activity(rows) = &report.rows else { panic!("expected activity rows") };
+    let authentication = rows.iter().find(|row| row.action == actions::USER_AUTHENTICATION).unwrap();
+    assert_eq!((authentication.total, authentication.successful, authentication.failed, authentication.users), (6, 1, 5, 1));
+    
+    let empty = audit_logger.generate_report(ReportTemplate::Activity, start - Duration::days(2), start - Duration::days(1)).unwrap();
+    assert_eq!(empty.summary.total_operations, 0);
+    assert!(empty.rows.is_empty());
+    
+    // Test the access review covers every user with their sign-ins, changes and denials
+    let report = audit_logger.generate_report(ReportTemplate::AccessReview, start, end).unwrap();
+    assert!(report.controls.contains(&"ISO 27001 A.5.18".to_string()));
+    let ReportRows::AccessReview(rows) = &report.rows else { panic!("expected access review rows") };
+    let users: Vec<&str> = rows.iter().map(|row| row.user_id.as_str()).collect();
+    assert_eq!(users, vec!["alice", "bob", "carol"]);
+    assert_eq!((rows[0].successful_logins, rows[0].failed_logins), (1, 1));
+    assert_eq!((rows[1].permission_changes, rows[1].denied_requests), (1, 1));
+    assert_eq!(rows[1].impersonated_by, vec!["support-1".to_string()]);
+    
+    // Test key usage is counted per key
+    let report = audit_logger.generate_report(ReportTemplate::KeyUsage, start, end).unwrap();
+    let ReportRows::KeyUsage(rows) = &report.rows else { panic!("expected key usage rows") };
+    assert_eq!(rows.len(), 1);
+    assert_eq!((rows[0].encryptions, rows[0].decryptions, rows[0].signings, rows[0].failures), (2, 1, 0, 1));
+    assert_eq!(rows[0].principals, vec!["alice".to_string(), "bob".to_string()]);
+    
+    // Test every escrow recovery is listed with its approvals
+    let report = audit_logger.generate_report(ReportTemplate::EscrowRecoveries, start, end).unwrap();
+    let ReportRows::EscrowRecoveries(rows) = &report.rows else { panic!("expected escrow recovery rows") };
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].requested_by.as_deref(), Some("carol"));
+    assert_eq!(rows[0].signers, vec!["custodian-1".to_string(), "custodian-2".to_string()]);
+    
+    // Test failed logins are grouped by the username tried, most targeted first
+    let report = audit_logger.generate_report(ReportTemplate::FailedLogins, start, end).unwrap();
+    let ReportRows::FailedLogins(rows) = &report.rows else { panic!("expected failed login rows") };
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows[0].username, "mallory");
+    assert_eq!(rows[0].failures, 2);
+    assert_eq!(rows[0].source_addresses, vec!["203.0.113.7".to_string(), "203.0.113.8".to_string()]);
+    
+    // Test rendering escapes what attackers control
+    let csv = report.render(ReportFormat::Csv).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next().unwrap(), "Username,Failures,Source addresses,First failure,Last failure");
+    assert!(lines.next().unwrap().starts_with("mallory,2,203.0.113.7; 203.0.113.8,"));
+    assert!(csv.contains("\"'=HYPERLINK(\"\"http://evil\"\")\""));
+    
+    let html = report.render(ReportFormat::Html).unwrap();
+    assert!(html.contains("<title>Failed Sign-in Attempts</title>"));
+    assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!html.contains("<script>"));
+    
+    let json = report.render(ReportFormat::Json).unwrap();
+    let parsed: AuditReport = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.report_id, report.report_id);
+    assert_eq!(parsed.template(), ReportTemplate::FailedLogins);
+    assert_eq!(parsed.rows.len(), 4);
+    
+    // Test a detached signature verifies the report as rendered
+    let signer = LocalTokenSigner::generate();
+    let signature = ReportSignature::sign(&report, ReportFormat::Json, json.as_bytes(), &signer).await.unwrap();
+    assert_eq!(signature.key_id, signer.key_id());
+    signature.verify(json.as_bytes(), &signer.verifying_key()).unwrap();
+    
+    let tampered = json.replace("mallory", "mallroy");
+    assert!(signature.verify(tampered.as_bytes(), &signer.verifying_key()).is_err());
+    assert!(signature.verify(json.as_bytes(), &LocalTokenSigner::generate().verifying_key()).is_err());
+    let stored: ReportSignature = serde_json::from_str(&serde_json::to_string(&signature).unwrap()).unwrap();
+    stored.verify(json.as_bytes(), &signer.verifying_key()).unwrap();
+}
+
+#[tokio::test]
+async fn test_redaction() {
+    use dreas::config::RedactionConfig;
+    use dreas::security::audit::{actions, AuditEntry, AuditResult};
+    use dreas::security::redaction::{RedactingFields, Redactor, REDACTED};
+    use dreas::DreasError;
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::sync::Arc;
+    
+    let redactor = Redactor::new(b"test-key".to_vec())
+        .with_denied_field("prompt")
+        .with_hashed_field("Email")
+        .with_builtin_detectors()
+        .with_detector("employee_id", "EMP-[0-9]{6}")
+        .unwrap();
+    assert!(Redactor::new(Vec::new()).with_detector("broken", "[unclosed").is_err());
+    
+    // Test denied fields are removed and identifiers hashed consistently
+    assert_eq!(redactor.redact_field("PROMPT", "What is my diagnosis?"), REDACTED);
+    let hashed = redactor.redact_field("email", "alice@example.com");
+    assert!(hashed.starts_with("hmac:"));
+    assert_eq!(hashed.len(), "hmac:".len() + 16);
+    assert_eq!(redactor.redact_field("email", "alice@example.com"), hashed);
+    assert_ne!(redactor.redact_field("email", "bob@example.com"), hashed);
+    assert_ne!(Redactor::new(b"other-key".to_vec()).with_hashed_field("email").redact_field("email", "alice@example.com"), hashed);
+    
+    // Test detectors find personal data in free text, leaving lookalikes alone
+    let text = redactor.redact_text(
+        "Mail alice@example.com about card 4111 1111 1111 1111, SSN 123-45-6789, badge EMP-004211, \
+         header Bearer eyJhbGciOi.payload.sig and order 1234567890123"
+    );
+    assert!(text.contains("Mail [REDACTED:email] about card [REDACTED:card_number],"));
+    assert!(text.contains("SSN [REDACTED:us_ssn]"));
+    assert!(text.contains("badge [REDACTED:employee_id]"));
+    assert!(text.contains("header [REDACTED:bearer_token]"));
+    assert!(text.contains("order 1234567890123"));
+    
+    // Test JSON bodies are redacted by key and by content
+    let body = redactor.redact_body(r#"{"error":"No user alice@example.com","prompt":"secret","details":{"prompt":42,"count":3}}"#);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["error"], "No user [REDACTED:email]");
+    assert_eq!(body["prompt"], REDACTED);
+    assert_eq!(body["details"]["prompt"], REDACTED);
+    assert_eq!(body["details"]["count"], 3);
+    assert_eq!(redactor.redact_body("not json: bob@example.com"), "not json: [REDACTED:email]");
+    
+    // Test the configured defaults, and that redaction can be turned off
+    let configured = Redactor::from_config(&RedactionConfig::default()).unwrap();
+    assert_eq!(configured.redact_field("password", "hunter2"), REDACTED);
+    assert_eq!(configured.redact_field("tokens_used", "42"), "42");
+    let disabled = Redactor::from_config(&RedactionConfig { enabled: false, ..RedactionConfig::default() }).unwrap();
+    assert_eq!(disabled.redact_field("password", "hunter2"), "hunter2");
+    
+    // Test audit metadata is redacted before the entry is hashed into the chain
+    let redactor = Arc::new(redactor);
+    let mut audit_logger = AuditLogger::new(30).with_redactor(redactor.clone());
+    audit_logger.log_entry(
+        AuditEntry::new(actions::PROMPT_PROCESSED, "session:1", AuditResult::Success)
+            .with_metadata("prompt", "My SSN is 123-45-6789")
+            .with_metadata("email", "alice@example.com")
+            .with_metadata("note", "Escalated by carol@example.com")
+            .with_metadata("prompt_length", "21"),
+    ).await.unwrap();
+    let metadata = &audit_logger.entries()[0].metadata;
+    assert_eq!(metadata.get("prompt").unwrap(), REDACTED);
+    assert_eq!(metadata.get("email").unwrap(), &hashed);
+    assert_eq!(metadata.get("note").unwrap(), "Escalated by [REDACTED:email]");
+    assert_eq!(metadata.get("prompt_length").unwrap(), "21");
+    assert!(audit_logger.verify_chain().unwrap().valid);
+    
+    // Test API error bodies are redacted
+    let mut api_service = ApiService::new(8080);
+    api_service.set_redactor(redactor.clone());
+    let error_body = api_service.error_body(&DreasError::Authentication("Unknown user dave@example.com".to_string()));
+    assert!(error_body.contains("[REDACTED:email]"));
+    assert!(!error_body.contains("dave@example.com"));
+    
+    // Test failed requests are answered with redacted error bodies rather than raw errors
+    let response = api_service.process_request(dreas::services::api::ApiRequest {
+        request_id: Uuid::new_v4(),
+        method: dreas::services::api::HttpMethod::GET,
+        path: "/users/dave@example.com".to_string(),
+        headers: HashMap::new(),
+        body: None,
+        query_params: HashMap::new(),
+        timestamp: chrono::Utc::now(),
+        peer_address: None,
+    }).await.unwrap();
+    assert_eq!(response.status_code, 404);
+    let body = response.body.unwrap();
+    assert!(body.contains("[REDACTED:email]"));
+    assert!(!body.contains("dave@example.com"));
+    
+    // Test endpoints of features that aren't enabled are answered as not found
+    api_service.register_endpoint(dreas::services::api::ApiEndpoint {
+        path: "/audit/entries".to_string(),
+        method: dreas::services::api::HttpMethod::GET,
+        handler: "audit_search".to_string(),
+        requires_auth: false,
+        rate_limit: None,
+        timeout_seconds: None,
+    }).await.unwrap();
+    let response = api_service.process_request(dreas::services::api::ApiRequest {
+        request_id: Uuid::new_v4(),
+        method: dreas::services::api::HttpMethod::GET,
+        path: "/audit/entries".to_string(),
+        headers: HashMap::new(),
+        body: None,
+        query_params: HashMap::new(),
+        timestamp: chrono::Utc::now(),
+        peer_address: None,
+    }).await.unwrap();
+    assert_eq!(response.status_code, 404);
+    assert!(response.body.unwrap().contains("Audit search not enabled"));
+    
+    // Test tracing events are written with their fields redacted
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<std::sync::Mutex<Vec<u8>>>);
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .fmt_fields(RedactingFields::new(redactor))
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .finish();
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(prompt = "tell me a secret", email = "erin@example.com", attempts = 3, "Signed in {}", "frank@example.com");
+    });
+    
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    assert!(output.contains("Signed in [REDACTED:email]"), "{}", output);
+    assert!(output.contains("prompt=[REDACTED]"));
+    assert!(output.contains("email=hmac:"));
+    assert!(output.contains("attempts=3"));
+    assert!(!output.contains("example.com"));
+}
+
+#[tokio::test]
+async fn test_audit_streaming() {
+    use dreas::security::audit::{actions, AuditEntry, AuditResult};
+    use dreas::security::audit_query::AuditFilter;
+    use dreas::security::audit_stream::{AuditEvent, AUDIT_STREAM_CAPACITY};
+    use dreas::security::tenant::TenantId;
+    use dreas::services::api::{ApiRequest, HttpMethod, EVENT_STREAM_CONTENT_TYPE};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::{Mutex, RwLock};
+    
+    let entry = |action: &str, user_id: &str, result: AuditResult| {
+        AuditEntry::new(action, format!("keys/{}", user_id), result).with_user(user_id)
+    };
+    
+    // Test filters built from query parameters combine the query string with the shorthands
+    let params: HashMap<String, String> = [("q", "resource:keys/*"), ("action", "key_*"), ("result", "failure")]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    let filter = AuditFilter::from_query_params(&params).unwrap();
+    assert!(filter.matches(&entry(actions::KEY_RECOVERY, "alice", AuditResult::Failure)));
+    assert!(!filter.matches(&entry(actions::KEY_RECOVERY, "alice", AuditResult::Success)));
+    assert!(!filter.matches(&entry(actions::STORAGE_READ, "alice", AuditResult::Failure)));
+    assert_eq!(AuditFilter::from_query_params(&HashMap::new()).unwrap(), AuditFilter::All);
+    assert!(AuditFilter::from_query_params(&[("result".to_string(), "maybe".to_string())].into_iter().collect()).is_err());
+    
+    // Test subscribers receive matching entries as they're logged, and nothing else
+    let mut audit_logger = AuditLogger::new(365);
+    let mut failures = audit_logger.subscribe(AuditFilter::Result(AuditResult::Failure));
+    let mut bob = audit_logger.subscribe(AuditFilter::User("bob".to_string()));
+    assert_eq!(audit_logger.get_audit_stats()["subscribers"], 2);
+    
+    audit_logger.log_entry(entry(actions::KEY_RECOVERY, "alice", AuditResult::Success)).await.unwrap();
+    audit_logger.log_entry(entry(actions::USER_AUTHENTICATION, "bob", AuditResult::Failure)).await.unwrap();
+    audit_logger.log_entry(entry(actions::KEY_RECOVERY, "carol", AuditResult::Failure)).await.unwrap();
+    
+    for (subscription, expected) in [(&mut failures, vec![2, 3]), (&mut bob, vec![2])] {
+        for sequence in expected {
+            match subscription.next().await.unwrap() {
+                AuditEvent::Entry(entry) => assert_eq!(entry.sequence, sequence),
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+        assert!(tokio::time::timeout(Duration::from_millis(50), subscription.next()).await.is_err());
+    }
+    
+    // Test resuming replays the retained entries after the last one seen
+    let mut resumed = audit_logger.subscribe_after(AuditFilter::Result(AuditResult::Failure), 2);
+    audit_logger.log_entry(entry(actions::DATA_DECRYPTION, "dave", AuditResult::Failure)).await.unwrap();
+    for sequence in [3, 4] {
+        match resumed.next().await.unwrap() {
+            AuditEvent::Entry(entry) => assert_eq!(entry.sequence, sequence),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+    
+    // Test slow subscribers are told how many entries they missed
+    let mut slow = audit_logger.subscribe(AuditFilter::All);
+    for i in 0..AUDIT_STREAM_CAPACITY + 5 {
+        audit_logger.log_entry(entry(actions::STORAGE_READ, &format!("user-{}", i), AuditResult::Success)).await.unwrap();
+    }
+    match slow.next().await.unwrap() {
+        AuditEvent::Lagged { missed } => assert_eq!(missed, 5),
+        other => panic!("unexpected event {:?}", other),
+    }
+    assert!(matches!(slow.next().await.unwrap(), AuditEvent::Entry(_)));
+    
+    // Test subscriptions end once the logger is gone
+    let mut orphaned = AuditLogger::new(365).subscribe(AuditFilter::All);
+    assert!(orphaned.next().await.is_none());
+    
+    // Test the API streams Server-Sent Events to principals allowed to read the audit log
+    let mut identity_manager = IdentityManager::new();
+    identity_manager.create_role(
+        "auditor".to_string(),
+        vec!["audit:read".to_string(), "audit:read_all_tenants".to_string()],
+        "Audit review".to_string(),
+    ).await.unwrap();
+    identity_manager.create_role("tenant_auditor".to_string(), vec!["audit:read".to_string()], "Tenant audit review".to_string()).await.unwrap();
+    identity_manager.create_role("reader".to_string(), vec!["read_data".to_string()], "Read-only access".to_string()).await.unwrap();
+    let soc = identity_manager.create_service_account(
+        "soc".to_string(),
+        "SOC tooling".to_string(),
+        vec!["auditor".to_string()],
+    ).await.unwrap();
+    let soc_key = identity_manager
+        .create_api_key(&soc.id, "soc".to_string(), vec!["audit:read".to_string(), "audit:read_all_tenants".to_string()], None)
+        .unwrap();
+    let acme = identity_manager.create_service_account(
+        "acme-soc".to_string(),
+        "Acme's SOC tooling".to_string(),
+        vec!["tenant_auditor".to_string()],
+    ).await.unwrap();
+    identity_manager.set_service_account_tenant(&acme.id, TenantId::new("acme").unwrap()).unwrap();
+    let acme_key = identity_manager
+        .create_api_key(&acme.id, "acme".to_string(), vec!["audit:read".to_string()], None)
+        .unwrap();
+    let reporting = identity_manager.create_service_account(
+        "reporting".to_string(),
+        "Reporting job".to_string(),
+        vec!["reader".to_string()],
+    ).await.unwrap();
+    let reporting_key = identity_manager
+        .create_api_key(&reporting.id, "reports".to_string(), vec!["read_data".to_string()], None)
+        .unwrap();
+    
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::new(365)));
+    let identity_manager = Arc::new(RwLock::new(identity_manager));
+    let mut api_service = ApiService::new(8080);
+    api_service.set_identity_manager(identity_manager.clone());
+    api_service.enable_audit_streaming(audit_logger.clone()).await.unwrap();
+    
+    let request = |api_key: &str, headers: &[(&str, &str)], query: &[(&str, &str)]| ApiRequest {
+        request_id: Uuid::new_v4(),
+        method: HttpMethod::GET,
+        path: "/audit/stream".to_string(),
+        headers: std::iter::once(("Authorization".to_string(), format!("Bearer {}", api_key)))
+            .chain(headers.iter().map(|(name, value)| (name.to_string(), value.to_string())))
+            .collect(),
+        body: None,
+        query_params: query.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<HashMap<_, _>>(),
+        timestamp: chrono::Utc::now(),
+        peer_address: None,
+    };
+    
+    let mut stream = api_service.open_audit_stream(request(&soc_key.api_key, &[], &[("action", "key_*")])).await.unwrap();
+    assert_eq!(stream.headers()["Content-Type"], EVENT_STREAM_CONTENT_TYPE);
+    audit_logger.lock().await.log_entry(entry(actions::STORAGE_READ, "alice", AuditResult::Success)).await.unwrap();
+    audit_logger.lock().await.log_entry(entry(actions::KEY_RECOVERY, "alice", AuditResult::Success)).await.unwrap();
+    
+    let frame = stream.next_frame().await.unwrap().unwrap();
+    assert!(frame.starts_with("id: 2\nevent: audit_entry\ndata: "));
+    assert!(frame.ends_with("\n\n"));
+    let data: serde_json::Value = serde_json::from_str(frame.lines().nth(2).unwrap().strip_prefix("data: ").unwrap()).unwrap();
+    assert_eq!(data["action"], actions::KEY_RECOVERY);
+    
+    // Reconnecting with the last event ID replays what was missed
+    audit_logger.lock().await.log_entry(entry(actions::KEY_ESCROW, "bob", AuditResult::Success)).await.unwrap();
+    let mut reconnected = api_service.open_audit_stream(request(&soc_key.api_key, &[("Last-Event-ID", "2")], &[])).await.unwrap();
+    assert!(reconnected.next_frame().await.unwrap().unwrap().starts_with("id: 3\n"));
+    assert!(api_service.open_audit_stream(request(&soc_key.api_key, &[("Last-Event-ID", "latest")], &[])).await.is_err());
+    
+    // Subscribing needs the permission to read the audit log and a valid filter
+    assert!(api_service.open_audit_stream(request(&reporting_key.api_key, &[], &[])).await.is_err());
+    assert!(api_service.open_audit_stream(request(&soc_key.api_key, &[], &[("q", "user:(alice")])).await.is_err());
+    
+    // A plain request to the stream endpoint can't be answered
+    let response = api_service.process_request(request(&soc_key.api_key, &[], &[])).await.unwrap();
+    assert_eq!(response.status_code, 406);
+    
+    // Test principals without the cross-tenant permission only see their own tenant's entries
+    let mut tenant_stream = api_service.open_audit_stream(request(&acme_key.api_key, &[], &[("q", "tenant:globex")])).await.unwrap();
+    assert!(matches!(tenant_stream.filter(), AuditFilter::And(filters) if filters.contains(&AuditFilter::Tenant(TenantId::new("acme").unwrap()))));
+    let mut acme_stream = api_service.open_audit_stream(request(&acme_key.api_key, &[], &[])).await.unwrap();
+    for tenant in ["globex", "acme"] {
+        let entry = entry(actions::KEY_RECOVERY, "alice", AuditResult::Success).with_tenant(TenantId::new(tenant).unwrap());
+        audit_logger.lock().await.log_entry(entry).await.unwrap();
+    }
+    let frame = acme_stream.next_frame().await.unwrap().unwrap();
+    assert!(frame.contains("\"tenant_id\":\"acme\""));
+    assert!(tokio::time::timeout(Duration::from_millis(50), tenant_stream.next_frame()).await.is_err());
+    
+    // Test a stream with no entries arriving still closes once its permission is revoked
+    api_service.set_audit_stream_recheck_interval(Duration::from_millis(50));
+    let mut idle = api_service.open_audit_stream(request(&acme_key.api_key, &[], &[("action", "nothing_matches")])).await.unwrap();
+    identity_manager.write().await.deactivate_service_account(&acme.id).unwrap();
+    let closed = tokio::time::timeout(Duration::from_secs(5), idle.next_frame()).await.unwrap();
+    assert!(closed.is_err());
+}
+
+#[tokio::test]
+async fn test_anomaly_detection() {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use dreas::config::AnomalyConfig;
+    use dreas::security::anomaly::{AnomalyDetector, AnomalyKind};
+    use dreas::security::audit::{actions, AuditEntry, AuditResult};
+    use dreas::security::audit_query::AuditFilter;
+    use dreas::security::escrow::RecoveryRequest;
+    use dreas::services::observer::AlertSeverity;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    
+    // Monday morning, within business hours
+    let monday = Utc.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap();
+    let at = |action: &str, user_id: &str, timestamp: DateTime<Utc>, result: AuditResult| {
+        let mut entry = AuditEntry::new(action, format!("keys/{}", user_id), result).with_user(user_id);
+        entry.timestamp = timestamp;
+        entry
+    };
+    let login = |user_id: &str, ip_address: &str, timestamp: DateTime<Utc>| {
+        let mut entry = at(actions::USER_AUTHENTICATION, user_id, timestamp, AuditResult::Success);
+        entry.ip_address = Some(ip_address.to_string());
+        entry
+    };
+    
+    let config = AnomalyConfig { decrypt_volume_minimum: 10, ..AnomalyConfig::default() };
+    let mut detector = AnomalyDetector::from_config(&config).unwrap();
+    
+    // Test decrypt volume is judged against the user's own average, here 4 an hour
+    for hour in 0..5 {
+        for minute in 0..4 {
+            let entry = at(actions::DATA_DECRYPTION, "alice", monday + Duration::hours(hour) + Duration::minutes(minute), AuditResult::Success);
+            assert!(detector.inspect(&entry).is_empty());
+        }
+    }
+    let busy_hour = monday + Duration::hours(5);
+    for minute in 0..11 {
+        let entry = at(actions::DATA_DECRYPTION, "alice", busy_hour + Duration::minutes(minute), AuditResult::Success);
+        assert!(detector.inspect(&entry).is_empty());
+    }
+    let found = detector.inspect(&at(actions::DATA_DECRYPTION, "alice", busy_hour + Duration::minutes(30), AuditResult::Success));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].kind, AnomalyKind::UnusualDecryptVolume);
+    assert_eq!(found[0].subject, "alice");
+    assert_eq!(found[0].details["decrypts"], "12");
+    assert_eq!(found[0].details["threshold"], "12");
+    assert_eq!(found[0].evidence.len(), 12);
+    assert!(found[0].evidence.iter().all(|entry| entry.timestamp >= busy_hour));
+    
+    // The same anomaly isn't raised again within the cooldown
+    assert!(detector.inspect(&at(actions::DATA_DECRYPTION, "alice", busy_hour + Duration::minutes(31), AuditResult::Success)).is_empty());
+    
+    // Without a baseline, the minimum applies
+    let found: Vec<_> = (0..10)
+        .flat_map(|minute| detector.inspect(&at(actions::DATA_DECRYPTION, "bob", monday + Duration::minutes(minute), AuditResult::Success)))
+        .collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].details["decrypts"], "10");
+    
+    // Decrypts by services outside any user's request count against the acting principal, else the key
+    let service_decrypt = |actor_id: Option<&str>, minute: i64| {
+        let mut entry = AuditEntry::new(actions::DATA_DECRYPTION, "kms_key:agents", AuditResult::Success);
+        entry.actor_id = actor_id.map(str::to_string);
+        entry.timestamp = monday + Duration::minutes(minute);
+        entry
+    };
+    let found: Vec<_> = (0..10).flat_map(|minute| detector.inspect(&service_decrypt(Some("svc-batch"), minute))).collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].subject, "svc-batch");
+    let found: Vec<_> = (0..10).flat_map(|minute| detector.inspect(&service_decrypt(None, minute))).collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].subject, "kms_key:agents");
+    
+    // Test logins from an address the user hasn't used are flagged, but not their first login
+    assert!(detector.inspect(&login("alice", "10.0.0.1", monday)).is_empty());
+    assert!(detector.inspect(&login("alice", "10.0.0.1", monday + Duration::hours(1))).is_empty());
+    let found = detector.inspect(&login("alice", "203.0.113.9", monday + Duration::hours(2)));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].kind, AnomalyKind::NewLoginIp);
+    assert_eq!(found[0].details["ip_address"], "203.0.113.9");
+    assert_eq!(found[0].evidence[0].ip_address.as_deref(), Some("203.0.113.9"));
+    
+    let mut failed = login("alice", "198.51.100.7", monday + Duration::hours(3));
+    failed.result = AuditResult::Failure;
+    assert!(detector.inspect(&failed).is_empty());
+    
+    // Addresses unused for longer than the baseline are forgotten rather than held against the user
+    assert!(detector.inspect(&login("alice", "192.0.2.1", monday + Duration::days(30))).is_empty());
+    
+    // Test repeated escrow recovery attempts within a window are flagged
+    for minute in [0, 10] {
+        assert!(detector.inspect(&at(actions::KEY_RECOVERY, "mallory", monday + Duration::minutes(minute), AuditResult::Failure)).is_empty());
+    }
+    let found = detector.inspect(&at(actions::KEY_RECOVERY, "mallory", monday + Duration::minutes(20), AuditResult::Success));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].kind, AnomalyKind::RepeatedEscrowRecovery);
+    assert_eq!(found[0].details["attempts"], "3");
+    assert_eq!(found[0].details["unsuccessful"], "2");
+    assert_eq!(found[0].evidence.len(), 3);
+    assert!(detector.inspect(&at(actions::KEY_RECOVERY, "mallory", monday + Duration::hours(3), AuditResult::Failure)).is_empty());
+    
+    // Test key administration outside business hours is flagged
+    assert!(detector.inspect(&at(actions::KEY_ESCROW, "dave", monday + Duration::hours(1), AuditResult::Success)).is_empty());
+    let saturday = Utc.with_ymd_and_hms(2026, 10, 24, 3, 0, 0).unwrap();
+    let found = detector.inspect(&at(actions::KEY_ESCROW, "dave", saturday, AuditResult::Success));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].kind, AnomalyKind::AfterHoursKeyAdministration);
+    assert_eq!(found[0].kind.severity(), AlertSeverity::Medium);
+    assert_eq!(detector.inspect(&at(actions::KEY_ESCROW, "erin", monday + Duration::hours(13), AuditResult::Success)).len(), 1);
+    
+    // Business hours are local to the configured offset
+    let mut tokyo = AnomalyDetector::from_config(&AnomalyConfig { utc_offset_hours: 9, ..AnomalyConfig::default() }).unwrap();
+    assert!(tokyo.inspect(&at(actions::KEY_ESCROW, "dave", monday - Duration::hours(7), AuditResult::Success)).is_empty());
+    assert_eq!(tokyo.inspect(&at(actions::KEY_ESCROW, "dave", monday, AuditResult::Success)).len(), 1);
+    
+    assert!(AnomalyDetector::from_config(&AnomalyConfig { business_days: vec!["Funday".to_string()], ..AnomalyConfig::default() }).is_err());
+    assert!(AnomalyDetector::from_config(&AnomalyConfig { utc_offset_hours: 30, ..AnomalyConfig::default() }).is_err());
+    assert!(AnomalyDetector::from_config(&AnomalyConfig { business_hours_start: 18, business_hours_end: 8, ..AnomalyConfig::default() }).is_err());
+    
+    // Test sweeping forgets subjects idle for longer than the baseline, window and cooldown
+    let tracked = detector.tracked_subjects();
+    assert!(tracked > 0);
+    detector.sweep(saturday);
+    let kept = detector.tracked_subjects();
+    assert!(kept > 0 && kept < tracked);
+    detector.sweep(monday + Duration::days(60));
+    assert_eq!(detector.tracked_subjects(), 0);
+    
+    // Test the detector follows the audit log, learning from retained entries and raising observer alerts
+    let observer = Arc::new(Mutex::new(ObserverService::new()));
+    let mut audit_logger = AuditLogger::new(365);
+    audit_logger.log_entry(login("frank", "10.0.0.1", monday)).await.unwrap();
+    
+    let mut detector = AnomalyDetector::new().with_observer(observer.clone());
+    detector.learn(audit_logger.entries());
+    let watcher = tokio::spawn(detector.run(audit_logger.subscribe(AuditFilter::All)));
+    audit_logger.log_entry(login("frank", "10.0.0.1", monday + Duration::hours(1))).await.unwrap();
+    audit_logger.log_entry(login("frank", "203.0.113.9", monday + Duration::hours(2))).await.unwrap();
+    drop(audit_logger);
+    watcher.await.unwrap();
+    
+    let alerts = observer.lock().await.get_active_alerts();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].name, AnomalyKind::NewLoginIp.alert_name());
+    assert_eq!(alerts[0].severity, AlertSeverity::Medium);
+    assert_eq!(alerts[0].metadata["anomaly"], "new_login_ip");
+    assert_eq!(alerts[0].metadata["subject"], "frank");
+    assert_eq!(alerts[0].evidence.len(), 1);
+    assert_eq!(alerts[0].evidence[0].sequence, 3);
+    
+    // Test a detector watching the log escrow writes to flags repeated recoveries by one requester
+    let observer = Arc::new(Mutex::new(ObserverService::new()));
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::new(365)));
+    let escrow = KeyEscrow::new(vec!["admin1".to_string(), "admin2".to_string()], 2).unwrap()
+        .with_audit_logger(audit_logger.clone());
+    let watcher = tokio::spawn(AnomalyDetector::new().with_observer(observer.clone()).watch(audit_logger.clone()));
+    tokio::task::yield_now().await;
+    for _ in 0..3 {
+        let request = RecoveryRequest {
+            request_id: Uuid::new_v4(),
+            requester: "mallory".to_string(),
+            key_id: "vault-key".to_string(),
+            reason: "Unseal".to_string(),
+            signatures: Vec::new(),
+            timestamp: Utc::now(),
+        };
+        assert!(escrow.recover_key(request).await.is_err());
+    }
+    drop(escrow);
+    drop(audit_logger);
+    watcher.await.unwrap();
+    
+    let alerts = observer.lock().await.get_active_alerts();
+    let recoveries: Vec<_> = alerts.iter()
+        .filter(|alert| alert.name == AnomalyKind::RepeatedEscrowRecovery.alert_name())
+        .collect();
+    assert_eq!(recoveries.len(), 1);
+    assert_eq!(recoveries[0].metadata["subject"], "mallory");
+}
+
+#[tokio::test]
+async fn test_agent_encryption() {
+    use dreas::security::audit::{actions, AuditResult};
+    use dreas::security::key_provider::KmsKeyProvider;
+    use dreas::security::TenantId;
+    use sha2::{Digest, Sha256};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    
+    let key_provider = Arc::new(LocalKeyProvider::new()
+        .with_generated_key("session-key")
+        .with_key("other-key", &[7u8; 32]));
+    let context = AgentContext::new(Uuid::new_v4(), "session-key".to_string());
+    
+    // Test prompts are sealed with the context's key
+    let prompt_agent = PromptAgent::new(context.clone(), key_provider.clone());
+    let prompt = prompt_agent.process_prompt("What is our refund policy?".to_string()).await.unwrap();
+    assert_eq!(prompt.agent_id, prompt_agent.id());
+    assert_eq!(prompt.prompt_hash, hex::encode(Sha256::digest(b"What is our refund policy?")));
+    assert_ne!(prompt.encrypted_prompt, b"What is our refund policy?".to_vec());
+    assert_eq!(prompt.metadata["encryption_key_id"], "session-key");
+    assert_eq!(prompt.metadata["encrypted"], true);
+    assert_eq!(prompt.metadata["prompt_length"], 26);
+    assert_eq!(prompt.metadata["encrypted_length"], prompt.encrypted_prompt.len());
+    let opened = key_provider.decrypt("session-key", &prompt.encrypted_prompt).await.unwrap();
+    assert_eq!(opened, b"What is our refund policy?".to_vec());
+    assert!(key_provider.decrypt("other-key", &prompt.encrypted_prompt).await.is_err());
+    
+    // Test sealing the same prompt twice gives different ciphertexts
+    let again = prompt_agent.process_prompt("What is our refund policy?".to_string()).await.unwrap();
+    assert_ne!(again.encrypted_prompt, prompt.encrypted_prompt);
+    assert_eq!(again.prompt_hash, prompt.prompt_hash);
+    
+    // Test responses are opened with the context's key
+    let response_agent = ResponseAgent::new(context.clone(), key_provider.clone());
+    let sealed = key_provider.encrypt("session-key", b"Refunds are issued within 30 days").await.unwrap();
+    let response = response_agent.process_response(sealed.clone()).await.unwrap();
+    assert_eq!(response.agent_id, response_agent.id());
+    assert_eq!(response.decrypted_response, "Refunds are issued within 30 days");
+    assert_eq!(response.response_hash, hex::encode(Sha256::digest(b"Refunds are issued within 30 days")));
+    assert_eq!(response.metadata["encryption_key_id"], "session-key");
+    assert_eq!(response.metadata["encrypted"], true);
+    
+    // Test responses sealed with another key, tampered with or too short are refused
+    let foreign = key_provider.encrypt("other-key", b"Refunds are issued within 30 days").await.unwrap();
+    assert!(response_agent.process_response(foreign).await.is_err());
+    let mut tampered = sealed.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 0x01;
+    assert!(response_agent.process_response(tampered).await.is_err());
+    assert!(response_agent.process_response(b"short".to_vec()).await.is_err());
+    
+    // Test a context naming a key the provider doesn't hold can't seal prompts
+    let unknown = PromptAgent::new(AgentContext::new(Uuid::new_v4(), "missing-key".to_string()), key_provider.clone());
+    assert!(unknown.process_prompt("Hello".to_string()).await.is_err());
+    
+    // Test disabling encryption passes payloads through unchanged
+    let mut plain_prompt_agent = PromptAgent::new(context.clone(), key_provider.clone());
+    plain_prompt_agent.set_encryption(false);
+    let plain = plain_prompt_agent.process_prompt("Hello".to_string()).await.unwrap();
+    assert_eq!(plain.encrypted_prompt, b"Hello".to_vec());
+    assert_eq!(plain.metadata["encrypted"], false);
+    let mut plain_response_agent = ResponseAgent::new(context, key_provider.clone());
+    plain_response_agent.set_encryption(false);
+    let plain = plain_response_agent.process_response(b"Hi there".to_vec()).await.unwrap();
+    assert_eq!(plain.decrypted_response, "Hi there");
+    assert!(plain_response_agent.process_response(vec![0xff, 0xfe]).await.is_err());
+    
+    // Test the KMS provider uses the context's key version through its client's tenant and audit log
+    let audit_logger = Arc::new(Mutex::new(AuditLogger::new(30)));
+    let acme = TenantId::new("acme").unwrap();
+    let kms = start_mock_kms().await;
+    let kms_client = KmsClient::new(
+        "test-project".to_string(),
+        "us-central1".to_string(),
+        "test-ring".to_string(),
+        "default-key".to_string(),
+        "1".to_string(),
+    ).with_endpoints(format!("{}/v1", kms), format!("{}/token", kms))
+        .with_audit_logger(audit_logger.clone()).for_tenant(acme.clone());
+    let kms_provider = Arc::new(KmsKeyProvider::new(kms_client));
+    let key_uri = "projects/test-project/locations/us-central1/keyRings/test-ring/cryptoKeys/prompts/cryptoKeyVersions/3";
+    let kms_context = AgentContext::new(Uuid::new_v4(), key_uri.to_string());
+    let prompt = PromptAgent::new(kms_context.clone(), kms_provider.clone())
+        .process_prompt("Summarise the contract".to_string()).await.unwrap();
+    let response = ResponseAgent::new(kms_context, kms_provider.clone())
+        .process_response(prompt.encrypted_prompt).await.unwrap();
+    assert_eq!(response.decrypted_response, "Summarise the contract");
+    
+    let logger = audit_logger.lock().await;
+    let key_uses: Vec<_> = logger.entries().iter()
+        .filter(|entry| entry.action == actions::DATA_ENCRYPTION || entry.action == actions::DATA_DECRYPTION)
+        .collect();
+    assert_eq!(key_uses.len(), 2);
+    for entry in key_uses {
+        assert!(entry.resource.ends_with("/keyRings/test-ring-acme/cryptoKeys/prompts/cryptoKeyVersions/3"));
+        assert_eq!(entry.tenant_id, Some(acme.clone()));
+        assert_eq!(entry.result, AuditResult::Success);
+    }
+    drop(logger);
+    
+    // Test key IDs that aren't key version resource names, or name another tenant's key, are refused unused
+    assert!(kms_provider.encrypt("session-key", b"data").await.is_err());
+    let globex_key_uri = "projects/test-project/locations/us-central1/keyRings/test-ring-globex/cryptoKeys/prompts/cryptoKeyVersions/3";
+    assert!(kms_provider.encrypt(globex_key_uri, b"data").await.is_err());
+    assert!(kms_provider.decrypt(globex_key_uri, b"sealed").await.is_err());
+    assert_eq!(audit_logger.lock().await.entries().len(), 2);
 }
 
 #[tokio::test]
@@ -237,6 +3797,7 @@ async fn test_api_service() {
         body: None,
         query_params: std::collections::HashMap::new(),
         timestamp: chrono::Utc::now(),
+        peer_address: None,
     };
     
     let response = api_service.process_request(request).await.unwrap();